@@ -308,6 +308,7 @@ pub fn create_calendar(
             font_weight: 700, // Bold
             line_width: 100,
             line_spacing: 1.2,
+            ..Default::default()
         },
     );
 
@@ -323,6 +324,7 @@ pub fn create_calendar(
             border_radius: 8.0,
             padding: 0.0,
             horizontal_alignment: HorizontalAlignment::Center,
+            stroke_style: StrokeStyle::default(),
         },
     );
 
@@ -349,6 +351,7 @@ pub fn create_calendar(
                 font_weight: 700, // Bold
                 line_width: 20,
                 line_spacing: 1.0,
+                ..Default::default()
             },
         );
 
@@ -364,6 +367,7 @@ pub fn create_calendar(
                 padding: 0.0,
                 border_radius: 0.0,
                 horizontal_alignment: HorizontalAlignment::Center,
+                stroke_style: StrokeStyle::default(),
             },
         );
 
@@ -424,6 +428,7 @@ pub fn create_calendar(
                     font_weight: if is_bold { 700 } else { 400 },
                     line_width: 20,
                     line_spacing: 1.0,
+                    ..Default::default()
                 },
             );
 
@@ -444,6 +449,7 @@ pub fn create_calendar(
                             font_weight: 400,
                             line_width: 15,
                             line_spacing: 1.0,
+                            ..Default::default()
                         },
                     );
 
@@ -459,6 +465,7 @@ pub fn create_calendar(
                             padding: 2.0,
                             border_radius: 2.0,
                             horizontal_alignment: HorizontalAlignment::Left,
+                            stroke_style: StrokeStyle::default(),
                         },
                     );
 
@@ -486,6 +493,7 @@ pub fn create_calendar(
                     padding: 5.0,
                     border_radius: 0.0,
                     horizontal_alignment: HorizontalAlignment::Left,
+                    stroke_style: StrokeStyle::default(),
                 },
             );
 
@@ -525,6 +533,7 @@ pub fn create_calendar(
             width_behavior: SizeBehavior::Content,
             height_behavior: SizeBehavior::Content,
             horizontal_alignment: HorizontalAlignment::Center,
+            stroke_style: StrokeStyle::default(),
         },
     );
 
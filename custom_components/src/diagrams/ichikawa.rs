@@ -9,7 +9,6 @@ use crate::document::style::{
     SPACE_MD, SPACE_SM, SPACE_XS, TEXT_2XL, TEXT_3XL, TEXT_BASE, TEXT_LG, TEXT_XL, TEXT_XS,
     WIDTH_FULL, WIDTH_LG, WIDTH_MD, WIDTH_PROPERTY_PANEL, WIDTH_SM, WIDTH_XL,
 };
-use crate::document::theme::BODY_COLOR;
 use crate::parser::{
     get_array_attr, get_bool_attr, get_float_attr, get_int_attr, get_string_attr, JsonLinesParser,
 };
@@ -104,8 +103,9 @@ pub fn create_ishikawa(
         LinePointReference::PointID(spine_start_point_id.clone()),
         LinePointReference::PointID(spine_end_point_id.clone()),
         LineOptions {
-            stroke_color: BODY_COLOR.to_string(),
+            stroke_color: PRIMARY_TEXT.to_string(),
             stroke_width: 2.0,
+            stroke_style: StrokeStyle::default(),
         },
     );
 
@@ -402,6 +402,7 @@ fn create_top_branch(
             stroke_color: "transparent".to_owned(),
             stroke_width: 1.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         },
     );
 
@@ -413,8 +414,9 @@ fn create_top_branch(
         LinePointReference::PointID(line_start_id.clone()),
         LinePointReference::PointID(line_end_id.clone()),
         LineOptions {
-            stroke_color: BODY_COLOR.to_owned(),
+            stroke_color: PRIMARY_TEXT.to_owned(),
             stroke_width: 1.0,
+            stroke_style: StrokeStyle::default(),
         },
     );
 
@@ -495,10 +497,10 @@ fn create_top_branch(
                 source_port: Port::Right,
                 target_port: Port::Left,
                 connector_type: ConnectorType::Straight,
-                stroke_color: BODY_COLOR.to_owned(),
+                stroke_color: PRIMARY_TEXT.to_owned(),
                 stroke_width: 1.0,
-                arrow_end: false,
-                arrow_start: false,
+                marker_start: Marker::None,
+                marker_end: Marker::None,
                 arrow_size: 0.0,
                 curve_offset: None,
                 ..Default::default()
@@ -532,10 +534,10 @@ fn create_top_branch(
                 source_port: Port::Left,
                 target_port: Port::Right,
                 connector_type: ConnectorType::Straight,
-                stroke_color: BODY_COLOR.to_owned(),
+                stroke_color: PRIMARY_TEXT.to_owned(),
                 stroke_width: 1.0,
-                arrow_end: false,
-                arrow_start: false,
+                marker_start: Marker::None,
+                marker_end: Marker::None,
                 arrow_size: 0.0,
                 curve_offset: None,
                 ..Default::default()
@@ -622,6 +624,7 @@ fn create_bottom_branch(
             stroke_color: "transparent".to_owned(),
             stroke_width: 1.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         },
     );
 
@@ -633,8 +636,9 @@ fn create_bottom_branch(
         LinePointReference::PointID(line_start_id.clone()),
         LinePointReference::PointID(line_end_id.clone()),
         LineOptions {
-            stroke_color: BODY_COLOR.to_owned(),
+            stroke_color: PRIMARY_TEXT.to_owned(),
             stroke_width: 1.0,
+            stroke_style: StrokeStyle::default(),
         },
     );
 
@@ -715,10 +719,10 @@ fn create_bottom_branch(
                 source_port: Port::Right,
                 target_port: Port::Left,
                 connector_type: ConnectorType::Straight,
-                stroke_color: BODY_COLOR.to_owned(),
+                stroke_color: PRIMARY_TEXT.to_owned(),
                 stroke_width: 1.0,
-                arrow_end: false,
-                arrow_start: false,
+                marker_start: Marker::None,
+                marker_end: Marker::None,
                 arrow_size: 0.0,
                 curve_offset: None,
                 ..Default::default()
@@ -752,10 +756,10 @@ fn create_bottom_branch(
                 source_port: Port::Left,
                 target_port: Port::Right,
                 connector_type: ConnectorType::Straight,
-                stroke_color: BODY_COLOR.to_owned(),
+                stroke_color: PRIMARY_TEXT.to_owned(),
                 stroke_width: 1.0,
-                arrow_end: false,
-                arrow_start: false,
+                marker_start: Marker::None,
+                marker_end: Marker::None,
                 arrow_size: 0.0,
                 curve_offset: None,
                 ..Default::default()
@@ -910,13 +914,13 @@ fn create_left_item(
                 child.entity_id.clone(),
                 ConnectorOptions {
                     connector_type: ConnectorType::Orthogonal,
-                    stroke_color: BODY_COLOR.to_owned(),
+                    stroke_color: PRIMARY_TEXT.to_owned(),
                     stroke_width: 1.0,
                     curve_offset: Some(-1.0),
                     source_port: Port::Left,
                     target_port: Port::Right,
-                    arrow_start: false,
-                    arrow_end: false,
+                    marker_start: Marker::None,
+                    marker_end: Marker::None,
                     arrow_size: 0.0,
                     ..Default::default()
                 },
@@ -1021,13 +1025,13 @@ fn create_right_item(
                 child.entity_id.clone(),
                 ConnectorOptions {
                     connector_type: ConnectorType::Orthogonal,
-                    stroke_color: BODY_COLOR.to_owned(),
+                    stroke_color: PRIMARY_TEXT.to_owned(),
                     stroke_width: 1.0,
                     curve_offset: Some(-2.0),
                     source_port: Port::Right,
                     target_port: Port::Left,
-                    arrow_start: false,
-                    arrow_end: false,
+                    marker_start: Marker::None,
+                    marker_end: Marker::None,
                     arrow_size: 0.0,
                     ..Default::default()
                 },
@@ -5,14 +5,8 @@ use std::fmt::format;
 
 use crate::diagram_builder::{DiagramBuilder, DiagramTreeNode};
 use crate::document::style::{
-    BG_ACCENT, BG_PRIMARY, BG_SECONDARY, BORDER_LIGHT_COLOR, DOCUMENT_WIDTH_DEFAULT, FONT_SANS,
-    FONT_WEIGHT_BOLD_LIGHT, FONT_WEIGHT_BOLD_MAX, FONT_WEIGHT_BOLD_MD, FONT_WEIGHT_NORMAL,
-    LINE_HEIGHT_NORMAL, LINE_HEIGHT_RELAXED, LINE_HEIGHT_TIGHT, MUTED_TEXT, PADDING_NORMAL,
-    PRIMARY_TEXT, SECONDARY_TEXT, SPACE_3XL, SPACE_MD, SPACE_SM, SPACE_XS, TEXT_2XL, TEXT_3XL,
-    TEXT_BASE, TEXT_LG, TEXT_XL, TEXT_XS, WIDTH_FULL, WIDTH_LG, WIDTH_MD, WIDTH_PROPERTY_PANEL,
-    WIDTH_SM, WIDTH_XL,
+    WIDTH_FULL, WIDTH_LG, WIDTH_MD, WIDTH_PROPERTY_PANEL, WIDTH_SM, WIDTH_XL,
 };
-use crate::document::theme::BODY_COLOR;
 use crate::parser::{
     get_array_attr, get_bool_attr, get_float_attr, get_int_attr, get_string_attr, JsonLinesParser,
 };
@@ -22,8 +16,13 @@ use uuid::fmt::Simple;
 use uuid::uuid;
 use anyhow::{bail, Result};
 
+pub mod theme;
+pub use theme::DocumentTheme;
+
 /// Document Style Constants
-/// These constants define a consistent design system for document components
+/// These constants define a consistent design system for document components.
+/// Components read colors, type and spacing from a [`DocumentTheme`], whose
+/// light preset is built from these values.
 pub mod style {
     use crate::Float;
 
@@ -148,43 +147,46 @@ pub mod style {
     pub const FOOTER_TEXT_SIZE: Float = TEXT_XS;
 }
 
-/// Theme Constants
-/// Higher-level semantic constants for different component themes
-pub mod theme {
-    use super::style::*;
-
-    // Document theme
-    pub const DOCUMENT_HEADER_BG: &str = BG_SECONDARY;
-    pub const DOCUMENT_CONTENT_BG: &str = BG_PRIMARY;
-    pub const DOCUMENT_FOOTER_BG: &str = BG_MUTED;
-    pub const DOCUMENT_BORDER: &str = BORDER_LIGHT_COLOR;
-
-    // Text themes
-    pub const TITLE_COLOR: &str = PRIMARY_TEXT;
-    pub const SUBTITLE_COLOR: &str = SECONDARY_TEXT;
-    pub const BODY_COLOR: &str = PRIMARY_TEXT;
-    pub const META_COLOR: &str = MUTED_TEXT;
-    pub const LINK_COLOR: &str = ACCENT_TEXT;
-
-    // Component themes
-    pub const CARD_BG: &str = BG_PRIMARY;
-    pub const CARD_BORDER: &str = BORDER_LIGHT_COLOR;
-    pub const CARD_SHADOW: bool = true;
-
-    pub const ALERT_SUCCESS_BG: &str = "#d1e7dd";
-    pub const ALERT_SUCCESS_BORDER: &str = SUCCESS;
-    pub const ALERT_WARNING_BG: &str = "#fff3cd";
-    pub const ALERT_WARNING_BORDER: &str = WARNING;
-    pub const ALERT_DANGER_BG: &str = "#f8d7da";
-    pub const ALERT_DANGER_BORDER: &str = DANGER;
+/**
+ * Document root with optional header, content and footer
+ *
+ * theme attribute: light|dark|high_contrast, applies to everything built inside
+ * the document. Without it the builder's theme is used.
+ */
+pub fn create_document_container(
+    id: &str,
+    attrs: &Map<String, Value>,
+    builder: &mut DiagramBuilder,
+    parser: &JsonLinesParser,
+) -> Result<DiagramTreeNode> {
+    let theme_name = get_string_attr(attrs, &["theme"], "");
+    let previous_theme = if theme_name.is_empty() {
+        None
+    } else if let Some(theme) = Theme::by_name(&theme_name) {
+        Some(std::mem::replace(&mut builder.theme, theme))
+    } else {
+        println!(
+            "⚠️ Unknown theme '{}' on '{}', using '{}'",
+            theme_name, id, builder.theme.name
+        );
+        None
+    };
+
+    let document = document_container(id, attrs, builder, parser);
+
+    if let Some(previous_theme) = previous_theme {
+        builder.theme = previous_theme;
+    }
+    document
 }
 
-pub fn create_document_container(
+fn document_container(
     id: &str,
     attrs: &Map<String, Value>,
     builder: &mut DiagramBuilder,
     parser: &JsonLinesParser,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     let mut children = Vec::new();
     let header_id = get_string_attr(attrs, &["header_id"], "");
     let content_id = get_string_attr(attrs, &["content_id"], "");
@@ -194,10 +196,10 @@ pub fn create_document_container(
     // let newparser = JsonLinesParser::new();
     if let Ok(header_child) = parser.build(&header_id, builder) {
         let mut header_options = BoxOptions::new();
-        header_options.padding = PADDING_NORMAL;
-        header_options.fill_color = Fill::Color(BG_PRIMARY.to_string());
+        header_options.padding = theme.padding;
+        header_options.fill_color = Fill::Color(theme.bg_primary.clone());
         header_options.stroke_width = 0.0;
-        header_options.stroke_color = BG_PRIMARY.to_string();
+        header_options.stroke_color = theme.bg_primary.clone();
 
         let header_container = builder.new_box(
             format!("{}_header_container", &id),
@@ -211,10 +213,10 @@ pub fn create_document_container(
     match parser.build(&content_id, builder) {
         Ok(content_child) => {
             let mut content_options = BoxOptions::new();
-            content_options.padding = PADDING_NORMAL;
-            content_options.fill_color = Fill::Color(BG_PRIMARY.to_string());
+            content_options.padding = theme.padding;
+            content_options.fill_color = Fill::Color(theme.bg_primary.clone());
             content_options.stroke_width = 0.0;
-            content_options.stroke_color = BG_PRIMARY.to_string();
+            content_options.stroke_color = theme.bg_primary.clone();
 
             let content_container = builder.new_box(
                 format!("{}_content_container", &id),
@@ -231,10 +233,10 @@ pub fn create_document_container(
 
     if let Ok(footer_child) = parser.build(&footer_id, builder) {
         let mut footer_options = BoxOptions::new();
-        footer_options.padding = PADDING_NORMAL;
-        footer_options.fill_color = Fill::Color(BG_PRIMARY.to_string());
+        footer_options.padding = theme.padding;
+        footer_options.fill_color = Fill::Color(theme.bg_primary.clone());
         footer_options.stroke_width = 0.0;
-        footer_options.stroke_color = BG_PRIMARY.to_string();
+        footer_options.stroke_color = theme.bg_primary.clone();
 
         let footer_container = builder.new_box(
             format!("{}_footer_container", &id),
@@ -245,9 +247,20 @@ pub fn create_document_container(
         children.push(footer_container);
     }
 
-    let vstack = builder.new_vstack(id.to_string(), children, HorizontalAlignment::Left);
+    let vstack = builder.new_vstack(format!("{}_stack", id), children, HorizontalAlignment::Left);
+
+    // Page background, sections can be narrower than the document
+    let page_options = BoxOptions {
+        fill_color: Fill::Color(theme.bg_primary.clone()),
+        stroke_color: theme.bg_primary.clone(),
+        stroke_width: 0.0,
+        padding: 0.0,
+        horizontal_alignment: HorizontalAlignment::Left,
+        ..BoxOptions::new()
+    };
+    let page = builder.new_box(id.to_string(), vstack, page_options);
 
-    return Ok(vstack);
+    Ok(page)
 }
 
 /**
@@ -285,38 +298,39 @@ pub fn document_text(
     content: String,
     max_width: f32,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     let mut toptions = match variant.as_str() {
         "xlarge" => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_XL,
-            text_color: PRIMARY_TEXT.to_string(),
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_xl,
+            text_color: theme.primary_text.clone(),
             ..TextOptions::default()
         },
         "large" | "emphasized" => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_LG,
-            text_color: PRIMARY_TEXT.to_string(),
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_lg,
+            text_color: theme.primary_text.clone(),
             ..TextOptions::default()
         },
 
         "small" => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_XS,
-            text_color: PRIMARY_TEXT.to_string(),
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_xs,
+            text_color: theme.primary_text.clone(),
             ..TextOptions::default()
         },
 
         "subtle" => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_XS,
-            text_color: SECONDARY_TEXT.to_string(),
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_xs,
+            text_color: theme.secondary_text.clone(),
             ..TextOptions::default()
         },
 
         _ => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_BASE,
-            text_color: PRIMARY_TEXT.to_string(),
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_base,
+            text_color: theme.primary_text.clone(),
             ..TextOptions::default()
         },
     };
@@ -338,6 +352,7 @@ pub fn document_text(
         width_behavior: SizeBehavior::Content,
         height_behavior: SizeBehavior::Content,
         horizontal_alignment: HorizontalAlignment::Left,
+        stroke_style: StrokeStyle::default(),
     };
     let container = builder.new_box(format!("{}_text_container", id), text, coptions);
 
@@ -348,8 +363,8 @@ pub fn document_text(
             RectOptions {
                 stroke_width: 1.0,
                 width_behavior: SizeBehavior::Fixed(1.0),
-                stroke_color: PRIMARY_TEXT.to_owned(),
-                fill_color: Fill::Color(PRIMARY_TEXT.to_owned()),
+                stroke_color: theme.primary_text.clone(),
+                fill_color: Fill::Color(theme.primary_text.clone()),
                 ..Default::default()
             },
         );
@@ -362,7 +377,7 @@ pub fn document_text(
             SimpleConstraint::HorizontalSpacing(
                 left_line.entity_id.clone(),
                 container.entity_id.clone(),
-                SPACE_SM,
+                theme.space_sm,
             ),
         ];
         let e_container = builder.new_constraint_layout_container(
@@ -398,57 +413,58 @@ fn document_title(
     content: String,
     container_width: f32,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     let mut toptions = match variant.as_str() {
         "h1" => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_3XL,
-            text_color: PRIMARY_TEXT.to_string(),
-            line_spacing: TEXT_3XL * 0.1,
-            font_weight: FONT_WEIGHT_BOLD_MAX,
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_3xl,
+            text_color: theme.primary_text.clone(),
+            line_spacing: theme.text_3xl * 0.1,
+            font_weight: theme.font_weight_bold_max,
             ..TextOptions::default()
         },
         "h2" => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_2XL,
-            text_color: PRIMARY_TEXT.to_string(),
-            line_spacing: TEXT_2XL * 0.4,
-            font_weight: FONT_WEIGHT_BOLD_MAX,
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_2xl,
+            text_color: theme.primary_text.clone(),
+            line_spacing: theme.text_2xl * 0.4,
+            font_weight: theme.font_weight_bold_max,
             ..TextOptions::default()
         },
 
         "h3" => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_XL,
-            text_color: PRIMARY_TEXT.to_string(),
-            line_spacing: TEXT_XL * 0.5,
-            font_weight: FONT_WEIGHT_BOLD_MD,
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_xl,
+            text_color: theme.primary_text.clone(),
+            line_spacing: theme.text_xl * 0.5,
+            font_weight: theme.font_weight_bold_md,
             ..TextOptions::default()
         },
 
         "h4" => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_LG,
-            text_color: SECONDARY_TEXT.to_string(),
-            line_spacing: TEXT_LG * 0.5,
-            font_weight: FONT_WEIGHT_BOLD_LIGHT,
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_lg,
+            text_color: theme.secondary_text.clone(),
+            line_spacing: theme.text_lg * 0.5,
+            font_weight: theme.font_weight_bold_light,
             ..TextOptions::default()
         },
 
         "h5" => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_BASE,
-            text_color: SECONDARY_TEXT.to_string(),
-            line_spacing: TEXT_BASE * 0.1,
-            font_weight: FONT_WEIGHT_BOLD_LIGHT,
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_base,
+            text_color: theme.secondary_text.clone(),
+            line_spacing: theme.text_base * 0.1,
+            font_weight: theme.font_weight_bold_light,
             ..TextOptions::default()
         },
 
         _ => TextOptions {
-            font_family: FONT_SANS.to_string(),
-            font_size: TEXT_2XL,
-            text_color: PRIMARY_TEXT.to_string(),
-            line_spacing: TEXT_2XL * 0.5,
-            font_weight: FONT_WEIGHT_BOLD_LIGHT,
+            font_family: theme.font_sans.clone(),
+            font_size: theme.text_2xl,
+            text_color: theme.primary_text.clone(),
+            line_spacing: theme.text_2xl * 0.5,
+            font_weight: theme.font_weight_bold_light,
             ..TextOptions::default()
         },
     };
@@ -468,6 +484,7 @@ fn document_title(
         width_behavior: w_size,
         height_behavior: SizeBehavior::Content,
         horizontal_alignment: HorizontalAlignment::Left,
+        stroke_style: StrokeStyle::default(),
     };
     let spacer = builder.new_spacer(
         format!("{}_spacer", id),
@@ -518,6 +535,7 @@ fn vstack(
     parser: &JsonLinesParser,
     children: Vec<DiagramTreeNode>,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     let mut final_children = Vec::<DiagramTreeNode>::new();
 
     for (ix, elem) in children.iter().enumerate() {
@@ -528,7 +546,7 @@ fn vstack(
             spacer_id,
             SpacerOptions {
                 width: 0.0,
-                height: SPACE_SM,
+                height: theme.space_sm,
                 direction: SpacerDirection::Vertical,
             },
         );
@@ -567,6 +585,7 @@ fn hstack(
     parser: &JsonLinesParser,
     children: Vec<DiagramTreeNode>,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     let mut final_children = Vec::<DiagramTreeNode>::new();
 
     for (ix, elem) in children.iter().enumerate() {
@@ -576,7 +595,7 @@ fn hstack(
         let spacer = builder.new_spacer(
             spacer_id,
             SpacerOptions {
-                width: SPACE_SM,
+                width: theme.space_sm,
                 height: 0.0,
                 direction: SpacerDirection::Horizontal,
             },
@@ -596,15 +615,16 @@ pub fn create_properties(
     builder: &mut DiagramBuilder,
     parser: &JsonLinesParser,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     let properties = get_properties(attrs, &["properties", "items"]);
     let title = get_string_attr(attrs, &["title", "meta"], "");
 
     let table_opts = TableOptions {
-        cell_padding: SPACE_SM,
+        cell_padding: theme.space_sm,
         with_header: false,
-        border_color: BORDER_LIGHT_COLOR.to_string(),
+        border_color: theme.border_light.clone(),
         border_width: 1,
-        fill_color: BG_SECONDARY.to_string(),
+        fill_color: theme.bg_secondary.clone(),
         ..Default::default()
     };
 
@@ -727,6 +747,7 @@ pub fn create_document_section(
     builder: &mut DiagramBuilder,
     parser: &JsonLinesParser,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     let mut section_children = Vec::new();
 
     // Extract attributes
@@ -741,7 +762,7 @@ pub fn create_document_section(
         section_children.push(header_node);
         let header_spacer_opts = SpacerOptions {
             width: 0.0,
-            height: SPACE_MD,
+            height: theme.space_md,
             direction: SpacerDirection::Vertical,
         };
         let header_spacer = builder.new_spacer(format!("{}_header_spacer", id), header_spacer_opts);
@@ -768,7 +789,7 @@ pub fn create_document_section(
         format!("{}_bottom_spacer", id),
         SpacerOptions {
             width: 0.0,
-            height: SPACE_XS,
+            height: theme.space_xs,
             direction: SpacerDirection::Vertical,
         },
     );
@@ -794,6 +815,7 @@ fn create_section_header(
     parser: &JsonLinesParser,
     width: Float,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     let mut header_children = Vec::new();
 
     // Create title if present using document.title component
@@ -829,7 +851,7 @@ fn create_section_header(
                 format!("{}_meta_spacer", id),
                 SpacerOptions {
                     width: 0.0,
-                    height: SPACE_XS,
+                    height: theme.space_xs,
                     direction: SpacerDirection::Vertical,
                 },
             );
@@ -850,13 +872,15 @@ fn create_section_header(
 }
 
 fn create_meta_text(builder: &mut DiagramBuilder, id: &str, text: &str) -> DiagramTreeNode {
+    let theme = DocumentTheme::for_builder(builder);
     let meta_options = TextOptions {
-        font_family: FONT_SANS.to_string(),
-        font_size: TEXT_XS,
-        text_color: MUTED_TEXT.to_string(),
+        font_family: theme.font_sans.clone(),
+        font_size: theme.text_xs,
+        text_color: theme.muted_text.clone(),
         line_width: 100,
-        line_spacing: LINE_HEIGHT_NORMAL,
+        line_spacing: theme.line_height_normal,
         font_weight: 400,
+        ..Default::default()
     };
     builder.new_text(id.to_string(), text, meta_options)
 }
@@ -870,6 +894,7 @@ fn create_columns_layout(
     builder: &mut DiagramBuilder,
     parser: &JsonLinesParser,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     let mut column_nodes = Vec::new();
 
     for (idx, column_id) in column_ids.iter().enumerate() {
@@ -886,6 +911,7 @@ fn create_columns_layout(
                     width_behavior: SizeBehavior::Content, // Size based on column content
                     height_behavior: SizeBehavior::Content,
                     horizontal_alignment: HorizontalAlignment::Left,
+                    stroke_style: StrokeStyle::default(),
                 };
 
                 let column_box = builder.new_box(
@@ -898,7 +924,7 @@ fn create_columns_layout(
                 if idx < column_ids.len() - 1 {
                     // Add some spacing between columns
                     let col_spacer_opts = SpacerOptions {
-                        width: SPACE_MD,
+                        width: theme.space_md,
                         height: 0.0,
                         direction: SpacerDirection::Horizontal,
                     };
@@ -946,6 +972,7 @@ pub fn create_bullet_list(
     builder: &mut DiagramBuilder,
     _: &JsonLinesParser,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     // Extract items array
     let items = get_array_attr(attrs, "items");
     let meta = get_string_attr(attrs, &["meta"], "");
@@ -977,7 +1004,7 @@ pub fn create_bullet_list(
                 format!("{}_item_spacer_{}", id, idx),
                 SpacerOptions {
                     width: 0.0,
-                    height: SPACE_SM,
+                    height: theme.space_sm,
                     direction: SpacerDirection::Vertical,
                 },
             );
@@ -997,7 +1024,7 @@ pub fn create_bullet_list(
         format!("{}_bottom_spacer", id),
         SpacerOptions {
             width: 0.0,
-            height: SPACE_MD,
+            height: theme.space_md,
             direction: SpacerDirection::Vertical,
         },
     );
@@ -1025,25 +1052,27 @@ fn create_list_item(
     builder: &mut DiagramBuilder,
     container_width: Float,
 ) -> Result<DiagramTreeNode> {
+    let theme = DocumentTheme::for_builder(builder);
     // Create bullet/marker
     let marker_options = TextOptions {
-        font_family: FONT_SANS.to_string(),
-        font_size: TEXT_BASE,
-        text_color: PRIMARY_TEXT.to_string(),
+        font_family: theme.font_sans.clone(),
+        font_size: theme.text_base,
+        text_color: theme.primary_text.clone(),
         line_width: 20,
-        line_spacing: TEXT_BASE * 0.4,
-        font_weight: FONT_WEIGHT_NORMAL,
+        line_spacing: theme.text_base * 0.4,
+        font_weight: theme.font_weight_normal,
+        ..Default::default()
     };
 
     let marker_node = builder.new_text(format!("{}_marker", id), marker, marker_options);
 
     // Create text content
     let mut text_options = TextOptions {
-        font_family: FONT_SANS.to_string(),
-        font_size: TEXT_BASE,
-        text_color: PRIMARY_TEXT.to_string(),
-        line_spacing: TEXT_BASE * 0.4,
-        font_weight: FONT_WEIGHT_NORMAL,
+        font_family: theme.font_sans.clone(),
+        font_size: theme.text_base,
+        text_color: theme.primary_text.clone(),
+        line_spacing: theme.text_base * 0.4,
+        font_weight: theme.font_weight_normal,
         ..Default::default()
     };
 
@@ -1062,6 +1091,7 @@ fn create_list_item(
         width_behavior: SizeBehavior::Content,
         height_behavior: SizeBehavior::Content,
         horizontal_alignment: HorizontalAlignment::Left,
+        stroke_style: StrokeStyle::default(),
     };
 
     let text_box = builder.new_box(format!("{}_text_box", id), text_node, text_box_options);
@@ -1069,7 +1099,7 @@ fn create_list_item(
     let spacer = builder.new_spacer(
         format!("{}_spacer", id),
         SpacerOptions {
-            width: SPACE_XS,
+            width: theme.space_xs,
             height: 0.0,
             direction: SpacerDirection::Horizontal,
         },
@@ -0,0 +1,266 @@
+// Runtime themes for the document components.
+//
+// The preset is picked from the name of the builder's `Theme`, which a
+// `document` root can switch with its `theme` attribute, so `light`, `dark`
+// and `high_contrast` documents share the same component code.
+
+use super::style::*;
+use crate::{DiagramBuilder, Float};
+
+/// Palette, type scale and spacing used by the `document.*` components
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentTheme {
+    pub name: String,
+
+    // Text colors
+    pub primary_text: String,
+    pub secondary_text: String,
+    pub muted_text: String,
+    pub accent_text: String,
+
+    // Background colors
+    pub bg_primary: String,
+    pub bg_secondary: String,
+    pub bg_muted: String,
+    pub bg_accent: String,
+
+    // Border colors
+    pub border_light: String,
+    pub border_medium: String,
+    pub border_strong: String,
+
+    // Status colors
+    pub success: String,
+    pub warning: String,
+    pub danger: String,
+    pub info: String,
+
+    // Typography
+    pub font_sans: String,
+    pub font_serif: String,
+    pub font_mono: String,
+    pub text_xs: Float,
+    pub text_sm: Float,
+    pub text_base: Float,
+    pub text_lg: Float,
+    pub text_xl: Float,
+    pub text_2xl: Float,
+    pub text_3xl: Float,
+    pub font_weight_normal: u32,
+    pub font_weight_bold_light: u32,
+    pub font_weight_bold_md: u32,
+    pub font_weight_bold_max: u32,
+    pub line_height_normal: Float,
+
+    // Spacing
+    pub space_xs: Float,
+    pub space_sm: Float,
+    pub space_md: Float,
+    pub space_lg: Float,
+    pub padding: Float,
+}
+
+impl DocumentTheme {
+    pub fn light() -> DocumentTheme {
+        DocumentTheme {
+            name: "light".to_string(),
+            primary_text: PRIMARY_TEXT.to_string(),
+            secondary_text: SECONDARY_TEXT.to_string(),
+            muted_text: MUTED_TEXT.to_string(),
+            accent_text: ACCENT_TEXT.to_string(),
+            bg_primary: BG_PRIMARY.to_string(),
+            bg_secondary: BG_SECONDARY.to_string(),
+            bg_muted: BG_MUTED.to_string(),
+            bg_accent: BG_ACCENT.to_string(),
+            border_light: BORDER_LIGHT_COLOR.to_string(),
+            border_medium: BORDER_MEDIUM_COLOR.to_string(),
+            border_strong: BORDER_STRONG_COLOR.to_string(),
+            success: SUCCESS.to_string(),
+            warning: WARNING.to_string(),
+            danger: DANGER.to_string(),
+            info: INFO.to_string(),
+            font_sans: FONT_SANS.to_string(),
+            font_serif: FONT_SERIF.to_string(),
+            font_mono: FONT_MONO.to_string(),
+            text_xs: TEXT_XS,
+            text_sm: TEXT_SM,
+            text_base: TEXT_BASE,
+            text_lg: TEXT_LG,
+            text_xl: TEXT_XL,
+            text_2xl: TEXT_2XL,
+            text_3xl: TEXT_3XL,
+            font_weight_normal: FONT_WEIGHT_NORMAL,
+            font_weight_bold_light: FONT_WEIGHT_BOLD_LIGHT,
+            font_weight_bold_md: FONT_WEIGHT_BOLD_MD,
+            font_weight_bold_max: FONT_WEIGHT_BOLD_MAX,
+            line_height_normal: LINE_HEIGHT_NORMAL,
+            space_xs: SPACE_XS,
+            space_sm: SPACE_SM,
+            space_md: SPACE_MD,
+            space_lg: SPACE_LG,
+            padding: PADDING_NORMAL,
+        }
+    }
+
+    pub fn dark() -> DocumentTheme {
+        DocumentTheme {
+            name: "dark".to_string(),
+            primary_text: "#e9ecef".to_string(),
+            secondary_text: "#ced4da".to_string(),
+            muted_text: "#adb5bd".to_string(),
+            accent_text: "#6ea8fe".to_string(),
+            bg_primary: "#1e1e1e".to_string(),
+            bg_secondary: "#2b2b2b".to_string(),
+            bg_muted: "#343a40".to_string(),
+            bg_accent: "#1c2c43".to_string(),
+            border_light: "#495057".to_string(),
+            border_medium: "#6c757d".to_string(),
+            border_strong: "#adb5bd".to_string(),
+            success: "#75b798".to_string(),
+            warning: "#ffda6a".to_string(),
+            danger: "#ea868f".to_string(),
+            info: "#6edff6".to_string(),
+            ..DocumentTheme::light()
+        }
+    }
+
+    /// Pure black and white with yellow accents, body text one step larger
+    pub fn high_contrast() -> DocumentTheme {
+        DocumentTheme {
+            name: "high_contrast".to_string(),
+            primary_text: "#ffffff".to_string(),
+            secondary_text: "#ffffff".to_string(),
+            muted_text: "#ffff00".to_string(),
+            accent_text: "#00ffff".to_string(),
+            bg_primary: "#000000".to_string(),
+            bg_secondary: "#000000".to_string(),
+            bg_muted: "#000000".to_string(),
+            bg_accent: "#000080".to_string(),
+            border_light: "#ffffff".to_string(),
+            border_medium: "#ffffff".to_string(),
+            border_strong: "#ffffff".to_string(),
+            success: "#00ff00".to_string(),
+            warning: "#ffff00".to_string(),
+            danger: "#ff4040".to_string(),
+            info: "#00ffff".to_string(),
+            text_xs: TEXT_SM,
+            text_sm: TEXT_BASE,
+            font_weight_normal: 500,
+            ..DocumentTheme::light()
+        }
+    }
+
+    /// Preset by name, `None` for unknown names
+    pub fn by_name(name: &str) -> Option<DocumentTheme> {
+        match name {
+            "light" => Some(DocumentTheme::light()),
+            "dark" => Some(DocumentTheme::dark()),
+            "high_contrast" | "high-contrast" => Some(DocumentTheme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// Preset matching the builder's theme, light for custom themes
+    pub fn for_builder(builder: &DiagramBuilder) -> DocumentTheme {
+        DocumentTheme::by_name(&builder.theme.name).unwrap_or_else(DocumentTheme::light)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{document_text, register_document_components};
+    use crate::parser::JsonLinesParser;
+    use crate::{Fill, Theme};
+
+    #[test]
+    fn test_presets() {
+        let light = DocumentTheme::light();
+        let dark = DocumentTheme::dark();
+        let high_contrast = DocumentTheme::high_contrast();
+
+        for (a, b) in [
+            (&light, &dark),
+            (&light, &high_contrast),
+            (&dark, &high_contrast),
+        ] {
+            assert_ne!(a.bg_primary, b.bg_primary);
+            assert_ne!(a.primary_text, b.primary_text);
+        }
+        // Dark only recolors, high contrast also enlarges the small text
+        assert_eq!(dark.text_sm, light.text_sm);
+        assert!(high_contrast.text_sm > light.text_sm);
+
+        assert_eq!(DocumentTheme::by_name("high-contrast"), Some(high_contrast));
+        assert_eq!(DocumentTheme::by_name("sepia"), None);
+
+        let mut builder = DiagramBuilder::new();
+        assert_eq!(DocumentTheme::for_builder(&builder), light);
+        builder.theme = Theme::dark();
+        assert_eq!(DocumentTheme::for_builder(&builder), dark);
+    }
+
+    #[test]
+    fn test_component_follows_builder_theme() {
+        let text_color = |theme: Theme| {
+            let mut builder = DiagramBuilder::new();
+            builder.theme = theme;
+            document_text("t", &mut builder, "default".into(), "Hello".into(), 200.0).unwrap();
+            builder
+                .get_text("t_text".to_string())
+                .text_options
+                .text_color
+                .clone()
+        };
+
+        assert_eq!(
+            text_color(Theme::light()),
+            DocumentTheme::light().primary_text
+        );
+        assert_eq!(
+            text_color(Theme::dark()),
+            DocumentTheme::dark().primary_text
+        );
+    }
+
+    #[test]
+    fn test_document_theme_attribute() {
+        let build = |document: &str| {
+            let input = format!(
+                "{}\n{}",
+                document, r#"{"id":"body","type":"document.text","text":"Hello"}"#
+            );
+            let mut parser = JsonLinesParser::new();
+            let root_id = parser.parse_string(&input).unwrap();
+            let mut builder = DiagramBuilder::new();
+            register_document_components(&mut builder);
+            parser.build(&root_id, &mut builder).unwrap();
+
+            // The document's theme only applies while it is built
+            assert_eq!(builder.theme.name, "light");
+            (
+                builder
+                    .get_box("doc".to_string())
+                    .box_options
+                    .fill_color
+                    .clone(),
+                builder
+                    .get_text("body_text".to_string())
+                    .text_options
+                    .text_color
+                    .clone(),
+            )
+        };
+
+        let (light_fill, light_text) =
+            build(r#"{"id":"doc","type":"document","content_id":"body"}"#);
+        let (dark_fill, dark_text) =
+            build(r#"{"id":"doc","type":"document","theme":"dark","content_id":"body"}"#);
+
+        let dark = DocumentTheme::dark();
+        assert_eq!(light_fill, Fill::Color(DocumentTheme::light().bg_primary));
+        assert_eq!(dark_fill, Fill::Color(dark.bg_primary));
+        assert_ne!(light_text, dark_text);
+        assert_eq!(dark_text, dark.primary_text);
+    }
+}
@@ -1,7 +1,6 @@
 // src/bin/arrows_ellipses_demo.rs
 // Demo program to test arrows and ellipses positioning
 
-use demo::measure_text::measure_text_svg_character_advance;
 use std::fs::File;
 use std::path::Path;
 use volare_engine_layout::*;
@@ -130,7 +129,6 @@ fn generate_svg_from_jsonl(
 
     // Create a fresh builder for parsing
     let mut parse_builder = DiagramBuilder::new();
-    parse_builder.set_measure_text_fn(measure_text_svg_character_advance);
 
     // Build the diagram
     let diagram = parser.build(&root_id, &mut parse_builder)?;
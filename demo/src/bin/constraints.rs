@@ -1,7 +1,6 @@
 // src/bin/intricate_city.rs
 // Demo program that generates an intricate city using only polylines, rects, and free containers
 
-use demo::measure_text::measure_text_svg_character_advance;
 use resvg::usvg::roxmltree::Children;
 use uuid::fmt::Simple;
 use volare_engine_layout::diagram_builder::DiagramTreeNode;
@@ -15,7 +14,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let output_dir = std::env::temp_dir().join("constraints_demo");
     std::fs::create_dir_all(&output_dir)?;
     let output_path = output_dir.join("constraints1.svg");
-    builder.set_measure_text_fn(measure_text_svg_character_advance);
     let children = vec![builder.new_rectangle(
         "r1".to_string(),
         RectOptions {
@@ -25,6 +23,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             stroke_color: "black".to_string(),
             stroke_width: 1.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         },
     ),
     builder.new_rectangle(
@@ -36,6 +35,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             stroke_color: "black".to_string(),
             stroke_width: 1.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         },
     ),
     builder.new_rectangle(
@@ -47,6 +47,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             stroke_color: "black".to_string(),
             stroke_width: 1.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         },
     ),
       builder.new_rectangle(
@@ -58,6 +59,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             stroke_color: "black".to_string(),
             stroke_width: 1.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         },
     )
     ];
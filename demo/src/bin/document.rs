@@ -2,7 +2,6 @@
 // Demo program that generates an intricate city using only polylines, rects, and free containers
 
 use custom_components::register_all_components;
-use demo::measure_text::measure_text_svg_character_advance;
 use std::fs::File;
 use std::path::Path;
 use volare_engine_layout::*;
@@ -36,7 +35,6 @@ let input = r##"{"id":"root","type":"activity_diagram","swimlanes":[{"name":"Use
 
     // Create a fresh builder for parsing
     let mut parse_builder = DiagramBuilder::new();
-    parse_builder.set_measure_text_fn(measure_text_svg_character_advance);
     register_all_components(&mut parse_builder);
 
     // Build the diagram
@@ -2,14 +2,13 @@
 
 //import svg_renderer
 // use image_renderer::PNGRenderer;
-use demo::measure_text::measure_text_ultra_tight;
 use svg_renderer::SVGRenderer;
 use volare_engine_layout::{renderer_base::Renderer, BoxOptions, GradientStop, LineOptions};
 
 //import layout
 use volare_engine_layout::{
     diagram_builder::DiagramTreeNode, layout::layout_tree_node, DiagramBuilder, EllipseOptions,
-    Fill, TableOptions, TextOptions,
+    Fill, FontStyle, TableOptions, TextOptions,
 };
 //import io modules to write to file
 use std::fs::File;
@@ -17,8 +16,13 @@ use std::fs::File;
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     //create session
     let mut session = DiagramBuilder::new();
+    session.fonts.register_font_file(
+        "AnonymicePro Nerd Font",
+        400,
+        FontStyle::Normal,
+        concat!(env!("CARGO_MANIFEST_DIR"), "/assets/AnonymiceProNerdFont-Regular.ttf"),
+    )?;
 
-    session.set_measure_text_fn(measure_text_ultra_tight);
 
     let mut table_items = Vec::new();
 
@@ -1,7 +1,6 @@
 // src/bin/visual_experiments.rs
 // Demo program that generates SVGs for visual experiments using JSONL format
 
-use demo::measure_text::measure_text_svg_character_advance;
 use std::fs::File;
 use std::path::Path;
 use volare_engine_layout::*;
@@ -264,7 +263,6 @@ fn generate_svg_from_jsonl(
 
     // Create a fresh builder for parsing
     let mut parse_builder = DiagramBuilder::new();
-    parse_builder.set_measure_text_fn(measure_text_svg_character_advance);
 
     // Build the diagram
     let diagram = parser.build(&root_id, &mut parse_builder)?;
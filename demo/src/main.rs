@@ -8,21 +8,26 @@ use image_renderer::PNGRenderer;
 use resvg::tiny_skia::Rect;
 use svg_renderer::SVGRenderer;
 use volare_engine_layout::{renderer_base::Renderer, BoxOptions, GradientStop, LineOptions};
-use volare_engine_layout::{RectOptions, SimpleConstraint, SizeBehavior};
+use volare_engine_layout::{RectOptions, SimpleConstraint, SizeBehavior, StrokeStyle};
 
 //import layout
 use volare_engine_layout::{
     diagram_builder::DiagramTreeNode, layout::layout_tree_node, DiagramBuilder, EllipseOptions,
-    Fill, TableOptions, TextOptions,
+    Fill, FontStyle, TableOptions, TextOptions,
 };
 //import io modules to write to file
-use measure_text::measure_text_svg_character_advance; // Use the ultra-tight measurement for text
 
 use std::fs::File;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     //create session
     let mut session = DiagramBuilder::new();
+    session.fonts.register_font_file(
+        "AnonymicePro Nerd Font",
+        400,
+        FontStyle::Normal,
+        concat!(env!("CARGO_MANIFEST_DIR"), "/assets/AnonymiceProNerdFont-Regular.ttf"),
+    )?;
     let text_options = TextOptions {
         font_family: "AnonymicePro Nerd Font".to_string(),
         font_size: 12.0,
@@ -31,7 +36,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         line_spacing: 0.0,
         ..Default::default()
     };
-    session.set_measure_text_fn(measure_text_svg_character_advance);
 
     //Create a polyline for a star
     let mut points = Vec::new();
@@ -54,6 +58,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         LineOptions {
             stroke_color: "black".to_string(),
             stroke_width: 1.0,
+            stroke_style: StrokeStyle::default(),
         },
     );
 
@@ -103,6 +108,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         LineOptions {
             stroke_color: "blue".to_string(),
             stroke_width: 2.0,
+            stroke_style: StrokeStyle::default(),
         },
     );
 
@@ -11,8 +11,12 @@ use std::path::Path;
 use volare_engine_layout::Float;
 
 use volare_engine_layout::{
-    diagram_builder::DiagramTreeNode, ConnectorType, DiagramBuilder, EntityID, EntityType, Fill,
-    LinePointReference, OrthogonalRoutingStrategy, Point, Renderer, RendererError,
+    arc_path, dash_polyline, diagram_builder::DiagramTreeNode, graphemes,
+    ArrowAnchor, ConnectorGeometry, FillRule, MarkerGeometry, MarkerPaint, PathCommand,
+    point_along_polyline, polyline_length, resolve_gradient_stops, runs_right_to_left,
+    visual_order, BlendMode, Color, DiagramBuilder, EntityID, EntityType, Fill, FontRun, FontStyle,
+    LabelAlignment, Layer, LayerRoot, LineCap, LineJoin, PathData, Pattern, PatternFill,
+    Point, Renderer, RendererError, ShadowOptions, StrokeStyle, TextDecoration, TextPathOptions,
 };
 
 /**
@@ -45,8 +49,9 @@ impl<W: Write> Renderer<W> for PNGRenderer {
 
         println!("Creating PNG image with dimensions: {}x{}", width, height);
 
-        // Create an image with a white background
-        let mut imgbuf = RgbaImage::from_fn(width, height, |_, _| Rgba([255, 255, 255, 255]));
+        // PNGs always get a canvas, white unless the theme sets one
+        let background = parse_color(session.theme.background.as_deref().unwrap_or("white"));
+        let mut imgbuf = RgbaImage::from_fn(width, height, |_, _| background);
 
         // Draw a border to see image bounds (for debugging)
         let border_color = Rgba([200, 200, 200, 255]);
@@ -59,8 +64,16 @@ impl<W: Write> Renderer<W> for PNGRenderer {
             imgbuf.put_pixel(width - 1, y, border_color);
         }
 
-        // Render using absolute positions from cache
-        render_node(diagram_node, session, &mut imgbuf, scaling_factor);
+        // Render using absolute positions from cache, one pass per layer
+        for layer in Layer::ALL {
+            if layer == Layer::Content && !session.layer_roots.contains_key(&diagram_node.entity_id)
+            {
+                render_node(diagram_node, session, &mut imgbuf, scaling_factor);
+            }
+            for root in session.layer_entries(layer) {
+                render_layer_root(root, session, &mut imgbuf, scaling_factor);
+            }
+        }
 
         // Write the PNG image to the stream
         let encoder = image::png::PngEncoder::new(stream);
@@ -83,6 +96,55 @@ fn render_node(
     session: &DiagramBuilder,
     imgbuf: &mut RgbaImage,
     scale: Float,
+) {
+    // Translucent, clipped, masked and blended entities are drawn, children
+    // included, on their own layer
+    let opacity = session.get_opacity(node.entity_id.clone()).opacity;
+    let mask = clip_path_and_mask(&node.entity_id, session, imgbuf, scale);
+    let blend_mode = session.get_layer_options(node.entity_id.clone()).blend_mode;
+    with_masked_layer(imgbuf, opacity, mask.as_deref(), blend_mode, |layer| {
+        render_node_contents(node, session, layer, scale)
+    });
+}
+
+/// An entity moved to a layer, drawn through the opacity, clips and masks of
+/// the entities it is nested in as if it had stayed in the tree
+fn render_layer_root(
+    root: &LayerRoot,
+    session: &DiagramBuilder,
+    imgbuf: &mut RgbaImage,
+    scale: Float,
+) {
+    let mut opacity = 1.0;
+    let mut mask: Option<Vec<Float>> = None;
+    for ancestor in &root.ancestors {
+        opacity *= session.get_opacity(ancestor.clone()).opacity;
+        let clipped = session
+            .get_clip(ancestor.clone())
+            .is_some_and(|clip| clip.clip);
+        let outline = clipped.then(|| outline_coverage(session, ancestor, imgbuf, scale));
+        let shapes = clip_path_and_mask(ancestor, session, imgbuf, scale);
+        for coverage in outline.into_iter().chain(shapes) {
+            mask = Some(match mask {
+                Some(mask) => mask.iter().zip(coverage).map(|(m, c)| m * c).collect(),
+                None => coverage,
+            });
+        }
+    }
+    with_masked_layer(
+        imgbuf,
+        opacity,
+        mask.as_deref(),
+        BlendMode::Normal,
+        |layer| render_node(&root.node, session, layer, scale),
+    );
+}
+
+fn render_node_contents(
+    node: &DiagramTreeNode,
+    session: &DiagramBuilder,
+    imgbuf: &mut RgbaImage,
+    scale: Float,
 ) {
     let entity_id = node.entity_id.clone();
 
@@ -216,6 +278,16 @@ fn render_node(
         EntityType::ConstraintLayoutContainer => {
             render_constraint_layout(session, imgbuf, node, scale);
         }
+        EntityType::PathShape => {
+            render_path(
+                session,
+                imgbuf,
+                entity_id.clone(),
+                screen_x,
+                screen_y,
+                scale,
+            );
+        }
         _ => {}
     }
 }
@@ -227,9 +299,11 @@ fn render_group(
     scale: Float,
 ) {
     // Groups just render their children
-    for child in node.children.iter() {
-        render_node(child, session, imgbuf, scale);
-    }
+    clip_children(session, &node.entity_id, imgbuf, scale, |imgbuf| {
+        for child in session.paint_order(node) {
+            render_node(child, session, imgbuf, scale);
+        }
+    });
 }
 
 fn render_box(
@@ -261,31 +335,229 @@ fn render_box(
 
     let rect = Rect::at(x, y).of_size(width, height);
 
+    let opacity = session.get_opacity(entity_id.clone());
+
+    if let Some(shadow) = session.get_shadow(entity_id.clone()) {
+        draw_shadow(imgbuf, shadow, rect, scale, |_, _| 1.0);
+    }
+
     // Fill
-    match &box_shape.box_options.fill_color {
+    fill_rect(
+        session,
+        imgbuf,
+        rect,
+        &box_shape.box_options.fill_color,
+        opacity.fill_opacity,
+        scale,
+    );
+
+    // Stroke
+    let stroke_color = parse_color(&box_shape.box_options.stroke_color);
+    let stroke_width = box_shape.box_options.stroke_width * scale;
+    let stroke_style = &box_shape.box_options.stroke_style;
+    stroke_layer(
+        imgbuf,
+        stroke_color,
+        opacity.stroke_opacity,
+        |layer, color| {
+            draw_styled_rect_border(layer, rect, stroke_width, color, stroke_style, scale);
+        },
+    );
+
+    // Render children (they have their own absolute positions)
+    clip_children(session, &entity_id, imgbuf, scale, |imgbuf| {
+        for child in session.paint_order(node) {
+            render_node(child, session, imgbuf, scale);
+        }
+    });
+}
+
+/// Paint a rectangle with a color, a gradient or a pattern. Gradients are
+/// evaluated per pixel in bounding box units, the same space the SVG
+/// renderer uses.
+fn fill_rect(
+    session: &DiagramBuilder,
+    imgbuf: &mut RgbaImage,
+    rect: Rect,
+    fill: &Fill,
+    opacity: Float,
+    scale: Float,
+) {
+    let stops = match fill {
         Fill::Color(color) => {
-            let rgba = parse_color(color);
-            draw_filled_rect_mut(imgbuf, rect, rgba);
+            fill_solid_rect(imgbuf, rect, with_opacity(parse_color(color), opacity));
+            return;
         }
-        _ => {
-            draw_filled_rect_mut(imgbuf, rect, Rgba([255, 255, 255, 255]));
+        Fill::Pattern(pattern) => {
+            fill_pattern(session, imgbuf, rect, pattern, opacity, scale);
+            return;
         }
+        Fill::LinearGradient(gradient) => &gradient.stops,
+        Fill::RadialGradient(gradient) => &gradient.stops,
+    };
+
+    // (offset, rgba) with the stop opacity folded into alpha
+    let stops: Vec<(Float, [Float; 4])> = resolve_gradient_stops(stops)
+        .iter()
+        .map(|stop| {
+            let c = parse_color(&stop.color);
+            let alpha = c[3] as Float * stop.opacity * opacity;
+            (
+                stop.offset,
+                [c[0] as Float, c[1] as Float, c[2] as Float, alpha],
+            )
+        })
+        .collect();
+    if stops.is_empty() {
+        return;
     }
 
-    // Stroke
-    let stroke_color = parse_color(&box_shape.box_options.stroke_color);
-    let stroke_width = (box_shape.box_options.stroke_width * scale).ceil() as u32;
+    let (width, height) = (rect.width() as Float, rect.height() as Float);
+    for py in 0..rect.height() {
+        for px in 0..rect.width() {
+            let u = (px as Float + 0.5) / width;
+            let v = (py as Float + 0.5) / height;
+            let t = match fill {
+                Fill::LinearGradient(g) => {
+                    let (dx, dy) = (g.x2 - g.x1, g.y2 - g.y1);
+                    let length_sq = dx * dx + dy * dy;
+                    if length_sq == 0.0 {
+                        0.0
+                    } else {
+                        ((u - g.x1) * dx + (v - g.y1) * dy) / length_sq
+                    }
+                }
+                Fill::RadialGradient(g) => {
+                    let distance = ((u - g.cx).powi(2) + (v - g.cy).powi(2)).sqrt();
+                    if g.r == 0.0 {
+                        1.0
+                    } else {
+                        distance / g.r
+                    }
+                }
+                Fill::Color(_) | Fill::Pattern(_) => 0.0,
+            };
 
-    for i in 0..stroke_width {
-        let inner_rect = Rect::at(x + i as i32, y + i as i32)
-            .of_size(width.saturating_sub(2 * i), height.saturating_sub(2 * i));
-        draw_hollow_rect_mut(imgbuf, inner_rect, stroke_color);
+            let [r, g, b, a] = gradient_color_at(&stops, t.clamp(0.0, 1.0));
+            blend_pixel(
+                imgbuf,
+                rect.left() + px as i32,
+                rect.top() + py as i32,
+                Rgba([r as u8, g as u8, b as u8, 255]),
+                a / 255.0,
+            );
+        }
     }
+}
 
-    // Render children (they have their own absolute positions)
-    for child in node.children.iter() {
-        render_node(child, session, imgbuf, scale);
+/// Tiles a pattern over a rectangle from its top-left corner, rotated around
+/// that corner. Hatches are evaluated per pixel with a one pixel soft edge,
+/// entity tiles are rendered once and sampled.
+fn fill_pattern(
+    session: &DiagramBuilder,
+    imgbuf: &mut RgbaImage,
+    rect: Rect,
+    pattern: &PatternFill,
+    opacity: Float,
+    scale: Float,
+) {
+    if let Some(background) = &pattern.background {
+        fill_solid_rect(imgbuf, rect, with_opacity(parse_color(background), opacity));
+    }
+
+    let entity_size = match &pattern.pattern {
+        Pattern::Entity(tile) => session.get_size(tile.clone()),
+        _ => (0.0, 0.0),
+    };
+    let (tile_width, tile_height) = pattern.tile_size(entity_size);
+    if tile_width <= 0.0 || tile_height <= 0.0 {
+        return;
+    }
+    let (tile_width, tile_height) = (tile_width * scale, tile_height * scale);
+
+    let tile_image = match &pattern.pattern {
+        Pattern::Entity(tile) => {
+            let tile = match session.get_pattern_tile(tile.clone()) {
+                Some(tile) => tile,
+                None => return,
+            };
+            let mut image = RgbaImage::new(
+                (entity_size.0 * scale).ceil().max(1.0) as u32,
+                (entity_size.1 * scale).ceil().max(1.0) as u32,
+            );
+            render_node(tile, session, &mut image, scale);
+            Some(image)
+        }
+        _ => None,
+    };
+
+    let color = parse_color(&pattern.color);
+    let half_width = pattern.line_width * scale / 2.0;
+    let edge = |distance: Float| (half_width - distance + 0.5).clamp(0.0, 1.0);
+    let (sin, cos) = (-pattern.angle.to_radians()).sin_cos();
+    let center = (tile_width / 2.0, tile_height / 2.0);
+    for py in 0..rect.height() {
+        for px in 0..rect.width() {
+            // Pixel center in pattern space, wrapped into one tile
+            let (x, y) = (px as Float + 0.5, py as Float + 0.5);
+            let u = (x * cos - y * sin).rem_euclid(tile_width);
+            let v = (x * sin + y * cos).rem_euclid(tile_height);
+
+            let (pixel, coverage) = match (&pattern.pattern, &tile_image) {
+                (Pattern::Diagonal, _) => (color, edge((v - center.1).abs())),
+                (Pattern::Cross | Pattern::Grid, _) => (
+                    color,
+                    edge((v - center.1).abs()).max(edge((u - center.0).abs())),
+                ),
+                (Pattern::Dots, _) => {
+                    let distance = ((u - center.0).powi(2) + (v - center.1).powi(2)).sqrt();
+                    (color, edge(distance - half_width))
+                }
+                (Pattern::Entity(_), Some(image)) => {
+                    let (tx, ty) = (u as u32, v as u32);
+                    if tx >= image.width() || ty >= image.height() {
+                        continue;
+                    }
+                    (*image.get_pixel(tx, ty), 1.0)
+                }
+                (Pattern::Entity(_), None) => continue,
+            };
+            if coverage > 0.0 {
+                blend_pixel(
+                    imgbuf,
+                    rect.left() + px as i32,
+                    rect.top() + py as i32,
+                    pixel,
+                    coverage * opacity,
+                );
+            }
+        }
+    }
+}
+
+/// Linear interpolation between the two stops around `t`
+fn gradient_color_at(stops: &[(Float, [Float; 4])], t: Float) -> [Float; 4] {
+    let first = stops[0];
+    if t <= first.0 {
+        return first.1;
+    }
+    for pair in stops.windows(2) {
+        let ((from_offset, from), (to_offset, to)) = (pair[0], pair[1]);
+        if t <= to_offset {
+            let span = to_offset - from_offset;
+            let f = if span > 0.0 {
+                (t - from_offset) / span
+            } else {
+                1.0
+            };
+            let mut color = [0.0; 4];
+            for i in 0..4 {
+                color[i] = from[i] + (to[i] - from[i]) * f;
+            }
+            return color;
+        }
     }
+    stops[stops.len() - 1].1
 }
 
 fn render_rectangle(
@@ -316,24 +588,30 @@ fn render_rectangle(
 
     let rect = Rect::at(x, y).of_size(width, height);
 
-    match &rect_shape.rect_options.fill_color {
-        Fill::Color(color) => {
-            let rgba = parse_color(color);
-            draw_filled_rect_mut(imgbuf, rect, rgba);
-        }
-        _ => {
-            draw_filled_rect_mut(imgbuf, rect, Rgba([255, 255, 255, 255]));
-        }
+    let opacity = session.get_opacity(entity_id.clone());
+    if let Some(shadow) = session.get_shadow(entity_id.clone()) {
+        draw_shadow(imgbuf, shadow, rect, scale, |_, _| 1.0);
     }
+    fill_rect(
+        session,
+        imgbuf,
+        rect,
+        &rect_shape.rect_options.fill_color,
+        opacity.fill_opacity,
+        scale,
+    );
 
     let stroke_color = parse_color(&rect_shape.rect_options.stroke_color);
-    let stroke_width = (rect_shape.rect_options.stroke_width * scale).ceil() as u32;
-
-    for i in 0..stroke_width {
-        let inner_rect = Rect::at(x + i as i32, y + i as i32)
-            .of_size(width.saturating_sub(2 * i), height.saturating_sub(2 * i));
-        draw_hollow_rect_mut(imgbuf, inner_rect, stroke_color);
-    }
+    let stroke_width = rect_shape.rect_options.stroke_width * scale;
+    let stroke_style = &rect_shape.rect_options.stroke_style;
+    stroke_layer(
+        imgbuf,
+        stroke_color,
+        opacity.stroke_opacity,
+        |layer, color| {
+            draw_styled_rect_border(layer, rect, stroke_width, color, stroke_style, scale);
+        },
+    );
 }
 fn render_text(
     session: &DiagramBuilder,
@@ -344,16 +622,34 @@ fn render_text(
     scale: Float,
 ) {
     let text_shape = session.get_text(entity_id.clone());
-    let size = session.get_size(entity_id.clone());
-
-    let font_data = include_bytes!("../../demo/assets/AnonymiceProNerdFont-Regular.ttf");
-  
-    let font = Font::try_from_bytes(font_data as &[u8]).unwrap();
+    let text_options = &text_shape.text_options;
+
+    // Same face the layout engine measured this text with
+    let face = match session.fonts.resolve_for(text_options) {
+        Some(face) => face,
+        None => {
+            println!("⚠️ No font registered, skipping text {}", entity_id);
+            return;
+        }
+    };
 
-    let text_color = parse_color(&text_shape.text_options.text_color);
-    let font_size = text_shape.text_options.font_size;
+    let font_size = text_options.font_size;
     let font_scale = Scale::uniform(font_size * scale);
 
+    // Slant the glyphs when italic is requested but no italic face is registered
+    let synthetic_italic =
+        text_options.font_style == FontStyle::Italic && face.style != FontStyle::Italic;
+
+    let style = TextRunStyle {
+        color: with_opacity(
+            parse_color(&text_options.text_color),
+            session.get_opacity(entity_id.clone()).fill_opacity,
+        ),
+        letter_spacing: text_options.letter_spacing * scale,
+        slant: if synthetic_italic { 0.2 } else { 0.0 },
+        decoration: text_options.text_decoration,
+    };
+
     for line_id in text_shape.lines.iter() {
         let line = session.get_text_line(line_id.clone());
 
@@ -364,63 +660,263 @@ fn render_text(
         let line_screen_x = (screen_x + line_local_pos.0 * scale) as i32;
         let line_screen_y = (screen_y + line_local_pos.1 * scale) as i32;
 
+        // Glyphs are placed left to right, so RTL runs are reordered first
+        let visual_text = visual_order(&text_options.display_text(&line.text));
+        let runs = session.fonts.font_runs(&visual_text, text_options);
+
         draw_high_quality_text(
             imgbuf,
-            &line.text,
+            &runs,
             line_screen_x,
             line_screen_y,
-            &font,
+            &face.font(),
             font_scale,
-            text_color,
-            (size.0 * scale) as i32,
+            &style,
         );
     }
 }
 
+// Paint attributes for a run of glyphs (already in screen units)
+struct TextRunStyle {
+    color: Rgba<u8>,
+    letter_spacing: f32,
+    // horizontal shear applied to glyphs (synthetic italic)
+    slant: f32,
+    decoration: TextDecoration,
+}
+
 fn draw_high_quality_text(
     imgbuf: &mut RgbaImage,
-    text: &str,
+    runs: &[FontRun],
     x: i32,
     y: i32,
-    font: &Font,
+    baseline_font: &Font,
     scale: Scale,
-    color: Rgba<u8>,
-    _max_width: i32,
+    style: &TextRunStyle,
 ) {
-    let v_metrics = font.v_metrics(scale);
+    let color = style.color;
+    // All runs share the baseline of the primary face
+    let v_metrics = baseline_font.v_metrics(scale);
     let offset_y = v_metrics.ascent;
     let mut caret = rusttype::point(0.0, offset_y);
-    let mut last_glyph_id = None;
     let mut glyphs: Vec<rusttype::PositionedGlyph> = Vec::new();
 
-    for c in text.chars() {
-        let base_glyph = font.glyph(c);
+    for run in runs {
+        let font = run.face.font();
+        // Kerning only applies between glyphs of the same face
+        let mut last_glyph_id = None;
 
-        if let Some(previous) = last_glyph_id {
-            caret.x += font.pair_kerning(scale, previous, base_glyph.id());
-        }
+        for grapheme in graphemes(&run.text) {
+            for c in grapheme.chars() {
+                let base_glyph = font.glyph(c);
+
+                if let Some(previous) = last_glyph_id {
+                    caret.x += font.pair_kerning(scale, previous, base_glyph.id());
+                }
 
-        last_glyph_id = Some(base_glyph.id());
-        let advance_width = base_glyph.scaled(scale).h_metrics().advance_width;
-        let positioned_glyph = font.glyph(c).scaled(scale).positioned(caret);
-        glyphs.push(positioned_glyph);
-        caret.x += advance_width;
+                last_glyph_id = Some(base_glyph.id());
+                let advance_width = base_glyph.scaled(scale).h_metrics().advance_width;
+                let positioned_glyph = font.glyph(c).scaled(scale).positioned(caret);
+                glyphs.push(positioned_glyph);
+                caret.x += advance_width;
+            }
+            caret.x += style.letter_spacing;
+        }
     }
 
     for glyph in &glyphs {
         if let Some(bounding_box) = glyph.pixel_bounding_box() {
             glyph.draw(|gx, gy, glyph_opacity| {
-                let px = x + bounding_box.min.x + gx as i32;
-                let py = y + bounding_box.min.y + gy as i32;
+                let glyph_y = bounding_box.min.y + gy as i32;
+                let shear = ((offset_y - glyph_y as f32) * style.slant).round() as i32;
+                let px = x + bounding_box.min.x + gx as i32 + shear;
+                let py = y + glyph_y;
 
                 if px >= 0 && px < imgbuf.width() as i32 && py >= 0 && py < imgbuf.height() as i32 {
-                    let alpha = (glyph_opacity * color[3] as f32) as u8;
-                    let antialiased_color = Rgba([color[0], color[1], color[2], alpha]);
-                    blend_pixel(imgbuf, px, py, antialiased_color, glyph_opacity);
+                    blend_pixel(imgbuf, px, py, color, glyph_opacity);
                 }
             });
         }
     }
+
+    if style.decoration.is_none() || glyphs.is_empty() {
+        return;
+    }
+
+    // Decoration lines span the advance of the run, without the trailing letter spacing
+    let run_width = (caret.x - style.letter_spacing).max(0.0);
+    let thickness = (scale.y / 14.0).max(1.0);
+    if style.decoration.underline {
+        draw_text_decoration_line(
+            imgbuf,
+            x,
+            y as f32 + offset_y + scale.y * 0.1,
+            run_width,
+            thickness,
+            color,
+        );
+    }
+    if style.decoration.line_through {
+        draw_text_decoration_line(
+            imgbuf,
+            x,
+            y as f32 + offset_y - scale.y * 0.28,
+            run_width,
+            thickness,
+            color,
+        );
+    }
+}
+
+/// Places glyphs one by one along a path, each rotated to the direction of the
+/// path at its center. `path` is in layout units, `origin` in screen pixels.
+fn draw_text_on_path(
+    imgbuf: &mut RgbaImage,
+    session: &DiagramBuilder,
+    path: &PathData,
+    origin: (Float, Float),
+    scale: Float,
+    label: &str,
+    label_path: &TextPathOptions,
+) {
+    let text_options = &label_path.text_options;
+    if session.fonts.resolve_for(text_options).is_none() {
+        return;
+    }
+
+    let mut points: Vec<(Float, Float)> = match path.flatten().into_iter().next() {
+        Some(points) => points
+            .iter()
+            .map(|(x, y)| (origin.0 + x * scale, origin.1 + y * scale))
+            .collect(),
+        None => return,
+    };
+
+    // Keep the text upright, Start stays near the source
+    let mut alignment = label_path.alignment.clone();
+    if runs_right_to_left(&points) {
+        points.reverse();
+        alignment = match alignment {
+            LabelAlignment::Start => LabelAlignment::End,
+            LabelAlignment::End => LabelAlignment::Start,
+            LabelAlignment::Center => LabelAlignment::Center,
+        };
+    }
+
+    let font_scale = Scale::uniform(text_options.font_size * scale);
+    let letter_spacing = text_options.letter_spacing * scale;
+    let visual_text = visual_order(&text_options.display_text(label));
+
+    // (glyph, distance of its left edge from the start of the text)
+    let mut glyphs: Vec<(rusttype::ScaledGlyph, Float, Float)> = Vec::new();
+    let mut caret = 0.0;
+    for run in session.fonts.font_runs(&visual_text, text_options) {
+        let font = run.face.font();
+        let mut last_glyph_id = None;
+        for grapheme in graphemes(&run.text) {
+            for c in grapheme.chars() {
+                let glyph = font.glyph(c).scaled(font_scale);
+                if let Some(previous) = last_glyph_id {
+                    caret += font.pair_kerning(font_scale, previous, glyph.id());
+                }
+                last_glyph_id = Some(glyph.id());
+                let advance = glyph.h_metrics().advance_width;
+                glyphs.push((glyph, caret, advance));
+                caret += advance;
+            }
+            caret += letter_spacing;
+        }
+    }
+    let text_width = (caret - letter_spacing).max(0.0);
+
+    let length = polyline_length(&points);
+    if length == 0.0 {
+        return;
+    }
+    let start_distance = match alignment {
+        LabelAlignment::Start => length * 0.05,
+        LabelAlignment::Center => (length - text_width) / 2.0,
+        LabelAlignment::End => length * 0.95 - text_width,
+    };
+
+    let color = parse_color(&text_options.text_color);
+    let baseline_offset = label_path.offset * scale;
+
+    for (glyph, glyph_x, advance) in glyphs {
+        let center_distance = start_distance + glyph_x + advance / 2.0;
+        let ((px, py), angle) = point_along_polyline(&points, center_distance);
+        let (sin, cos) = angle.sin_cos();
+        // Baseline center of the glyph, moved along the normal that points "up" for the text
+        let base_x = px + sin * baseline_offset;
+        let base_y = py - cos * baseline_offset;
+
+        // Rasterize unrotated with the baseline center at the origin
+        let positioned = glyph.positioned(rusttype::point(-advance / 2.0, 0.0));
+        let bounding_box = match positioned.pixel_bounding_box() {
+            Some(bb) => bb,
+            None => continue,
+        };
+        let glyph_w = bounding_box.width() as usize;
+        let glyph_h = bounding_box.height() as usize;
+        let mut coverage = vec![0.0f32; glyph_w * glyph_h];
+        positioned.draw(|gx, gy, v| coverage[gy as usize * glyph_w + gx as usize] = v);
+
+        let sample = |lx: Float, ly: Float| -> Float {
+            let gx = lx - bounding_box.min.x as Float - 0.5;
+            let gy = ly - bounding_box.min.y as Float - 0.5;
+            let (x0, y0) = (gx.floor(), gy.floor());
+            let (fx, fy) = (gx - x0, gy - y0);
+            let at = |x: Float, y: Float| {
+                if x < 0.0 || y < 0.0 || x >= glyph_w as Float || y >= glyph_h as Float {
+                    0.0
+                } else {
+                    coverage[y as usize * glyph_w + x as usize]
+                }
+            };
+            let top = at(x0, y0) * (1.0 - fx) + at(x0 + 1.0, y0) * fx;
+            let bottom = at(x0, y0 + 1.0) * (1.0 - fx) + at(x0 + 1.0, y0 + 1.0) * fx;
+            top * (1.0 - fy) + bottom * fy
+        };
+
+        // Inverse map every pixel of the rotated bounding box back into the glyph
+        let radius = ((bounding_box.min.x.abs().max(bounding_box.max.x.abs()) as Float).powi(2)
+            + (bounding_box.min.y.abs().max(bounding_box.max.y.abs()) as Float).powi(2))
+        .sqrt()
+            + 1.0;
+        let (min_x, max_x) = ((base_x - radius).floor() as i32, (base_x + radius).ceil() as i32);
+        let (min_y, max_y) = ((base_y - radius).floor() as i32, (base_y + radius).ceil() as i32);
+        for sy in min_y..=max_y {
+            for sx in min_x..=max_x {
+                let dx = sx as Float + 0.5 - base_x;
+                let dy = sy as Float + 0.5 - base_y;
+                let lx = dx * cos + dy * sin;
+                let ly = -dx * sin + dy * cos;
+                let alpha = sample(lx, ly);
+                if alpha > 0.0 {
+                    blend_pixel(imgbuf, sx, sy, color, alpha);
+                }
+            }
+        }
+    }
+}
+
+fn draw_text_decoration_line(
+    imgbuf: &mut RgbaImage,
+    x: i32,
+    top: f32,
+    width: f32,
+    thickness: f32,
+    color: Rgba<u8>,
+) {
+    let y_start = top.round() as i32;
+    let y_end = (top + thickness).round().max(top.round() + 1.0) as i32;
+    for py in y_start..y_end {
+        for px in x..(x + width.round() as i32) {
+            if px >= 0 && px < imgbuf.width() as i32 && py >= 0 && py < imgbuf.height() as i32 {
+                blend_pixel(imgbuf, px, py, color, 1.0);
+            }
+        }
+    }
 }
 
 fn render_stack(
@@ -430,9 +926,11 @@ fn render_stack(
     scale: Float,
 ) {
     // Stacks just render their children (which have their own absolute positions)
-    for child in node.children.iter() {
-        render_node(child, session, imgbuf, scale);
-    }
+    clip_children(session, &node.entity_id, imgbuf, scale, |imgbuf| {
+        for child in session.paint_order(node) {
+            render_node(child, session, imgbuf, scale);
+        }
+    });
 }
 
 fn render_line(
@@ -442,35 +940,10 @@ fn render_line(
     scale: Float,
 ) {
     let line_shape = session.get_line(entity_id.clone());
-
-    let mut p_start = Point::new(0.0, 0.0);
-    let mut p_end = Point::new(0.0, 0.0);
-
-    match line_shape.start.clone() {
-        LinePointReference::Value(x, y) => {
-            p_start.x = x;
-            p_start.y = y;
-        }
-        LinePointReference::PointID(id) => {
-            let pos = session.get_local_position(id.clone());
-            let line_pos = session.get_local_position(line_shape.entity.clone());
-            p_start.x = pos.0 - line_pos.0;
-            p_start.y = pos.1 - line_pos.1;
-        }
-    }
-
-    match line_shape.end.clone() {
-        LinePointReference::Value(x, y) => {
-            p_end.x = x;
-            p_end.y = y;
-        }
-        LinePointReference::PointID(id) => {
-            let pos = session.get_local_position(id.clone());
-            let line_pos = session.get_local_position(line_shape.entity.clone());
-            p_end.x = pos.0 - line_pos.0;
-            p_end.y = pos.1 - line_pos.1;
-        }
-    }
+    let (x1, y1) = session.resolve_line_point(&line_shape.entity, &line_shape.start);
+    let (x2, y2) = session.resolve_line_point(&line_shape.entity, &line_shape.end);
+    let p_start = Point::new(x1, y1);
+    let p_end = Point::new(x2, y2);
 
     // Get line's absolute position
     let (line_abs_x, line_abs_y) = session
@@ -479,15 +952,33 @@ fn render_line(
         .copied()
         .unwrap_or_else(|| session.get_local_position(entity_id.clone()));
 
-    let x1 = ((line_abs_x + p_start.x) * scale) as i32;
-    let y1 = ((line_abs_y + p_start.y) * scale) as i32;
-    let x2 = ((line_abs_x + p_end.x) * scale) as i32;
-    let y2 = ((line_abs_y + p_end.y) * scale) as i32;
+    let points = [
+        (
+            (line_abs_x + p_start.x) * scale,
+            (line_abs_y + p_start.y) * scale,
+        ),
+        (
+            (line_abs_x + p_end.x) * scale,
+            (line_abs_y + p_end.y) * scale,
+        ),
+    ];
 
     let stroke_color = parse_color(&line_shape.line_options.stroke_color);
     let stroke_width = line_shape.line_options.stroke_width * scale;
-
-    draw_anti_aliased_line(imgbuf, x1, y1, x2, y2, stroke_color, stroke_width);
+    let stroke_opacity = session.get_opacity(entity_id.clone()).stroke_opacity;
+    let stroke_style = &line_shape.line_options.stroke_style;
+
+    stroke_layer(imgbuf, stroke_color, stroke_opacity, |layer, color| {
+        draw_stroke(
+            layer,
+            &points,
+            false,
+            color,
+            stroke_width,
+            stroke_style,
+            scale,
+        );
+    });
 }
 
 fn render_arrow(
@@ -505,30 +996,34 @@ fn render_arrow(
         .copied()
         .unwrap_or_else(|| session.get_local_position(entity_id.clone()));
 
-    let x1 = ((arrow_abs_x + arrow_shape.start.0) * scale) as i32;
-    let y1 = ((arrow_abs_y + arrow_shape.start.1) * scale) as i32;
-    let x2 = ((arrow_abs_x + arrow_shape.end.0) * scale) as i32;
-    let y2 = ((arrow_abs_y + arrow_shape.end.1) * scale) as i32;
+    let geometry = session.get_arrow_geometry(entity_id.clone());
+    let path_points: Vec<Vec<(Float, Float)>> = geometry
+        .path
+        .translated(arrow_abs_x, arrow_abs_y)
+        .flatten()
+        .into_iter()
+        .map(|points| points.iter().map(|(x, y)| (x * scale, y * scale)).collect())
+        .collect();
 
     let stroke_color = parse_color(&arrow_shape.arrow_options.stroke_color);
     let stroke_width = arrow_shape.arrow_options.stroke_width * scale;
+    let stroke_opacity = session.get_opacity(entity_id.clone()).stroke_opacity;
 
-    draw_anti_aliased_line(imgbuf, x1, y1, x2, y2, stroke_color, stroke_width);
-
-    // Draw arrowhead
-    let dx = (x2 - x1) as f32;
-    let dy = (y2 - y1) as f32;
-    let angle = dy.atan2(dx);
-    let arrow_size = 10.0 * scale;
-
-    let arrow_angle = 0.5;
-    let left_x = x2 - (arrow_size * (angle - arrow_angle).cos()) as i32;
-    let left_y = y2 - (arrow_size * (angle - arrow_angle).sin()) as i32;
-    let right_x = x2 - (arrow_size * (angle + arrow_angle).cos()) as i32;
-    let right_y = y2 - (arrow_size * (angle + arrow_angle).sin()) as i32;
-
-    draw_anti_aliased_line(imgbuf, x2, y2, left_x, left_y, stroke_color, stroke_width);
-    draw_anti_aliased_line(imgbuf, x2, y2, right_x, right_y, stroke_color, stroke_width);
+    stroke_layer(imgbuf, stroke_color, stroke_opacity, |layer, color| {
+        for points in &path_points {
+            let style = StrokeStyle::default();
+            draw_stroke(layer, points, false, color, stroke_width, &style, scale);
+        }
+        draw_markers(
+            layer,
+            session,
+            &geometry,
+            (arrow_abs_x, arrow_abs_y),
+            scale,
+            color,
+            stroke_width,
+        );
+    });
 }
 
 fn render_connector(
@@ -538,244 +1033,191 @@ fn render_connector(
     scale: Float,
 ) {
     let connector = session.get_connector(entity_id.clone());
-
-    // Get absolute positions of start and end points
-    let (start_x, start_y) = session
-        .absolute_positions
-        .get(&connector.start_point_id)
-        .copied()
-        .unwrap_or_else(|| session.get_local_position(connector.start_point_id.clone()));
-
-    let (end_x, end_y) = session
-        .absolute_positions
-        .get(&connector.end_point_id)
-        .copied()
-        .unwrap_or_else(|| session.get_local_position(connector.end_point_id.clone()));
-
-    // Apply PNG scale
-    let x1 = (start_x * scale) as i32;
-    let y1 = (start_y * scale) as i32;
-    let x2 = (end_x * scale) as i32;
-    let y2 = (end_y * scale) as i32;
+    let Some(geometry) = session.get_connector_geometry(entity_id.clone()) else {
+        return;
+    };
 
     let stroke_color = parse_color(&connector.options.stroke_color);
     let stroke_width = connector.options.stroke_width * scale;
 
-    match connector.options.connector_type {
-        ConnectorType::Straight => {
-            draw_anti_aliased_line(imgbuf, x1, y1, x2, y2, stroke_color, stroke_width);
-        }
-        ConnectorType::Curved => {
-            let curve_offset = connector.options.curve_offset.unwrap_or(50.0) * scale;
-            let dx = x2 - x1;
-            let dy = y2 - y1;
-            let is_horizontal = dx.abs() > dy.abs();
-
-            if is_horizontal {
-                let cp1_x = x1 + dx / 2;
-                let cp1_y = y1 + curve_offset as i32;
-                let cp2_x = x2 - dx / 2;
-                let cp2_y = y2 + curve_offset as i32;
-
-                draw_bezier_curve(
-                    imgbuf,
-                    x1,
-                    y1,
-                    cp1_x,
-                    cp1_y,
-                    cp2_x,
-                    cp2_y,
-                    x2,
-                    y2,
-                    stroke_color,
-                    stroke_width,
-                );
-            } else {
-                let cp1_x = x1 + curve_offset as i32;
-                let cp1_y = y1 + dy / 2;
-                let cp2_x = x2 + curve_offset as i32;
-                let cp2_y = y2 - dy / 2;
+    // The geometry from layout, in pixels
+    let path_points: Vec<Vec<(Float, Float)>> = geometry
+        .path
+        .flatten()
+        .into_iter()
+        .map(|points| points.iter().map(|(x, y)| (x * scale, y * scale)).collect())
+        .collect();
 
-                draw_bezier_curve(
+    let stroke_opacity = session.get_opacity(entity_id.clone()).stroke_opacity;
+    stroke_layer(
+        imgbuf,
+        stroke_color,
+        stroke_opacity,
+        |imgbuf, stroke_color| {
+            for points in &path_points {
+                draw_stroke(
                     imgbuf,
-                    x1,
-                    y1,
-                    cp1_x,
-                    cp1_y,
-                    cp2_x,
-                    cp2_y,
-                    x2,
-                    y2,
+                    points,
+                    false,
                     stroke_color,
                     stroke_width,
+                    &connector.options.stroke_style,
+                    scale,
                 );
             }
-        }
-        ConnectorType::Orthogonal => {
-            draw_orthogonal_path(
+            draw_markers(
                 imgbuf,
-                x1,
-                y1,
-                x2,
-                y2,
-                connector.options.routing_strategy,
+                session,
+                geometry,
+                (0.0, 0.0),
+                scale,
                 stroke_color,
                 stroke_width,
             );
-        }
-    }
+        },
+    );
 
-    if connector.options.arrow_end {
-        draw_arrow_marker(
-            imgbuf,
-            x1,
-            y1,
-            x2,
-            y2,
-            connector.options.arrow_size * scale,
-            stroke_color,
-            stroke_width,
-        );
-    }
-    if connector.options.arrow_start {
-        draw_arrow_marker(
+    if let (Some(label), Some(label_path)) = (&connector.label, &connector.options.label_path) {
+        draw_text_on_path(
             imgbuf,
-            x2,
-            y2,
-            x1,
-            y1,
-            connector.options.arrow_size * scale,
-            stroke_color,
-            stroke_width,
+            session,
+            &geometry.path,
+            (0.0, 0.0),
+            scale,
+            label,
+            label_path,
         );
     }
 }
 
-fn draw_bezier_curve(
+/// Paints both end markers of a connector or arrow, `offset` moves the
+/// geometry to absolute diagram coordinates and `stroke_width` is in pixels
+fn draw_markers(
     imgbuf: &mut RgbaImage,
-    x0: i32,
-    y0: i32,
-    cp1_x: i32,
-    cp1_y: i32,
-    cp2_x: i32,
-    cp2_y: i32,
-    x3: i32,
-    y3: i32,
+    session: &DiagramBuilder,
+    geometry: &ConnectorGeometry,
+    offset: (Float, Float),
+    scale: Float,
     color: Rgba<u8>,
-    thickness: f32,
+    stroke_width: Float,
 ) {
-    let num_segments = 50;
-    let mut prev_x = x0;
-    let mut prev_y = y0;
-
-    for i in 1..=num_segments {
-        let t = i as f32 / num_segments as f32;
-        let t2 = t * t;
-        let t3 = t2 * t;
-        let mt = 1.0 - t;
-        let mt2 = mt * mt;
-        let mt3 = mt2 * mt;
-
-        let x = (mt3 * x0 as f32
-            + 3.0 * mt2 * t * cp1_x as f32
-            + 3.0 * mt * t2 * cp2_x as f32
-            + t3 * x3 as f32) as i32;
-        let y = (mt3 * y0 as f32
-            + 3.0 * mt2 * t * cp1_y as f32
-            + 3.0 * mt * t2 * cp2_y as f32
-            + t3 * y3 as f32) as i32;
-
-        draw_anti_aliased_line(imgbuf, prev_x, prev_y, x, y, color, thickness);
-        prev_x = x;
-        prev_y = y;
+    let ends = [
+        (&geometry.start_marker, &geometry.start_arrow),
+        (&geometry.end_marker, &geometry.end_arrow),
+    ];
+    for (marker, anchor) in ends {
+        let anchor = ArrowAnchor {
+            tip: (anchor.tip.0 + offset.0, anchor.tip.1 + offset.1),
+            angle: anchor.angle,
+        };
+        for shape in &marker.shapes {
+            let outlines: Vec<Vec<(Float, Float)>> = shape
+                .path
+                .flatten()
+                .iter()
+                .map(|points| {
+                    points
+                        .iter()
+                        .map(|point| {
+                            let (x, y) = anchor.place(*point);
+                            (x * scale, y * scale)
+                        })
+                        .collect()
+                })
+                .collect();
+            match shape.paint {
+                MarkerPaint::Fill => fill_polygons(imgbuf, &outlines, color, FillRule::NonZero),
+                MarkerPaint::Stroke => {
+                    let closed = shape.path.commands.last() == Some(&PathCommand::Close);
+                    let style = StrokeStyle::default();
+                    for points in &outlines {
+                        draw_stroke(imgbuf, points, closed, color, stroke_width, &style, scale);
+                    }
+                }
+            }
+        }
+        draw_entity_marker(imgbuf, session, marker, &anchor, scale);
     }
 }
 
-fn draw_orthogonal_path(
+/// Fills polygons given in pixels with a solid color
+fn fill_polygons(
     imgbuf: &mut RgbaImage,
-    x1: i32,
-    y1: i32,
-    x2: i32,
-    y2: i32,
-    strategy: OrthogonalRoutingStrategy,
+    polygons: &[Vec<(Float, Float)>],
     color: Rgba<u8>,
-    thickness: f32,
+    fill_rule: FillRule,
 ) {
-    let dx = x2 - x1;
-    let dy = y2 - y1;
-
-    match strategy {
-        OrthogonalRoutingStrategy::HV => {
-            draw_anti_aliased_line(imgbuf, x1, y1, x2, y1, color, thickness);
-            draw_anti_aliased_line(imgbuf, x2, y1, x2, y2, color, thickness);
-        }
-        OrthogonalRoutingStrategy::VH => {
-            draw_anti_aliased_line(imgbuf, x1, y1, x1, y2, color, thickness);
-            draw_anti_aliased_line(imgbuf, x1, y2, x2, y2, color, thickness);
-        }
-        OrthogonalRoutingStrategy::HVH => {
-            let mid_x = (x1 + x2) / 2;
-            draw_anti_aliased_line(imgbuf, x1, y1, mid_x, y1, color, thickness);
-            draw_anti_aliased_line(imgbuf, mid_x, y1, mid_x, y2, color, thickness);
-            draw_anti_aliased_line(imgbuf, mid_x, y2, x2, y2, color, thickness);
-        }
-        OrthogonalRoutingStrategy::VHV => {
-            if dx.abs() < 5 || dy.abs() < 5 {
-                draw_anti_aliased_line(imgbuf, x1, y1, x2, y2, color, thickness);
-            } else {
-                let mid_y = (y1 + y2) / 2;
-                draw_anti_aliased_line(imgbuf, x1, y1, x1, mid_y, color, thickness);
-                draw_anti_aliased_line(imgbuf, x1, mid_y, x2, mid_y, color, thickness);
-                draw_anti_aliased_line(imgbuf, x2, mid_y, x2, y2, color, thickness);
-            }
-        }
-        OrthogonalRoutingStrategy::Auto => {
-            if dy.abs() > dx.abs() {
-                if dx.abs() < 5 {
-                    draw_anti_aliased_line(imgbuf, x1, y1, x2, y2, color, thickness);
-                } else {
-                    let mid_y = (y1 + y2) / 2;
-                    draw_anti_aliased_line(imgbuf, x1, y1, x1, mid_y, color, thickness);
-                    draw_anti_aliased_line(imgbuf, x1, mid_y, x2, mid_y, color, thickness);
-                    draw_anti_aliased_line(imgbuf, x2, mid_y, x2, y2, color, thickness);
-                }
-            } else {
-                if dy.abs() < 5 {
-                    draw_anti_aliased_line(imgbuf, x1, y1, x2, y2, color, thickness);
-                } else {
-                    let mid_x = (x1 + x2) / 2;
-                    draw_anti_aliased_line(imgbuf, x1, y1, mid_x, y1, color, thickness);
-                    draw_anti_aliased_line(imgbuf, mid_x, y1, mid_x, y2, color, thickness);
-                    draw_anti_aliased_line(imgbuf, mid_x, y2, x2, y2, color, thickness);
-                }
+    let coverage = fill_coverage(imgbuf.width(), imgbuf.height(), polygons, fill_rule);
+    let (min_x, min_y, max_x, max_y) = polygons.iter().flatten().fold(
+        (Float::MAX, Float::MAX, Float::MIN, Float::MIN),
+        |(min_x, min_y, max_x, max_y), (x, y)| {
+            (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y))
+        },
+    );
+    let (width, height) = (imgbuf.width(), imgbuf.height());
+    let columns = (min_x.floor().max(0.0) as u32)..(max_x.ceil().max(0.0) as u32).min(width);
+    for y in (min_y.floor().max(0.0) as u32)..(max_y.ceil().max(0.0) as u32).min(height) {
+        for x in columns.clone() {
+            let alpha = coverage[(y * width + x) as usize];
+            if alpha > 0.0 {
+                blend_pixel(imgbuf, x as i32, y as i32, color, alpha);
             }
         }
     }
 }
 
-fn draw_arrow_marker(
+/// Draws an entity marker's tile turned to the anchor's angle, with the
+/// middle of its right side at the tip
+fn draw_entity_marker(
     imgbuf: &mut RgbaImage,
-    from_x: i32,
-    from_y: i32,
-    to_x: i32,
-    to_y: i32,
-    size: Float,
-    color: Rgba<u8>,
-    thickness: f32,
+    session: &DiagramBuilder,
+    marker: &MarkerGeometry,
+    anchor: &ArrowAnchor,
+    scale: Float,
 ) {
-    let dx = (to_x - from_x) as f32;
-    let dy = (to_y - from_y) as f32;
-    let angle = dy.atan2(dx);
-
-    let arrow_angle = 0.5;
-    let left_x = to_x - (size * (angle - arrow_angle).cos()) as i32;
-    let left_y = to_y - (size * (angle - arrow_angle).sin()) as i32;
-    let right_x = to_x - (size * (angle + arrow_angle).cos()) as i32;
-    let right_y = to_y - (size * (angle + arrow_angle).sin()) as i32;
-
-    draw_anti_aliased_line(imgbuf, to_x, to_y, left_x, left_y, color, thickness);
-    draw_anti_aliased_line(imgbuf, to_x, to_y, right_x, right_y, color, thickness);
-    draw_anti_aliased_line(imgbuf, left_x, left_y, right_x, right_y, color, thickness);
+    let Some((entity, (width, height))) = &marker.entity else {
+        return;
+    };
+    let Some(tile) = session.get_pattern_tile(entity.clone()) else {
+        return;
+    };
+    let mut image = RgbaImage::new(
+        (width * scale).ceil().max(1.0) as u32,
+        (height * scale).ceil().max(1.0) as u32,
+    );
+    render_node(tile, session, &mut image, scale);
+
+    // Every pixel the turned tile covers, sampled back in the tile
+    let corners = [
+        (-width, -height / 2.0),
+        (0.0, -height / 2.0),
+        (0.0, height / 2.0),
+        (-width, height / 2.0),
+    ]
+    .map(|corner| {
+        let (x, y) = anchor.place(corner);
+        (x * scale, y * scale)
+    });
+    let (min_x, min_y, max_x, max_y) = corners.iter().fold(
+        (Float::MAX, Float::MAX, Float::MIN, Float::MIN),
+        |(min_x, min_y, max_x, max_y), (x, y)| {
+            (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y))
+        },
+    );
+    let (sin, cos) = anchor.angle.sin_cos();
+    for py in (min_y.floor() as i32)..(max_y.ceil() as i32) {
+        for px in (min_x.floor() as i32)..(max_x.ceil() as i32) {
+            let dx = (px as Float + 0.5) / scale - anchor.tip.0;
+            let dy = (py as Float + 0.5) / scale - anchor.tip.1;
+            let u = ((dx * cos + dy * sin) + width) * scale;
+            let v = ((-dx * sin + dy * cos) + height / 2.0) * scale;
+            if u < 0.0 || v < 0.0 || u >= image.width() as Float || v >= image.height() as Float {
+                continue;
+            }
+            let pixel = *image.get_pixel(u as u32, v as u32);
+            blend_pixel(imgbuf, px, py, pixel, 1.0);
+        }
+    }
 }
 
 fn render_table(
@@ -805,70 +1247,84 @@ fn render_table(
         return;
     }
 
+    let opacity = session.get_opacity(entity_id.clone());
     let border_color = parse_color(&table_shape.table_options.border_color);
     let border_width = (table_shape.table_options.border_width as f32 * scale).ceil() as u32;
 
-    // Outer border
-    for i in 0..border_width {
-        let inner_rect = Rect::at(x + i as i32, y + i as i32)
-            .of_size(width.saturating_sub(2 * i), height.saturating_sub(2 * i));
-        draw_hollow_rect_mut(imgbuf, inner_rect, border_color);
-    }
-
     // Header
-    if table_shape.header_rect.is_some() {
-        let header_rect_size = session.get_size(table_shape.clone().header_rect.unwrap());
-        let header_height = (header_rect_size.1 * scale).ceil() as u32;
-        if header_height > 0 {
-            let header_fill = parse_color(&table_shape.table_options.header_fill_color);
-            let header_rect = Rect::at(x, y).of_size(width, header_height);
-            draw_filled_rect_mut(imgbuf, header_rect, header_fill);
-            draw_hollow_rect_mut(imgbuf, header_rect, border_color);
-        }
-    }
-
-    // Column lines
-    for col_line_id in &table_shape.col_lines {
-        let (line_abs_x, _) = session
-            .absolute_positions
-            .get(col_line_id)
-            .copied()
-            .unwrap_or_else(|| session.get_local_position(col_line_id.clone()));
-
-        let line_x = (line_abs_x * scale).round() as i32;
-
-        if line_x >= 0 && line_x < imgbuf.width() as i32 {
-            for i in 0..height {
-                let y_pos = y + i as i32;
-                if y_pos >= 0 && y_pos < imgbuf.height() as i32 {
-                    imgbuf.put_pixel(line_x as u32, y_pos as u32, border_color);
-                }
-            }
-        }
+    let header_rect = table_shape.header_rect.as_ref().and_then(|header_id| {
+        let header_height = (session.get_size(header_id.clone()).1 * scale).ceil() as u32;
+        (header_height > 0).then(|| Rect::at(x, y).of_size(width, header_height))
+    });
+    if let Some(header_rect) = header_rect {
+        let header_fill = parse_color(&table_shape.table_options.header_fill_color);
+        fill_solid_rect(
+            imgbuf,
+            header_rect,
+            with_opacity(header_fill, opacity.fill_opacity),
+        );
     }
 
-    // Row lines
-    for row_line_id in &table_shape.row_lines {
-        let (_, line_abs_y) = session
-            .absolute_positions
-            .get(row_line_id)
-            .copied()
-            .unwrap_or_else(|| session.get_local_position(row_line_id.clone()));
+    // Outer border, header border and grid lines share one stroke layer
+    stroke_layer(
+        imgbuf,
+        border_color,
+        opacity.stroke_opacity,
+        |imgbuf, border_color| {
+            draw_rect_border(
+                imgbuf,
+                Rect::at(x, y).of_size(width, height),
+                border_width,
+                border_color,
+            );
+            if let Some(header_rect) = header_rect {
+                draw_hollow_rect_mut(imgbuf, header_rect, border_color);
+            }
 
-        let line_y = (line_abs_y * scale).round() as i32;
+            // Column lines
+            for col_line_id in &table_shape.col_lines {
+                let (line_abs_x, _) = session
+                    .absolute_positions
+                    .get(col_line_id)
+                    .copied()
+                    .unwrap_or_else(|| session.get_local_position(col_line_id.clone()));
+
+                let line_x = (line_abs_x * scale).round() as i32;
+
+                if line_x >= 0 && line_x < imgbuf.width() as i32 {
+                    for i in 0..height {
+                        let y_pos = y + i as i32;
+                        if y_pos >= 0 && y_pos < imgbuf.height() as i32 {
+                            imgbuf.put_pixel(line_x as u32, y_pos as u32, border_color);
+                        }
+                    }
+                }
+            }
 
-        if line_y >= 0 && line_y < imgbuf.height() as i32 {
-            for i in 0..width {
-                let x_pos = x + i as i32;
-                if x_pos >= 0 && x_pos < imgbuf.width() as i32 {
-                    imgbuf.put_pixel(x_pos as u32, line_y as u32, border_color);
+            // Row lines
+            for row_line_id in &table_shape.row_lines {
+                let (_, line_abs_y) = session
+                    .absolute_positions
+                    .get(row_line_id)
+                    .copied()
+                    .unwrap_or_else(|| session.get_local_position(row_line_id.clone()));
+
+                let line_y = (line_abs_y * scale).round() as i32;
+
+                if line_y >= 0 && line_y < imgbuf.height() as i32 {
+                    for i in 0..width {
+                        let x_pos = x + i as i32;
+                        if x_pos >= 0 && x_pos < imgbuf.width() as i32 {
+                            imgbuf.put_pixel(x_pos as u32, line_y as u32, border_color);
+                        }
+                    }
                 }
             }
-        }
-    }
+        },
+    );
 
     // Render children
-    for child in node.children.iter() {
+    for child in session.paint_order(node) {
         render_node(child, session, imgbuf, scale);
     }
 }
@@ -899,13 +1355,35 @@ fn render_ellipse(
         return;
     }
 
-    let fill_color = parse_color(&ellipse_shape.ellipse_options.fill_color);
+    let opacity = session.get_opacity(entity_id.clone());
+    let fill_color = with_opacity(
+        parse_color(&ellipse_shape.ellipse_options.fill_color),
+        opacity.fill_opacity,
+    );
     let stroke_color = parse_color(&ellipse_shape.ellipse_options.stroke_color);
 
     let center_x = x + (width / 2) as i32;
     let center_y = y + (height / 2) as i32;
     let radius_x = (width / 2) as i32;
     let radius_y = (height / 2) as i32;
+    let inside = |px: i32, py: i32| {
+        let dx = (px - center_x) as f32;
+        let dy = (py - center_y) as f32;
+        let rx = radius_x as f32;
+        let ry = radius_y as f32;
+        (dx * dx) / (rx * rx) + (dy * dy) / (ry * ry) <= 1.0
+    };
+
+    if let Some(shadow) = session.get_shadow(entity_id.clone()) {
+        let rect = Rect::at(x, y).of_size(width, height);
+        draw_shadow(imgbuf, shadow, rect, scale, |px, py| {
+            if inside(px, py) {
+                1.0
+            } else {
+                0.0
+            }
+        });
+    }
 
     // Fill
     for py in y..y + height as i32 {
@@ -918,28 +1396,29 @@ fn render_ellipse(
                 continue;
             }
 
-            let dx = (px - center_x) as f32;
-            let dy = (py - center_y) as f32;
-            let rx = radius_x as f32;
-            let ry = radius_y as f32;
-
-            let eq_value = (dx * dx) / (rx * rx) + (dy * dy) / (ry * ry);
-
-            if eq_value <= 1.0 {
-                imgbuf.put_pixel(px as u32, py as u32, fill_color);
+            if inside(px, py) {
+                blend_pixel(imgbuf, px, py, fill_color, 1.0);
             }
         }
     }
 
     // Stroke
-    draw_anti_aliased_ellipse(
+    let stroke_width = ellipse_shape.ellipse_options.stroke_width * scale;
+    stroke_layer(
         imgbuf,
-        center_x,
-        center_y,
-        radius_x,
-        radius_y,
         stroke_color,
-        ellipse_shape.ellipse_options.stroke_width * scale,
+        opacity.stroke_opacity,
+        |layer, color| {
+            draw_anti_aliased_ellipse(
+                layer,
+                center_x,
+                center_y,
+                radius_x,
+                radius_y,
+                color,
+                stroke_width,
+            );
+        },
     );
 }
 
@@ -962,8 +1441,12 @@ fn render_arc(
     let center_x = screen_x as i32 + (size.0 * scale / 2.0) as i32;
     let center_y = screen_y as i32 + (size.1 * scale / 2.0) as i32;
 
+    let opacity = session.get_opacity(entity_id.clone());
     let stroke_color = parse_color(&arc_shape.arc_options.stroke_color);
-    let fill_color = parse_color(&arc_shape.arc_options.fill_color);
+    let fill_color = with_opacity(
+        parse_color(&arc_shape.arc_options.fill_color),
+        opacity.fill_opacity,
+    );
     let stroke_width = arc_shape.arc_options.stroke_width * scale;
 
     let (start_angle, end_angle) = arc_shape.normalize_angles();
@@ -979,20 +1462,48 @@ fn render_arc(
             start_rad,
             end_rad,
             fill_color,
-            stroke_color,
-            stroke_width,
         );
-    } else {
-        render_arc_curve(
-            imgbuf,
-            center_x,
-            center_y,
-            scaled_radius,
-            start_rad,
-            end_rad,
-            stroke_color,
-            stroke_width,
+    }
+
+    // Outline as one path so dashes and joins run around the sector's corners
+    let center = (center_x as Float, center_y as Float);
+    let mut outline = arc_path(center, scaled_radius, start_angle, end_angle)
+        .flatten()
+        .swap_remove(0);
+    let full_circle = (arc_shape.angle_sweep() - 360.0).abs() < 0.1;
+    if arc_shape.arc_options.filled && !full_circle {
+        outline.insert(0, center);
+        outline.push(center);
+    }
+    let closed = full_circle || arc_shape.arc_options.filled;
+    let stroke_style = &arc_shape.arc_options.stroke_style;
+
+    stroke_layer(
+        imgbuf,
+        stroke_color,
+        opacity.stroke_opacity,
+        |layer, color| {
+            draw_stroke(
+                layer,
+                &outline,
+                closed,
+                color,
+                stroke_width,
+                stroke_style,
+                scale,
+            );
+        },
+    );
+
+    if let Some(label) = &arc_shape.arc_options.label {
+        let path = arc_path(
+            (size.0 / 2.0, size.1 / 2.0),
+            arc_shape.radius,
+            start_angle,
+            end_angle,
         );
+        let label_path = &arc_shape.arc_options.label_path;
+        draw_text_on_path(imgbuf, session, &path, (screen_x, screen_y), scale, label, label_path);
     }
 }
 
@@ -1004,8 +1515,6 @@ fn render_filled_arc_sector(
     start_rad: Float,
     end_rad: Float,
     fill_color: Rgba<u8>,
-    stroke_color: Rgba<u8>,
-    stroke_width: Float,
 ) {
     let radius_i = radius as i32;
 
@@ -1035,99 +1544,12 @@ fn render_filled_arc_sector(
                     };
 
                     if angle_in_arc {
-                        imgbuf.put_pixel(x as u32, y as u32, fill_color);
+                        blend_pixel(imgbuf, x, y, fill_color, 1.0);
                     }
                 }
             }
         }
     }
-
-    render_arc_curve(
-        imgbuf,
-        center_x,
-        center_y,
-        radius,
-        start_rad,
-        end_rad,
-        stroke_color,
-        stroke_width,
-    );
-
-    let start_x = center_x + (radius * start_rad.cos()) as i32;
-    let start_y = center_y + (radius * start_rad.sin()) as i32;
-    let end_x = center_x + (radius * end_rad.cos()) as i32;
-    let end_y = center_y + (radius * end_rad.sin()) as i32;
-
-    draw_anti_aliased_line(
-        imgbuf,
-        center_x,
-        center_y,
-        start_x,
-        start_y,
-        stroke_color,
-        stroke_width,
-    );
-    draw_anti_aliased_line(
-        imgbuf,
-        center_x,
-        center_y,
-        end_x,
-        end_y,
-        stroke_color,
-        stroke_width,
-    );
-}
-
-fn render_arc_curve(
-    imgbuf: &mut RgbaImage,
-    center_x: i32,
-    center_y: i32,
-    radius: Float,
-    start_rad: Float,
-    end_rad: Float,
-    stroke_color: Rgba<u8>,
-    stroke_width: Float,
-) {
-    let num_steps = (radius * 2.0).max(60.0) as i32;
-    let angle_range = if end_rad > start_rad {
-        end_rad - start_rad
-    } else {
-        (2.0 * std::f32::consts::PI) - start_rad + end_rad
-    };
-
-    let angle_step = angle_range / num_steps as Float;
-
-    let mut prev_x = center_x + (radius * start_rad.cos()) as i32;
-    let mut prev_y = center_y + (radius * start_rad.sin()) as i32;
-
-    for i in 1..=num_steps {
-        let current_angle = if end_rad > start_rad {
-            start_rad + angle_step * i as Float
-        } else {
-            let angle = start_rad + angle_step * i as Float;
-            if angle > 2.0 * std::f32::consts::PI {
-                angle - 2.0 * std::f32::consts::PI
-            } else {
-                angle
-            }
-        };
-
-        let current_x = center_x + (radius * current_angle.cos()) as i32;
-        let current_y = center_y + (radius * current_angle.sin()) as i32;
-
-        draw_anti_aliased_line(
-            imgbuf,
-            prev_x,
-            prev_y,
-            current_x,
-            current_y,
-            stroke_color,
-            stroke_width,
-        );
-
-        prev_x = current_x;
-        prev_y = current_y;
-    }
 }
 
 fn render_polyline(
@@ -1140,6 +1562,7 @@ fn render_polyline(
 
     let stroke_color = parse_color(&polyline.line_options.stroke_color);
     let stroke_width = polyline.line_options.stroke_width * scale;
+    let stroke_opacity = session.get_opacity(entity_id.clone()).stroke_opacity;
 
     if polyline.points.len() < 2 {
         return;
@@ -1152,25 +1575,77 @@ fn render_polyline(
         .copied()
         .unwrap_or_else(|| session.get_local_position(entity_id.clone()));
 
-    for i in 0..polyline.points.len() - 1 {
-        let (x1, y1) = polyline.points[i];
-        let (x2, y2) = polyline.points[i + 1];
-
-        let x1_scaled = ((polyline_abs_x + x1) * scale) as i32;
-        let y1_scaled = ((polyline_abs_y + y1) * scale) as i32;
-        let x2_scaled = ((polyline_abs_x + x2) * scale) as i32;
-        let y2_scaled = ((polyline_abs_y + y2) * scale) as i32;
-
-        draw_anti_aliased_line(
-            imgbuf,
-            x1_scaled,
-            y1_scaled,
-            x2_scaled,
-            y2_scaled,
-            stroke_color,
+    let points: Vec<(Float, Float)> = polyline
+        .points
+        .iter()
+        .map(|(x, y)| ((polyline_abs_x + x) * scale, (polyline_abs_y + y) * scale))
+        .collect();
+    let stroke_style = &polyline.line_options.stroke_style;
+
+    stroke_layer(imgbuf, stroke_color, stroke_opacity, |layer, color| {
+        draw_stroke(
+            layer,
+            &points,
+            false,
+            color,
             stroke_width,
+            stroke_style,
+            scale,
         );
+    });
+}
+
+fn render_path(
+    session: &DiagramBuilder,
+    imgbuf: &mut RgbaImage,
+    entity_id: EntityID,
+    screen_x: Float,
+    screen_y: Float,
+    scale: Float,
+) {
+    let path_shape = session.get_path(entity_id.clone());
+    let options = &path_shape.path_options;
+    let opacity = session.get_opacity(entity_id.clone());
+
+    let subpaths: Vec<Vec<(Float, Float)>> = path_shape
+        .path
+        .flatten()
+        .into_iter()
+        .map(|points| {
+            points
+                .into_iter()
+                .map(|(x, y)| (screen_x + x * scale, screen_y + y * scale))
+                .collect()
+        })
+        .collect();
+
+    let fill_color = with_opacity(parse_color(&options.fill_color), opacity.fill_opacity);
+    if fill_color[3] > 0 {
+        fill_polygons(imgbuf, &subpaths, fill_color, options.fill_rule);
     }
+
+    let stroke_color = parse_color(&options.stroke_color);
+    let stroke_width = options.stroke_width * scale;
+    stroke_layer(
+        imgbuf,
+        stroke_color,
+        opacity.stroke_opacity,
+        |layer, color| {
+            for points in &subpaths {
+                // Closed subpaths come back to their first point
+                let closed = points.len() > 2 && points.first() == points.last();
+                draw_stroke(
+                    layer,
+                    points,
+                    closed,
+                    color,
+                    stroke_width,
+                    &options.stroke_style,
+                    scale,
+                );
+            }
+        },
+    );
 }
 
 fn render_free_container(
@@ -1190,11 +1665,13 @@ fn render_free_container(
     let width = (size.0 * scale).ceil() as u32;
     let height = (size.1 * scale).ceil() as u32;
 
+    let opacity = session.get_opacity(entity_id.clone());
+    let rect = Rect::at(x, y).of_size(width, height);
+
     // Background
     if let Some(bg_color) = &container.background_color {
-        let fill_color = parse_color(bg_color);
-        let rect = Rect::at(x, y).of_size(width, height);
-        draw_filled_rect_mut(imgbuf, rect, fill_color);
+        let fill_color = with_opacity(parse_color(bg_color), opacity.fill_opacity);
+        fill_solid_rect(imgbuf, rect, fill_color);
     }
 
     // Border
@@ -1202,19 +1679,23 @@ fn render_free_container(
         if container.border_width > 0.0 {
             let stroke_color = parse_color(border_color);
             let stroke_width = (container.border_width * scale).ceil() as u32;
-
-            for i in 0..stroke_width {
-                let inner_rect = Rect::at(x + i as i32, y + i as i32)
-                    .of_size(width.saturating_sub(2 * i), height.saturating_sub(2 * i));
-                draw_hollow_rect_mut(imgbuf, inner_rect, stroke_color);
-            }
+            stroke_layer(
+                imgbuf,
+                stroke_color,
+                opacity.stroke_opacity,
+                |layer, color| {
+                    draw_rect_border(layer, rect, stroke_width, color);
+                },
+            );
         }
     }
 
     // Render children (they have their own absolute positions)
-    for child in node.children.iter() {
-        render_node(child, session, imgbuf, scale);
-    }
+    clip_children(session, &entity_id, imgbuf, scale, |imgbuf| {
+        for child in session.paint_order(node) {
+            render_node(child, session, imgbuf, scale);
+        }
+    });
 }
 
 fn render_constraint_layout(
@@ -1224,9 +1705,11 @@ fn render_constraint_layout(
     scale: Float,
 ) {
     // Render all children (they have their own absolute positions)
-    for child in node.children.iter() {
-        render_node(child, session, imgbuf, scale);
-    }
+    clip_children(session, &node.entity_id, imgbuf, scale, |imgbuf| {
+        for child in session.paint_order(node) {
+            render_node(child, session, imgbuf, scale);
+        }
+    });
 }
 
 fn render_image(
@@ -1279,17 +1762,17 @@ fn render_image(
     let resized_img = loaded_img.resize_exact(width, height, image::imageops::FilterType::Lanczos3);
     let img_rgba = resized_img.to_rgba8();
 
-    for (ix, iy, pixel) in img_rgba.enumerate_pixels() {
-        let dest_x = x + ix as i32;
-        let dest_y = y + iy as i32;
+    // The shadow follows the image's own alpha, as SVG's SourceAlpha does
+    if let Some(shadow) = session.get_shadow(entity_id.clone()) {
+        let rect = Rect::at(x, y).of_size(width, height);
+        draw_shadow(imgbuf, shadow, rect, scale, |px, py| {
+            img_rgba.get_pixel((px - x) as u32, (py - y) as u32)[3] as Float / 255.0
+        });
+    }
 
-        if dest_x >= 0
-            && dest_x < imgbuf.width() as i32
-            && dest_y >= 0
-            && dest_y < imgbuf.height() as i32
-        {
-            imgbuf.put_pixel(dest_x as u32, dest_y as u32, *pixel);
-        }
+    // Blended so transparent parts of the image keep what is behind them
+    for (ix, iy, pixel) in img_rgba.enumerate_pixels() {
+        blend_pixel(imgbuf, x + ix as i32, y + iy as i32, *pixel, 1.0);
     }
 
     let border_color = Rgba([80, 80, 80, 255]);
@@ -1346,55 +1829,499 @@ fn draw_placeholder_image(imgbuf: &mut RgbaImage, x: i32, y: i32, width: u32, he
     }
 }
 
+/// Colors are validated by the parser, so an unparseable one can only come
+/// from options built in code. It is drawn magenta to stand out.
 fn parse_color(color_str: &str) -> Rgba<u8> {
-    match color_str.to_lowercase().as_str() {
-        "black" => Rgba([0, 0, 0, 255]),
-        "white" => Rgba([255, 255, 255, 255]),
-        "red" => Rgba([255, 0, 0, 255]),
-        "green" => Rgba([0, 255, 0, 255]),
-        "blue" => Rgba([0, 0, 255, 255]),
-        "yellow" => Rgba([255, 255, 0, 255]),
-        "gray" | "grey" => Rgba([128, 128, 128, 255]),
-        "lightgray" | "lightgrey" | "light gray" | "light grey" => Rgba([200, 200, 200, 255]),
-        "darkgray" | "darkgrey" | "dark gray" | "dark grey" => Rgba([80, 80, 80, 255]),
-        "orange" => Rgba([255, 165, 0, 255]),
-        "purple" => Rgba([128, 0, 128, 255]),
-        "brown" => Rgba([165, 42, 42, 255]),
-        "cyan" => Rgba([0, 255, 255, 255]),
-        "magenta" | "pink" => Rgba([255, 0, 255, 255]),
-        _ => {
-            if color_str.starts_with('#') && (color_str.len() == 7 || color_str.len() == 9) {
-                let r = u8::from_str_radix(&color_str[1..3], 16).unwrap_or(0);
-                let g = u8::from_str_radix(&color_str[3..5], 16).unwrap_or(0);
-                let b = u8::from_str_radix(&color_str[5..7], 16).unwrap_or(0);
-                let a = if color_str.len() == 9 {
-                    u8::from_str_radix(&color_str[7..9], 16).unwrap_or(255)
+    match Color::parse(color_str) {
+        Ok(color) => Rgba(color.to_rgba8()),
+        Err(_) => Rgba([255, 0, 255, 255]),
+    }
+}
+
+/// Solid rectangle fill that honours the color's alpha
+fn fill_solid_rect(imgbuf: &mut RgbaImage, rect: Rect, color: Rgba<u8>) {
+    if color[3] == 255 {
+        draw_filled_rect_mut(imgbuf, rect, color);
+        return;
+    }
+    for y in rect.top()..=rect.bottom() {
+        for x in rect.left()..=rect.right() {
+            blend_pixel(imgbuf, x, y, color, 1.0);
+        }
+    }
+}
+
+/// Border drawn inwards from the edge of `rect`, `width` pixels thick
+fn draw_rect_border(imgbuf: &mut RgbaImage, rect: Rect, width: u32, color: Rgba<u8>) {
+    let (x, y) = (rect.left(), rect.top());
+    for i in 0..width {
+        let inner_rect = Rect::at(x + i as i32, y + i as i32).of_size(
+            rect.width().saturating_sub(2 * i),
+            rect.height().saturating_sub(2 * i),
+        );
+        draw_hollow_rect_mut(imgbuf, inner_rect, color);
+    }
+}
+
+/// Border inside `rect` with a dash pattern or joins. Plain borders keep the
+/// pixel exact `draw_rect_border`.
+fn draw_styled_rect_border(
+    imgbuf: &mut RgbaImage,
+    rect: Rect,
+    width: Float,
+    color: Rgba<u8>,
+    style: &StrokeStyle,
+    scale: Float,
+) {
+    if *style == StrokeStyle::default() {
+        draw_rect_border(imgbuf, rect, width.ceil() as u32, color);
+        return;
+    }
+
+    // Centre line of the border, half the width in from the edge
+    let half = width / 2.0;
+    let (left, top) = (rect.left() as Float + half, rect.top() as Float + half);
+    let right = (rect.left() + rect.width() as i32) as Float - half;
+    let bottom = (rect.top() + rect.height() as i32) as Float - half;
+    let outline = [
+        (left, top),
+        (right, top),
+        (right, bottom),
+        (left, bottom),
+        (left, top),
+    ];
+    draw_stroke(imgbuf, &outline, true, color, width, style, scale);
+}
+
+/// Drop shadow drawn before the shape itself, the same steps as the SVG
+/// filter: the shape's coverage is grown or shrunk by the spread with a
+/// square kernel, blurred with a Gaussian and painted at the offset.
+/// `coverage` gives the shape's alpha at a pixel inside `rect`.
+fn draw_shadow<F: Fn(i32, i32) -> Float>(
+    imgbuf: &mut RgbaImage,
+    shadow: &ShadowOptions,
+    rect: Rect,
+    scale: Float,
+    coverage: F,
+) {
+    let pad = (shadow.extent() * scale).ceil() as i32 + 1;
+    let (left, top) = (rect.left() - pad, rect.top() - pad);
+    let width = rect.width() as i32 + 2 * pad;
+    let height = rect.height() as i32 + 2 * pad;
+
+    let mut mask = vec![0.0; (width * height) as usize];
+    for y in 0..rect.height() as i32 {
+        for x in 0..rect.width() as i32 {
+            let value = coverage(rect.left() + x, rect.top() + y);
+            mask[((y + pad) * width + x + pad) as usize] = value;
+        }
+    }
+
+    let spread = (shadow.spread * scale).round() as i32;
+    if spread != 0 {
+        morph_mask(&mut mask, width, height, spread);
+    }
+    blur_mask(&mut mask, width, height, shadow.std_deviation() * scale);
+
+    let color = parse_color(&shadow.color);
+    let offset_x = (shadow.offset_x * scale).round() as i32;
+    let offset_y = (shadow.offset_y * scale).round() as i32;
+    for y in 0..height {
+        for x in 0..width {
+            let value = mask[(y * width + x) as usize];
+            if value > 0.0 {
+                blend_pixel(
+                    imgbuf,
+                    left + x + offset_x,
+                    top + y + offset_y,
+                    color,
+                    value,
+                );
+            }
+        }
+    }
+}
+
+/// Square dilation for a positive `radius`, erosion for a negative one, done
+/// one axis at a time like SVG's feMorphology
+fn morph_mask(mask: &mut [Float], width: i32, height: i32, radius: i32) {
+    let dilate = radius > 0;
+    let radius = radius.abs();
+    let pick = |a: Float, b: Float| if dilate { a.max(b) } else { a.min(b) };
+    let source = mask.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let row = (y * width) as usize;
+            let value = ((x - radius).max(0)..=(x + radius).min(width - 1))
+                .map(|sx| source[row + sx as usize])
+                .fold(if dilate { 0.0 } else { 1.0 }, pick);
+            mask[row + x as usize] = value;
+        }
+    }
+    let source = mask.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let value = ((y - radius).max(0)..=(y + radius).min(height - 1))
+                .map(|sy| source[(sy * width + x) as usize])
+                .fold(if dilate { 0.0 } else { 1.0 }, pick);
+            mask[(y * width + x) as usize] = value;
+        }
+    }
+}
+
+/// Separable Gaussian blur, pixels outside the mask count as empty
+fn blur_mask(mask: &mut [Float], width: i32, height: i32, sigma: Float) {
+    if sigma < 0.1 {
+        return;
+    }
+    let radius = (3.0 * sigma).ceil() as i32;
+    let weights: Vec<Float> = (-radius..=radius)
+        .map(|i| (-((i * i) as Float) / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: Float = weights.iter().sum();
+    let weights: Vec<Float> = weights.iter().map(|w| w / total).collect();
+
+    let source = mask.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let mut value = 0.0;
+            for (k, weight) in weights.iter().enumerate() {
+                let sx = x + k as i32 - radius;
+                if sx >= 0 && sx < width {
+                    value += source[(y * width + sx) as usize] * weight;
+                }
+            }
+            mask[(y * width + x) as usize] = value;
+        }
+    }
+    let source = mask.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let mut value = 0.0;
+            for (k, weight) in weights.iter().enumerate() {
+                let sy = y + k as i32 - radius;
+                if sy >= 0 && sy < height {
+                    value += source[(sy * width + x) as usize] * weight;
+                }
+            }
+            mask[(y * width + x) as usize] = value;
+        }
+    }
+}
+
+fn with_opacity(color: Rgba<u8>, opacity: Float) -> Rgba<u8> {
+    let alpha = (color[3] as Float * opacity.clamp(0.0, 1.0)).round() as u8;
+    Rgba([color[0], color[1], color[2], alpha])
+}
+
+/// Draws into a transparent layer the size of the image, then composites it
+/// with `opacity`. Overlapping primitives inside the layer don't show
+/// through each other, as with SVG group opacity.
+fn with_layer<F: FnOnce(&mut RgbaImage)>(imgbuf: &mut RgbaImage, opacity: Float, draw: F) {
+    with_masked_layer(imgbuf, opacity, None, BlendMode::Normal, draw);
+}
+
+/// `with_layer` where each pixel is also scaled by an image sized alpha mask
+/// and mixed with the image below using `blend_mode`
+fn with_masked_layer<F: FnOnce(&mut RgbaImage)>(
+    imgbuf: &mut RgbaImage,
+    opacity: Float,
+    mask: Option<&[Float]>,
+    blend_mode: BlendMode,
+    draw: F,
+) {
+    if opacity >= 1.0 && mask.is_none() && blend_mode == BlendMode::Normal {
+        draw(imgbuf);
+        return;
+    }
+    if opacity <= 0.0 {
+        return;
+    }
+
+    let mut layer = RgbaImage::new(imgbuf.width(), imgbuf.height());
+    draw(&mut layer);
+    let width = layer.width();
+    for (x, y, pixel) in layer.enumerate_pixels() {
+        if pixel[3] > 0 {
+            let coverage = mask.map_or(1.0, |mask| mask[(y * width + x) as usize]);
+            if coverage > 0.0 {
+                let color = blend_with_backdrop(imgbuf, x, y, *pixel, blend_mode);
+                blend_pixel(imgbuf, x as i32, y as i32, color, opacity * coverage);
+            }
+        }
+    }
+}
+
+/// Draws a container's children clipped to its outline when it has
+/// `clip: true` or `overflow: "hidden"`
+fn clip_children<F: FnOnce(&mut RgbaImage)>(
+    session: &DiagramBuilder,
+    entity_id: &EntityID,
+    imgbuf: &mut RgbaImage,
+    scale: Float,
+    draw: F,
+) {
+    let clipped = session
+        .get_clip(entity_id.clone())
+        .is_some_and(|clip| clip.clip);
+    if !clipped {
+        draw(imgbuf);
+        return;
+    }
+    let mask = outline_coverage(session, entity_id, imgbuf, scale);
+    with_masked_layer(imgbuf, 1.0, Some(&mask), BlendMode::Normal, draw);
+}
+
+/// Alpha mask for an entity's `clip_path` and `mask` shapes, `None` when it
+/// has neither. The clip path contributes its outline's coverage, the mask
+/// the alpha of the shape as rendered.
+fn clip_path_and_mask(
+    entity_id: &EntityID,
+    session: &DiagramBuilder,
+    imgbuf: &RgbaImage,
+    scale: Float,
+) -> Option<Vec<Float>> {
+    let clip = session.get_clip(entity_id.clone())?;
+    let mut coverage: Option<Vec<Float>> = None;
+
+    if let Some(shape) = &clip.clip_path {
+        coverage = Some(outline_coverage(session, &shape.entity_id, imgbuf, scale));
+    }
+
+    if let Some(shape) = &clip.mask {
+        let mut layer = RgbaImage::new(imgbuf.width(), imgbuf.height());
+        render_node(shape, session, &mut layer, scale);
+        let alpha = layer.pixels().map(|pixel| pixel[3] as Float / 255.0);
+        coverage = Some(match coverage {
+            Some(clip) => clip.iter().zip(alpha).map(|(c, a)| c * a).collect(),
+            None => alpha.collect(),
+        });
+    }
+
+    coverage
+}
+
+/// Image sized coverage of an entity's fill outline, placed at its absolute
+/// position
+fn outline_coverage(
+    session: &DiagramBuilder,
+    entity_id: &EntityID,
+    imgbuf: &RgbaImage,
+    scale: Float,
+) -> Vec<Float> {
+    let (abs_x, abs_y) = session
+        .absolute_positions
+        .get(entity_id)
+        .copied()
+        .unwrap_or_else(|| session.get_local_position(entity_id.clone()));
+    let polygons: Vec<Vec<(Float, Float)>> = session
+        .get_outline(entity_id.clone())
+        .flatten()
+        .into_iter()
+        .map(|points| {
+            points
+                .into_iter()
+                .map(|(x, y)| ((abs_x + x) * scale, (abs_y + y) * scale))
+                .collect()
+        })
+        .collect();
+    fill_coverage(
+        imgbuf.width(),
+        imgbuf.height(),
+        &polygons,
+        FillRule::NonZero,
+    )
+}
+
+/// Coverage of closed polygons under the fill rule, 4x4 samples per pixel
+fn fill_coverage(
+    width: u32,
+    height: u32,
+    polygons: &[Vec<(Float, Float)>],
+    fill_rule: FillRule,
+) -> Vec<Float> {
+    const SAMPLES: i32 = 4;
+    let (width, height) = (width as i32, height as i32);
+    let mut coverage = vec![0.0; (width * height) as usize];
+    let weight = 1.0 / (SAMPLES * SAMPLES) as Float;
+
+    let edges: Vec<((Float, Float), (Float, Float))> = polygons
+        .iter()
+        .filter(|points| points.len() > 2)
+        .flat_map(|points| {
+            points
+                .iter()
+                .copied()
+                .zip(points.iter().copied().cycle().skip(1))
+        })
+        .filter(|(a, b)| a.1 != b.1)
+        .collect();
+    if edges.is_empty() {
+        return coverage;
+    }
+    let top = edges
+        .iter()
+        .map(|(a, b)| a.1.min(b.1))
+        .fold(Float::MAX, Float::min);
+    let bottom = edges
+        .iter()
+        .map(|(a, b)| a.1.max(b.1))
+        .fold(Float::MIN, Float::max);
+
+    let first_row = ((top * SAMPLES as Float).floor() as i32).max(0);
+    let last_row = ((bottom * SAMPLES as Float).ceil() as i32).min(height * SAMPLES);
+    let mut crossings: Vec<(Float, i32)> = Vec::new();
+    for row in first_row..last_row {
+        let y = (row as Float + 0.5) / SAMPLES as Float;
+        crossings.clear();
+        for &(a, b) in &edges {
+            let (upper, lower, direction) = if a.1 < b.1 { (a, b, 1) } else { (b, a, -1) };
+            if y >= upper.1 && y < lower.1 {
+                let t = (y - upper.1) / (lower.1 - upper.1);
+                crossings.push((upper.0 + t * (lower.0 - upper.0), direction));
+            }
+        }
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let pixel_row = (row / SAMPLES * width) as usize;
+        let mut winding = 0;
+        for pair in crossings.windows(2) {
+            winding += pair[0].1;
+            let inside = match fill_rule {
+                FillRule::NonZero => winding != 0,
+                FillRule::EvenOdd => winding % 2 != 0,
+            };
+            if !inside {
+                continue;
+            }
+            // Sample columns whose centers fall in [start, end)
+            let start = ((pair[0].0 * SAMPLES as Float - 0.5).ceil() as i32).max(0);
+            let end = ((pair[1].0 * SAMPLES as Float - 0.5).ceil() as i32).min(width * SAMPLES);
+            for column in start..end {
+                coverage[pixel_row + (column / SAMPLES) as usize] += weight;
+            }
+        }
+    }
+    coverage
+}
+
+/// Strokes are stamped many times along the path, so their alpha (color
+/// alpha times stroke opacity) is applied once through a layer and the
+/// stroke itself is drawn opaque.
+fn stroke_layer<F: FnOnce(&mut RgbaImage, Rgba<u8>)>(
+    imgbuf: &mut RgbaImage,
+    color: Rgba<u8>,
+    opacity: Float,
+    draw: F,
+) {
+    let alpha = opacity * color[3] as Float / 255.0;
+    let opaque = Rgba([color[0], color[1], color[2], 255]);
+    with_layer(imgbuf, alpha, |layer| draw(layer, opaque));
+}
+
+/// Source color after mixing with the backdrop pixel, as in the W3C
+/// compositing spec: the blended color is weighted by the backdrop's alpha
+/// and source-over is applied afterwards
+fn blend_with_backdrop(
+    imgbuf: &RgbaImage,
+    x: u32,
+    y: u32,
+    color: Rgba<u8>,
+    blend_mode: BlendMode,
+) -> Rgba<u8> {
+    if blend_mode == BlendMode::Normal {
+        return color;
+    }
+    let backdrop = imgbuf.get_pixel(x, y);
+    let backdrop_alpha = backdrop[3] as Float / 255.0;
+    let channel = |i: usize| {
+        let source = color[i] as Float / 255.0;
+        let blended = blend_channel(blend_mode, backdrop[i] as Float / 255.0, source);
+        let mixed = (1.0 - backdrop_alpha) * source + backdrop_alpha * blended;
+        (mixed.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+    Rgba([channel(0), channel(1), channel(2), color[3]])
+}
+
+/// Separable blend functions, `backdrop` and `source` in 0..1
+fn blend_channel(blend_mode: BlendMode, backdrop: Float, source: Float) -> Float {
+    let hard_light = |backdrop: Float, source: Float| {
+        if source <= 0.5 {
+            backdrop * 2.0 * source
+        } else {
+            let screen_source = 2.0 * source - 1.0;
+            backdrop + screen_source - backdrop * screen_source
+        }
+    };
+    match blend_mode {
+        BlendMode::Normal => source,
+        BlendMode::Multiply => backdrop * source,
+        BlendMode::Screen => backdrop + source - backdrop * source,
+        BlendMode::Overlay => hard_light(source, backdrop),
+        BlendMode::Darken => backdrop.min(source),
+        BlendMode::Lighten => backdrop.max(source),
+        BlendMode::ColorDodge => {
+            if backdrop == 0.0 {
+                0.0
+            } else if source >= 1.0 {
+                1.0
+            } else {
+                (backdrop / (1.0 - source)).min(1.0)
+            }
+        }
+        BlendMode::ColorBurn => {
+            if backdrop >= 1.0 {
+                1.0
+            } else if source <= 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - backdrop) / source).min(1.0)
+            }
+        }
+        BlendMode::HardLight => hard_light(backdrop, source),
+        BlendMode::SoftLight => {
+            if source <= 0.5 {
+                backdrop - (1.0 - 2.0 * source) * backdrop * (1.0 - backdrop)
+            } else {
+                let d = if backdrop <= 0.25 {
+                    ((16.0 * backdrop - 12.0) * backdrop + 4.0) * backdrop
                 } else {
-                    255
+                    backdrop.sqrt()
                 };
-                Rgba([r, g, b, a])
-            } else {
-                Rgba([255, 0, 255, 255])
+                backdrop + (2.0 * source - 1.0) * (d - backdrop)
             }
         }
+        BlendMode::Difference => (backdrop - source).abs(),
+        BlendMode::Exclusion => backdrop + source - 2.0 * backdrop * source,
     }
 }
 
+/// Source-over with straight alpha. `alpha` is a coverage that scales the
+/// color's own alpha.
 fn blend_pixel(imgbuf: &mut RgbaImage, x: i32, y: i32, color: Rgba<u8>, alpha: f32) {
     if x < 0 || x >= imgbuf.width() as i32 || y < 0 || y >= imgbuf.height() as i32 {
         return;
     }
 
-    let existing = imgbuf.get_pixel(x as u32, y as u32);
-    let blend_alpha = alpha.max(0.0).min(1.0);
-    let inv_alpha = 1.0 - blend_alpha;
+    let src_alpha = alpha.clamp(0.0, 1.0) * color[3] as f32 / 255.0;
+    if src_alpha <= 0.0 {
+        return;
+    }
 
-    let r = (color[0] as f32 * blend_alpha + existing[0] as f32 * inv_alpha) as u8;
-    let g = (color[1] as f32 * blend_alpha + existing[1] as f32 * inv_alpha) as u8;
-    let b = (color[2] as f32 * blend_alpha + existing[2] as f32 * inv_alpha) as u8;
-    let a = (color[3] as f32 * blend_alpha + existing[3] as f32 * inv_alpha) as u8;
+    let existing = imgbuf.get_pixel(x as u32, y as u32);
+    let dst_alpha = existing[3] as f32 / 255.0 * (1.0 - src_alpha);
+    let out_alpha = src_alpha + dst_alpha;
+    let channel = |i: usize| {
+        ((color[i] as f32 * src_alpha + existing[i] as f32 * dst_alpha) / out_alpha) as u8
+    };
 
-    imgbuf.put_pixel(x as u32, y as u32, Rgba([r, g, b, a]));
+    let blended = Rgba([
+        channel(0),
+        channel(1),
+        channel(2),
+        (out_alpha * 255.0).round() as u8,
+    ]);
+    imgbuf.put_pixel(x as u32, y as u32, blended);
 }
 
 fn draw_anti_aliased_line(
@@ -1435,6 +2362,326 @@ fn draw_anti_aliased_line(
     }
 }
 
+/// Strokes a polyline given in pixels with the dash pattern, caps and joins
+/// of `style`. Dash lengths are in diagram units and multiplied by `scale`.
+/// Closed paths repeat their first point at the end and, when solid, get a
+/// join there instead of caps.
+fn draw_stroke(
+    imgbuf: &mut RgbaImage,
+    points: &[(Float, Float)],
+    closed: bool,
+    color: Rgba<u8>,
+    width: Float,
+    style: &StrokeStyle,
+    scale: Float,
+) {
+    if points.len() < 2 || width <= 0.0 {
+        return;
+    }
+
+    if style.is_dashed() {
+        let dashes: Vec<Float> = style.dash_array.iter().map(|d| d * scale).collect();
+        for dash in dash_polyline(points, &dashes, style.dash_offset * scale) {
+            stroke_polyline(imgbuf, &dash, false, color, width, style);
+        }
+    } else {
+        stroke_polyline(imgbuf, points, closed, color, width, style);
+    }
+}
+
+/// One solid piece of a stroke. Segment bodies, caps and joins are shaded
+/// into a coverage mask first so their anti-aliased edges don't add up
+/// where they overlap.
+fn stroke_polyline(
+    imgbuf: &mut RgbaImage,
+    points: &[(Float, Float)],
+    closed: bool,
+    color: Rgba<u8>,
+    width: Float,
+    style: &StrokeStyle,
+) {
+    let half = width / 2.0;
+    // Miters can reach MITER_LIMIT half widths from the path
+    let reach = half * MITER_LIMIT + 1.0;
+    let (mut min, mut max) = (points[0], points[0]);
+    for p in points {
+        min = (min.0.min(p.0), min.1.min(p.1));
+        max = (max.0.max(p.0), max.1.max(p.1));
+    }
+    let mut mask = CoverageMask::new(
+        imgbuf,
+        (min.0 - reach, min.1 - reach),
+        (max.0 + reach, max.1 + reach),
+    );
+
+    let segments: Vec<((Float, Float), (Float, Float))> = points
+        .windows(2)
+        .filter(|w| w[0] != w[1])
+        .map(|w| (w[0], w[1]))
+        .collect();
+
+    if segments.is_empty() {
+        // Zero length dash, only caps are visible
+        let center = points[0];
+        match style.line_cap {
+            LineCap::Butt => {}
+            LineCap::Round => mask.shade_disc(center, half),
+            LineCap::Square => {
+                mask.shade_segment(center, (center.0 + 1e-3, center.1), half, (half, half))
+            }
+        }
+        mask.blend(imgbuf, color);
+        return;
+    }
+
+    let cap_extension = if style.line_cap == LineCap::Square && !closed {
+        half
+    } else {
+        0.0
+    };
+    let last = segments.len() - 1;
+    // Where the path runs on almost straight, as along flattened curves, the
+    // segment ends overlap by half a pixel so their anti-aliased edges don't
+    // leave a seam across the stroke
+    let runs_on = |incoming: usize, outgoing: usize| {
+        let (a, vertex) = segments[incoming];
+        let (_, b) = segments[outgoing];
+        let d1 = (vertex.0 - a.0, vertex.1 - a.1);
+        let d2 = (b.0 - vertex.0, b.1 - vertex.1);
+        d1.0 * d2.0 + d1.1 * d2.1 > SMOOTH_JOIN_COS * d1.0.hypot(d1.1) * d2.0.hypot(d2.1)
+    };
+    let seam = |incoming: usize, outgoing: usize| {
+        if runs_on(incoming, outgoing) {
+            0.5
+        } else {
+            0.0
+        }
+    };
+    for (i, (a, b)) in segments.iter().enumerate() {
+        let start_extension = match i {
+            0 if closed && last > 0 => seam(last, 0),
+            0 => cap_extension,
+            _ => seam(i - 1, i),
+        };
+        let end_extension = match i {
+            _ if i == last && closed && last > 0 => seam(last, 0),
+            _ if i == last => cap_extension,
+            _ => seam(i, i + 1),
+        };
+        mask.shade_segment(*a, *b, half, (start_extension, end_extension));
+    }
+
+    if style.line_cap == LineCap::Round && !closed {
+        mask.shade_disc(segments[0].0, half);
+        mask.shade_disc(segments[last].1, half);
+    }
+
+    let mut corners: Vec<(usize, usize)> = (0..last).map(|i| (i, i + 1)).collect();
+    if closed && last > 0 {
+        corners.push((last, 0));
+    }
+    for (incoming, outgoing) in corners {
+        let (a, vertex) = segments[incoming];
+        let (_, b) = segments[outgoing];
+        shade_join(&mut mask, a, vertex, b, half, style.line_join);
+    }
+
+    mask.blend(imgbuf, color);
+}
+
+/// Fills the outer corner between segments `a -> vertex` and `vertex -> b`
+fn shade_join(
+    mask: &mut CoverageMask,
+    a: (Float, Float),
+    vertex: (Float, Float),
+    b: (Float, Float),
+    half: Float,
+    join: LineJoin,
+) {
+    let unit = |from: (Float, Float), to: (Float, Float)| {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        (dx / length, dy / length)
+    };
+    let d1 = unit(a, vertex);
+    let d2 = unit(vertex, b);
+    let cross = d1.0 * d2.1 - d1.1 * d2.0;
+    if cross.abs() < 1e-4 && d1.0 * d2.0 + d1.1 * d2.1 > 0.0 {
+        // Straight through, the segment bodies already meet
+        return;
+    }
+
+    if join == LineJoin::Round {
+        mask.shade_disc(vertex, half);
+        return;
+    }
+
+    // Offsets of both segments on the outside of the turn
+    let side = if cross > 0.0 { -1.0 } else { 1.0 };
+    let outer1 = (vertex.0 - side * d1.1 * half, vertex.1 + side * d1.0 * half);
+    let outer2 = (vertex.0 - side * d2.1 * half, vertex.1 + side * d2.0 * half);
+
+    if join == LineJoin::Miter {
+        let bisector = (
+            outer1.0 + outer2.0 - 2.0 * vertex.0,
+            outer1.1 + outer2.1 - 2.0 * vertex.1,
+        );
+        let bisector_length = (bisector.0 * bisector.0 + bisector.1 * bisector.1).sqrt();
+        if bisector_length > 1e-6 {
+            // cos of the angle between the bisector and each offset
+            let cos = bisector_length / (2.0 * half);
+            if 1.0 / cos <= MITER_LIMIT {
+                let reach = half / cos / bisector_length;
+                let tip = (vertex.0 + bisector.0 * reach, vertex.1 + bisector.1 * reach);
+                mask.shade_convex(&[vertex, outer1, tip, outer2]);
+                return;
+            }
+        }
+    }
+
+    mask.shade_convex(&[vertex, outer1, outer2]);
+}
+
+/// SVG's default stroke-miterlimit, longer miters are beveled
+const MITER_LIMIT: Float = 4.0;
+
+/// Cosine of the sharpest turn, about 10 degrees, whose segments overlap
+/// instead of meeting at anti-aliased ends
+const SMOOTH_JOIN_COS: Float = 0.985;
+
+/// Per pixel coverage of a shape over a window of the image, combined with
+/// max so overlapping parts of the same shape are painted once
+struct CoverageMask {
+    left: i32,
+    top: i32,
+    width: i32,
+    height: i32,
+    coverage: Vec<Float>,
+}
+
+impl CoverageMask {
+    fn new(imgbuf: &RgbaImage, min: (Float, Float), max: (Float, Float)) -> CoverageMask {
+        let left = (min.0.floor() as i32).max(0);
+        let top = (min.1.floor() as i32).max(0);
+        let right = (max.0.ceil() as i32).min(imgbuf.width() as i32);
+        let bottom = (max.1.ceil() as i32).min(imgbuf.height() as i32);
+        let width = (right - left).max(0);
+        let height = (bottom - top).max(0);
+        CoverageMask {
+            left,
+            top,
+            width,
+            height,
+            coverage: vec![0.0; (width * height) as usize],
+        }
+    }
+
+    /// Evaluates a signed distance function (negative inside) at the pixel
+    /// centers of a bounding box and keeps a one pixel anti-aliased edge
+    fn shade<F: Fn(Float, Float) -> Float>(
+        &mut self,
+        min: (Float, Float),
+        max: (Float, Float),
+        distance: F,
+    ) {
+        let x0 = ((min.0 - 1.0).floor() as i32).max(self.left);
+        let y0 = ((min.1 - 1.0).floor() as i32).max(self.top);
+        let x1 = ((max.0 + 1.0).ceil() as i32).min(self.left + self.width);
+        let y1 = ((max.1 + 1.0).ceil() as i32).min(self.top + self.height);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let value = (0.5 - distance(x as Float + 0.5, y as Float + 0.5)).clamp(0.0, 1.0);
+                let index = ((y - self.top) * self.width + (x - self.left)) as usize;
+                if value > self.coverage[index] {
+                    self.coverage[index] = value;
+                }
+            }
+        }
+    }
+
+    fn shade_disc(&mut self, center: (Float, Float), radius: Float) {
+        self.shade(
+            (center.0 - radius, center.1 - radius),
+            (center.0 + radius, center.1 + radius),
+            |x, y| ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt() - radius,
+        );
+    }
+
+    /// Body of the segment `a -> b` with butt ends pushed out by `extension`
+    fn shade_segment(
+        &mut self,
+        a: (Float, Float),
+        b: (Float, Float),
+        half: Float,
+        (start_extension, end_extension): (Float, Float),
+    ) {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        let (ux, uy) = (dx / length, dy / length);
+        let reach = half + start_extension.max(end_extension);
+        self.shade(
+            (a.0.min(b.0) - reach, a.1.min(b.1) - reach),
+            (a.0.max(b.0) + reach, a.1.max(b.1) + reach),
+            |x, y| {
+                let along = (x - a.0) * ux + (y - a.1) * uy;
+                let across = ((x - a.0) * -uy + (y - a.1) * ux).abs();
+                let outside_along = (-start_extension - along).max(along - length - end_extension);
+                let outside_across = across - half;
+                let outside =
+                    (outside_along.max(0.0).powi(2) + outside_across.max(0.0).powi(2)).sqrt();
+                outside + outside_along.max(outside_across).min(0.0)
+            },
+        );
+    }
+
+    fn shade_convex(&mut self, polygon: &[(Float, Float)]) {
+        let area: Float = polygon
+            .iter()
+            .zip(polygon.iter().cycle().skip(1))
+            .map(|(p, q)| p.0 * q.1 - q.0 * p.1)
+            .sum();
+        if area.abs() < 1e-6 {
+            return;
+        }
+        // Outward edge normals, whichever way the polygon winds
+        let orientation = area.signum();
+        let edges: Vec<((Float, Float), (Float, Float))> = polygon
+            .iter()
+            .zip(polygon.iter().cycle().skip(1))
+            .filter(|(p, q)| p != q)
+            .map(|(p, q)| {
+                let (ex, ey) = (q.0 - p.0, q.1 - p.1);
+                let length = (ex * ex + ey * ey).sqrt();
+                (*p, (orientation * ey / length, -orientation * ex / length))
+            })
+            .collect();
+
+        let mut min = polygon[0];
+        let mut max = polygon[0];
+        for p in polygon {
+            min = (min.0.min(p.0), min.1.min(p.1));
+            max = (max.0.max(p.0), max.1.max(p.1));
+        }
+        self.shade(min, max, |x, y| {
+            edges
+                .iter()
+                .map(|(p, n)| (x - p.0) * n.0 + (y - p.1) * n.1)
+                .fold(Float::MIN, Float::max)
+        });
+    }
+
+    fn blend(&self, imgbuf: &mut RgbaImage, color: Rgba<u8>) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let coverage = self.coverage[(y * self.width + x) as usize];
+                if coverage > 0.0 {
+                    blend_pixel(imgbuf, self.left + x, self.top + y, color, coverage);
+                }
+            }
+        }
+    }
+}
+
 fn draw_anti_aliased_ellipse(
     imgbuf: &mut RgbaImage,
     cx: i32,
@@ -1478,3 +2725,209 @@ fn draw_anti_aliased_ellipse(
 
     draw_anti_aliased_line(imgbuf, prev_x, prev_y, first_x, first_y, color, thickness);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use volare_engine_layout::{layout::layout_diagram, parser::JsonLinesParser};
+
+    /// Lays out and renders a JSON Lines document, decoded back to pixels
+    fn render_png(input: &str) -> RgbaImage {
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let diagram = parser.build(&root_id, &mut builder).unwrap();
+        layout_diagram(&mut builder, &diagram);
+
+        let mut png = Vec::new();
+        PNGRenderer.render(&builder, &diagram, &mut png).unwrap();
+        image::load_from_memory(&png).unwrap().to_rgba8()
+    }
+
+    /// Pixel under a point in diagram units, the renderer scales by 1.5
+    fn pixel(image: &RgbaImage, x: Float, y: Float) -> [u8; 4] {
+        image.get_pixel((x * 1.5) as u32, (y * 1.5) as u32).0
+    }
+
+    #[test]
+    fn test_linear_gradient_fill() {
+        let image = render_png(
+            r#"
+{"id":"root","type":"free_container","children":["g"]}
+{"id":"g","type":"rect","x":10,"y":10,"width":100,"height":40,"stroke_width":0,"fill":"linear-gradient(90deg, red, blue)"}
+"#,
+        );
+
+        let start = pixel(&image, 12.0, 30.0);
+        let middle = pixel(&image, 60.0, 30.0);
+        let end = pixel(&image, 108.0, 30.0);
+        assert!(start[0] > 240 && start[2] < 15);
+        assert!(end[0] < 15 && end[2] > 240);
+        assert!((120..136).contains(&middle[0]) && (120..136).contains(&middle[2]));
+        // Outside the shape the canvas stays white
+        assert_eq!(pixel(&image, 60.0, 60.0), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_opacity() {
+        let image = render_png(
+            r#"
+{"id":"root","type":"free_container","children":["r","group","f"]}
+{"id":"r","type":"rect","x":10,"y":10,"width":40,"height":40,"fill":"red","stroke_width":0,"opacity":0.5}
+{"id":"group","type":"free_container","x":60,"y":10,"opacity":0.5,"children":["b"]}
+{"id":"b","type":"rect","x":0,"y":0,"width":40,"height":40,"fill":"blue","stroke_width":0}
+{"id":"f","type":"rect","x":10,"y":60,"width":40,"height":40,"fill":"red","stroke_color":"blue","stroke_width":6,"fill_opacity":0.25}
+"#,
+        );
+
+        assert_eq!(pixel(&image, 30.0, 30.0), [255, 127, 127, 255]);
+        // A container's opacity applies to its children
+        assert_eq!(pixel(&image, 80.0, 30.0), [127, 127, 255, 255]);
+        // fill_opacity leaves the stroke opaque
+        assert_eq!(pixel(&image, 30.0, 80.0), [255, 191, 191, 255]);
+        assert_eq!(pixel(&image, 11.0, 80.0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_dashed_line() {
+        let image = render_png(
+            r#"
+{"id":"root","type":"free_container","children":["l"]}
+{"id":"l","type":"line","x":10,"y":20,"x1":0,"y1":0,"x2":100,"y2":0,"stroke_width":4,"stroke_color":"black","stroke_dasharray":"10 10"}
+"#,
+        );
+
+        // Dashes at 0-10, 20-30..., gaps in between
+        for dash in [15.0, 35.0, 55.0] {
+            assert_eq!(pixel(&image, dash, 20.0), [0, 0, 0, 255]);
+        }
+        for gap in [25.0, 45.0, 65.0] {
+            assert_eq!(pixel(&image, gap, 20.0), [255, 255, 255, 255]);
+        }
+    }
+
+    #[test]
+    fn test_drop_shadow() {
+        let image = render_png(
+            r#"
+{"id":"root","type":"free_container","children":["sharp","soft"]}
+{"id":"sharp","type":"rect","x":20,"y":20,"width":40,"height":40,"fill":"white","stroke_width":0,"shadow":"10 10 0 0 black"}
+{"id":"soft","type":"rect","x":20,"y":80,"width":40,"height":40,"fill":"white","stroke_width":0,"shadow":"0 0 8 0 black"}
+"#,
+        );
+
+        // Offset shadow shows below and right of the shape, which covers the rest
+        assert_eq!(pixel(&image, 65.0, 65.0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&image, 40.0, 40.0), [255, 255, 255, 255]);
+        assert_eq!(pixel(&image, 15.0, 15.0), [255, 255, 255, 255]);
+        assert_eq!(pixel(&image, 65.0, 25.0), [255, 255, 255, 255]);
+
+        // A blurred shadow fades out away from the edge
+        let near = pixel(&image, 62.0, 100.0)[0];
+        let far = pixel(&image, 70.0, 100.0)[0];
+        assert!(near < far && far < 255);
+    }
+
+    #[test]
+    fn test_clip_and_mask() {
+        let image = render_png(
+            r#"
+{"id":"root","type":"free_container","children":["b","masked","circle"]}
+{"id":"b","type":"box","x":10,"y":10,"clip":true,"padding":0,"width":40,"height":40,"children":["inner"]}
+{"id":"inner","type":"rect","width":80,"height":80,"fill":"red","stroke_width":0}
+{"id":"masked","type":"rect","x":70,"y":10,"width":50,"height":50,"fill":"red","stroke_width":0,"mask":"top"}
+{"id":"top","type":"rect","width":50,"height":25,"fill":"white","stroke_width":0}
+{"id":"circle","type":"rect","x":10,"y":70,"width":40,"height":40,"fill":"blue","stroke_width":0,"clip_path":"disc"}
+{"id":"disc","type":"ellipse","rx":20,"ry":20}
+"#,
+        );
+
+        // Children of a clipping box stop at its outline
+        assert_eq!(pixel(&image, 20.0, 20.0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 60.0, 30.0), [255, 255, 255, 255]);
+        assert_eq!(pixel(&image, 30.0, 60.0), [255, 255, 255, 255]);
+
+        // Outside the mask the shape is transparent
+        assert_eq!(pixel(&image, 95.0, 20.0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 95.0, 50.0), [255, 255, 255, 255]);
+
+        // Clip path: the disc's center is kept, the square's corner is not
+        assert_eq!(pixel(&image, 30.0, 90.0), [0, 0, 255, 255]);
+        assert_eq!(pixel(&image, 12.0, 72.0), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_pattern_fill() {
+        let image = render_png(
+            r#"
+{"id":"root","type":"free_container","children":["r"]}
+{"id":"r","type":"rect","x":10,"y":10,"width":60,"height":60,"stroke_width":0,"fill":{"type":"pattern","pattern":"grid","spacing":10,"color":"red","background":"yellow"}}
+"#,
+        );
+
+        // Grid lines run through the middle of each 10x10 tile
+        assert_eq!(pixel(&image, 35.0, 30.0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 30.0, 35.0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 30.0, 30.0), [255, 255, 0, 255]);
+        assert_eq!(pixel(&image, 80.0, 30.0), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_blend_modes_and_z_index() {
+        let image = render_png(
+            r#"
+{"id":"root","type":"free_container","children":["a","b","c","d"]}
+{"id":"a","type":"rect","x":10,"y":10,"width":40,"height":40,"fill":"red","stroke_width":0}
+{"id":"b","type":"rect","x":30,"y":10,"width":40,"height":40,"fill":"blue","stroke_width":0,"mix_blend_mode":"multiply"}
+{"id":"c","type":"rect","x":10,"y":60,"width":40,"height":40,"fill":"red","stroke_width":0,"z_index":2}
+{"id":"d","type":"rect","x":30,"y":60,"width":40,"height":40,"fill":"blue","stroke_width":0}
+"#,
+        );
+
+        // Red multiplied by blue is black
+        assert_eq!(pixel(&image, 40.0, 30.0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&image, 60.0, 30.0), [0, 0, 255, 255]);
+
+        // The higher z_index wins over tree order
+        assert_eq!(pixel(&image, 40.0, 80.0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 60.0, 80.0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_layer_root_keeps_ancestor_effects() {
+        let image = render_png(
+            r#"
+{"id":"root","type":"free_container","children":["faded","clipped"]}
+{"id":"faded","type":"free_container","x":10,"y":10,"opacity":0.5,"children":["r"]}
+{"id":"r","type":"rect","width":40,"height":40,"fill":"red","stroke_width":0,"layer":"annotations"}
+{"id":"clipped","type":"box","x":60,"y":10,"clip":true,"padding":0,"width":40,"height":40,"children":["big"]}
+{"id":"big","type":"rect","width":60,"height":60,"fill":"blue","stroke_width":0,"layer":"annotations"}
+"#,
+        );
+
+        // Drawn in the annotations pass, still at the container's opacity
+        assert_eq!(pixel(&image, 30.0, 30.0), [255, 127, 127, 255]);
+        // and inside its clip
+        assert_eq!(pixel(&image, 80.0, 30.0), [0, 0, 255, 255]);
+        assert_eq!(pixel(&image, 80.0, 60.0), [255, 255, 255, 255]);
+        assert_eq!(pixel(&image, 110.0, 30.0), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_path_fill_rule() {
+        let image = render_png(
+            r#"
+{"id":"root","type":"free_container","children":["odd","nonzero"]}
+{"id":"odd","type":"path","x":10,"y":10,"d":"M0 0h40v40h-40z M10 10h20v20h-20z","fill":"gray","stroke_width":0,"fill_rule":"evenodd"}
+{"id":"nonzero","type":"path","x":60,"y":10,"d":"M0 0h40v40h-40z M10 10h20v20h-20z","fill":"gray","stroke_width":0}
+"#,
+        );
+
+        // The inner square is a hole with evenodd
+        assert_eq!(pixel(&image, 15.0, 15.0), [128, 128, 128, 255]);
+        assert_eq!(pixel(&image, 30.0, 30.0), [255, 255, 255, 255]);
+        // and filled with nonzero, both squares wind the same way
+        assert_eq!(pixel(&image, 65.0, 15.0), [128, 128, 128, 255]);
+        assert_eq!(pixel(&image, 80.0, 30.0), [128, 128, 128, 255]);
+    }
+}
@@ -5,6 +5,7 @@ use volare_engine_layout::*;
 //use error
 use crate::transform::Transform;
 use std::io::Error;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 
 pub struct SVGRenderer;
 
@@ -25,14 +26,48 @@ impl<W: Write> Renderer<W> for SVGRenderer {
 
          svg.push_str(r#"<defs>"#);
         add_arrow_markers(&mut svg, session, diagram_node);
+        add_font_faces(&mut svg, session, diagram_node);
+        add_gradient_defs(&mut svg, session, diagram_node);
         svg.push_str(r#"</defs>"#);
 
-        svg.push_str(render_node(diagram_node, session).as_str());
+        if let Some(background) = &session.theme.background {
+            svg.push_str(&format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" />"#,
+                root_bounds.x,
+                root_bounds.y,
+                root_bounds.width,
+                root_bounds.height,
+                svg_color(background)
+            ));
+        }
+
+        // The tree is painted in the content layer, entities moved to other
+        // layers get a group per layer. Connectors always had their own.
+        for layer in Layer::ALL {
+            let mut layer_content = String::new();
+            if layer == Layer::Content
+                && !session.layer_roots.contains_key(&diagram_node.entity_id)
+            {
+                layer_content.push_str(&render_node(diagram_node, session));
+            }
+            for root in session.layer_entries(layer) {
+                layer_content.push_str(&render_layer_root(root, session));
+            }
 
-        // Render all connectors in a separate group at the end (on top)
-        svg.push_str(r#"<g id="connectors-layer">"#);
-        render_all_connectors(diagram_node, session, &mut svg);
-        svg.push_str("</g>");
+            match layer {
+                Layer::Content => svg.push_str(&layer_content),
+                Layer::Connectors => svg.push_str(&format!(
+                    r#"<g id="connectors-layer">{}</g>"#,
+                    layer_content
+                )),
+                _ if !layer_content.is_empty() => svg.push_str(&format!(
+                    r#"<g id="{}-layer">{}</g>"#,
+                    layer.name(),
+                    layer_content
+                )),
+                _ => {}
+            }
+        }
 
         //close svg tag
         svg.push_str("</svg>");
@@ -47,14 +82,9 @@ impl<W: Write> Renderer<W> for SVGRenderer {
     }
 }
 
-/// Renders a node and its children, but skips connectors
+/// Renders a node and its children, except the children painted in
+/// another layer
 fn render_node(node: &DiagramTreeNode, session: &DiagramBuilder) -> String {
-    // Skip connectors - they'll be rendered in a separate pass
-    if node.entity_type == EntityType::ConnectorShape {
-        return String::new();
-    }
-    
-    // Render this node normally
     let entity_id = node.entity_id.clone();
     let mut result = String::new();
 
@@ -71,115 +101,426 @@ fn render_node(node: &DiagramTreeNode, session: &DiagramBuilder) -> String {
         EntityType::PolyLine => render_polyline(session, &mut result, entity_id.clone(), node),
         EntityType::FreeContainer => render_free_container(session, &mut result, entity_id.clone(), node),
         EntityType::ArcShape => render_arc(session, &mut result, entity_id.clone(), node),
+        EntityType::PathShape => render_path(session, &mut result, entity_id.clone(), node),
         EntityType::VerticalStackShape => render_vertical_stack(session, &mut result, entity_id.clone(), node),
         EntityType::HorizontalStackShape => render_horizontal_stack(session, &mut result, entity_id.clone(), node),
         EntityType::ConstraintLayoutContainer => render_constraint_layout_container(session, &mut result, entity_id.clone(), node),
-        EntityType::ConnectorShape => {}, // Skip - handled separately
+        EntityType::ConnectorShape => render_connector(session, &mut result, entity_id.clone(), node),
         _ => {}
     }
 
     result
 }
 
-/// Collects all unique arrow configurations and creates marker definitions
+/// One `<marker>` per drawn connector or arrow end
 fn add_arrow_markers(svg: &mut String, session: &DiagramBuilder, node: &DiagramTreeNode) {
-    let mut markers = std::collections::HashSet::new();
+    let mut markers = Vec::new();
     collect_connector_markers(node, session, &mut markers);
-    
-    for marker_id in markers {
-        svg.push_str(&marker_id);
+
+    for marker in markers {
+        svg.push_str(&marker);
+    }
+}
+
+/// Geometry, stroke color and stroke width of the connector or arrow a node draws
+fn line_geometry(
+    session: &DiagramBuilder,
+    node: &DiagramTreeNode,
+) -> Option<(ConnectorGeometry, String, Float)> {
+    match node.entity_type {
+        EntityType::ConnectorShape => {
+            let connector = session.get_connector(node.entity_id.clone());
+            let geometry = session.get_connector_geometry(node.entity_id.clone())?;
+            let options = &connector.options;
+            Some((geometry.clone(), options.stroke_color.clone(), options.stroke_width))
+        }
+        EntityType::ArrowShape => {
+            let options = &session.get_arrow(node.entity_id.clone()).arrow_options;
+            Some((
+                session.get_arrow_geometry(node.entity_id.clone()),
+                options.stroke_color.clone(),
+                options.stroke_width,
+            ))
+        }
+        _ => None,
     }
 }
 
-/// Recursively collects all connector marker definitions
+/// Recursively collects the marker definitions of connectors and arrows
 fn collect_connector_markers(
     node: &DiagramTreeNode,
     session: &DiagramBuilder,
-    markers: &mut std::collections::HashSet<String>,
+    markers: &mut Vec<String>,
 ) {
-    if node.entity_type == EntityType::ConnectorShape {
-        let connector = session.get_connector(node.entity_id.clone());
-        
-        if connector.options.arrow_start || connector.options.arrow_end {
-            let marker_id = format!("arrow-{}", node.entity_id);
-            let color = &connector.options.stroke_color;
-            let size = connector.options.arrow_size;
-            
-            if connector.options.arrow_end {
-                markers.insert(format!(
-                    r#"<marker id="{}-end" markerWidth="{}" markerHeight="{}" refX="{}" refY="{}" orient="auto" markerUnits="strokeWidth">
-                        <path d="M0,0 L0,{} L{},{} z" fill="{}" />
-                    </marker>"#,
-                    marker_id, size, size, size, size / 2.0,
-                    size, size, size / 2.0, color
-                ));
-            }
-            
-            if connector.options.arrow_start {
-                markers.insert(format!(
-                    r#"<marker id="{}-start" markerWidth="{}" markerHeight="{}" refX="0" refY="{}" orient="auto" markerUnits="strokeWidth">
-                        <path d="M{},0 L{},{} L{},{} z" fill="{}" />
-                    </marker>"#,
-                    marker_id, size, size, size / 2.0,
-                    size, size, size / 2.0, size, size, color
-                ));
+    if let Some((geometry, color, width)) = line_geometry(session, node) {
+        let ends = [
+            ("start", &geometry.start_marker, &geometry.start_arrow),
+            ("end", &geometry.end_marker, &geometry.end_arrow),
+        ];
+        for (end, marker, anchor) in ends {
+            let id = format!("marker-{}-{}", node.entity_id, end);
+            if let Some(def) = marker_def(session, &id, marker, anchor, &color, width) {
+                if !markers.contains(&def) {
+                    markers.push(def);
+                }
             }
         }
     }
-    
+
     for child in &node.children {
         collect_connector_markers(child, session, markers);
     }
 }
 
-/// Recursively finds and renders all connectors in the tree
-fn render_all_connectors(node: &DiagramTreeNode, session: &DiagramBuilder, svg: &mut String) {
-    // If this is a connector, render it
-    if node.entity_type == EntityType::ConnectorShape {
-        render_connector(session, svg, node.entity_id.clone(), node);
+/// `<marker>` drawing an end marker in user space, turned to the angle of its
+/// anchor with the line's end at `refX`. `None` when there is nothing to draw
+fn marker_def(
+    session: &DiagramBuilder,
+    id: &str,
+    marker: &MarkerGeometry,
+    anchor: &ArrowAnchor,
+    color: &str,
+    stroke_width: Float,
+) -> Option<String> {
+    if marker.shapes.is_empty() && marker.entity.is_none() {
+        return None;
+    }
+
+    let color = svg_color(color);
+    let mut content = String::new();
+    for shape in &marker.shapes {
+        content.push_str(&match shape.paint {
+            MarkerPaint::Fill => format!(
+                r#"<path d="{}" fill="{}" />"#,
+                shape.path.to_svg_path(),
+                color
+            ),
+            MarkerPaint::Stroke => format!(
+                r#"<path d="{}" fill="none" stroke="{}" stroke-width="{}" />"#,
+                shape.path.to_svg_path(),
+                color,
+                stroke_width
+            ),
+        });
+    }
+    if let Some((entity, (width, height))) = &marker.entity {
+        if let Some(tile) = session.get_pattern_tile(entity.clone()) {
+            content.push_str(&format!(
+                r#"<g transform="translate({} {})">{}</g>"#,
+                -width,
+                -height / 2.0,
+                render_node(tile, session)
+            ));
+        }
     }
-    // If this is a group (potentially a connector wrapper), render it with its children
-    else if node.entity_type == EntityType::GroupShape {
-        // Check if this group contains a connector
-        let has_connector = node.children.iter().any(|child| child.entity_type == EntityType::ConnectorShape);
 
-        if has_connector {
-            // This is a connector wrapper group - render it with transform
-            let transform = session.get_transform(node.entity_id.clone());
-            let transform_str = transform.to_svg_string();
+    Some(format!(
+        r#"<marker id="{}" markerUnits="userSpaceOnUse" markerWidth="1" markerHeight="1" refX="{}" refY="0" orient="{}" overflow="visible">{}</marker>"#,
+        id,
+        -marker.inset,
+        anchor.angle.to_degrees(),
+        content
+    ))
+}
 
-            if transform_str.is_empty() {
-                svg.push_str("<g>");
-            } else {
-                svg.push_str(&format!(r#"<g transform="{}">"#, transform_str));
+/// `marker-start`/`marker-end` attributes for the ends that have a marker
+fn marker_references(entity_id: &EntityID, geometry: &ConnectorGeometry) -> String {
+    let ends = [
+        ("start", &geometry.start_marker),
+        ("end", &geometry.end_marker),
+    ];
+    ends.iter()
+        .filter(|(_, marker)| !marker.shapes.is_empty() || marker.entity.is_some())
+        .map(|(end, _)| format!(r#" marker-{end}="url(#marker-{entity_id}-{end})""#))
+        .collect()
+}
+
+/// One <linearGradient>/<radialGradient>/<pattern> per unique gradient or
+/// pattern fill
+fn add_gradient_defs(svg: &mut String, session: &DiagramBuilder, node: &DiagramTreeNode) {
+    let mut fills: Vec<Fill> = Vec::new();
+    collect_gradient_fills(node, session, &mut fills);
+
+    for fill in fills.iter() {
+        let id = gradient_id(fill);
+        let (element, stops) = match fill {
+            Fill::LinearGradient(gradient) => (
+                format!(
+                    r#"<linearGradient id="{}" x1="{}" y1="{}" x2="{}" y2="{}">"#,
+                    id, gradient.x1, gradient.y1, gradient.x2, gradient.y2
+                ),
+                &gradient.stops,
+            ),
+            Fill::RadialGradient(gradient) => (
+                format!(
+                    r#"<radialGradient id="{}" cx="{}" cy="{}" r="{}">"#,
+                    id, gradient.cx, gradient.cy, gradient.r
+                ),
+                &gradient.stops,
+            ),
+            Fill::Pattern(pattern) => {
+                svg.push_str(&pattern_def(session, &id, pattern));
+                continue;
             }
+            Fill::Color(_) => continue,
+        };
 
-            // Render all children (connector and label)
-            for child in &node.children {
-                if child.entity_type == EntityType::ConnectorShape {
-                    render_connector(session, svg, child.entity_id.clone(), child);
-                } else if child.entity_type == EntityType::TextShape {
-                    // Render the label text
-                    render_text(session, svg, child.entity_id.clone(), child);
+        svg.push_str(&element);
+        for stop in resolve_gradient_stops(stops) {
+            // Alpha in the color itself goes to stop-opacity
+            let color = Color::parse(&stop.color).unwrap_or(Color::BLACK);
+            let opacity = stop.opacity * color.a;
+            svg.push_str(&format!(
+                r#"<stop offset="{}" stop-color="{}"{} />"#,
+                stop.offset,
+                Color { a: 1.0, ..color }.to_css(),
+                if opacity < 1.0 {
+                    format!(r#" stop-opacity="{}""#, opacity)
+                } else {
+                    String::new()
+                }
+            ));
+        }
+        svg.push_str(match fill {
+            Fill::RadialGradient(_) => "</radialGradient>",
+            _ => "</linearGradient>",
+        });
+    }
+}
+
+fn collect_gradient_fills(node: &DiagramTreeNode, session: &DiagramBuilder, fills: &mut Vec<Fill>) {
+    let id = node.entity_id.clone();
+    let fill = match node.entity_type {
+        EntityType::BoxShape => Some(&session.get_box(id).box_options.fill_color),
+        EntityType::RectShape => Some(&session.get_rectangle(id).rect_options.fill_color),
+        _ => None,
+    };
+    if let Some(fill) = fill {
+        if (fill.is_gradient() || fill.is_pattern()) && !fills.contains(fill) {
+            fills.push(fill.clone());
+            // Entity patterns can use gradients and patterns of their own
+            if let Fill::Pattern(PatternFill {
+                pattern: Pattern::Entity(tile),
+                ..
+            }) = fill
+            {
+                if let Some(tile) = session.get_pattern_tile(tile.clone()) {
+                    collect_gradient_fills(tile, session, fills);
                 }
             }
+        }
+    }
 
-            svg.push_str("</g>");
-        } else {
-            // Not a connector wrapper, recurse into children
-            for child in &node.children {
-                render_all_connectors(child, session, svg);
+    // Entity markers can use them too
+    if let Some((geometry, _, _)) = line_geometry(session, node) {
+        for marker in [&geometry.start_marker, &geometry.end_marker] {
+            let tile = marker.entity.as_ref().map(|(id, _)| id.clone());
+            if let Some(tile) = tile.and_then(|id| session.get_pattern_tile(id)) {
+                collect_gradient_fills(tile, session, fills);
+            }
+        }
+    }
+
+    for child in &node.children {
+        collect_gradient_fills(child, session, fills);
+    }
+    if let Some(clip) = session.get_clip(node.entity_id.clone()) {
+        for shape in clip.shapes() {
+            collect_gradient_fills(shape, session, fills);
+        }
+    }
+}
+
+/// `<pattern>` in the user space of the filled shape, so tiles start at its
+/// top-left corner as they do in the PNG renderer
+fn pattern_def(session: &DiagramBuilder, id: &str, pattern: &PatternFill) -> String {
+    let entity_size = match &pattern.pattern {
+        Pattern::Entity(tile) => session.get_size(tile.clone()),
+        _ => (0.0, 0.0),
+    };
+    let (width, height) = pattern.tile_size(entity_size);
+
+    let mut content = String::new();
+    if let Some(background) = &pattern.background {
+        content.push_str(&format!(
+            r#"<rect width="{}" height="{}" fill="{}" />"#,
+            width,
+            height,
+            svg_color(background)
+        ));
+    }
+
+    let color = svg_color(&pattern.color);
+    let horizontal = format!(
+        r#"<line x1="0" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}" />"#,
+        height / 2.0,
+        width,
+        height / 2.0,
+        color,
+        pattern.line_width
+    );
+    let vertical = format!(
+        r#"<line x1="{}" y1="0" x2="{}" y2="{}" stroke="{}" stroke-width="{}" />"#,
+        width / 2.0,
+        width / 2.0,
+        height,
+        color,
+        pattern.line_width
+    );
+    match &pattern.pattern {
+        Pattern::Diagonal => content.push_str(&horizontal),
+        Pattern::Cross | Pattern::Grid => {
+            content.push_str(&horizontal);
+            content.push_str(&vertical);
+        }
+        Pattern::Dots => content.push_str(&format!(
+            r#"<circle cx="{}" cy="{}" r="{}" fill="{}" />"#,
+            width / 2.0,
+            height / 2.0,
+            pattern.line_width,
+            color
+        )),
+        Pattern::Entity(tile) => {
+            if let Some(tile) = session.get_pattern_tile(tile.clone()) {
+                content.push_str(&render_node(tile, session));
+            }
+        }
+    }
+
+    let transform = if pattern.angle != 0.0 {
+        format!(r#" patternTransform="rotate({})""#, pattern.angle)
+    } else {
+        String::new()
+    };
+    format!(
+        r#"<pattern id="{}" patternUnits="userSpaceOnUse" width="{}" height="{}"{}>{}</pattern>"#,
+        id, width, height, transform, content
+    )
+}
+
+/// Same gradient, same id, so shapes sharing a fill share the definition
+fn gradient_id(fill: &Fill) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    format!("{:?}", fill).hash(&mut hasher);
+    let prefix = if fill.is_pattern() { "pattern" } else { "gradient" };
+    format!("{}-{:x}", prefix, hasher.finish())
+}
+
+/// Value of a fill attribute
+fn svg_fill(fill: &Fill) -> String {
+    match fill {
+        Fill::Color(color) => svg_color(color),
+        _ => format!("url(#{})", gradient_id(fill)),
+    }
+}
+
+/// Any CSS color normalized to `#rrggbb` / `rgba()`, so the SVG looks the same
+/// in every viewer. Unparseable strings are passed through escaped.
+fn svg_color(color: &str) -> String {
+    if color.trim().eq_ignore_ascii_case("none") {
+        return "none".to_string();
+    }
+    match Color::parse(color) {
+        Ok(color) => color.to_css(),
+        Err(_) => escape_xml(color),
+    }
+}
+
+/// Embeds the registered fonts used by text entities so the viewer draws
+/// text with the same faces the layout engine measured it with.
+/// The bundled default face is not embedded to keep the output small.
+fn add_font_faces(svg: &mut String, session: &DiagramBuilder, node: &DiagramTreeNode) {
+    let mut faces: Vec<&FontFace> = Vec::new();
+    collect_font_faces(node, session, &mut faces);
+    if faces.is_empty() {
+        return;
+    }
+
+    svg.push_str("<style>");
+    for face in faces {
+        svg.push_str(&format!(
+            "@font-face{{font-family:'{}';font-weight:{};font-style:{};src:url(data:font/ttf;base64,{});}}",
+            escape_xml(&face.family),
+            face.weight,
+            face.style.as_css(),
+            BASE64.encode(face.data())
+        ));
+    }
+    svg.push_str("</style>");
+}
+
+fn collect_font_faces<'a>(
+    node: &DiagramTreeNode,
+    session: &'a DiagramBuilder,
+    faces: &mut Vec<&'a FontFace>,
+) {
+    // Include fallback faces picked for glyphs missing in the primary face
+    let mut add_runs = |text: &str, text_options: &TextOptions| {
+        let display_text = text_options.display_text(text);
+        for run in session.fonts.font_runs(&display_text, text_options) {
+            let already_added = faces.iter().any(|f| std::ptr::eq(*f, run.face));
+            if !run.face.builtin && !already_added {
+                faces.push(run.face);
             }
         }
+    };
+
+    match node.entity_type {
+        EntityType::TextShape => {
+            let text_shape = session.get_text(node.entity_id.clone());
+            for line_id in text_shape.lines.iter() {
+                let line = session.get_text_line(line_id.clone());
+                add_runs(&line.text, &text_shape.text_options);
+            }
+        }
+        EntityType::ConnectorShape => {
+            let connector = session.get_connector(node.entity_id.clone());
+            let label_path = connector.options.label_path.as_ref();
+            if let (Some(label), Some(label_path)) = (&connector.label, label_path) {
+                add_runs(label, &label_path.text_options);
+            }
+        }
+        EntityType::ArcShape => {
+            let arc_options = &session.get_arc(node.entity_id.clone()).arc_options;
+            if let Some(label) = &arc_options.label {
+                add_runs(label, &arc_options.label_path.text_options);
+            }
+        }
+        _ => {}
     }
-    else {
-        // Recursively process all children for other node types
-        for child in &node.children {
-            render_all_connectors(child, session, svg);
+
+    for child in &node.children {
+        collect_font_faces(child, session, faces);
+    }
+}
+
+/// font-family attribute value. When the requested family is not registered the
+/// face used for measurement goes first so viewers that have it match the layout.
+/// Lines are written in logical order, viewers apply the bidi algorithm.
+fn svg_font_family(session: &DiagramBuilder, text_options: &TextOptions) -> String {
+    let requested_family = first_family(&text_options.font_family);
+    let mut families = match session.fonts.resolve_for(text_options) {
+        Some(face) if !session.fonts.has_family(requested_family) => {
+            format!("{}, {}", face.family, text_options.font_family)
         }
+        _ => text_options.font_family.clone(),
+    };
+    // Same fallback chain as the layout engine, for characters missing in the main font
+    for fallback in session.fonts.fallback_families() {
+        families.push_str(&format!(", {}", fallback));
     }
+    families
 }
 
+fn first_family(font_family: &str) -> &str {
+    font_family
+        .split(',')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+}
 
 fn render_box(
     session: &DiagramBuilder,
@@ -190,7 +531,8 @@ fn render_box(
     let size = session.get_size(entity_id.clone());
     let box_shape = session.get_box(node.entity_id.clone());
 
-    let mut box_content = String::new();
+    let (shadow_defs, shadow_attribute) = shadow_filter(session, &entity_id);
+    let mut box_content = shadow_defs;
 
     let stroke_val = if box_shape.box_options.stroke_width == 0.0 {
         String::from("0")
@@ -199,27 +541,23 @@ fn render_box(
     };
 
     // Draw box rectangle
-    match &box_shape.box_options.fill_color {
-        Fill::Color(color) => {
-            
-            box_content.push_str(&format!(
-                r#"<rect x="0" y="0" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="{}" rx="{}" ry="{}" />"#,
-                size.0, size.1, color,
-                box_shape.box_options.stroke_color,
-                stroke_val.as_str(),
-                box_shape.box_options.border_radius,
-                box_shape.box_options.border_radius
-            ));
-        }
-        _ => {
-            // TODO: Handle gradients
-        }
-    }
+    box_content.push_str(&format!(
+        r#"<rect x="0" y="0" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="{}" rx="{}" ry="{}"{}{}{} />"#,
+        size.0, size.1,
+        svg_fill(&box_shape.box_options.fill_color),
+        svg_color(&box_shape.box_options.stroke_color),
+        stroke_val.as_str(),
+        box_shape.box_options.border_radius,
+        box_shape.box_options.border_radius,
+        stroke_style_attributes(&box_shape.box_options.stroke_style),
+        paint_opacity_attributes(&session.get_opacity(entity_id.clone())),
+        shadow_attribute
+    ));
 
     // Render children
-    if !node.children.is_empty() {
-        let first_child = &node.children[0];
-        box_content.push_str(&render_node(first_child, session));
+    if let Some(first_child) = session.paint_order(node).first() {
+        let child_content = render_node(first_child, session);
+        box_content.push_str(&clip_children(session, &entity_id, child_content));
     }
 
     render_with_transform(session, svg, entity_id, &box_content);
@@ -233,9 +571,10 @@ fn render_vertical_stack(
 ) {
     let mut stack_content = String::new();
 
-    for child in node.children.iter() {
+    for child in session.paint_order(node) {
         stack_content.push_str(&render_node(child, session));
     }
+    let stack_content = clip_children(session, &entity_id, stack_content);
 
     render_with_transform(session, svg, entity_id, &stack_content);
 }
@@ -248,9 +587,10 @@ fn render_horizontal_stack(
 ) {
     let mut stack_content = String::new();
 
-    for child in node.children.iter() {
+    for child in session.paint_order(node) {
         stack_content.push_str(&render_node(child, session));
     }
+    let stack_content = clip_children(session, &entity_id, stack_content);
 
     render_with_transform(session, svg, entity_id, &stack_content);
 }
@@ -271,10 +611,11 @@ fn render_polyline(
         .join(" ");
 
     let polyline_content = format!(
-        r#"<polyline points="{}" stroke="{}" stroke-width="{}" fill="none" />"#,
+        r#"<polyline points="{}" stroke="{}" stroke-width="{}"{} fill="none" />"#,
         points_str,
-        polyline_shape.line_options.stroke_color,
-        polyline_shape.line_options.stroke_width
+        svg_color(&polyline_shape.line_options.stroke_color),
+        polyline_shape.line_options.stroke_width,
+        stroke_style_attributes(&polyline_shape.line_options.stroke_style)
     );
 
     render_with_transform(session, svg, entity_id, &polyline_content);
@@ -294,15 +635,20 @@ fn render_free_container(
     // If there's a background color, draw a rectangle with it
     if let Some(bg_color) = &container.background_color {
         container_content.push_str(&format!(
-            r#"<rect x="0" y="0" width="{}" height="{}" fill="{}" />"#,
-            size.0, size.1, bg_color
+            r#"<rect x="0" y="0" width="{}" height="{}" fill="{}"{} />"#,
+            size.0,
+            size.1,
+            svg_color(bg_color),
+            paint_opacity_attributes(&session.get_opacity(entity_id.clone()))
         ));
     }
 
     // Render all children
-    for child in node.children.iter() {
-        container_content.push_str(&render_node(child, session));
+    let mut children_content = String::new();
+    for child in session.paint_order(node) {
+        children_content.push_str(&render_node(child, session));
     }
+    container_content.push_str(&clip_children(session, &entity_id, children_content));
 
     render_with_transform(session, svg, entity_id, &container_content);
 }
@@ -321,9 +667,11 @@ fn render_constraint_layout_container(
     let mut container_content = String::new();
 
     // Render all children
-    for child in node.children.iter() {
-        container_content.push_str(&render_node(child, session));
+    let mut children_content = String::new();
+    for child in session.paint_order(node) {
+        children_content.push_str(&render_node(child, session));
     }
+    container_content.push_str(&clip_children(session, &entity_id, children_content));
 
     render_with_transform(session, svg, entity_id, &container_content);
 }
@@ -358,9 +706,10 @@ fn render_image(
         }
     };
 
+    let (shadow_defs, shadow_attribute) = shadow_filter(session, &entity_id);
     let image_content = format!(
-        r#"<image x="0" y="0" width="{}" height="{}" xlink:href="{}" />"#,
-        size.0, size.1, image_src
+        r#"{}<image x="0" y="0" width="{}" height="{}" xlink:href="{}"{} />"#,
+        shadow_defs, size.0, size.1, image_src, shadow_attribute
     );
 
     render_with_transform(session, svg, entity_id, &image_content);
@@ -406,50 +755,21 @@ fn render_line(
     node: &DiagramTreeNode,
 ) {
     let line_shape = session.get_line(node.entity_id.clone());
-    let mut p_start = Point::new(0.0, 0.0);
-    let mut p_end = Point::new(0.0, 0.0);
-
-    match line_shape.start.clone() {
-        LinePointReference::Value(x, y) => {
-            p_start.x = x;
-            p_start.y = y;
-        }
-
-        LinePointReference::PointID(id) => {
-            let pos = session.get_local_position(id);
-            let line_pos = session.get_local_position(line_shape.entity.clone());
-
-            // This is required to avoid applying transformation twice
-            p_start.x = pos.0 - line_pos.0; // Convert to line-relative coords
-            p_start.y = pos.1 - line_pos.1;
-        }
-    }
-
-    match line_shape.end.clone() {
-        LinePointReference::Value(x, y) => {
-            p_end.x = x;
-            p_end.y = y;
-        }
-
-        LinePointReference::PointID(id) => {
-            let pos = session.get_local_position(id);
-            let line_pos = session.get_local_position(line_shape.entity.clone());
-
-            // This is required to avoid applying transformation twice
-            p_end.x = pos.0 - line_pos.0; // Convert to line-relative coords
-            p_end.y = pos.1 - line_pos.1;
-        }
-    }
+    let (x1, y1) = session.resolve_line_point(&line_shape.entity, &line_shape.start);
+    let (x2, y2) = session.resolve_line_point(&line_shape.entity, &line_shape.end);
+    let p_start = Point::new(x1, y1);
+    let p_end = Point::new(x2, y2);
 
     print!("Rendering line!!!!");
     let line_content = format!(
-        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}" />"#,
+        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"{} />"#,
         p_start.x,
         p_start.y,
         p_end.x,
         p_end.y,
-        line_shape.line_options.stroke_color,
-        line_shape.line_options.stroke_width
+        svg_color(&line_shape.line_options.stroke_color),
+        line_shape.line_options.stroke_width,
+        stroke_style_attributes(&line_shape.line_options.stroke_style)
     );
 
     render_with_transform(session, svg, entity_id, &line_content);
@@ -461,253 +781,36 @@ fn render_connector(
     node: &DiagramTreeNode,
 ) {
     let connector = session.get_connector(node.entity_id.clone());
-    
-    // Get point positions
-    let start_pos = session.get_local_position(connector.start_point_id.clone());
-    let end_pos = session.get_local_position(connector.end_point_id.clone());
+    let Some(geometry) = session.get_connector_geometry(node.entity_id.clone()) else {
+        return;
+    };
+
+    // The geometry is absolute, the connector is drawn at its own position
     let connector_pos = session.get_local_position(connector.entity.clone());
-    
-    // Convert to relative coordinates
-    let rel_start_x = start_pos.0 - connector_pos.0;
-    let rel_start_y = start_pos.1 - connector_pos.1;
-    let rel_end_x = end_pos.0 - connector_pos.0;
-    let rel_end_y = end_pos.1 - connector_pos.1;
+    let path = geometry.path.translated(-connector_pos.0, -connector_pos.1);
     
     let mut line_content = String::new();
-    
-    // Add arrow marker definitions if needed
-    if connector.options.arrow_start || connector.options.arrow_end {
-        let marker_id = format!("arrow-{}", entity_id);
-        line_content.push_str(&format!(
-            r#"<defs>
-                <marker id="{}-end" markerWidth="{}" markerHeight="{}" refX="{}" refY="{}" orient="auto" markerUnits="strokeWidth">
-                    <path d="M0,0 L0,{} L{},{}  z" fill="{}" />
-                </marker>
-                <marker id="{}-start" markerWidth="{}" markerHeight="{}" refX="0" refY="{}" orient="auto" markerUnits="strokeWidth">
-                    <path d="M{},0 L{},{} L{},{}  z" fill="{}" />
-                </marker>
-            </defs>"#,
-            marker_id, 
-            connector.options.arrow_size, 
-            connector.options.arrow_size,
-            connector.options.arrow_size,
-            connector.options.arrow_size / 2.0,
-            connector.options.arrow_size,
-            connector.options.arrow_size,
-            connector.options.arrow_size / 2.0,
-            connector.options.stroke_color,
-            marker_id,
-            connector.options.arrow_size,
-            connector.options.arrow_size,
-            connector.options.arrow_size / 2.0,
-            connector.options.arrow_size,
-            connector.options.arrow_size,
-            connector.options.arrow_size / 2.0,
-            connector.options.arrow_size,
-            connector.options.arrow_size,
-            connector.options.stroke_color
-        ));
-    }
-    
-    // Create marker references
-    let marker_start = if connector.options.arrow_start {
-        format!(r#" marker-start="url(#arrow-{}-start)""#, entity_id)
-    } else {
-        String::new()
-    };
-    
-    let marker_end = if connector.options.arrow_end {
-        format!(r#" marker-end="url(#arrow-{}-end)""#, entity_id)
-    } else {
-        String::new()
-    };
-    
-    // Generate path based on connector type
-    match connector.options.connector_type {
-        ConnectorType::Straight => {
-            // Original straight line implementation
-            line_content.push_str(&format!(
-                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"{}{} />"#,
-                rel_start_x,
-                rel_start_y,
-                rel_end_x,
-                rel_end_y,
-                connector.options.stroke_color,
-                connector.options.stroke_width,
-                marker_start,
-                marker_end
-            ));
-        },
-        ConnectorType::Curved => {
-            // Cubic Bezier curve
-            let curve_offset = connector.options.curve_offset.unwrap_or(50.0);
-            
-            // Calculate direction and control points
-            let dx = rel_end_x - rel_start_x;
-            let dy = rel_end_y - rel_start_y;
-            
-            // Determine if we're going mostly horizontal or vertical
-            let is_horizontal = dx.abs() > dy.abs();
-            
-            let (cp1_x, cp1_y, cp2_x, cp2_y) = if is_horizontal {
-                // Horizontal curve: control points offset vertically
-                (
-                    rel_start_x + dx * 0.5,
-                    rel_start_y + curve_offset,
-                    rel_end_x - dx * 0.5,
-                    rel_end_y + curve_offset
-                )
-            } else {
-                // Vertical curve: control points offset horizontally
-                (
-                    rel_start_x + curve_offset,
-                    rel_start_y + dy * 0.5,
-                    rel_end_x + curve_offset,
-                    rel_end_y - dy * 0.5
-                )
-            };
-            
-            let path_data = format!(
-                "M {} {} C {} {}, {} {}, {} {}",
-                rel_start_x, rel_start_y,
-                cp1_x, cp1_y,
-                cp2_x, cp2_y,
-                rel_end_x, rel_end_y
-            );
-            
-            line_content.push_str(&format!(
-                r#"<path d="{}" fill="none" stroke="{}" stroke-width="{}"{}{} />"#,
-                path_data,
-                connector.options.stroke_color,
-                connector.options.stroke_width,
-                marker_start,
-                marker_end
-            ));
-        },
-      ConnectorType::Orthogonal => {
-    let dx = rel_end_x - rel_start_x;
-    let dy = rel_end_y - rel_start_y;
-    
-    let path_data = match connector.options.routing_strategy {
-        // 2-segment strategies (one turn)
-        OrthogonalRoutingStrategy::HV => {
-            // Horizontal first, then vertical
-            // Path: Start → go horizontal to target X → go vertical to target Y
-            format!(
-                "M {} {} L {} {} L {} {}",
-                rel_start_x, rel_start_y,    // 1. Start point
-                rel_end_x, rel_start_y,      // 2. Horizontal to target X
-                rel_end_x, rel_end_y         // 3. Vertical to target Y
-            )
-        }
-        
-        OrthogonalRoutingStrategy::VH => {
-            // Vertical first, then horizontal
-            // Path: Start → go vertical to target Y → go horizontal to target X
-            format!(
-                "M {} {} L {} {} L {} {}",
-                rel_start_x, rel_start_y,    // 1. Start point
-                rel_start_x, rel_end_y,      // 2. Vertical to target Y
-                rel_end_x, rel_end_y         // 3. Horizontal to target X
-            )
-        }
-        
-        // 3-segment strategies (two turns, with midpoint)
-        OrthogonalRoutingStrategy::HVH => {
-            // Horizontal-Vertical-Horizontal
-            let mid_x = (rel_start_x + rel_end_x) / 2.0;
-            format!(
-                "M {} {} L {} {} L {} {} L {} {}",
-                rel_start_x, rel_start_y,    // 1. Start
-                mid_x, rel_start_y,          // 2. Horizontal to midpoint
-                mid_x, rel_end_y,            // 3. Vertical to target Y
-                rel_end_x, rel_end_y         // 4. Horizontal to target X
-            )
-        }
-        
-        OrthogonalRoutingStrategy::VHV => {
-            // Vertical-Horizontal-Vertical
-            if dx.abs() < 5.0 {
-                // Nearly vertical - simplify to straight line
-                format!(
-                    "M {} {} L {} {}",
-                    rel_start_x, rel_start_y,
-                    rel_end_x, rel_end_y
-                )
-            } else if dy.abs() < 5.0 {
-                // Nearly horizontal - simplify to straight line
-                format!(
-                    "M {} {} L {} {}",
-                    rel_start_x, rel_start_y,
-                    rel_end_x, rel_end_y
-                )
-            } else {
-                // Full VHV with midpoint
-                let mid_y = (rel_start_y + rel_end_y) / 2.0;
-                format!(
-                    "M {} {} L {} {} L {} {} L {} {}",
-                    rel_start_x, rel_start_y,    // 1. Start
-                    rel_start_x, mid_y,          // 2. Vertical to midpoint
-                    rel_end_x, mid_y,            // 3. Horizontal to target X
-                    rel_end_x, rel_end_y         // 4. Vertical to target Y
-                )
-            }
-        }
-        
-        OrthogonalRoutingStrategy::Auto => {
-            // Choose based on which direction is dominant
-            if dy.abs() > dx.abs() {
-                // More vertical distance: use VHV
-                if dx.abs() < 5.0 {
-                    // Nearly vertical - straight line
-                    format!(
-                        "M {} {} L {} {}",
-                        rel_start_x, rel_start_y,
-                        rel_end_x, rel_end_y
-                    )
-                } else {
-                    let mid_y = (rel_start_y + rel_end_y) / 2.0;
-                    format!(
-                        "M {} {} L {} {} L {} {} L {} {}",
-                        rel_start_x, rel_start_y,
-                        rel_start_x, mid_y,
-                        rel_end_x, mid_y,
-                        rel_end_x, rel_end_y
-                    )
-                }
-            } else {
-                // More horizontal distance: use HVH
-                if dy.abs() < 5.0 {
-                    // Nearly horizontal - straight line
-                    format!(
-                        "M {} {} L {} {}",
-                        rel_start_x, rel_start_y,
-                        rel_end_x, rel_end_y
-                    )
-                } else {
-                    let mid_x = (rel_start_x + rel_end_x) / 2.0;
-                    format!(
-                        "M {} {} L {} {} L {} {} L {} {}",
-                        rel_start_x, rel_start_y,
-                        mid_x, rel_start_y,
-                        mid_x, rel_end_y,
-                        rel_end_x, rel_end_y
-                    )
-                }
-            }
-        }
-    };
-    
+    let markers = marker_references(&entity_id, geometry);
+
+    let stroke_style = stroke_style_attributes(&connector.options.stroke_style);
+
     line_content.push_str(&format!(
         r#"<path d="{}" fill="none" stroke="{}" stroke-width="{}"{}{} />"#,
-        path_data,
-        connector.options.stroke_color,
+        path.to_svg_path(),
+        svg_color(&connector.options.stroke_color),
         connector.options.stroke_width,
-        marker_start,
-        marker_end
+        stroke_style,
+        markers
     ));
-           }
 
+    if let (Some(label), Some(label_path)) = (&connector.label, &connector.options.label_path) {
+        line_content.push_str(&render_text_path(
+            session,
+            &format!("{}-label-path", entity_id),
+            &path,
+            label,
+            label_path,
+        ));
     }
     
     render_with_transform(session, svg, entity_id, &line_content);
@@ -723,18 +826,19 @@ fn render_rectangle(
     let rect_shape = session.get_rectangle(node.entity_id.clone());
 
 
+    let (shadow_defs, shadow_attribute) = shadow_filter(session, &entity_id);
     let rect_content = format!(
-        r#"<rect x="0" y="0" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="{}" rx="{}" ry="{}" />"#,
+        r#"{}<rect x="0" y="0" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="{}" rx="{}" ry="{}"{}{} />"#,
+        shadow_defs,
         size.0,
         size.1,
-        match &rect_shape.rect_options.fill_color {
-            Fill::Color(color) => color.clone(),
-            _ => "white".to_string(), // TODO: Handle gradients
-        },
-        rect_shape.rect_options.stroke_color,
+        svg_fill(&rect_shape.rect_options.fill_color),
+        svg_color(&rect_shape.rect_options.stroke_color),
         rect_shape.rect_options.stroke_width,
         rect_shape.rect_options.border_radius,
-        rect_shape.rect_options.border_radius
+        rect_shape.rect_options.border_radius,
+        stroke_style_attributes(&rect_shape.rect_options.stroke_style),
+        shadow_attribute
     );
 
     render_with_transform(session, svg, entity_id, &rect_content);
@@ -747,15 +851,14 @@ fn render_arrow(
     node: &DiagramTreeNode,
 ) {
     let arrow_shape = session.get_arrow(node.entity_id.clone());
+    let geometry = session.get_arrow_geometry(node.entity_id.clone());
 
     let arrow_content = format!(
-        r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}" />"#,
-        arrow_shape.start.0,
-        arrow_shape.start.1,
-        arrow_shape.end.0,
-        arrow_shape.end.1,
-        arrow_shape.arrow_options.stroke_color,
-        arrow_shape.arrow_options.stroke_width
+        r#"<path d="{}" fill="none" stroke="{}" stroke-width="{}"{} />"#,
+        geometry.path.to_svg_path(),
+        svg_color(&arrow_shape.arrow_options.stroke_color),
+        arrow_shape.arrow_options.stroke_width,
+        marker_references(&entity_id, &geometry)
     );
 
     render_with_transform(session, svg, entity_id, &arrow_content);
@@ -774,15 +877,18 @@ fn render_ellipse(
     let cx = ellipse_shape.radius.0; // Center X = radius X
     let cy = ellipse_shape.radius.1; // Center Y = radius Y
 
+    let (shadow_defs, shadow_attribute) = shadow_filter(session, &entity_id);
     let ellipse_content = format!(
-        r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" stroke="{}" stroke-width="{}" fill="{}" />"#,
+        r#"{}<ellipse cx="{}" cy="{}" rx="{}" ry="{}" stroke="{}" stroke-width="{}" fill="{}"{} />"#,
+        shadow_defs,
         cx,
         cy,
         ellipse_shape.radius.0,
         ellipse_shape.radius.1,
-        ellipse_shape.ellipse_options.stroke_color,
+        svg_color(&ellipse_shape.ellipse_options.stroke_color),
         ellipse_shape.ellipse_options.stroke_width,
-        ellipse_shape.ellipse_options.fill_color
+        svg_color(&ellipse_shape.ellipse_options.fill_color),
+        shadow_attribute
     );
 
     render_with_transform(session, svg, entity_id, &ellipse_content);
@@ -796,9 +902,10 @@ fn render_group(
 ) {
     let mut group_content = String::new();
 
-    for child in node.children.iter() {
+    for child in session.paint_order(node) {
         group_content.push_str(&render_node(child, session));
     }
+    let group_content = clip_children(session, &entity_id, group_content);
 
     render_with_transform(session, svg, entity_id, &group_content);
 }
@@ -816,16 +923,17 @@ fn render_table(
 
     // Render container rect element
     table_content.push_str(&format!(
-        r#"<rect x="0" y="0" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="{}" />"#,
+        r#"<rect x="0" y="0" width="{}" height="{}" fill="{}" stroke="{}" stroke-width="{}"{} />"#,
         size.0,
         size.1,
-        table_shape.table_options.fill_color,
-        table_shape.table_options.border_color,
-        table_shape.table_options.border_width
+        svg_color(&table_shape.table_options.fill_color),
+        svg_color(&table_shape.table_options.border_color),
+        table_shape.table_options.border_width,
+        paint_opacity_attributes(&session.get_opacity(entity_id.clone()))
     ));
 
     // Render header, cells, lines, etc. Should have been added to the node
-    for child in node.children.iter() {
+    for child in session.paint_order(node) {
         table_content.push_str(&render_node(child, session));
     }
 
@@ -842,11 +950,13 @@ fn render_text(
     let text_shape = session.get_text(node.entity_id.clone());
     let size = session.get_size(entity_id.clone());
 
+    let text_options = &text_shape.text_options;
     let mut text_content = format!(
-        r#"<text x="0" y="0" fill="{}" font-size="{}px" font-family="{}">"#,
-        text_shape.text_options.text_color,
-        text_shape.text_options.font_size,
-        text_shape.text_options.font_family
+        r#"<text x="0" y="0" fill="{}" font-size="{}px" font-family="{}"{}>"#,
+        svg_color(&text_options.text_color),
+        text_options.font_size,
+        escape_xml(&svg_font_family(session, text_options)),
+        svg_text_style_attributes(text_options)
     );
 
     // Render lines
@@ -862,7 +972,7 @@ fn render_text(
         let escaped_text = if line.text.trim().is_empty() {
             "&#8203;".to_string()
         } else {
-            escape_xml(&line.text)
+            escape_xml(&text_options.display_text(&line.text))
         };
 
         text_content.push_str(&escaped_text);
@@ -872,6 +982,33 @@ fn render_text(
 
     render_with_transform(session, svg, entity_id, &text_content);
 }
+/// Optional text attributes, only emitted when they differ from the defaults
+fn svg_text_style_attributes(text_options: &TextOptions) -> String {
+    let mut attrs = String::new();
+    if text_options.font_weight != 400 {
+        attrs.push_str(&format!(r#" font-weight="{}""#, text_options.font_weight));
+    }
+    if text_options.font_style != FontStyle::Normal {
+        attrs.push_str(&format!(
+            r#" font-style="{}""#,
+            text_options.font_style.as_css()
+        ));
+    }
+    if !text_options.text_decoration.is_none() {
+        attrs.push_str(&format!(
+            r#" text-decoration="{}""#,
+            text_options.text_decoration.to_css()
+        ));
+    }
+    if text_options.letter_spacing != 0.0 {
+        attrs.push_str(&format!(
+            r#" letter-spacing="{}""#,
+            text_options.letter_spacing
+        ));
+    }
+    attrs
+}
+
 fn render_arc(
     session: &DiagramBuilder,
     svg: &mut String,
@@ -907,34 +1044,38 @@ fn render_arc(
     // Always sweep in positive direction (clockwise in SVG coordinates)
     let sweep_flag = 1;
 
+    let stroke_style = stroke_style_attributes(&arc_shape.arc_options.stroke_style);
+
     let arc_content = if (sweep - 360.0).abs() < 0.1 {
         // Special case for full circles (360°)
         if arc_shape.arc_options.filled {
             // Filled circle
             format!(
-                r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="{}" stroke-width="{}" />"#,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="{}" stroke-width="{}"{} />"#,
                 center_x,
                 center_y,
                 arc_shape.radius,
-                arc_shape.arc_options.fill_color,
-                arc_shape.arc_options.stroke_color,
-                arc_shape.arc_options.stroke_width
+                svg_color(&arc_shape.arc_options.fill_color),
+                svg_color(&arc_shape.arc_options.stroke_color),
+                arc_shape.arc_options.stroke_width,
+                stroke_style
             )
         } else {
             // Unfilled circle
             format!(
-                r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="{}" stroke-width="{}" />"#,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="{}" stroke-width="{}"{} />"#,
                 center_x,
                 center_y,
                 arc_shape.radius,
-                arc_shape.arc_options.stroke_color,
-                arc_shape.arc_options.stroke_width
+                svg_color(&arc_shape.arc_options.stroke_color),
+                arc_shape.arc_options.stroke_width,
+                stroke_style
             )
         }
     } else if arc_shape.arc_options.filled {
         // For filled arcs, create a path that includes the center (pie slice)
         format!(
-            r#"<path d="M {} {} L {} {} A {} {} 0 {} {} {} {} Z" fill="{}" stroke="{}" stroke-width="{}" />"#,
+            r#"<path d="M {} {} L {} {} A {} {} 0 {} {} {} {} Z" fill="{}" stroke="{}" stroke-width="{}"{} />"#,
             center_x,
             center_y, // Move to center
             start_x,
@@ -945,14 +1086,15 @@ fn render_arc(
             sweep_flag, // Arc flags
             end_x,
             end_y, // Arc end point
-            arc_shape.arc_options.fill_color,
-            arc_shape.arc_options.stroke_color,
-            arc_shape.arc_options.stroke_width
+            svg_color(&arc_shape.arc_options.fill_color),
+            svg_color(&arc_shape.arc_options.stroke_color),
+            arc_shape.arc_options.stroke_width,
+            stroke_style
         )
     } else {
         // For unfilled arcs, just draw the arc
         format!(
-            r#"<path d="M {} {} A {} {} 0 {} {} {} {}" fill="none" stroke="{}" stroke-width="{}" />"#,
+            r#"<path d="M {} {} A {} {} 0 {} {} {} {}" fill="none" stroke="{}" stroke-width="{}"{} />"#,
             start_x,
             start_y, // Move to start point
             arc_shape.radius,
@@ -961,14 +1103,106 @@ fn render_arc(
             sweep_flag, // Arc flags
             end_x,
             end_y, // Arc end point
-            arc_shape.arc_options.stroke_color,
-            arc_shape.arc_options.stroke_width
+            svg_color(&arc_shape.arc_options.stroke_color),
+            arc_shape.arc_options.stroke_width,
+            stroke_style
         )
     };
 
+    let mut arc_content = arc_content;
+    if let Some(label) = &arc_shape.arc_options.label {
+        let path = arc_path((center_x, center_y), arc_shape.radius, start_angle, end_angle);
+        arc_content.push_str(&render_text_path(
+            session,
+            &format!("{}-label-path", entity_id),
+            &path,
+            label,
+            &arc_shape.arc_options.label_path,
+        ));
+    }
+
     render_with_transform(session, svg, entity_id, &arc_content);
 }
 
+fn render_path(
+    session: &DiagramBuilder,
+    svg: &mut String,
+    entity_id: EntityID,
+    node: &DiagramTreeNode,
+) {
+    let path_shape = session.get_path(node.entity_id.clone());
+    let options = &path_shape.path_options;
+    let fill_rule = match options.fill_rule {
+        FillRule::NonZero => "nonzero",
+        FillRule::EvenOdd => "evenodd",
+    };
+
+    let (shadow_defs, shadow_attribute) = shadow_filter(session, &entity_id);
+    let path_content = format!(
+        r#"{}<path d="{}" fill="{}" fill-rule="{}" stroke="{}" stroke-width="{}"{}{} />"#,
+        shadow_defs,
+        path_shape.path.to_svg_path(),
+        svg_color(&options.fill_color),
+        fill_rule,
+        svg_color(&options.stroke_color),
+        options.stroke_width,
+        stroke_style_attributes(&options.stroke_style),
+        shadow_attribute
+    );
+
+    render_with_transform(session, svg, entity_id, &path_content);
+}
+
+/// Text laid along a path with <textPath>. Paths that run right to left are
+/// reversed so the text reads left to right and stays upright.
+fn render_text_path(
+    session: &DiagramBuilder,
+    path_id: &str,
+    path: &PathData,
+    label: &str,
+    label_path: &TextPathOptions,
+) -> String {
+    let flattened = path.flatten();
+    let reverse = flattened
+        .first()
+        .map(|points| runs_right_to_left(points))
+        .unwrap_or(false);
+    let (path, alignment) = if reverse {
+        // Start stays near the source, which is now the end of the path
+        let alignment = match label_path.alignment {
+            LabelAlignment::Start => LabelAlignment::End,
+            LabelAlignment::End => LabelAlignment::Start,
+            LabelAlignment::Center => LabelAlignment::Center,
+        };
+        (path.reversed(), alignment)
+    } else {
+        (path.clone(), label_path.alignment.clone())
+    };
+
+    let (start_offset, text_anchor) = match alignment {
+        LabelAlignment::Start => ("5%", "start"),
+        LabelAlignment::Center => ("50%", "middle"),
+        LabelAlignment::End => ("95%", "end"),
+    };
+
+    let text_options = &label_path.text_options;
+    format!(
+        r##"<path id="{}" d="{}" fill="none" stroke="none" /><text fill="{}" font-size="{}px" font-family="{}"{}><textPath href="#{}" xlink:href="#{}" startOffset="{}" text-anchor="{}"><tspan dy="{}">{}</tspan></textPath></text>"##,
+        path_id,
+        path.to_svg_path(),
+        svg_color(&text_options.text_color),
+        text_options.font_size,
+        escape_xml(&svg_font_family(session, text_options)),
+        svg_text_style_attributes(text_options),
+        path_id,
+        path_id,
+        start_offset,
+        text_anchor,
+        -label_path.offset,
+        escape_xml(&text_options.display_text(label))
+    )
+}
+
 
 // Add this helper function
 fn render_with_transform(
@@ -985,18 +1219,258 @@ fn render_with_transform(
         transform_str
     );
 
-    if transform_str.is_empty() {
-        // No transform, use simple group
-        svg.push_str("<g>");
-    } else {
-        // Apply transform
-        svg.push_str(&format!(r#"<g transform="{}">"#, transform_str));
+    let mut attributes = String::new();
+    if !transform_str.is_empty() {
+        attributes.push_str(&format!(r#" transform="{}""#, transform_str));
+    }
+
+    // Group opacity composites the entity and its children as a single layer
+    let opacity = session.get_opacity(entity_id.clone());
+    if opacity.opacity < 1.0 {
+        attributes.push_str(&format!(r#" opacity="{}""#, opacity.opacity));
+    }
+
+    // fill-opacity and stroke-opacity are inherited, so containers put them
+    // on their own background instead of the group
+    let is_container = matches!(
+        session.entityTypes.get(&entity_id),
+        Some(
+            EntityType::BoxShape
+                | EntityType::FreeContainer
+                | EntityType::TableShape
+                | EntityType::GroupShape
+                | EntityType::VerticalStackShape
+                | EntityType::HorizontalStackShape
+                | EntityType::ConstraintLayoutContainer
+        )
+    );
+    if !is_container {
+        attributes.push_str(&paint_opacity_attributes(&opacity));
     }
 
+    let blend_mode = session.get_layer_options(entity_id.clone()).blend_mode;
+    if blend_mode != BlendMode::Normal {
+        attributes.push_str(&format!(
+            r#" style="mix-blend-mode: {}""#,
+            blend_mode.css_name()
+        ));
+    }
+
+    let (clip_defs, clip_attributes) = clip_path_and_mask(session, &entity_id);
+    svg.push_str(&clip_defs);
+    attributes.push_str(&clip_attributes);
+
+    svg.push_str(&format!("<g{}>", attributes));
     svg.push_str(content);
     svg.push_str("</g>");
 }
 
+fn paint_opacity_attributes(opacity: &OpacityOptions) -> String {
+    let mut attributes = String::new();
+    if opacity.fill_opacity < 1.0 {
+        attributes.push_str(&format!(r#" fill-opacity="{}""#, opacity.fill_opacity));
+    }
+    if opacity.stroke_opacity < 1.0 {
+        attributes.push_str(&format!(r#" stroke-opacity="{}""#, opacity.stroke_opacity));
+    }
+    attributes
+}
+
+/// An entity moved to a layer, inside the transforms, opacity, clips and
+/// masks of the entities it is nested in. The clip paths and masks are the
+/// ones declared where the ancestors are rendered, a clipping container gets
+/// a clip of its own since it only declares one for children left in the tree.
+fn render_layer_root(root: &LayerRoot, session: &DiagramBuilder) -> String {
+    let mut svg = String::new();
+    let mut depth = 0;
+    for ancestor in &root.ancestors {
+        let mut attributes = String::new();
+        let transform = session.get_transform(ancestor.clone()).to_svg_string();
+        if !transform.is_empty() {
+            attributes.push_str(&format!(r#" transform="{}""#, transform));
+        }
+        let opacity = session.get_opacity(ancestor.clone()).opacity;
+        if opacity < 1.0 {
+            attributes.push_str(&format!(r#" opacity="{}""#, opacity));
+        }
+        attributes.push_str(&clip_path_and_mask(session, ancestor).1);
+        if !attributes.is_empty() {
+            svg.push_str(&format!("<g{}>", attributes));
+            depth += 1;
+        }
+
+        if session
+            .get_clip(ancestor.clone())
+            .is_some_and(|clip| clip.clip)
+        {
+            let id = format!("clip-{}-{}", ancestor, root.node.entity_id);
+            svg.push_str(&format!(
+                r#"<clipPath id="{}"><path d="{}" /></clipPath><g clip-path="url(#{})">"#,
+                id,
+                session.get_outline(ancestor.clone()).to_svg_path(),
+                id
+            ));
+            depth += 1;
+        }
+    }
+    svg.push_str(&render_node(&root.node, session));
+    svg.push_str(&"</g>".repeat(depth));
+    svg
+}
+
+/// Wraps a container's children in a clip to its outline when it has
+/// `clip: true` or `overflow: "hidden"`. The container's own background and
+/// stroke stay outside the clip.
+fn clip_children(session: &DiagramBuilder, entity_id: &EntityID, children: String) -> String {
+    let clipped = session
+        .get_clip(entity_id.clone())
+        .is_some_and(|clip| clip.clip);
+    if !clipped || children.is_empty() {
+        return children;
+    }
+
+    let id = format!("clip-{}", entity_id);
+    format!(
+        r#"<clipPath id="{}"><path d="{}" /></clipPath><g clip-path="url(#{})">{}</g>"#,
+        id,
+        session.get_outline(entity_id.clone()).to_svg_path(),
+        id,
+        children
+    )
+}
+
+/// Inline `<clipPath>`/`<mask>` built from an entity's `clip_path` and
+/// `mask` shapes and the attributes that apply them to its group. Both live
+/// in the group's coordinates, so the shapes keep their own transforms.
+fn clip_path_and_mask(session: &DiagramBuilder, entity_id: &EntityID) -> (String, String) {
+    let mut defs = String::new();
+    let mut attributes = String::new();
+    let clip = match session.get_clip(entity_id.clone()) {
+        Some(clip) => clip,
+        None => return (defs, attributes),
+    };
+
+    if let Some(shape) = &clip.clip_path {
+        let id = format!("clip-path-{}", entity_id);
+        let transform = session.get_transform(shape.entity_id.clone()).to_svg_string();
+        let transform_attribute = if transform.is_empty() {
+            String::new()
+        } else {
+            format!(r#" transform="{}""#, transform)
+        };
+        defs.push_str(&format!(
+            r#"<clipPath id="{}"><path d="{}"{} /></clipPath>"#,
+            id,
+            session.get_outline(shape.entity_id.clone()).to_svg_path(),
+            transform_attribute
+        ));
+        attributes.push_str(&format!(r#" clip-path="url(#{})""#, id));
+    }
+
+    if let Some(shape) = &clip.mask {
+        let id = format!("mask-{}", entity_id);
+        defs.push_str(&format!(
+            r#"<mask id="{}" mask-type="alpha">{}</mask>"#,
+            id,
+            render_node(shape, session)
+        ));
+        attributes.push_str(&format!(r#" mask="url(#{})""#, id));
+    }
+
+    (defs, attributes)
+}
+
+/// Inline `<filter>` for an entity's drop shadow and the `filter` attribute
+/// that applies it, both empty without a shadow. The filter region is set in
+/// local units so wide blurs and offsets aren't clipped.
+fn shadow_filter(session: &DiagramBuilder, entity_id: &EntityID) -> (String, String) {
+    let shadow = match session.get_shadow(entity_id.clone()) {
+        Some(shadow) => shadow,
+        None => return (String::new(), String::new()),
+    };
+    let size = session.get_size(entity_id.clone());
+    let id = format!("shadow-{}", entity_id);
+
+    let extent = shadow.extent() + 1.0;
+    let region = (
+        shadow.offset_x.min(0.0) - extent,
+        shadow.offset_y.min(0.0) - extent,
+        size.0 + shadow.offset_x.abs() + 2.0 * extent,
+        size.1 + shadow.offset_y.abs() + 2.0 * extent,
+    );
+
+    // Alpha in the color itself goes to flood-opacity
+    let color = Color::parse(&shadow.color).unwrap_or(Color::BLACK);
+    let flood = format!(
+        r#"flood-color="{}" flood-opacity="{}""#,
+        Color { a: 1.0, ..color }.to_css(),
+        color.a
+    );
+
+    let primitives = if shadow.spread == 0.0 {
+        format!(
+            r#"<feDropShadow dx="{}" dy="{}" stdDeviation="{}" {} />"#,
+            shadow.offset_x,
+            shadow.offset_y,
+            shadow.std_deviation(),
+            flood
+        )
+    } else {
+        // feDropShadow has no spread, grow or shrink the alpha and merge by hand
+        format!(
+            concat!(
+                r#"<feMorphology in="SourceAlpha" operator="{}" radius="{}" />"#,
+                r#"<feGaussianBlur stdDeviation="{}" />"#,
+                r#"<feOffset dx="{}" dy="{}" result="shadow" />"#,
+                r#"<feFlood {} />"#,
+                r#"<feComposite in2="shadow" operator="in" />"#,
+                r#"<feMerge><feMergeNode /><feMergeNode in="SourceGraphic" /></feMerge>"#
+            ),
+            if shadow.spread > 0.0 { "dilate" } else { "erode" },
+            shadow.spread.abs(),
+            shadow.std_deviation(),
+            shadow.offset_x,
+            shadow.offset_y,
+            flood
+        )
+    };
+
+    (
+        format!(
+            r#"<defs><filter id="{}" filterUnits="userSpaceOnUse" x="{}" y="{}" width="{}" height="{}">{}</filter></defs>"#,
+            id, region.0, region.1, region.2, region.3, primitives
+        ),
+        format!(r#" filter="url(#{})""#, id),
+    )
+}
+
+fn stroke_style_attributes(style: &StrokeStyle) -> String {
+    let mut attributes = String::new();
+    if style.is_dashed() {
+        let dashes = style
+            .dash_array
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        attributes.push_str(&format!(r#" stroke-dasharray="{}""#, dashes));
+        if style.dash_offset != 0.0 {
+            attributes.push_str(&format!(r#" stroke-dashoffset="{}""#, style.dash_offset));
+        }
+    }
+    match style.line_cap {
+        LineCap::Butt => {}
+        LineCap::Round => attributes.push_str(r#" stroke-linecap="round""#),
+        LineCap::Square => attributes.push_str(r#" stroke-linecap="square""#),
+    }
+    match style.line_join {
+        LineJoin::Miter => {}
+        LineJoin::Round => attributes.push_str(r#" stroke-linejoin="round""#),
+        LineJoin::Bevel => attributes.push_str(r#" stroke-linejoin="bevel""#),
+    }
+    attributes
+}
+
 fn escape_xml(text: &str) -> String {
     text.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -1004,3 +1478,191 @@ fn escape_xml(text: &str) -> String {
         .replace('"', "&quot;")
         .replace('\'', "&apos;")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use volare_engine_layout::{layout::layout_diagram, parser::JsonLinesParser};
+
+    /// Lays out and renders a JSON Lines document to SVG markup
+    fn render_svg(input: &str) -> String {
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let diagram = parser.build(&root_id, &mut builder).unwrap();
+        layout_diagram(&mut builder, &diagram);
+
+        let mut svg = Vec::new();
+        SVGRenderer.render(&builder, &diagram, &mut svg).unwrap();
+        String::from_utf8(svg).unwrap()
+    }
+
+    #[test]
+    fn test_linear_gradient_fill() {
+        let svg = render_svg(
+            r#"
+{"id":"root","type":"free_container","children":["g"]}
+{"id":"g","type":"rect","x":10,"y":10,"width":100,"height":40,"stroke_width":0,"fill":"linear-gradient(90deg, red, blue)"}
+"#,
+        );
+
+        assert!(svg.contains(r#"x1="0" y1="0.5" x2="1" y2="0.5""#));
+        assert!(svg.contains(r##"<stop offset="0" stop-color="#ff0000" />"##));
+        assert!(svg.contains(r##"<stop offset="1" stop-color="#0000ff" />"##));
+        // The shape refers to the gradient declared in <defs>
+        let id = svg
+            .split(r#"<linearGradient id=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap();
+        assert!(svg.contains(&format!(r#"fill="url(#{})""#, id)));
+    }
+
+    #[test]
+    fn test_opacity() {
+        let svg = render_svg(
+            r#"
+{"id":"root","type":"free_container","children":["r","group"]}
+{"id":"r","type":"rect","x":10,"y":10,"width":40,"height":40,"fill":"red","opacity":0.5,"fill_opacity":0.25,"stroke_opacity":0.75}
+{"id":"group","type":"free_container","x":60,"y":10,"opacity":0.5,"children":["b"]}
+{"id":"b","type":"rect","x":0,"y":0,"width":40,"height":40,"fill":"blue"}
+"#,
+        );
+
+        assert!(svg.contains(
+            r#"<g transform="translate(10 10)" opacity="0.5" fill-opacity="0.25" stroke-opacity="0.75">"#
+        ));
+        // Containers take group opacity only
+        assert!(svg.contains(r#"<g transform="translate(60 10)" opacity="0.5"><g>"#));
+    }
+
+    #[test]
+    fn test_dashed_line() {
+        let svg = render_svg(
+            r#"
+{"id":"root","type":"free_container","children":["l","r"]}
+{"id":"l","type":"line","x":10,"y":20,"x2":100,"stroke_width":4,"stroke_dasharray":"10 10","stroke_linecap":"round"}
+{"id":"r","type":"rect","x":10,"y":40,"width":50,"height":20,"border_width":2,"stroke_dasharray":"dotted"}
+"#,
+        );
+
+        assert!(
+            svg.contains(r#"stroke-width="4" stroke-dasharray="10 10" stroke-linecap="round" />"#)
+        );
+        // Dots are as long as the stroke is wide, with round caps
+        assert!(svg.contains(r#"stroke-dasharray="0 4" stroke-linecap="round""#));
+    }
+
+    #[test]
+    fn test_drop_shadow() {
+        let svg = render_svg(
+            r#"
+{"id":"root","type":"free_container","children":["r"]}
+{"id":"r","type":"rect","x":20,"y":20,"width":40,"height":40,"shadow":"10 10 4 0 rgba(0,0,0,0.5)"}
+"#,
+        );
+
+        assert!(svg.contains(r#"<filter id="shadow-r" filterUnits="userSpaceOnUse""#));
+        // The blur radius is twice the standard deviation, alpha goes to flood-opacity
+        assert!(svg.contains(
+            r##"<feDropShadow dx="10" dy="10" stdDeviation="2" flood-color="#000000" flood-opacity="0.5" />"##
+        ));
+        assert!(svg.contains(r#"filter="url(#shadow-r)""#));
+    }
+
+    #[test]
+    fn test_clip_and_mask() {
+        let svg = render_svg(
+            r#"
+{"id":"root","type":"free_container","children":["b","r"]}
+{"id":"b","type":"box","x":10,"y":10,"clip":true,"padding":0,"width":40,"height":40,"children":["inner"]}
+{"id":"inner","type":"rect","width":80,"height":80}
+{"id":"r","type":"rect","x":60,"y":10,"width":50,"height":50,"clip_path":"disc","mask":"top"}
+{"id":"disc","type":"ellipse","rx":20,"ry":20}
+{"id":"top","type":"rect","width":50,"height":25,"fill":"white"}
+"#,
+        );
+
+        // The box clips its children, not its own background
+        assert!(svg.contains(
+            r#"<clipPath id="clip-b"><path d="M 0 0 L 40 0 L 40 40 L 0 40 Z" /></clipPath><g clip-path="url(#clip-b)">"#
+        ));
+        assert!(svg.contains(r#"<clipPath id="clip-path-r"><path d="M 0 20 A 20 20"#));
+        assert!(svg.contains(r#"<mask id="mask-r" mask-type="alpha">"#));
+        assert!(svg.contains(
+            r#"<g transform="translate(60 10)" clip-path="url(#clip-path-r)" mask="url(#mask-r)">"#
+        ));
+    }
+
+    #[test]
+    fn test_pattern_fill() {
+        let svg = render_svg(
+            r#"
+{"id":"root","type":"free_container","children":["r"]}
+{"id":"r","type":"rect","x":10,"y":10,"width":60,"height":60,"fill":{"type":"pattern","pattern":"grid","spacing":10,"color":"red","background":"yellow"}}
+"#,
+        );
+
+        assert!(svg.contains(r#"patternUnits="userSpaceOnUse" width="10" height="10">"#));
+        assert!(svg.contains(r##"<rect width="10" height="10" fill="#ffff00" />"##));
+        assert!(svg.contains(
+            r##"<line x1="0" y1="5" x2="10" y2="5" stroke="#ff0000" stroke-width="1" />"##
+        ));
+        let id = svg
+            .split(r#"<pattern id=""#)
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap();
+        assert!(svg.contains(&format!(r#"fill="url(#{})""#, id)));
+    }
+
+    #[test]
+    fn test_blend_modes_and_layers() {
+        let svg = render_svg(
+            r#"
+{"id":"root","type":"free_container","children":["a","b","faded"]}
+{"id":"a","type":"rect","x":10,"y":10,"width":40,"height":40,"fill":"red","z_index":2}
+{"id":"b","type":"rect","x":30,"y":10,"width":40,"height":40,"fill":"blue","mix_blend_mode":"multiply"}
+{"id":"faded","type":"box","x":10,"y":60,"opacity":0.5,"clip":true,"padding":0,"width":40,"height":40,"children":["note"]}
+{"id":"note","type":"rect","width":80,"height":80,"fill":"green","layer":"annotations"}
+"#,
+        );
+
+        assert!(
+            svg.contains(r#"<g transform="translate(30 10)" style="mix-blend-mode: multiply">"#)
+        );
+        // The higher z_index is painted later
+        let red = svg.find(r##"fill="#ff0000""##).unwrap();
+        let blue = svg.find(r##"fill="#0000ff""##).unwrap();
+        assert!(blue < red);
+
+        // A layer root keeps the opacity and clip of the container it is nested in
+        let layer = &svg[svg.find(r#"<g id="annotations-layer">"#).unwrap()..];
+        assert!(layer.starts_with(
+            r#"<g id="annotations-layer"><g transform="translate(10 60)" opacity="0.5"><clipPath id="clip-faded-note">"#
+        ));
+        assert!(layer.contains(r#"<g clip-path="url(#clip-faded-note)">"#));
+        assert!(layer.contains(r##"fill="#008000""##));
+    }
+
+    #[test]
+    fn test_path_fill_rule() {
+        let svg = render_svg(
+            r#"
+{"id":"root","type":"free_container","children":["odd","nonzero"]}
+{"id":"odd","type":"path","x":10,"y":10,"d":"M0 0h40v40h-40z M10 10h20v20h-20z","fill":"gray","fill_rule":"evenodd"}
+{"id":"nonzero","type":"path","x":60,"y":10,"d":"M0 0h40v40h-40z M10 10h20v20h-20z","fill":"gray"}
+"#,
+        );
+
+        let d = "M 0 0 L 40 0 L 40 40 L 0 40 Z M 10 10 L 30 10 L 30 30 L 10 30 Z";
+        assert!(svg.contains(&format!(
+            r##"<path d="{}" fill="#808080" fill-rule="evenodd""##,
+            d
+        )));
+        assert!(svg.contains(&format!(
+            r##"<path d="{}" fill="#808080" fill-rule="nonzero""##,
+            d
+        )));
+    }
+}
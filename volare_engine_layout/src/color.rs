@@ -0,0 +1,734 @@
+use crate::components::Float;
+
+// CSS color parsing shared by the parser and both renderers.
+//
+// Every color attribute stays a `String` in the component options so the
+// JSONL round-trips untouched; this module turns those strings into sRGB.
+// Wide gamut spaces (lab, lch, oklab, oklch, display-p3...) are converted to
+// sRGB and clamped, since both outputs are sRGB.
+
+/// An sRGB color with straight (non premultiplied) alpha in 0..1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: Float,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorError {
+    Empty,
+    UnknownName(String),
+    InvalidHex(String),
+    InvalidFunction { input: String, reason: String },
+    UnsupportedColorSpace(String),
+}
+
+impl std::fmt::Display for ColorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorError::Empty => write!(f, "empty color"),
+            ColorError::UnknownName(name) => write!(f, "unknown color name '{}'", name),
+            ColorError::InvalidHex(hex) => {
+                write!(
+                    f,
+                    "invalid hex color '{}', expected #rgb, #rgba, #rrggbb or #rrggbbaa",
+                    hex
+                )
+            }
+            ColorError::InvalidFunction { input, reason } => {
+                write!(f, "invalid color '{}': {}", input, reason)
+            }
+            ColorError::UnsupportedColorSpace(space) => {
+                write!(f, "unsupported color space '{}'", space)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ColorError {}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0.0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 1.0 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: Float) -> Self {
+        Color { r, g, b, a }
+    }
+
+    /// Parses any CSS Color Level 4 value: named colors, `transparent`,
+    /// `currentColor` (black, there is no inherited color), hex, `rgb()`,
+    /// `rgba()`, `hsl()`, `hsla()`, `hwb()`, `lab()`, `lch()`, `oklab()`,
+    /// `oklch()` and `color()`. `none` is accepted as transparent since
+    /// it is the usual SVG value for "no fill".
+    pub fn parse(input: &str) -> Result<Color, ColorError> {
+        let value = input.trim().to_ascii_lowercase();
+        if value.is_empty() {
+            return Err(ColorError::Empty);
+        }
+
+        if let Some(hex) = value.strip_prefix('#') {
+            return parse_hex(hex).ok_or_else(|| ColorError::InvalidHex(input.trim().to_string()));
+        }
+
+        if let Some(open) = value.find('(') {
+            let name = value[..open].trim();
+            let body =
+                value[open + 1..]
+                    .strip_suffix(')')
+                    .ok_or_else(|| ColorError::InvalidFunction {
+                        input: input.trim().to_string(),
+                        reason: "missing closing parenthesis".to_string(),
+                    })?;
+            return parse_function(name, body).map_err(|reason| match reason {
+                FunctionError::Space(space) => ColorError::UnsupportedColorSpace(space),
+                FunctionError::Syntax(reason) => ColorError::InvalidFunction {
+                    input: input.trim().to_string(),
+                    reason,
+                },
+            });
+        }
+
+        match value.as_str() {
+            "transparent" | "none" => Ok(Color::TRANSPARENT),
+            "currentcolor" => Ok(Color::BLACK),
+            // Names written as separate words ("light gray") were accepted
+            // before CSS parsing, they match the joined CSS keyword
+            name => named_color(name)
+                .or_else(|| named_color(&name.split_whitespace().collect::<String>()))
+                .ok_or_else(|| ColorError::UnknownName(input.trim().into())),
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.a >= 1.0
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.a <= 0.0
+    }
+
+    pub fn to_rgba8(&self) -> [u8; 4] {
+        [
+            self.r,
+            self.g,
+            self.b,
+            (self.a.clamp(0.0, 1.0) * 255.0).round() as u8,
+        ]
+    }
+
+    /// `#rrggbb` for opaque colors, `rgba(r, g, b, a)` otherwise. Both forms
+    /// are understood by every SVG consumer, unlike most CSS 4 syntax.
+    pub fn to_css(&self) -> String {
+        if self.is_opaque() {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            let alpha = (self.a.clamp(0.0, 1.0) * 1000.0).round() / 1000.0;
+            format!("rgba({}, {}, {}, {})", self.r, self.g, self.b, alpha)
+        }
+    }
+}
+
+impl std::str::FromStr for Color {
+    type Err = ColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Color::parse(s)
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|d| d * 17);
+    let pair = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let (r, g, b, a) = match hex.len() {
+        3 => (digit(0)?, digit(1)?, digit(2)?, 255),
+        4 => (digit(0)?, digit(1)?, digit(2)?, digit(3)?),
+        6 => (pair(0)?, pair(2)?, pair(4)?, 255),
+        8 => (pair(0)?, pair(2)?, pair(4)?, pair(6)?),
+        _ => return None,
+    };
+    Some(Color::rgba(r, g, b, a as Float / 255.0))
+}
+
+enum FunctionError {
+    Space(String),
+    Syntax(String),
+}
+
+fn syntax<T>(reason: impl Into<String>) -> Result<T, FunctionError> {
+    Err(FunctionError::Syntax(reason.into()))
+}
+
+/// A channel value: `50%`, `0.5`, `120deg` or the CSS 4 `none` keyword.
+#[derive(Clone, Copy)]
+enum Component {
+    Number(f64),
+    Percent(f64),
+    Angle(f64),
+    None,
+}
+
+impl Component {
+    fn parse(token: &str) -> Result<Component, FunctionError> {
+        if token == "none" {
+            return Ok(Component::None);
+        }
+        let number = |s: &str| match s.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(n),
+            _ => syntax(format!("'{}' is not a number", token)),
+        };
+        if let Some(n) = token.strip_suffix('%') {
+            return Ok(Component::Percent(number(n)?));
+        }
+        for (unit, to_deg) in [
+            ("deg", 1.0),
+            ("grad", 0.9),
+            ("rad", 180.0 / std::f64::consts::PI),
+            ("turn", 360.0),
+        ] {
+            if let Some(n) = token.strip_suffix(unit) {
+                return Ok(Component::Angle(number(n)? * to_deg));
+            }
+        }
+        Ok(Component::Number(number(token)?))
+    }
+
+    /// Plain numbers are taken as is, percentages are scaled so `100%` is `full`
+    fn value(self, full: f64) -> Result<f64, FunctionError> {
+        match self {
+            Component::Number(n) => Ok(n),
+            Component::Percent(p) => Ok(p / 100.0 * full),
+            Component::None => Ok(0.0),
+            Component::Angle(_) => syntax("unexpected angle"),
+        }
+    }
+
+    fn hue(self) -> Result<f64, FunctionError> {
+        match self {
+            Component::Number(n) | Component::Angle(n) => Ok(n.rem_euclid(360.0)),
+            Component::None => Ok(0.0),
+            Component::Percent(_) => syntax("hue cannot be a percentage"),
+        }
+    }
+
+    fn alpha(self) -> Result<f64, FunctionError> {
+        Ok(self.value(1.0)?.clamp(0.0, 1.0))
+    }
+}
+
+/// Splits `r g b / a` and `r, g, b, a` into three channels and an alpha
+fn split_channels(body: &str) -> Result<([Component; 3], Component), FunctionError> {
+    let spaced = body.replace('/', " / ");
+    let tokens: Vec<&str> = spaced
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let (channels, alpha) = match tokens.iter().position(|t| *t == "/") {
+        Some(slash) if slash + 2 == tokens.len() => (&tokens[..slash], Some(tokens[slash + 1])),
+        Some(_) => return syntax("expected a single alpha value after '/'"),
+        None if tokens.len() == 4 => (&tokens[..3], Some(tokens[3])),
+        None => (&tokens[..], None),
+    };
+    if channels.len() != 3 {
+        return syntax(format!("expected 3 channels, found {}", channels.len()));
+    }
+
+    let alpha = match alpha {
+        Some(token) => Component::parse(token)?,
+        None => Component::Number(1.0),
+    };
+    Ok((
+        [
+            Component::parse(channels[0])?,
+            Component::parse(channels[1])?,
+            Component::parse(channels[2])?,
+        ],
+        alpha,
+    ))
+}
+
+fn parse_function(name: &str, body: &str) -> Result<Color, FunctionError> {
+    if name == "color" {
+        return parse_color_function(body);
+    }
+
+    let ([c0, c1, c2], alpha) = split_channels(body)?;
+    let alpha = alpha.alpha()?;
+    let rgb = match name {
+        "rgb" | "rgba" => [
+            c0.value(255.0)? / 255.0,
+            c1.value(255.0)? / 255.0,
+            c2.value(255.0)? / 255.0,
+        ],
+        "hsl" | "hsla" => hsl_to_srgb(
+            c0.hue()?,
+            c1.value(100.0)? / 100.0,
+            c2.value(100.0)? / 100.0,
+        ),
+        "hwb" => hwb_to_srgb(
+            c0.hue()?,
+            c1.value(100.0)? / 100.0,
+            c2.value(100.0)? / 100.0,
+        ),
+        "lab" => lab_to_srgb(c0.value(100.0)?, c1.value(125.0)?, c2.value(125.0)?),
+        "lch" => {
+            let (a, b) = polar_to_cartesian(c1.value(150.0)?, c2.hue()?);
+            lab_to_srgb(c0.value(100.0)?, a, b)
+        }
+        "oklab" => oklab_to_srgb(c0.value(1.0)?, c1.value(0.4)?, c2.value(0.4)?),
+        "oklch" => {
+            let (a, b) = polar_to_cartesian(c1.value(0.4)?, c2.hue()?);
+            oklab_to_srgb(c0.value(1.0)?, a, b)
+        }
+        other => return syntax(format!("unknown color function '{}'", other)),
+    };
+    Ok(from_srgb(rgb, alpha))
+}
+
+/// `color(<space> c1 c2 c3 [/ alpha])`
+fn parse_color_function(body: &str) -> Result<Color, FunctionError> {
+    let body = body.trim();
+    let split = body.find(char::is_whitespace).unwrap_or(body.len());
+    let (space, rest) = body.split_at(split);
+    let ([c0, c1, c2], alpha) = split_channels(rest)?;
+    let alpha = alpha.alpha()?;
+    let c = [c0.value(1.0)?, c1.value(1.0)?, c2.value(1.0)?];
+
+    let rgb = match space {
+        "srgb" => c,
+        "srgb-linear" => c.map(srgb_gamma),
+        "display-p3" => xyz_d65_to_srgb(mul(&P3_TO_XYZ, c.map(srgb_linear))),
+        "rec2020" => xyz_d65_to_srgb(mul(&REC2020_TO_XYZ, c.map(rec2020_linear))),
+        "a98-rgb" => xyz_d65_to_srgb(mul(&A98_TO_XYZ, c.map(|v| signed_pow(v, 563.0 / 256.0)))),
+        "prophoto-rgb" => xyz_d50_to_srgb(mul(&PROPHOTO_TO_XYZ_D50, c.map(prophoto_linear))),
+        "xyz" | "xyz-d65" => xyz_d65_to_srgb(c),
+        "xyz-d50" => xyz_d50_to_srgb(c),
+        other => return Err(FunctionError::Space(other.to_string())),
+    };
+    Ok(from_srgb(rgb, alpha))
+}
+
+fn from_srgb(rgb: [f64; 3], alpha: f64) -> Color {
+    let channel = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Color::rgba(
+        channel(rgb[0]),
+        channel(rgb[1]),
+        channel(rgb[2]),
+        alpha as Float,
+    )
+}
+
+fn polar_to_cartesian(chroma: f64, hue: f64) -> (f64, f64) {
+    let h = hue.to_radians();
+    (chroma * h.cos(), chroma * h.sin())
+}
+
+fn hsl_to_srgb(hue: f64, saturation: f64, lightness: f64) -> [f64; 3] {
+    let s = saturation.clamp(0.0, 1.0);
+    let l = lightness.clamp(0.0, 1.0);
+    let f = |n: f64| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = s * l.min(1.0 - l);
+        l - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    [f(0.0), f(8.0), f(4.0)]
+}
+
+fn hwb_to_srgb(hue: f64, white: f64, black: f64) -> [f64; 3] {
+    let (w, b) = (white.max(0.0), black.max(0.0));
+    if w + b >= 1.0 {
+        return [w / (w + b); 3];
+    }
+    hsl_to_srgb(hue, 1.0, 0.5).map(|v| v * (1.0 - w - b) + w)
+}
+
+fn lab_to_srgb(l: f64, a: f64, b: f64) -> [f64; 3] {
+    const KAPPA: f64 = 24389.0 / 27.0;
+    const EPSILON: f64 = 216.0 / 24389.0;
+    const D50_WHITE: [f64; 3] = [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+
+    let f1 = (l + 16.0) / 116.0;
+    let f0 = a / 500.0 + f1;
+    let f2 = f1 - b / 200.0;
+    let cube_or_linear = |f: f64| {
+        if f.powi(3) > EPSILON {
+            f.powi(3)
+        } else {
+            (116.0 * f - 16.0) / KAPPA
+        }
+    };
+    let y = if l > KAPPA * EPSILON {
+        f1.powi(3)
+    } else {
+        l / KAPPA
+    };
+    let xyz = [cube_or_linear(f0), y, cube_or_linear(f2)];
+    xyz_d50_to_srgb([
+        xyz[0] * D50_WHITE[0],
+        xyz[1] * D50_WHITE[1],
+        xyz[2] * D50_WHITE[2],
+    ])
+}
+
+fn oklab_to_srgb(l: f64, a: f64, b: f64) -> [f64; 3] {
+    let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    [
+        4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_,
+        -1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_,
+        -0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_,
+    ]
+    .map(srgb_gamma)
+}
+
+fn xyz_d50_to_srgb(xyz: [f64; 3]) -> [f64; 3] {
+    xyz_d65_to_srgb(mul(&D50_TO_D65, xyz))
+}
+
+fn xyz_d65_to_srgb(xyz: [f64; 3]) -> [f64; 3] {
+    mul(&XYZ_TO_LINEAR_SRGB, xyz).map(srgb_gamma)
+}
+
+fn mul(m: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|i| m[i][0] * v[0] + m[i][1] * v[1] + m[i][2] * v[2])
+}
+
+fn signed_pow(v: f64, exponent: f64) -> f64 {
+    v.signum() * v.abs().powf(exponent)
+}
+
+fn srgb_linear(v: f64) -> f64 {
+    if v.abs() <= 0.04045 {
+        v / 12.92
+    } else {
+        v.signum() * ((v.abs() + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn srgb_gamma(v: f64) -> f64 {
+    if v.abs() > 0.0031308 {
+        v.signum() * (1.055 * v.abs().powf(1.0 / 2.4) - 0.055)
+    } else {
+        12.92 * v
+    }
+}
+
+fn rec2020_linear(v: f64) -> f64 {
+    const ALPHA: f64 = 1.09929682680944;
+    const BETA: f64 = 0.018053968510807;
+    if v.abs() < BETA * 4.5 {
+        v / 4.5
+    } else {
+        v.signum() * ((v.abs() + ALPHA - 1.0) / ALPHA).powf(1.0 / 0.45)
+    }
+}
+
+fn prophoto_linear(v: f64) -> f64 {
+    if v.abs() <= 16.0 / 512.0 {
+        v / 16.0
+    } else {
+        signed_pow(v, 1.8)
+    }
+}
+
+// Conversion matrices from the CSS Color 4 sample code
+const XYZ_TO_LINEAR_SRGB: [[f64; 3]; 3] = [
+    [3.2409699419045226, -1.537383177570094, -0.4986107602930034],
+    [-0.9692436362808796, 1.8759675015077202, 0.04155505740717559],
+    [
+        0.05563007969699366,
+        -0.20397695888897652,
+        1.0569715142428786,
+    ],
+];
+
+const D50_TO_D65: [[f64; 3]; 3] = [
+    [0.955473421488075, -0.02309845494876471, 0.06325924320057072],
+    [
+        -0.0283697093338637,
+        1.0099953980813041,
+        0.021041441191917323,
+    ],
+    [
+        0.012314014864481998,
+        -0.020507649298898964,
+        1.330365926242124,
+    ],
+];
+
+const P3_TO_XYZ: [[f64; 3]; 3] = [
+    [0.4865709486482162, 0.26566769316909306, 0.1982172852343625],
+    [0.2289745640697488, 0.6917385218365064, 0.079286914093745],
+    [0.0, 0.04511338185890264, 1.043944368900976],
+];
+
+const REC2020_TO_XYZ: [[f64; 3]; 3] = [
+    [0.6369580483012914, 0.14461690358620832, 0.1688809751641721],
+    [0.2627002120112671, 0.6779980715188708, 0.05930171646986196],
+    [0.0, 0.028072693049087428, 1.060985057710791],
+];
+
+const A98_TO_XYZ: [[f64; 3]; 3] = [
+    [0.5766690429101305, 0.1855582379065463, 0.1882286462349947],
+    [0.29734497525053605, 0.6273635662554661, 0.07529145849399788],
+    [0.02703136138641234, 0.07068885253582723, 0.9913375368376388],
+];
+
+const PROPHOTO_TO_XYZ_D50: [[f64; 3]; 3] = [
+    [0.7977604896723027, 0.13518583717574031, 0.0313493495815248],
+    [
+        0.2880711282292934,
+        0.7118432178101014,
+        0.00008565396060525902,
+    ],
+    [0.0, 0.0, 0.8251046025104601],
+];
+
+fn named_color(name: &str) -> Option<Color> {
+    NAMED_COLORS
+        .binary_search_by(|(candidate, _)| candidate.cmp(&name))
+        .ok()
+        .map(|index| {
+            let rgb = NAMED_COLORS[index].1;
+            Color::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+        })
+}
+
+// CSS named colors, sorted for binary search
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xF0F8FF),
+    ("antiquewhite", 0xFAEBD7),
+    ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF),
+    ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF),
+    ("blueviolet", 0x8A2BE2),
+    ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887),
+    ("cadetblue", 0x5F9EA0),
+    ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50),
+    ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC),
+    ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B),
+    ("darkcyan", 0x008B8B),
+    ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B),
+    ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A),
+    ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F),
+    ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493),
+    ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0),
+    ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF),
+    ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xADFF2F),
+    ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4),
+    ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082),
+    ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5),
+    ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD),
+    ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2),
+    ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3),
+    ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0),
+    ("lime", 0x00FF00),
+    ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6),
+    ("magenta", 0xFF00FF),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD),
+    ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC),
+    ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA),
+    ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500),
+    ("orangered", 0xFF4500),
+    ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98),
+    ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5),
+    ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F),
+    ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000),
+    ("rosybrown", 0xBC8F8F),
+    ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072),
+    ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57),
+    ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0),
+    ("skyblue", 0x87CEEB),
+    ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4),
+    ("tan", 0xD2B48C),
+    ("teal", 0x008080),
+    ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0),
+    ("violet", 0xEE82EE),
+    ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF),
+    ("whitesmoke", 0xF5F5F5),
+    ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba8(input: &str) -> [u8; 4] {
+        Color::parse(input).unwrap().to_rgba8()
+    }
+
+    #[test]
+    fn test_color_parsing() {
+        assert!(NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(NAMED_COLORS.len(), 148);
+
+        assert_eq!(rgba8("teal"), [0, 128, 128, 255]);
+        assert_eq!(rgba8("RebeccaPurple"), [102, 51, 153, 255]);
+        assert_eq!(rgba8("transparent"), [0, 0, 0, 0]);
+        assert_eq!(rgba8("light gray"), rgba8("lightgray"));
+        assert_eq!(rgba8("Dark  Grey"), rgba8("darkgrey"));
+        assert_eq!(rgba8("#abc"), [0xaa, 0xbb, 0xcc, 255]);
+        assert_eq!(rgba8("#abcd"), [0xaa, 0xbb, 0xcc, 0xdd]);
+        assert_eq!(rgba8("#0066CC"), [0, 0x66, 0xcc, 255]);
+        assert_eq!(rgba8("#ff000080"), [255, 0, 0, 128]);
+
+        assert_eq!(rgba8("rgb(255,0,0)"), [255, 0, 0, 255]);
+        assert_eq!(rgba8("rgba(0, 0, 0, 0.5)"), [0, 0, 0, 128]);
+        assert_eq!(rgba8("rgb(0 128 255 / 25%)"), [0, 128, 255, 64]);
+        assert_eq!(rgba8("rgb(100% 50% 0%)"), [255, 128, 0, 255]);
+        assert_eq!(rgba8("hsl(120, 100%, 25%)"), [0, 128, 0, 255]);
+        assert_eq!(rgba8("hsla(0deg 100% 50% / .5)"), [255, 0, 0, 128]);
+        assert_eq!(rgba8("hsl(0.5turn 100% 50%)"), [0, 255, 255, 255]);
+        assert_eq!(rgba8("hwb(0 0% 0%)"), [255, 0, 0, 255]);
+        assert_eq!(rgba8("hwb(0 60% 60%)"), [128, 128, 128, 255]);
+
+        // Reference values from the CSS Color 4 spec examples
+        assert_eq!(rgba8("lab(50% 0 0)"), [119, 119, 119, 255]);
+        assert_eq!(rgba8("lch(100 0 0)"), [255, 255, 255, 255]);
+        assert_eq!(rgba8("oklab(1 0 0)"), [255, 255, 255, 255]);
+        assert_eq!(rgba8("oklch(62.8% 0.2577 29.23)"), [255, 0, 0, 255]);
+        assert_eq!(rgba8("color(srgb 1 0.5 0)"), [255, 128, 0, 255]);
+        assert_eq!(rgba8("color(display-p3 0 0 0 / 0.5)"), [0, 0, 0, 128]);
+        assert_eq!(rgba8("color(xyz-d65 0.9505 1 1.089)"), [255, 255, 255, 255]);
+
+        assert_eq!(Color::parse("teal").unwrap().to_css(), "#008080");
+        assert_eq!(
+            Color::parse("#0008").unwrap().to_css(),
+            "rgba(0, 0, 0, 0.533)"
+        );
+
+        assert_eq!(Color::parse(""), Err(ColorError::Empty));
+        assert_eq!(
+            Color::parse("blurple"),
+            Err(ColorError::UnknownName("blurple".into()))
+        );
+        assert!(matches!(
+            Color::parse("#12345"),
+            Err(ColorError::InvalidHex(_))
+        ));
+        assert!(matches!(
+            Color::parse("rgb(1, 2)"),
+            Err(ColorError::InvalidFunction { .. })
+        ));
+        assert!(matches!(
+            Color::parse("rgb(1 2 3"),
+            Err(ColorError::InvalidFunction { .. })
+        ));
+        assert!(matches!(
+            Color::parse("hsl(10% 1 1)"),
+            Err(ColorError::InvalidFunction { .. })
+        ));
+        assert_eq!(
+            Color::parse("color(cmyk 0 0 0)"),
+            Err(ColorError::UnsupportedColorSpace("cmyk".into()))
+        );
+    }
+}
@@ -1,6 +1,10 @@
 pub mod table;
 
 use crate::constraints::{ConstraintSystem, SimpleConstraint};
+use crate::diagram_builder::DiagramTreeNode;
+use crate::fonts::FontStyle;
+use crate::geometry::PathData;
+use crate::text_shaping::grapheme_count;
 use crate::parser::JsonLinesParser;
 use core::fmt;
 use std::{any::Any, collections::HashMap, sync::Arc};
@@ -88,6 +92,7 @@ pub enum EntityType {
     ArcShape,
     SpacerShape,
     ConstraintLayoutContainer,
+    PathShape,
 }
 
 #[derive(Debug, Copy, PartialEq)]
@@ -143,10 +148,60 @@ pub struct ShapeConnector {
     pub start_point_id: EntityID,
     pub end_point_id: EntityID,
     pub label: Option<String>,
+    /// Text boxes placed along the path, the main label first unless it follows the path
+    pub labels: Vec<ConnectorLabel>,
     pub options: ConnectorOptions,
+    /// Bend points between the start and end points, in absolute coordinates.
+    /// Filled by the router during layout, empty when the path follows the routing strategy
+    pub waypoints: Vec<(Float, Float)>,
+    /// Moves the ends off their ports, set by layout to separate parallel
+    /// connectors and spread the ones sharing a port
+    pub start_offset: (Float, Float),
+    pub end_offset: (Float, Float),
 }
 
-#[derive(Debug, Clone)]
+impl ShapeConnector {
+    /// Id of the entity drawing `labels[index]`
+    pub fn label_entity(&self, index: usize) -> EntityID {
+        format!("{}_label_{}", self.entity, index)
+    }
+
+    /// Connects an entity to itself
+    pub fn is_self_loop(&self) -> bool {
+        self.source_id == self.target_id
+    }
+}
+
+/// Where along a connector a label sits
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LabelRole {
+    Source, // Next to the start, e.g. a cardinality
+    Middle, // Where `label_alignment` puts the main label
+    Target,
+}
+
+/// A text box placed along a connector
+#[derive(Clone, Debug)]
+pub struct ConnectorLabel {
+    pub text: String,
+    pub role: LabelRole,
+    pub text_options: TextOptions,
+    /// Box drawn behind the text
+    pub background: Option<BoxOptions>,
+}
+
+impl ConnectorLabel {
+    pub fn new(text: &str, role: LabelRole) -> ConnectorLabel {
+        ConnectorLabel {
+            text: text.to_string(),
+            role,
+            text_options: TextOptions::default(),
+            background: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Port {
     Center,
     Top,
@@ -157,14 +212,93 @@ pub enum Port {
     TopRight,
     BottomLeft,
     BottomRight,
+    /// A point along a side, `offset` 0 being its top or left end and 1 the other
+    Side { side: PortSide, offset: Float },
+    /// Where the line from the center towards the other end crosses the entity outline
+    Boundary,
+    /// The middle of the side facing the other end
+    Auto,
+    /// A port declared on the entity, see `DiagramBuilder::set_named_port`
+    Named(String),
 }
 
 impl Default for Port {
     fn default() -> Self {
-        Port::Center
+        Port::Boundary
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortSide {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Port {
+    /// The side a port sits on, the middle ones included
+    pub fn side(&self) -> Option<PortSide> {
+        match self {
+            Port::Top => Some(PortSide::Top),
+            Port::Bottom => Some(PortSide::Bottom),
+            Port::Left => Some(PortSide::Left),
+            Port::Right => Some(PortSide::Right),
+            Port::Side { side, .. } => Some(*side),
+            _ => None,
+        }
     }
 }
 
+impl PortSide {
+    /// Unit vector pointing out of the side
+    pub fn direction(&self) -> (Float, Float) {
+        match self {
+            PortSide::Top => (0.0, -1.0),
+            PortSide::Bottom => (0.0, 1.0),
+            PortSide::Left => (-1.0, 0.0),
+            PortSide::Right => (1.0, 0.0),
+        }
+    }
+}
+
+/// A point a connector passes through on its way to the target
+#[derive(Debug, Clone, PartialEq)]
+pub enum Waypoint {
+    Absolute(Float, Float),
+    /// `offset` away from a port of an entity, the center for ports that
+    /// depend on the other end. Follows the entity wherever layout puts it
+    Relative {
+        entity: EntityID,
+        port: Port,
+        offset: (Float, Float),
+    },
+}
+
+/// Decoration drawn at a connector or arrow end, pointing along the line into the end
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Marker {
+    #[default]
+    None,
+    Arrow,         // Filled triangle
+    OpenArrow,     // Two strokes meeting at the tip
+    HollowArrow,   // Outlined triangle, UML generalization
+    Diamond,       // UML composition
+    HollowDiamond, // UML aggregation
+    Circle,
+    HollowCircle,
+    Bar,
+    // Crow's foot cardinalities
+    CrowFoot,
+    OneOnly,
+    ZeroOrOne,
+    OneOrMany,
+    ZeroOrMany,
+    /// Any entity, drawn with the middle of its right side at the end and
+    /// turned to follow the line
+    Entity(EntityID),
+}
+
 #[derive(Clone, Debug, PartialEq, Copy)]
 pub enum OrthogonalRoutingStrategy {
     // 2-segment strategies (one turn, 3 points)
@@ -177,6 +311,9 @@ pub enum OrthogonalRoutingStrategy {
 
     // Smart strategy
     Auto, // Choose best based on direction
+
+    // Routed around the other entities, falls back to Auto when there is no route
+    Avoid,
 }
 #[derive(Clone, Debug)]
 pub struct ConnectorOptions {
@@ -186,15 +323,27 @@ pub struct ConnectorOptions {
     pub curve_offset: Option<Float>,
     pub source_port: Port,
     pub target_port: Port,
-    pub arrow_start: bool,
-    pub arrow_end: bool,
-    pub arrow_size: Float,
+    pub marker_start: Marker,
+    pub marker_end: Marker,
+    pub arrow_size: Float, // Size of the end markers
     pub routing_strategy: OrthogonalRoutingStrategy,
     pub label_alignment: LabelAlignment,
+    pub label_path: Option<TextPathOptions>, // When set the label follows the connector path
+    pub stroke_style: StrokeStyle,
+    pub clearance: Float, // Gap kept between routed segments and the entities they avoid
+    pub parallel_spacing: Float, // Gap between connectors joining the same points, 0 overlaps them
+    pub distribute_ports: bool,  // Spread connectors sharing a side port along that side
+    /// Connectors with the same bundle that share an end leave it along a common trunk
+    pub bundle: Option<String>,
+    /// Points the path must pass through, in order
+    pub waypoints: Vec<Waypoint>,
+    /// Side of the entity a connector to itself loops out of, and how far
+    pub loop_side: PortSide,
+    pub loop_size: Float,
 }
 
 // Add new enum for label alignment
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum LabelAlignment {
     Start,  // Near the source
     Center, // At midpoint (current behavior)
@@ -214,13 +363,40 @@ impl Default for ConnectorOptions {
             stroke_color: "black".to_string(),
             stroke_width: 1.0,
             curve_offset: None,
-            source_port: Port::Center,
-            target_port: Port::Center,
-            arrow_start: false,
-            arrow_end: false,
+            source_port: Port::default(),
+            target_port: Port::default(),
+            marker_start: Marker::None,
+            marker_end: Marker::None,
             arrow_size: 10.0,
             routing_strategy: OrthogonalRoutingStrategy::HVH,
             label_alignment: LabelAlignment::Center, // NEW FIELD
+            label_path: None,
+            stroke_style: StrokeStyle::default(),
+            clearance: 10.0,
+            parallel_spacing: 10.0,
+            distribute_ports: false,
+            bundle: None,
+            waypoints: Vec::new(),
+            loop_side: PortSide::Top,
+            loop_size: 30.0,
+        }
+    }
+}
+
+/// Text drawn along a path (connector or arc) instead of inside its own box
+#[derive(Clone, Debug)]
+pub struct TextPathOptions {
+    pub alignment: LabelAlignment, // Where along the path the text is anchored
+    pub offset: Float,             // Gap between the path and the text baseline, positive is above
+    pub text_options: TextOptions,
+}
+
+impl Default for TextPathOptions {
+    fn default() -> Self {
+        Self {
+            alignment: LabelAlignment::Center,
+            offset: 4.0,
+            text_options: TextOptions::default(),
         }
     }
 }
@@ -356,6 +532,8 @@ pub struct LinearGradient {
     pub stops: Vec<GradientStop>,
 }
 
+// Gradient coordinates are fractions of the filled shape's bounding box (0..1),
+// like SVG's objectBoundingBox units.
 impl LinearGradient {
     pub fn new(x1: Float, y1: Float, x2: Float, y2: Float, stops: Vec<GradientStop>) -> Self {
         LinearGradient {
@@ -366,6 +544,73 @@ impl LinearGradient {
             stops,
         }
     }
+
+    /// Gradient line for a CSS angle: 0deg points up, 90deg to the right.
+    /// The line is long enough for the corners to get the first and last colors.
+    pub fn from_angle(degrees: Float, stops: Vec<GradientStop>) -> Self {
+        let radians = degrees.to_radians();
+        let (sin, cos) = radians.sin_cos();
+        let half_length = (sin.abs() + cos.abs()) / 2.0;
+        let (dx, dy) = (sin * half_length, -cos * half_length);
+        // Drop float noise so "to right" is exactly (0, 0.5) -> (1, 0.5)
+        let snap = |v: Float| (v * 10000.0).round() / 10000.0;
+        LinearGradient::new(
+            snap(0.5 - dx),
+            snap(0.5 - dy),
+            snap(0.5 + dx),
+            snap(0.5 + dy),
+            stops,
+        )
+    }
+}
+
+/// A resolved gradient stop: offset (0..1), color and opacity
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorStop {
+    pub offset: Float,
+    pub color: String,
+    pub opacity: Float,
+}
+
+/// Stops sorted by offset and clamped to 0..1. Opacity stops take the color
+/// of the closest color stop before them (or after, if there is none).
+pub fn resolve_gradient_stops(stops: &[GradientStop]) -> Vec<ColorStop> {
+    let mut sorted: Vec<&GradientStop> = stops.iter().collect();
+    sorted.sort_by(|a, b| a.offset().total_cmp(&b.offset()));
+
+    let first_color = sorted.iter().find_map(|stop| match stop {
+        GradientStop::ColorStop { color, .. } => Some(color.clone()),
+        _ => None,
+    });
+
+    let mut current_color = first_color.unwrap_or_else(|| "black".to_string());
+    sorted
+        .into_iter()
+        .map(|stop| match stop {
+            GradientStop::ColorStop { offset, color } => {
+                current_color = color.clone();
+                ColorStop {
+                    offset: offset.clamp(0.0, 1.0),
+                    color: color.clone(),
+                    opacity: 1.0,
+                }
+            }
+            GradientStop::OpacityStop { offset, opacity } => ColorStop {
+                offset: offset.clamp(0.0, 1.0),
+                color: current_color.clone(),
+                opacity: opacity.clamp(0.0, 1.0),
+            },
+        })
+        .collect()
+}
+
+impl GradientStop {
+    pub fn offset(&self) -> Float {
+        match self {
+            GradientStop::ColorStop { offset, .. } => *offset,
+            GradientStop::OpacityStop { offset, .. } => *offset,
+        }
+    }
 }
 
 impl Clone for GradientStop {
@@ -385,12 +630,19 @@ impl Clone for GradientStop {
 
 #[derive(Debug)]
 pub struct RadialGradient {
+    // Center and radius in bounding box units, r = 0.5 reaches the sides
     pub cx: Float,
     pub cy: Float,
     pub r: Float,
     pub stops: Vec<GradientStop>,
 }
 
+impl RadialGradient {
+    pub fn new(cx: Float, cy: Float, r: Float, stops: Vec<GradientStop>) -> Self {
+        RadialGradient { cx, cy, r, stops }
+    }
+}
+
 impl Clone for RadialGradient {
     fn clone(&self) -> Self {
         RadialGradient {
@@ -419,6 +671,7 @@ pub enum Fill {
     Color(String),
     LinearGradient(LinearGradient),
     RadialGradient(RadialGradient),
+    Pattern(PatternFill),
 }
 
 impl Clone for Fill {
@@ -427,9 +680,20 @@ impl Clone for Fill {
             Fill::Color(color) => Fill::Color(color.clone()),
             Fill::LinearGradient(gradient) => Fill::LinearGradient(gradient.clone()),
             Fill::RadialGradient(gradient) => Fill::RadialGradient(gradient.clone()),
+            Fill::Pattern(pattern) => Fill::Pattern(pattern.clone()),
         }
     }
 }
+impl Fill {
+    pub fn is_gradient(&self) -> bool {
+        matches!(self, Fill::LinearGradient(_) | Fill::RadialGradient(_))
+    }
+
+    pub fn is_pattern(&self) -> bool {
+        matches!(self, Fill::Pattern(_))
+    }
+}
+
 //default trait for fill
 impl Default for Fill {
     fn default() -> Self {
@@ -444,6 +708,7 @@ impl fmt::Display for Fill {
             Fill::Color(color) => write!(f, "{}", color),
             Fill::LinearGradient(gradient) => write!(f, "{:?}", gradient),
             Fill::RadialGradient(gradient) => write!(f, "{:?}", gradient),
+            Fill::Pattern(pattern) => write!(f, "{:?}", pattern),
         }
     }
 }
@@ -454,6 +719,7 @@ impl PartialEq for Fill {
             (Fill::Color(a), Fill::Color(b)) => a == b,
             (Fill::LinearGradient(a), Fill::LinearGradient(b)) => a == b,
             (Fill::RadialGradient(a), Fill::RadialGradient(b)) => a == b,
+            (Fill::Pattern(a), Fill::Pattern(b)) => a == b,
             _ => false,
         }
     }
@@ -461,6 +727,63 @@ impl PartialEq for Fill {
 
 impl Eq for Fill {}
 
+/// What a pattern fill repeats
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Pattern {
+    /// Parallel lines, 45 degrees unless the pattern is rotated
+    #[default]
+    Diagonal,
+    /// Two sets of lines crossing at right angles, turned 45 degrees
+    Cross,
+    Dots,
+    /// Horizontal and vertical lines
+    Grid,
+    /// Copies of another entity, laid out on its own
+    Entity(EntityID),
+}
+
+/// Repeating fill. Tiles start at the shape's top-left corner and the whole
+/// pattern is rotated by `angle` around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternFill {
+    pub pattern: Pattern,
+    /// Distance between hatch lines or dots; the gap between copies for
+    /// entity patterns
+    pub spacing: Float,
+    /// Degrees, clockwise
+    pub angle: Float,
+    pub color: String,
+    /// Thickness of hatch lines, radius of dots
+    pub line_width: Float,
+    pub background: Option<String>,
+}
+
+impl PatternFill {
+    pub fn new(pattern: Pattern) -> Self {
+        let (spacing, angle) = match pattern {
+            Pattern::Diagonal | Pattern::Cross => (8.0, 45.0),
+            Pattern::Dots | Pattern::Grid => (8.0, 0.0),
+            Pattern::Entity(_) => (0.0, 0.0),
+        };
+        PatternFill {
+            pattern,
+            spacing,
+            angle,
+            color: "black".to_string(),
+            line_width: 1.0,
+            background: None,
+        }
+    }
+
+    /// Size of one tile; entity patterns pass the entity's size
+    pub fn tile_size(&self, entity_size: (Float, Float)) -> (Float, Float) {
+        match self.pattern {
+            Pattern::Entity(_) => (entity_size.0 + self.spacing, entity_size.1 + self.spacing),
+            _ => (self.spacing, self.spacing),
+        }
+    }
+}
+
 impl PartialEq for LinearGradient {
     fn eq(&self, other: &Self) -> bool {
         self.x1 == other.x1
@@ -511,6 +834,247 @@ impl PartialEq for GradientStop {
 
 impl Eq for GradientStop {}
 
+/// Opacity of any entity. `opacity` applies to the entity and its children
+/// composited as one layer, `fill_opacity` and `stroke_opacity` only to the
+/// shape's own paint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpacityOptions {
+    pub opacity: Float,
+    pub fill_opacity: Float,
+    pub stroke_opacity: Float,
+}
+
+impl Default for OpacityOptions {
+    fn default() -> Self {
+        OpacityOptions {
+            opacity: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+        }
+    }
+}
+
+impl OpacityOptions {
+    pub fn is_opaque(&self) -> bool {
+        self.opacity >= 1.0 && self.fill_opacity >= 1.0 && self.stroke_opacity >= 1.0
+    }
+}
+
+/// Drop shadow painted behind a box, rect, ellipse or image, with the meaning
+/// of CSS `box-shadow`: `blur` is the blur radius (twice the Gaussian standard
+/// deviation) and `spread` grows the shape before blurring. Shadows don't
+/// change the entity's size or position in the layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowOptions {
+    pub offset_x: Float,
+    pub offset_y: Float,
+    pub blur: Float,
+    pub spread: Float,
+    pub color: String,
+}
+
+impl Default for ShadowOptions {
+    fn default() -> Self {
+        ShadowOptions {
+            offset_x: 0.0,
+            offset_y: 2.0,
+            blur: 6.0,
+            spread: 0.0,
+            color: String::from("rgba(0, 0, 0, 0.25)"),
+        }
+    }
+}
+
+impl ShadowOptions {
+    /// Gaussian standard deviation of the blur
+    pub fn std_deviation(&self) -> Float {
+        self.blur.max(0.0) / 2.0
+    }
+
+    /// How far the shadow can reach past the shape's edge, ignoring the offset
+    pub fn extent(&self) -> Float {
+        self.spread.max(0.0) + 3.0 * self.std_deviation()
+    }
+}
+
+/// Clipping and masking of an entity. Shapes used as clip paths or masks are
+/// built apart from the tree and laid out in the entity's own coordinates.
+#[derive(Debug, Clone, Default)]
+pub struct ClipOptions {
+    /// Hide children outside the entity's bounds, rounded on boxes
+    pub clip: bool,
+    /// Only the area inside this shape's fill outline is drawn
+    pub clip_path: Option<DiagramTreeNode>,
+    /// The alpha of this shape, as rendered, scales the entity's alpha
+    pub mask: Option<DiagramTreeNode>,
+}
+
+impl ClipOptions {
+    /// Detached shapes that need layout
+    pub fn shapes(&self) -> impl Iterator<Item = &DiagramTreeNode> {
+        self.clip_path.iter().chain(self.mask.iter())
+    }
+}
+
+/// Named layers, painted in this order. Entities inherit their parent's
+/// layer, connectors default to `Connectors`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Layer {
+    Background,
+    #[default]
+    Content,
+    Annotations,
+    Connectors,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 4] = [
+        Layer::Background,
+        Layer::Content,
+        Layer::Annotations,
+        Layer::Connectors,
+    ];
+
+    pub fn parse(name: &str) -> Option<Layer> {
+        match name.trim().to_lowercase().as_str() {
+            "background" => Some(Layer::Background),
+            "content" => Some(Layer::Content),
+            "annotations" | "annotation" => Some(Layer::Annotations),
+            "connectors" | "connector" => Some(Layer::Connectors),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Background => "background",
+            Layer::Content => "content",
+            Layer::Annotations => "annotations",
+            Layer::Connectors => "connectors",
+        }
+    }
+}
+
+/// How an entity's colors mix with what is already painted below it, the
+/// separable CSS `mix-blend-mode` values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+}
+
+impl BlendMode {
+    pub fn parse(name: &str) -> Option<BlendMode> {
+        match name.trim().to_lowercase().replace('_', "-").as_str() {
+            "normal" => Some(BlendMode::Normal),
+            "multiply" => Some(BlendMode::Multiply),
+            "screen" => Some(BlendMode::Screen),
+            "overlay" => Some(BlendMode::Overlay),
+            "darken" => Some(BlendMode::Darken),
+            "lighten" => Some(BlendMode::Lighten),
+            "color-dodge" => Some(BlendMode::ColorDodge),
+            "color-burn" => Some(BlendMode::ColorBurn),
+            "hard-light" => Some(BlendMode::HardLight),
+            "soft-light" => Some(BlendMode::SoftLight),
+            "difference" => Some(BlendMode::Difference),
+            "exclusion" => Some(BlendMode::Exclusion),
+            _ => None,
+        }
+    }
+
+    pub fn css_name(&self) -> &'static str {
+        match self {
+            BlendMode::Normal => "normal",
+            BlendMode::Multiply => "multiply",
+            BlendMode::Screen => "screen",
+            BlendMode::Overlay => "overlay",
+            BlendMode::Darken => "darken",
+            BlendMode::Lighten => "lighten",
+            BlendMode::ColorDodge => "color-dodge",
+            BlendMode::ColorBurn => "color-burn",
+            BlendMode::HardLight => "hard-light",
+            BlendMode::SoftLight => "soft-light",
+            BlendMode::Difference => "difference",
+            BlendMode::Exclusion => "exclusion",
+        }
+    }
+}
+
+/// Paint order and compositing. Entities without an entry are painted in
+/// tree order, in their parent's layer, with normal blending.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LayerOptions {
+    /// Higher values are painted later, among siblings and among entities
+    /// moved to the same layer
+    pub z_index: i32,
+    /// Moves the entity and its children out of tree order into a layer
+    pub layer: Option<Layer>,
+    pub blend_mode: BlendMode,
+}
+
+/// An entity painted in a layer pass instead of with its parent
+#[derive(Debug, Clone)]
+pub struct LayerRoot {
+    pub node: DiagramTreeNode,
+    pub layer: Layer,
+    pub z_index: i32,
+    /// Position in the tree, keeps ties in tree order
+    pub order: usize,
+    /// Ids of the enclosing entities, outermost first, whose transforms
+    /// still apply. Empty for connectors, which are laid out in absolute
+    /// coordinates.
+    pub ancestors: Vec<EntityID>,
+}
+
+/// Shape of the ends of an open stroke
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineCap {
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+/// Shape of the corners where two stroke segments meet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// Dash pattern, caps and joins of a stroke, with the same meaning as the SVG
+/// `stroke-*` properties. An empty `dash_array` draws a solid stroke.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StrokeStyle {
+    pub dash_array: Vec<Float>,
+    pub dash_offset: Float,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+}
+
+impl StrokeStyle {
+    /// True when the dash pattern has something to alternate, SVG treats
+    /// negative or all-zero patterns as solid
+    pub fn is_dashed(&self) -> bool {
+        !self.dash_array.is_empty()
+            && self.dash_array.iter().all(|d| *d >= 0.0)
+            && self.dash_array.iter().sum::<Float>() > 0.0
+    }
+}
+
 #[derive(Debug)]
 pub struct BoxOptions {
     pub fill_color: Fill,
@@ -522,6 +1086,7 @@ pub struct BoxOptions {
     pub width_behavior: SizeBehavior,
     pub height_behavior: SizeBehavior,
     pub horizontal_alignment: HorizontalAlignment,
+    pub stroke_style: StrokeStyle,
 }
 
 impl Clone for BoxOptions {
@@ -535,6 +1100,7 @@ impl Clone for BoxOptions {
             width_behavior: self.width_behavior.clone(),
             height_behavior: self.height_behavior.clone(),
             horizontal_alignment: self.horizontal_alignment.clone(),
+            stroke_style: self.stroke_style.clone(),
         }
     }
 }
@@ -556,6 +1122,7 @@ impl BoxOptions {
             width_behavior: SizeBehavior::Content,
             height_behavior: SizeBehavior::Content,
             horizontal_alignment: HorizontalAlignment::Center,
+            stroke_style: StrokeStyle::default(),
         }
     }
 }
@@ -571,6 +1138,7 @@ pub struct RectOptions {
     pub stroke_color: String,
     pub stroke_width: Float,
     pub border_radius: Float,
+    pub stroke_style: StrokeStyle,
 }
 
 impl Clone for RectOptions {
@@ -582,6 +1150,7 @@ impl Clone for RectOptions {
             stroke_color: self.stroke_color.clone(),
             stroke_width: self.stroke_width,
             border_radius: self.border_radius,
+            stroke_style: self.stroke_style.clone(),
         }
     }
 }
@@ -596,6 +1165,7 @@ impl RectOptions {
             stroke_color: String::from("black"),
             stroke_width: 1.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         }
     }
 }
@@ -755,6 +1325,11 @@ pub struct TextOptions {
     pub line_width: usize,
     pub line_spacing: f32, // spacing between lines
     pub font_weight: u32,
+    pub font_style: FontStyle,
+    pub text_decoration: TextDecoration,
+    // extra space added after each character
+    pub letter_spacing: f32,
+    pub text_transform: TextTransform,
 }
 
 impl Clone for TextOptions {
@@ -766,6 +1341,10 @@ impl Clone for TextOptions {
             line_width: self.line_width,
             line_spacing: self.line_spacing,
             font_weight: self.font_weight,
+            font_style: self.font_style,
+            text_decoration: self.text_decoration,
+            letter_spacing: self.letter_spacing,
+            text_transform: self.text_transform,
         }
     }
 }
@@ -779,6 +1358,10 @@ impl Default for TextOptions {
             line_width: 200,
             line_spacing: 8.0,
             font_weight: 400,
+            font_style: FontStyle::Normal,
+            text_decoration: TextDecoration::default(),
+            letter_spacing: 0.0,
+            text_transform: TextTransform::None,
         }
     }
 }
@@ -791,6 +1374,61 @@ impl TextOptions {
             line_width: 20,
             line_spacing: 0.0,
             font_weight: 400,
+            font_style: FontStyle::Normal,
+            text_decoration: TextDecoration::default(),
+            letter_spacing: 0.0,
+            text_transform: TextTransform::None,
+        }
+    }
+
+    /// Text as it is displayed, after applying text_transform
+    pub fn display_text(&self, text: &str) -> String {
+        match self.text_transform {
+            TextTransform::None => text.to_string(),
+            TextTransform::Uppercase => text.to_uppercase(),
+            TextTransform::Lowercase => text.to_lowercase(),
+        }
+    }
+
+    /// Total letter spacing added to a line of displayed text (one per grapheme)
+    pub fn letter_spacing_width(&self, display_text: &str) -> Float {
+        self.letter_spacing * grapheme_count(display_text) as Float
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextTransform {
+    #[default]
+    None,
+    Uppercase,
+    Lowercase,
+}
+
+// Underline and line-through can be combined, e.g "underline line-through"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextDecoration {
+    pub underline: bool,
+    pub line_through: bool,
+}
+
+impl TextDecoration {
+    pub fn is_none(&self) -> bool {
+        !self.underline && !self.line_through
+    }
+
+    // value for the SVG / CSS text-decoration property
+    pub fn to_css(&self) -> String {
+        let mut values = Vec::new();
+        if self.underline {
+            values.push("underline");
+        }
+        if self.line_through {
+            values.push("line-through");
+        }
+        if values.is_empty() {
+            "none".to_string()
+        } else {
+            values.join(" ")
         }
     }
 }
@@ -920,10 +1558,24 @@ impl Clone for ShapeLine {
 
 /// A line can set the x,y values directly or through a separate Point entity
 /// Referencing through point entity is useful for using with constraint systems
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LinePointReference {
     Value(Float, Float),
     PointID(EntityID),
+    /// `offset` away from a port of any entity in the diagram, resolved once
+    /// layout has placed it. Boundary and auto ports give the center
+    EntityPort {
+        entity: EntityID,
+        port: Port,
+        offset: (Float, Float),
+    },
+}
+
+impl LinePointReference {
+    /// True when the point depends on where layout puts another entity
+    pub fn is_bound(&self) -> bool {
+        !matches!(self, LinePointReference::Value(..))
+    }
 }
 impl ShapeLine {
     pub fn new(
@@ -959,6 +1611,7 @@ impl Entity for ShapeLine {
 pub struct LineOptions {
     pub stroke_color: String,
     pub stroke_width: Float,
+    pub stroke_style: StrokeStyle,
 }
 
 impl Clone for LineOptions {
@@ -966,6 +1619,7 @@ impl Clone for LineOptions {
         LineOptions {
             stroke_color: self.stroke_color.clone(),
             stroke_width: self.stroke_width,
+            stroke_style: self.stroke_style.clone(),
         }
     }
 }
@@ -975,14 +1629,16 @@ impl LineOptions {
         LineOptions {
             stroke_color: String::from("black"),
             stroke_width: 1.0,
+            stroke_style: StrokeStyle::default(),
         }
     }
 }
 
-// TODO: Add LinePointReference  support.
 pub struct PolyLine {
     pub entity: EntityID,
+    /// Points in the polyline's own coordinates, `references` resolved by layout
     pub points: Vec<(Float, Float)>,
+    pub references: Vec<LinePointReference>,
     pub line_options: LineOptions,
 }
 
@@ -992,9 +1648,27 @@ impl PolyLine {
         points: Vec<(Float, Float)>,
         line_options: LineOptions,
     ) -> PolyLine {
+        let references = points
+            .iter()
+            .map(|(x, y)| LinePointReference::Value(*x, *y))
+            .collect();
         PolyLine {
             entity,
             points,
+            references,
+            line_options,
+        }
+    }
+
+    pub fn with_references(
+        entity: EntityID,
+        references: Vec<LinePointReference>,
+        line_options: LineOptions,
+    ) -> PolyLine {
+        PolyLine {
+            entity,
+            points: vec![(0.0, 0.0); references.len()],
+            references,
             line_options,
         }
     }
@@ -1019,6 +1693,7 @@ impl Clone for PolyLine {
         PolyLine {
             entity: self.entity.clone(),
             points: self.points.clone(),
+            references: self.references.clone(),
             line_options: self.line_options.clone(),
         }
     }
@@ -1061,6 +1736,8 @@ pub struct ArrowOptions {
     pub stroke_color: String,
     pub stroke_width: Float,
     pub arrow_size: Float,
+    pub marker_start: Marker,
+    pub marker_end: Marker,
 }
 
 impl Clone for ArrowOptions {
@@ -1069,6 +1746,8 @@ impl Clone for ArrowOptions {
             stroke_color: self.stroke_color.clone(),
             stroke_width: self.stroke_width,
             arrow_size: self.arrow_size,
+            marker_start: self.marker_start.clone(),
+            marker_end: self.marker_end.clone(),
         }
     }
 }
@@ -1079,6 +1758,8 @@ impl ArrowOptions {
             stroke_color: String::from("black"),
             stroke_width: 1.0,
             arrow_size: 8.0,
+            marker_start: Marker::None,
+            marker_end: Marker::Arrow,
         }
     }
 }
@@ -1338,6 +2019,9 @@ pub struct ArcOptions {
     pub stroke_color: String,
     pub stroke_width: Float,
     pub filled: bool, // Whether to fill the arc sector or just draw the outline
+    pub label: Option<String>, // Text drawn along the arc
+    pub label_path: TextPathOptions,
+    pub stroke_style: StrokeStyle,
 }
 
 impl Clone for ArcOptions {
@@ -1347,6 +2031,9 @@ impl Clone for ArcOptions {
             stroke_color: self.stroke_color.clone(),
             stroke_width: self.stroke_width,
             filled: self.filled,
+            label: self.label.clone(),
+            label_path: self.label_path.clone(),
+            stroke_style: self.stroke_style.clone(),
         }
     }
 }
@@ -1358,6 +2045,9 @@ impl ArcOptions {
             stroke_color: String::from("black"),
             stroke_width: 1.0,
             filled: false,
+            label: None,
+            label_path: TextPathOptions::default(),
+            stroke_style: StrokeStyle::default(),
         }
     }
 }
@@ -1369,6 +2059,9 @@ pub struct ShapeArc {
     pub start_angle: Float, // Start angle in degrees
     pub end_angle: Float,   // End angle in degrees
     pub arc_options: ArcOptions,
+    /// When set layout moves the arc so it runs clockwise from the first point to
+    /// the second, growing the radius if they are further apart than the diameter
+    pub endpoints: Option<(LinePointReference, LinePointReference)>,
 }
 
 impl Clone for ShapeArc {
@@ -1379,6 +2072,7 @@ impl Clone for ShapeArc {
             start_angle: self.start_angle,
             end_angle: self.end_angle,
             arc_options: self.arc_options.clone(),
+            endpoints: self.endpoints.clone(),
         }
     }
 }
@@ -1397,6 +2091,7 @@ impl ShapeArc {
             start_angle,
             end_angle,
             arc_options,
+            endpoints: None,
         }
     }
 
@@ -1445,6 +2140,91 @@ impl Entity for ShapeArc {
     }
 }
 
+/// Which parts of a path with overlapping or nested subpaths are filled, as
+/// the SVG `fill-rule` property
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    #[default]
+    NonZero,
+    EvenOdd,
+}
+
+#[derive(Default, Debug)]
+pub struct PathOptions {
+    pub fill_color: String,
+    pub stroke_color: String,
+    pub stroke_width: Float,
+    pub stroke_style: StrokeStyle,
+    pub fill_rule: FillRule,
+}
+
+impl Clone for PathOptions {
+    fn clone(&self) -> Self {
+        PathOptions {
+            fill_color: self.fill_color.clone(),
+            stroke_color: self.stroke_color.clone(),
+            stroke_width: self.stroke_width,
+            stroke_style: self.stroke_style.clone(),
+            fill_rule: self.fill_rule,
+        }
+    }
+}
+
+impl PathOptions {
+    pub fn new() -> PathOptions {
+        PathOptions {
+            fill_color: String::from("none"),
+            stroke_color: String::from("black"),
+            stroke_width: 1.0,
+            stroke_style: StrokeStyle::default(),
+            fill_rule: FillRule::NonZero,
+        }
+    }
+}
+
+/// Shape drawn from path data, used for SVG paths and splines
+pub struct ShapePath {
+    pub entity: EntityID,
+    /// In the shape's own coordinates, layout moves it so its bounds start at
+    /// the origin
+    pub path: PathData,
+    pub path_options: PathOptions,
+}
+
+impl Clone for ShapePath {
+    fn clone(&self) -> Self {
+        ShapePath {
+            entity: self.entity.clone(),
+            path: self.path.clone(),
+            path_options: self.path_options.clone(),
+        }
+    }
+}
+
+impl ShapePath {
+    pub fn new(entity: EntityID, path: PathData, path_options: PathOptions) -> ShapePath {
+        ShapePath {
+            entity,
+            path,
+            path_options,
+        }
+    }
+}
+
+impl Entity for ShapePath {
+    fn get_id(&self) -> EntityID {
+        self.entity.clone()
+    }
+
+    fn get_type(&self) -> EntityType {
+        EntityType::PathShape
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
 /// Type alias for custom component factory functions
 /// Takes a map of attributes and a mutable reference to DiagramBuilder
 /// Returns a Result with either a DiagramTreeNode or an error message
@@ -14,22 +14,54 @@ use std::{collections::HashMap, hash::Hash, rc::Rc, sync::Arc};
  */
 //use TextOptions
 use crate::{
-    components::*, parser::JsonLinesParser, transform::Transform, BoundingBox, ConstraintSystem,
-    SimpleConstraint,
+    components::*,
+    fonts::FontRegistry,
+    geometry::{
+        arc_path, arrow_geometry, ellipse_path, rounded_rect_path, spline_path, ConnectorGeometry,
+        MarkerGeometry, Outline, PathCommand, PathData,
+    },
+    measure_cache::{MeasureCacheStats, MeasureKey, TextMeasureCache},
+    parser::JsonLinesParser,
+    text_shaping::wrap_text,
+    theme::Theme,
+    transform::Transform,
+    BoundingBox, ConstraintSystem, SimpleConstraint,
 };
 
 use anyhow::{bail, Context, Result};
 
 pub struct DiagramBuilder {
+    // Custom measure function, when None text is measured with `fonts`
     pub measure_text: Option<fn(&str, &TextOptions) -> (Float, Float)>,
+    // Fonts used for measurement and shared with the renderers
+    pub fonts: FontRegistry,
+    // Defaults for attributes the document doesn't set, survives clear_cache
+    pub theme: Theme,
+    // Measurements survive clear_cache, they only depend on text and font
+    measure_cache: TextMeasureCache,
     pub entities: Vec<EntityID>,
     // Maps entity IDs to their positions in the container (used in free containers)
     pub container_relative_positions: HashMap<EntityID, Point>,
     pub sizes: HashMap<EntityID, Size>,
     // Maps entity IDS to their transforms for positioning, rotation, scaling, etc.
     pub transforms: HashMap<EntityID, Transform>,
+    // Only entities with an opacity attribute have an entry
+    pub opacities: HashMap<EntityID, OpacityOptions>,
+    pub shadows: HashMap<EntityID, ShadowOptions>,
+    pub clips: HashMap<EntityID, ClipOptions>,
+    // Entities repeated by pattern fills, keyed by their own id
+    pub pattern_tiles: HashMap<EntityID, DiagramTreeNode>,
+    pub layer_options: HashMap<EntityID, LayerOptions>,
+    // Filled in by layout
+    pub layer_roots: HashMap<EntityID, LayerRoot>,
+    pub connector_geometries: HashMap<EntityID, ConnectorGeometry>,
+    // Ports declared on entities, by entity and then port name
+    pub named_ports: HashMap<EntityID, HashMap<String, Port>>,
     pub entityTypes: HashMap<EntityID, EntityType>,
     pub absolute_positions: HashMap<EntityID, (Float, Float)>,
+    // Transforms from each entity's own coordinates to the diagram's, with
+    // those of all its containers. Filled in by layout next to absolute_positions
+    pub absolute_transforms: HashMap<EntityID, Transform>,
 
     // Components
     points: HashMap<EntityID, PointShape>,
@@ -51,6 +83,7 @@ pub struct DiagramBuilder {
     constraint_layout_containers: HashMap<EntityID, ConstraintLayoutContainer>,
     constraint_systems: HashMap<EntityID, ConstraintSystem>,
     arcs: HashMap<EntityID, ShapeArc>,
+    paths: HashMap<EntityID, ShapePath>,
     spacers: HashMap<EntityID, ShapeSpacer>,
 
     // Items that need to be at root level (e.g connectors)
@@ -80,6 +113,20 @@ impl DiagramTreeNode {
     fn add_child(&mut self, child: DiagramTreeNode) {
         self.children.push(Box::new(child));
     }
+
+    /// The node itself for connectors, the connector it holds for the group
+    /// wrapping a labelled connector
+    pub fn connector(&self) -> Option<&DiagramTreeNode> {
+        match self.entity_type {
+            EntityType::ConnectorShape => Some(self),
+            EntityType::GroupShape => self
+                .children
+                .iter()
+                .map(|child| child.as_ref())
+                .find(|child| child.entity_type == EntityType::ConnectorShape),
+            _ => None,
+        }
+    }
 }
 
 /* New architecture (data driven)
@@ -94,12 +141,23 @@ impl DiagramBuilder {
     pub fn new() -> DiagramBuilder {
         DiagramBuilder {
             entityTypes: HashMap::<EntityID, EntityType>::new(),
-            measure_text: Some(|_text, _text_options| (0.0, 0.0)),
+            measure_text: None,
+            fonts: FontRegistry::with_default_font(),
+            theme: Theme::default(),
+            measure_cache: TextMeasureCache::new(),
             entities: Vec::new(),
             // store desired positions relative to the container
             container_relative_positions: HashMap::new(),
             sizes: HashMap::new(),
             transforms: HashMap::new(),
+            opacities: HashMap::new(),
+            shadows: HashMap::new(),
+            clips: HashMap::new(),
+            pattern_tiles: HashMap::new(),
+            layer_options: HashMap::new(),
+            layer_roots: HashMap::new(),
+            connector_geometries: HashMap::new(),
+            named_ports: HashMap::new(),
             points: HashMap::new(),
             boxes: HashMap::new(),
             rectangles: HashMap::new(),
@@ -119,11 +177,13 @@ impl DiagramBuilder {
             constraint_layout_containers: HashMap::new(),
             constraint_systems: HashMap::new(),
             arcs: HashMap::new(),
+            paths: HashMap::new(),
             spacers: HashMap::new(),
 
             pending_root_nodes: Vec::new(),
             custom_components: CustomComponentRegistry::new(),
             absolute_positions: HashMap::new(),
+            absolute_transforms: HashMap::new(),
         }
     }
 
@@ -162,11 +222,21 @@ impl DiagramBuilder {
         self.free_containers.clear();
         self.constraint_layout_containers.clear();
         self.arcs.clear();
+        self.paths.clear();
         self.constraint_systems.clear();
         self.images.clear();
         self.transforms.clear();
+        self.opacities.clear();
+        self.shadows.clear();
+        self.clips.clear();
+        self.pattern_tiles.clear();
+        self.layer_options.clear();
+        self.layer_roots.clear();
+        self.connector_geometries.clear();
+        self.named_ports.clear();
         self.points.clear();
         self.absolute_positions.clear();
+        self.absolute_transforms.clear();
 
         // Note: We don't clear custom_components as those are reusable function definitions
         // Note: We don't clear measure_text function as it should persist across diagrams
@@ -243,6 +313,52 @@ impl DiagramBuilder {
     pub fn set_measure_text_fn(&mut self, measure_text: fn(&str, &TextOptions) -> (Float, Float)) {
         println!("Setting measure text function");
         self.measure_text = Option::Some(measure_text);
+        self.measure_cache.invalidate();
+    }
+
+    /// Measure a single line of text. Uses the custom measure function if one
+    /// was set, otherwise the font registry.
+    /// Case transforms and letter spacing are applied here so custom
+    /// measure functions don't need to handle them.
+    /// Results are cached by text, family, size, weight and style.
+    pub fn measure(&self, text: &str, text_options: &TextOptions) -> (Float, Float) {
+        let display_text = text_options.display_text(text);
+        let key = MeasureKey::new(&display_text, text_options);
+        let (width, height) = self
+            .measure_cache
+            .get_or_measure(key, self.fonts.revision(), || match self.measure_text {
+                Some(measure_text) => measure_text(&display_text, text_options),
+                None => self.fonts.measure(&display_text, text_options),
+            });
+        (
+            width + text_options.letter_spacing_width(&display_text),
+            height,
+        )
+    }
+
+    /// Replace the theme used for defaults, takes effect on the next build
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Replace the font registry used for measurement and rendering
+    pub fn set_font_registry(&mut self, fonts: FontRegistry) {
+        self.fonts = fonts;
+        self.measure_cache.invalidate();
+    }
+
+    /// Drop cached text measurements. Changes to `fonts` are detected automatically,
+    /// call this when the custom measure function depends on external state (e.g. a JS callback)
+    pub fn invalidate_measure_cache(&self) {
+        self.measure_cache.invalidate();
+    }
+
+    pub fn measure_cache_stats(&self) -> MeasureCacheStats {
+        self.measure_cache.stats()
+    }
+
+    pub fn reset_measure_cache_stats(&self) {
+        self.measure_cache.reset_stats();
     }
 
     // Replace position methods with transform methods
@@ -257,6 +373,92 @@ impl DiagramBuilder {
         self.transforms.insert(entity_id, transform);
     }
 
+    pub fn get_opacity(&self, entity_id: EntityID) -> OpacityOptions {
+        self.opacities.get(&entity_id).copied().unwrap_or_default()
+    }
+
+    pub fn set_opacity(&mut self, entity_id: EntityID, opacity: OpacityOptions) {
+        self.opacities.insert(entity_id, opacity);
+    }
+
+    pub fn get_shadow(&self, entity_id: EntityID) -> Option<&ShadowOptions> {
+        self.shadows.get(&entity_id)
+    }
+
+    pub fn set_shadow(&mut self, entity_id: EntityID, shadow: ShadowOptions) {
+        self.shadows.insert(entity_id, shadow);
+    }
+
+    pub fn get_clip(&self, entity_id: EntityID) -> Option<&ClipOptions> {
+        self.clips.get(&entity_id)
+    }
+
+    pub fn set_clip(&mut self, entity_id: EntityID, clip: ClipOptions) {
+        self.clips.insert(entity_id, clip);
+    }
+
+    pub fn get_pattern_tile(&self, entity_id: EntityID) -> Option<&DiagramTreeNode> {
+        self.pattern_tiles.get(&entity_id)
+    }
+
+    pub fn set_pattern_tile(&mut self, entity_id: EntityID, tile: DiagramTreeNode) {
+        self.pattern_tiles.insert(entity_id, tile);
+    }
+
+    pub fn get_layer_options(&self, entity_id: EntityID) -> LayerOptions {
+        self.layer_options
+            .get(&entity_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_layer_options(&mut self, entity_id: EntityID, options: LayerOptions) {
+        self.layer_options.insert(entity_id, options);
+    }
+
+    /// Layer options of a node. A labelled connector's wrapper group uses
+    /// the options of the connector it wraps.
+    pub fn node_layer_options(&self, node: &DiagramTreeNode) -> LayerOptions {
+        match node.connector() {
+            Some(connector) => self.get_layer_options(connector.entity_id.clone()),
+            None => self.get_layer_options(node.entity_id.clone()),
+        }
+    }
+
+    /// Layer a node asks for, `None` to stay in its parent's layer.
+    /// Connectors and their wrappers go to the connectors layer by default.
+    pub fn node_layer(&self, node: &DiagramTreeNode) -> Option<Layer> {
+        let layer = self.node_layer_options(node).layer;
+        if layer.is_none() && node.connector().is_some() {
+            return Some(Layer::Connectors);
+        }
+        layer
+    }
+
+    /// Children in the order they are painted: by z_index, ties in tree
+    /// order. Children painted in another layer are left out.
+    pub fn paint_order<'a>(&self, node: &'a DiagramTreeNode) -> Vec<&'a DiagramTreeNode> {
+        let mut children: Vec<&DiagramTreeNode> = node
+            .children
+            .iter()
+            .map(|child| child.as_ref())
+            .filter(|child| !self.layer_roots.contains_key(&child.entity_id))
+            .collect();
+        children.sort_by_key(|child| self.node_layer_options(child).z_index);
+        children
+    }
+
+    /// Entities moved to `layer`, in the order they are painted
+    pub fn layer_entries(&self, layer: Layer) -> Vec<&LayerRoot> {
+        let mut entries: Vec<&LayerRoot> = self
+            .layer_roots
+            .values()
+            .filter(|root| root.layer == layer)
+            .collect();
+        entries.sort_by_key(|root| (root.z_index, root.order));
+        entries
+    }
+
     // Convenience methods for common operations
     // Get the local position
     pub fn get_local_position(&self, entity_id: EntityID) -> (Float, Float) {
@@ -334,6 +536,61 @@ impl DiagramBuilder {
         self.set_transform(entity_id, current.combine(&scale));
     }
 
+    /// Fill outline in the entity's own coordinates, used by clip paths.
+    /// Arcs give their pie slice, entities without an area of their own
+    /// (text, stacks, images...) give their bounds.
+    pub fn get_outline(&self, entity_id: EntityID) -> PathData {
+        let size = self.get_size(entity_id.clone());
+        match self.entityTypes.get(&entity_id) {
+            Some(EntityType::BoxShape) => {
+                let radius = self.get_box(entity_id).box_options.border_radius;
+                rounded_rect_path(size.0, size.1, radius)
+            }
+            Some(EntityType::RectShape) => {
+                let radius = self.get_rectangle(entity_id).rect_options.border_radius;
+                rounded_rect_path(size.0, size.1, radius)
+            }
+            Some(EntityType::EllipseShape) => {
+                let radius = self.get_ellipse(entity_id).radius;
+                ellipse_path(radius, radius)
+            }
+            Some(EntityType::ArcShape) => {
+                let arc = self.get_arc(entity_id);
+                let center = (size.0 / 2.0, size.1 / 2.0);
+                if (arc.angle_sweep() - 360.0).abs() < 0.1 {
+                    return ellipse_path(center, (arc.radius, arc.radius));
+                }
+                let (start_angle, end_angle) = arc.normalize_angles();
+                let mut commands = vec![PathCommand::MoveTo(center)];
+                commands.extend(
+                    arc_path(center, arc.radius, start_angle, end_angle)
+                        .commands
+                        .into_iter()
+                        .map(|command| match command {
+                            PathCommand::MoveTo(point) => PathCommand::LineTo(point),
+                            command => command,
+                        }),
+                );
+                commands.push(PathCommand::Close);
+                PathData { commands }
+            }
+            Some(EntityType::PathShape) => self.get_path(entity_id).path.clone(),
+            Some(EntityType::PolyLine) => {
+                let points = &self.get_polyline(entity_id).points;
+                let mut path = PathData::new();
+                for (index, &(x, y)) in points.iter().enumerate() {
+                    path = if index == 0 {
+                        path.move_to(x, y)
+                    } else {
+                        path.line_to(x, y)
+                    };
+                }
+                path.close()
+            }
+            _ => rounded_rect_path(size.0, size.1, 0.0),
+        }
+    }
+
     // Get effective bounding box considering transform
     pub fn get_effective_bounds(&self, entity_id: EntityID) -> BoundingBox {
         let transform = self.get_transform(entity_id.clone());
@@ -416,6 +673,30 @@ impl DiagramBuilder {
         DiagramTreeNode::new(EntityType::ArcShape, arc_id)
     }
 
+    /// Shape drawn from path data, see `PathData::parse` for SVG path strings
+    pub fn new_path(
+        &mut self,
+        id: EntityID,
+        path: PathData,
+        options: PathOptions,
+    ) -> DiagramTreeNode {
+        let path_id = self.new_entity(id, EntityType::PathShape);
+        let shape = ShapePath::new(path_id.clone(), path, options);
+        self.paths.insert(path_id.clone(), shape);
+        DiagramTreeNode::new(EntityType::PathShape, path_id)
+    }
+
+    /// Path through `points` smoothed with a Catmull-Rom spline
+    pub fn new_spline(
+        &mut self,
+        id: EntityID,
+        points: Vec<(Float, Float)>,
+        closed: bool,
+        options: PathOptions,
+    ) -> DiagramTreeNode {
+        self.new_path(id, spline_path(&points, closed), options)
+    }
+
     // Add convenience methods for common arc types
     pub fn new_arc_degrees(
         &mut self,
@@ -538,7 +819,7 @@ impl DiagramBuilder {
     pub fn new_text(&mut self, id: EntityID, text: &str, options: TextOptions) -> DiagramTreeNode {
         let text_id = self.new_entity(id, EntityType::TextShape);
         //create the lines
-        let text_lines = textwrap::wrap(&text, options.line_width);
+        let text_lines = wrap_text(text, options.line_width);
         let lines: Vec<EntityID> = text_lines
             .iter()
             .enumerate()
@@ -595,7 +876,11 @@ impl DiagramBuilder {
             start_point_id: start_point.entity_id.clone(),
             end_point_id: end_point.entity_id.clone(),
             label: None,
+            labels: Vec::new(),
             options: options,
+            waypoints: Vec::new(),
+            start_offset: (0.0, 0.0),
+            end_offset: (0.0, 0.0),
         };
 
         self.connectors.insert(id.clone(), connector);
@@ -619,38 +904,48 @@ impl DiagramBuilder {
         label: String,
         options: ConnectorOptions,
     ) -> DiagramTreeNode {
-        self.new_entity(id.clone(), EntityType::ConnectorShape);
-
-        let label_text = self.new_text(format!("{}_label_elem", id), &label, TextOptions::default());
-        
-
-        // Create start and end points
-        let start_point = self.new_point(format!("{}_start", id.clone()));
-        let end_point = self.new_point(format!("{}_end", id.clone()));
-
-        let connector = ShapeConnector {
-            entity: id.clone(),
-            source_id: source_id,
-            target_id: target_id,
-            start_point_id: start_point.entity_id.clone(),
-            end_point_id: end_point.entity_id.clone(),
-            label: Some(label.to_string()),
-            options: options,
+        // Labels that follow the path are painted with the connector, there is no text box to place
+        let labels = if options.label_path.is_none() {
+            vec![ConnectorLabel::new(&label, LabelRole::Middle)]
+        } else {
+            Vec::new()
         };
+        self.new_connector_with_labels(id, source_id, target_id, Some(label), labels, options)
+    }
 
-        self.connectors.insert(id.clone(), connector);
-
-        let mut children = Vec::new();
-        children.push(Box::new(start_point.clone()));
-        children.push(Box::new(end_point.clone()));
-
-        let con_node = DiagramTreeNode {
-            entity_type: EntityType::ConnectorShape,
-            entity_id: id.clone(),
-            children: children,
-        };
+    /// Connector wrapped in a group with a text entity, or a box around one,
+    /// for each of its labels. `label` is the main label's text
+    pub fn new_connector_with_labels(
+        &mut self,
+        id: EntityID,
+        source_id: EntityID,
+        target_id: EntityID,
+        label: Option<String>,
+        labels: Vec<ConnectorLabel>,
+        options: ConnectorOptions,
+    ) -> DiagramTreeNode {
+        let con_node = self.new_connector(id.clone(), source_id, target_id, options);
+
+        let mut wrapper_children = vec![con_node];
+        let connector = self.connectors.get_mut(&id).unwrap();
+        connector.label = label;
+        connector.labels = labels.clone();
+        let label_ids: Vec<EntityID> = (0..labels.len())
+            .map(|index| connector.label_entity(index))
+            .collect();
 
-        self.new_group(format!("{}_wrapper", id), vec![con_node, label_text])
+        for (label, label_id) in labels.iter().zip(label_ids) {
+            let node = match &label.background {
+                Some(background) => {
+                    let text_id = format!("{}_text", label_id);
+                    let text = self.new_text(text_id, &label.text, label.text_options.clone());
+                    self.new_box(label_id, text, background.clone())
+                }
+                None => self.new_text(label_id, &label.text, label.text_options.clone()),
+            };
+            wrapper_children.push(node);
+        }
+        self.new_group(format!("{}_wrapper", id), wrapper_children)
     }
 
     pub fn new_ellipse(
@@ -736,6 +1031,7 @@ impl DiagramBuilder {
                 LineOptions {
                     stroke_color: options.border_color.clone(),
                     stroke_width: 1.0,
+                    stroke_style: StrokeStyle::default(),
                 },
             );
             self.lines.insert(line_id.clone(), line);
@@ -753,6 +1049,7 @@ impl DiagramBuilder {
                 LineOptions {
                     stroke_color: options.border_color.clone(),
                     stroke_width: 1.0,
+                    stroke_style: StrokeStyle::default(),
                 },
             );
             self.lines.insert(line_id.clone(), line);
@@ -831,6 +1128,19 @@ impl DiagramBuilder {
         DiagramTreeNode::new(EntityType::PolyLine, id.clone())
     }
 
+    /// Polyline whose points can follow other entities, see `LinePointReference`
+    pub fn new_polyline_with_references(
+        &mut self,
+        id: EntityID,
+        references: Vec<LinePointReference>,
+        options: LineOptions,
+    ) -> DiagramTreeNode {
+        self.new_entity(id.clone(), EntityType::PolyLine);
+        let polyline = PolyLine::with_references(id.clone(), references, options);
+        self.polylines.insert(id.clone(), polyline);
+        DiagramTreeNode::new(EntityType::PolyLine, id)
+    }
+
     /// Creates a new FreeContainer with all children at once
     pub fn new_free_container(
         &mut self,
@@ -962,6 +1272,49 @@ impl DiagramBuilder {
         &self.connectors[&id]
     }
 
+    pub fn get_connector_mut(&mut self, id: EntityID) -> Option<&mut ShapeConnector> {
+        self.connectors.get_mut(&id)
+    }
+
+    /// Geometry of an end marker, entity markers take the laid out size of their tile
+    pub fn get_marker_geometry(&self, marker: &Marker, size: Float) -> MarkerGeometry {
+        let entity_size = match marker {
+            Marker::Entity(id) => self.get_size(id.clone()),
+            _ => (0.0, 0.0),
+        };
+        MarkerGeometry::new(marker, size, entity_size)
+    }
+
+    /// Where a connector starts and ends: its ports plus the offsets layout gave them
+    pub fn get_connector_ends(
+        &self,
+        connector: &ShapeConnector,
+    ) -> ((Float, Float), (Float, Float)) {
+        let (start_toward, end_toward) = self.connector_references(connector);
+        let (source_port, target_port) = self.connector_port_options(connector);
+        let start = self.get_port_position_toward(&connector.source_id, &source_port, start_toward);
+        let end = self.get_port_position_toward(&connector.target_id, &target_port, end_toward);
+        (
+            (start.0 + connector.start_offset.0, start.1 + connector.start_offset.1),
+            (end.0 + connector.end_offset.0, end.1 + connector.end_offset.1),
+        )
+    }
+
+    pub fn set_connector_waypoints(&mut self, id: EntityID, waypoints: Vec<(Float, Float)>) {
+        if let Some(connector) = self.connectors.get_mut(&id) {
+            connector.waypoints = waypoints;
+        }
+    }
+
+    /// Path, arrow and label anchors of a connector, `None` until it is laid out
+    pub fn get_connector_geometry(&self, id: EntityID) -> Option<&ConnectorGeometry> {
+        self.connector_geometries.get(&id)
+    }
+
+    pub fn set_connector_geometry(&mut self, id: EntityID, geometry: ConnectorGeometry) {
+        self.connector_geometries.insert(id, geometry);
+    }
+
     pub fn get_line_mut(&mut self, id: EntityID) -> Option<&mut ShapeLine> {
         self.lines.get_mut(&id)
     }
@@ -984,6 +1337,18 @@ impl DiagramBuilder {
         &self.arrows[&id]
     }
 
+    /// Line and markers of an arrow shape, in the same coordinates as its start and end
+    pub fn get_arrow_geometry(&self, id: EntityID) -> ConnectorGeometry {
+        let arrow = self.get_arrow(id);
+        let options = &arrow.arrow_options;
+        arrow_geometry(
+            arrow.start,
+            arrow.end,
+            self.get_marker_geometry(&options.marker_start, options.arrow_size),
+            self.get_marker_geometry(&options.marker_end, options.arrow_size),
+        )
+    }
+
     pub fn get_table(&self, id: EntityID) -> &Table {
         &self.tables[&id]
     }
@@ -1000,6 +1365,10 @@ impl DiagramBuilder {
         &self.polylines[&id]
     }
 
+    pub fn get_polyline_mut(&mut self, id: EntityID) -> Option<&mut PolyLine> {
+        self.polylines.get_mut(&id)
+    }
+
     pub fn get_free_container(&self, id: EntityID) -> &FreeContainer {
         &self.free_containers[&id]
     }
@@ -1028,113 +1397,331 @@ impl DiagramBuilder {
         &self.arcs[&id]
     }
 
+    pub fn get_arc_mut(&mut self, id: EntityID) -> Option<&mut ShapeArc> {
+        self.arcs.get_mut(&id)
+    }
+
+    pub fn get_path(&self, id: EntityID) -> &ShapePath {
+        &self.paths[&id]
+    }
+
+    pub fn get_path_mut(&mut self, id: EntityID) -> Option<&mut ShapePath> {
+        self.paths.get_mut(&id)
+    }
+
+    /// Binds the ends of an arc, see `ShapeArc::endpoints`
+    pub fn set_arc_endpoints(
+        &mut self,
+        id: EntityID,
+        start: LinePointReference,
+        end: LinePointReference,
+    ) {
+        if let Some(arc) = self.arcs.get_mut(&id) {
+            arc.endpoints = Some((start, end));
+        }
+    }
+
+    /// Where a line, polyline or arc point is in the coordinates of `owner`, the
+    /// shape drawing it. Point ids are taken to be in the same container as the
+    /// shape, entity ports are found through the absolute transforms
+    pub fn resolve_line_point(
+        &self,
+        owner: &EntityID,
+        reference: &LinePointReference,
+    ) -> (Float, Float) {
+        match reference {
+            LinePointReference::Value(x, y) => (*x, *y),
+            LinePointReference::PointID(id) => {
+                let (x, y) = self.get_local_position(id.clone());
+                let (owner_x, owner_y) = self.get_local_position(owner.clone());
+                (x - owner_x, y - owner_y)
+            }
+            LinePointReference::EntityPort {
+                entity,
+                port,
+                offset,
+            } => {
+                let (x, y) = self.get_port_position(entity, port);
+                self.port_from_world(owner, (x + offset.0, y + offset.1))
+            }
+        }
+    }
+
     pub fn get_spacer(&self, id: EntityID) -> &ShapeSpacer {
         &self.spacers[&id]
     }
 
-    /// Get the position of a specific port on an element
+    /// Get the position of a specific port on an element. Ports that depend on
+    /// the other end of a connector (boundary, auto) give the center here,
+    /// see `get_port_position_toward`
     pub fn get_port_position(&self, entity_id: &EntityID, port: &Port) -> (Float, Float) {
-    let size = self.get_size(entity_id.clone());
-
-    // Get absolute position (for elements with rotation, this includes the transform)
-    let absolute_pos = self
-        .absolute_positions
-        .get(entity_id)
-        .copied()
-        .unwrap_or_else(|| {
-            println!("⚠️  WARNING: No absolute position cached for {}", entity_id);
-            self.get_local_position(entity_id.clone())
-        });
+        let size = self.get_size(entity_id.clone());
+        let center = (size.0 / 2.0, size.1 / 2.0);
+
+        // Calculate port offset based on size
+        let (offset_x, offset_y) = match port {
+            Port::Center | Port::Boundary | Port::Auto => center,
+            Port::Top => (size.0 / 2.0, 0.0),
+            Port::Bottom => (size.0 / 2.0, size.1),
+            Port::Left => (0.0, size.1 / 2.0),
+            Port::Right => (size.0, size.1 / 2.0),
+            Port::TopLeft => (0.0, 0.0),
+            Port::TopRight => (size.0, 0.0),
+            Port::BottomLeft => (0.0, size.1),
+            Port::BottomRight => (size.0, size.1),
+            Port::Side { side, offset } => match side {
+                PortSide::Top => (size.0 * offset, 0.0),
+                PortSide::Bottom => (size.0 * offset, size.1),
+                PortSide::Left => (0.0, size.1 * offset),
+                PortSide::Right => (size.0, size.1 * offset),
+            },
+            Port::Named(name) => match self.get_named_port(entity_id, name) {
+                Some(declared) => return self.get_port_position(entity_id, declared),
+                None => center,
+            },
+        };
 
-    // Calculate port offset based on size
-    let (offset_x, offset_y) = match port {
-        Port::Center => (size.0 / 2.0, size.1 / 2.0),
-        Port::Top => (size.0 / 2.0, 0.0),
-        Port::Bottom => (size.0 / 2.0, size.1),
-        Port::Left => (0.0, size.1 / 2.0),
-        Port::Right => (size.0, size.1 / 2.0),
-        Port::TopLeft => (0.0, 0.0),
-        Port::TopRight => (size.0, 0.0),
-        Port::BottomLeft => (0.0, size.1),
-        Port::BottomRight => (size.0, size.1),
-    };
-
-    // ADD THIS DEBUG LINE:
-    println!("  🔌 Port {:?} on {} (size {:.1}x{:.1}): absolute_pos=({:.1},{:.1}), offset=({:.1},{:.1})", 
-             port, entity_id, size.0, size.1, absolute_pos.0, absolute_pos.1, offset_x, offset_y);
-
-    // For rotated elements, we'd need to transform the port offset
-    // through the element's rotation transform here
-    let transform = self.get_transform(entity_id.clone());
-    let rotation_scale_transform = Transform {
-        matrix: [
-            transform.matrix[0], // a (scale/rotation)
-            transform.matrix[1], // b (rotation)
-            transform.matrix[2], // c (rotation)
-            transform.matrix[3], // d (scale/rotation)
-            0.0,                 // no translation
-            0.0,
-        ],
-    };
-
-    let transformed_offset = rotation_scale_transform.transform_point(offset_x, offset_y);
-
-    println!("     After transform: offset=({:.1},{:.1}), final=({:.1},{:.1})",
-             transformed_offset.0, transformed_offset.1,
-             absolute_pos.0 + transformed_offset.0,
-             absolute_pos.1 + transformed_offset.1);
-
-    (
-        absolute_pos.0 + transformed_offset.0,
-        absolute_pos.1 + transformed_offset.1,
-    )
-}
+        let position = self.port_to_world(entity_id, (offset_x, offset_y));
+        println!(
+            "  🔌 Port {:?} on {} (size {:.1}x{:.1}): offset=({:.1},{:.1}), final=({:.1},{:.1})",
+            port, entity_id, size.0, size.1, offset_x, offset_y, position.0, position.1
+        );
+        position
+    }
 
-    pub fn get_absolute_center(&self, entity_id: &EntityID) -> (Float, Float) {
-        let size = self.get_size(entity_id.clone());
+    /// Position of a connector end whose other end is at `toward`: boundary ports
+    /// land on the entity outline and auto ports on the side facing `toward`
+    pub fn get_port_position_toward(
+        &self,
+        entity_id: &EntityID,
+        port: &Port,
+        toward: (Float, Float),
+    ) -> (Float, Float) {
+        match self.resolve_port(entity_id, port, toward) {
+            Port::Boundary => {
+                let local_toward = self.port_from_world(entity_id, toward);
+                let exit = self.get_port_outline(entity_id).exit_point(local_toward);
+                self.port_to_world(entity_id, exit)
+            }
+            resolved => self.get_port_position(entity_id, &resolved),
+        }
+    }
 
-        // Local center point (relative to element's origin)
-        let local_center = (size.0 / 2.0, size.1 / 2.0);
+    /// The port a connector end uses: named ports are looked up and auto ports
+    /// become the side facing `toward`. Unknown names fall back to the boundary
+    pub fn resolve_port(&self, entity_id: &EntityID, port: &Port, toward: (Float, Float)) -> Port {
+        match port {
+            Port::Named(name) => match self.get_named_port(entity_id, name) {
+                Some(declared) => self.resolve_port(entity_id, declared, toward),
+                None => {
+                    println!("⚠️  No port named '{}' on {}", name, entity_id);
+                    Port::Boundary
+                }
+            },
+            Port::Auto => {
+                let (width, height) = self.get_size(entity_id.clone());
+                let (x, y) = self.port_from_world(entity_id, toward);
+                let (dx, dy) = (x - width / 2.0, y - height / 2.0);
+                // Compared relative to the size so a wide entity prefers its long sides
+                if dx.abs() * height >= dy.abs() * width {
+                    if dx >= 0.0 {
+                        Port::Right
+                    } else {
+                        Port::Left
+                    }
+                } else if dy >= 0.0 {
+                    Port::Bottom
+                } else {
+                    Port::Top
+                }
+            }
+            port => port.clone(),
+        }
+    }
 
-        // Get the transform for this element
-        let transform = self.get_transform(entity_id.clone());
+    /// The ports a connector uses at each end, see `resolve_port`
+    pub fn get_connector_ports(&self, connector: &ShapeConnector) -> (Port, Port) {
+        let (start_toward, end_toward) = self.connector_references(connector);
+        let (source_port, target_port) = self.connector_port_options(connector);
+        (
+            self.resolve_port(&connector.source_id, &source_port, start_toward),
+            self.resolve_port(&connector.target_id, &target_port, end_toward),
+        )
+    }
+
+    /// The ports a connector asks for. Both ends of a self-loop facing the loop
+    /// side would meet, auto ports on one leave from a quarter of the way along it
+    fn connector_port_options(&self, connector: &ShapeConnector) -> (Port, Port) {
+        let options = &connector.options;
+        if !connector.is_self_loop() || !options.waypoints.is_empty() {
+            return (options.source_port.clone(), options.target_port.clone());
+        }
+        let loop_port = |port: &Port, offset| match port {
+            Port::Auto => Port::Side {
+                side: options.loop_side,
+                offset,
+            },
+            port => port.clone(),
+        };
+        (
+            loop_port(&options.source_port, 0.25),
+            loop_port(&options.target_port, 0.75),
+        )
+    }
+
+    /// The points each end of a connector heads for: the nearest waypoint, or the
+    /// other end's port, the center for ports that depend on the other end.
+    /// Self-loops head out of their loop side, see `get_loop_references`
+    fn connector_references(&self, connector: &ShapeConnector) -> ((Float, Float), (Float, Float)) {
+        let waypoints = &connector.options.waypoints;
+        if let (Some(first), Some(last)) = (waypoints.first(), waypoints.last()) {
+            return (
+                self.get_waypoint_position(first),
+                self.get_waypoint_position(last),
+            );
+        }
+        if connector.is_self_loop() {
+            return self.get_loop_references(connector);
+        }
+        (
+            self.get_port_position(&connector.target_id, &connector.options.target_port),
+            self.get_port_position(&connector.source_id, &connector.options.source_port),
+        )
+    }
+
+    /// Where a connector waypoint is in absolute coordinates
+    pub fn get_waypoint_position(&self, waypoint: &Waypoint) -> (Float, Float) {
+        match waypoint {
+            Waypoint::Absolute(x, y) => (*x, *y),
+            Waypoint::Relative {
+                entity,
+                port,
+                offset,
+            } => {
+                let (x, y) = self.get_port_position(entity, port);
+                (x + offset.0, y + offset.1)
+            }
+        }
+    }
+
+    /// The points the ends of a self-loop head out to, `loop_size` beyond its
+    /// loop side. Placed so boundary ports on a box leave a quarter of the way
+    /// in from each end of the side. Sides are those of the unrotated box
+    pub fn get_loop_references(
+        &self,
+        connector: &ShapeConnector,
+    ) -> ((Float, Float), (Float, Float)) {
+        let (width, height) = self.get_size(connector.source_id.clone());
+        let center = self.get_absolute_center(&connector.source_id);
+        let (nx, ny) = connector.options.loop_side.direction();
+        // Clockwise along the side
+        let (tx, ty) = (-ny, nx);
+        let depth = (nx * width).abs() / 2.0 + (ny * height).abs() / 2.0;
+        let span = (tx * width).abs() / 2.0 + (ty * height).abs() / 2.0;
+
+        let out = depth + connector.options.loop_size;
+        let spread = if depth > 0.0 {
+            span / 2.0 * out / depth
+        } else {
+            span / 2.0
+        };
+        let reference = |along: Float| {
+            (
+                center.0 + nx * out + tx * along,
+                center.1 + ny * out + ty * along,
+            )
+        };
+        (reference(-spread), reference(spread))
+    }
+
+    /// Declares a port the connectors of an entity can refer to by name.
+    /// Declared ports can't be named ports themselves
+    pub fn set_named_port(&mut self, entity_id: EntityID, name: String, port: Port) {
+        if matches!(port, Port::Named(_)) {
+            println!("⚠️  Port '{}' on {} can't refer to another named port", name, entity_id);
+            return;
+        }
+        self.named_ports.entry(entity_id).or_default().insert(name, port);
+    }
+
+    pub fn get_named_port(&self, entity_id: &EntityID, name: &str) -> Option<&Port> {
+        self.named_ports.get(entity_id)?.get(name)
+    }
+
+    /// Outline boundary ports are clipped to, see `get_outline` for the shapes
+    pub fn get_port_outline(&self, entity_id: &EntityID) -> Outline {
+        let (width, height) = self.get_size(entity_id.clone());
+        let rect = |radius| Outline::Rect {
+            width,
+            height,
+            radius,
+        };
+        match self.entityTypes.get(entity_id) {
+            Some(EntityType::BoxShape) => {
+                rect(self.get_box(entity_id.clone()).box_options.border_radius)
+            }
+            Some(EntityType::RectShape) => {
+                rect(self.get_rectangle(entity_id.clone()).rect_options.border_radius)
+            }
+            Some(EntityType::EllipseShape) => Outline::Ellipse { width, height },
+            Some(EntityType::ArcShape) => {
+                let arc = self.get_arc(entity_id.clone());
+                if (arc.angle_sweep() - 360.0).abs() < 0.1 {
+                    return Outline::Ellipse { width, height };
+                }
+                Outline::Arc {
+                    radius: arc.radius,
+                    start: arc.normalize_angles().0,
+                    sweep: arc.angle_sweep(),
+                    filled: arc.arc_options.filled,
+                }
+            }
+            _ => rect(0.0),
+        }
+    }
+
+    /// Absolute position of a point given in the entity's own coordinates
+    fn port_to_world(&self, entity_id: &EntityID, point: (Float, Float)) -> (Float, Float) {
+        self.get_absolute_transform(entity_id)
+            .transform_point(point.0, point.1)
+    }
+
+    fn port_from_world(&self, entity_id: &EntityID, point: (Float, Float)) -> (Float, Float) {
+        let transform = self.get_absolute_transform(entity_id);
+        match transform.inverse() {
+            Some(inverse) => inverse.transform_point(point.0, point.1),
+            // Flattened to nothing, only the translation can be undone
+            None => (point.0 - transform.matrix[4], point.1 - transform.matrix[5]),
+        }
+    }
 
-        // Use cached absolute position
+    /// Transform from the entity's own coordinates to the diagram's, including
+    /// the rotation and scale of the containers it is in. Before layout has
+    /// cached it, only the entity's own rotation and scale are known.
+    /// The translation always comes from the absolute position, connectors
+    /// and their labels are moved after the transforms are cached
+    pub fn get_absolute_transform(&self, entity_id: &EntityID) -> Transform {
         let absolute_pos = self
             .absolute_positions
             .get(entity_id)
             .copied()
             .unwrap_or_else(|| {
-                println!(
-                    "⚠️  WARNING: No absolute position cached for {}, using local position",
-                    entity_id
-                );
-                // Fallback to local position (won't be correct for nested elements)
+                println!("⚠️  WARNING: No absolute position cached for {}", entity_id);
                 self.get_local_position(entity_id.clone())
             });
+        let mut transform = self
+            .absolute_transforms
+            .get(entity_id)
+            .cloned()
+            .unwrap_or_else(|| self.get_transform(entity_id.clone()));
+        transform.matrix[4] = absolute_pos.0;
+        transform.matrix[5] = absolute_pos.1;
+        transform
+    }
 
-        // For elements with rotation/scale, we need to transform the center point
-        // Extract just rotation/scale (no translation) from the transform
-        let rotation_scale_transform = Transform {
-            matrix: [
-                transform.matrix[0], // a (scale/rotation)
-                transform.matrix[1], // b (rotation)
-                transform.matrix[2], // c (rotation)
-                transform.matrix[3], // d (scale/rotation)
-                0.0,                 // e (no translation)
-                0.0,                 // f (no translation)
-            ],
-        };
-
-        // Transform the local center through rotation/scale
-        let transformed_center =
-            rotation_scale_transform.transform_point(local_center.0, local_center.1);
-
-        // Add to absolute position
-        (
-            absolute_pos.0 + transformed_center.0,
-            absolute_pos.1 + transformed_center.1,
-        )
+    pub fn get_absolute_center(&self, entity_id: &EntityID) -> (Float, Float) {
+        let (width, height) = self.get_size(entity_id.clone());
+        self.port_to_world(entity_id, (width / 2.0, height / 2.0))
     }
 
     pub fn add_to_pending_nodes(&mut self, elem: DiagramTreeNode) {
@@ -1185,6 +1772,61 @@ mod tests {
         assert_eq!(w, 60.0);
         assert_eq!(h, 12.0);
     }
+
+    #[test]
+    fn test_measure_cache() {
+        let mut session = DiagramBuilder::new();
+        let options = TextOptions {
+            font_size: 14.0,
+            ..Default::default()
+        };
+
+        let first = session.measure("cached text", &options);
+        let second = session.measure("cached text", &options);
+        assert_eq!(first, second);
+        let stats = session.measure_cache_stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+        // Size is part of the key
+        let bigger = session.measure(
+            "cached text",
+            &TextOptions {
+                font_size: 28.0,
+                ..options.clone()
+            },
+        );
+        assert!(bigger.0 > first.0);
+        assert_eq!(session.measure_cache_stats().misses, 2);
+
+        // Entries survive clearing the diagram
+        session.clear_cache();
+        session.measure("cached text", &options);
+        assert_eq!(session.measure_cache_stats().hits, 2);
+
+        // Font changes invalidate the cache
+        session.fonts.set_default_family("Roboto");
+        session.measure("cached text", &options);
+        let stats = session.measure_cache_stats();
+        assert_eq!((stats.misses, stats.entries), (3, 1));
+
+        session.set_measure_text_fn(|text, _| (text.len() as Float, 10.0));
+        assert_eq!(session.measure("cached text", &options), (11.0, 10.0));
+    }
+
+    #[test]
+    fn test_measure_cache_after_assigning_fonts() {
+        let mut session = DiagramBuilder::new();
+        let options = TextOptions::default();
+        assert!(session.measure("swapped", &options).0 > 0.0);
+
+        // A registry without faces measures nothing, the old entry must not be reused
+        session.fonts = FontRegistry::new();
+        assert_eq!(session.measure("swapped", &options), (0.0, 0.0));
+
+        session.fonts = FontRegistry::with_default_font();
+        assert!(session.measure("swapped", &options).0 > 0.0);
+        assert_eq!(session.measure_cache_stats().misses, 3);
+    }
 }
 
 //Component registration tests
@@ -0,0 +1,488 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::{path::Path, sync::Arc, sync::OnceLock};
+
+use rusttype::{Font, Scale};
+
+use crate::components::{Float, TextOptions};
+use crate::text_shaping::graphemes;
+
+/// Family name of the face bundled with the engine, used when nothing else matches.
+pub const DEFAULT_FONT_FAMILY: &str = "Roboto";
+
+static DEFAULT_FONT_DATA: &[u8] = include_bytes!("../assets/fonts/Roboto-Regular.ttf");
+
+// Shared by every registry so two different registries never have the same revision
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+}
+
+impl FontStyle {
+    pub fn as_css(&self) -> &'static str {
+        match self {
+            FontStyle::Normal => "normal",
+            FontStyle::Italic => "italic",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FontError {
+    IoError(String),
+    InvalidFontData(String),
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::IoError(msg) => write!(f, "IO error: {}", msg),
+            FontError::InvalidFontData(family) => {
+                write!(f, "Invalid font data for family {}", family)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+/// A single loaded face (one file) of a font family.
+#[derive(Clone)]
+pub struct FontFace {
+    pub family: String,
+    pub weight: u32,
+    pub style: FontStyle,
+    // true for the face bundled with the engine
+    pub builtin: bool,
+    // TTF/OTF bytes, shared by every clone of the face
+    data: Arc<[u8]>,
+}
+
+impl FontFace {
+    /// Parsed view of the face. Parsing only reads the table directory,
+    /// the bytes were validated when the face was registered.
+    pub fn font(&self) -> Font<'_> {
+        Font::try_from_bytes(&self.data).expect("face data is validated on registration")
+    }
+
+    /// Raw TTF/OTF bytes, used by the SVG renderer to embed the face.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// True if the face has a glyph for the character (not the .notdef box)
+    pub fn has_glyph(&self, ch: char) -> bool {
+        self.font().glyph(ch).id().0 != 0
+    }
+
+    /// Measures a single line of text: advance widths plus pair kerning,
+    /// height is ascent - descent.
+    pub fn measure(&self, text: &str, font_size: Float) -> (Float, Float) {
+        let font = self.font();
+        let scale = Scale::uniform(font_size);
+
+        let mut width = 0.0;
+        let mut prev_glyph_id = None;
+        for ch in text.chars() {
+            let glyph = font.glyph(ch).scaled(scale);
+            if let Some(prev_id) = prev_glyph_id {
+                width += font.pair_kerning(scale, prev_id, glyph.id());
+            }
+            width += glyph.h_metrics().advance_width;
+            prev_glyph_id = Some(glyph.id());
+        }
+
+        let v_metrics = font.v_metrics(scale);
+        (width, v_metrics.ascent - v_metrics.descent)
+    }
+}
+
+impl std::fmt::Debug for FontFace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FontFace")
+            .field("family", &self.family)
+            .field("weight", &self.weight)
+            .field("style", &self.style)
+            .field("builtin", &self.builtin)
+            .finish()
+    }
+}
+
+/**
+ * Font registry shared by layout and renderers.
+ *
+ * Faces are keyed by family / weight / style. Layout measures text with the
+ * same face the PNG renderer rasterizes with, so wrapped lines and drawn
+ * glyphs agree. The SVG renderer embeds the registered faces it uses.
+ *
+ * Usage:
+ *```rust,ignore
+ * let mut builder = DiagramBuilder::new();
+ * builder
+ *     .fonts
+ *     .register_font_file("Inter", 400, FontStyle::Normal, "assets/Inter-Regular.ttf")?;
+ *```
+ */
+#[derive(Debug, Clone)]
+pub struct FontRegistry {
+    faces: Vec<FontFace>,
+    default_family: Option<String>,
+    // Families tried for characters missing in the requested font (e.g emoji, CJK)
+    fallback_families: Vec<String>,
+    // Renewed on every change, cached measurements taken with another revision are stale
+    revision: u64,
+}
+
+impl Default for FontRegistry {
+    fn default() -> Self {
+        FontRegistry::new()
+    }
+}
+
+/// Consecutive graphemes of a line drawn with the same face
+#[derive(Debug, Clone)]
+pub struct FontRun<'a> {
+    pub face: &'a FontFace,
+    pub text: String,
+}
+
+impl FontRegistry {
+    /// Empty registry, text measures as (0, 0) until a face is registered
+    pub fn new() -> FontRegistry {
+        FontRegistry {
+            faces: Vec::new(),
+            default_family: None,
+            fallback_families: Vec::new(),
+            revision: next_revision(),
+        }
+    }
+
+    /// Registry with the bundled face registered as the default family
+    pub fn with_default_font() -> FontRegistry {
+        let mut registry = FontRegistry::new();
+        Font::try_from_bytes(DEFAULT_FONT_DATA).expect("bundled font is valid");
+        registry.faces.push(FontFace {
+            family: DEFAULT_FONT_FAMILY.to_string(),
+            weight: 400,
+            style: FontStyle::Normal,
+            builtin: true,
+            data: Arc::from(DEFAULT_FONT_DATA),
+        });
+        registry.default_family = Some(DEFAULT_FONT_FAMILY.to_string());
+        registry
+    }
+
+    /// Register a face from TTF/OTF bytes. A face with the same
+    /// family/weight/style replaces the previous one.
+    pub fn register_font_bytes(
+        &mut self,
+        family: &str,
+        weight: u32,
+        style: FontStyle,
+        bytes: Vec<u8>,
+    ) -> Result<(), FontError> {
+        let data: Arc<[u8]> = Arc::from(bytes);
+        if Font::try_from_bytes(&data).is_none() {
+            return Err(FontError::InvalidFontData(family.to_string()));
+        }
+
+        self.faces.retain(|face| {
+            !(same_family(&face.family, family) && face.weight == weight && face.style == style)
+        });
+        self.faces.push(FontFace {
+            family: family.to_string(),
+            weight,
+            style,
+            builtin: false,
+            data,
+        });
+        self.revision = next_revision();
+        Ok(())
+    }
+
+    /// Register a face from a TTF/OTF file on disk
+    pub fn register_font_file<P: AsRef<Path>>(
+        &mut self,
+        family: &str,
+        weight: u32,
+        style: FontStyle,
+        path: P,
+    ) -> Result<(), FontError> {
+        let bytes = std::fs::read(path.as_ref())
+            .map_err(|e| FontError::IoError(format!("{}: {}", path.as_ref().display(), e)))?;
+        self.register_font_bytes(family, weight, style, bytes)
+    }
+
+    /// Family used when a requested family is not registered
+    pub fn set_default_family(&mut self, family: &str) {
+        self.default_family = Some(family.to_string());
+        self.revision = next_revision();
+    }
+
+    pub fn default_family(&self) -> Option<&str> {
+        self.default_family.as_deref()
+    }
+
+    /// Append a family to the fallback chain used for missing glyphs
+    pub fn add_fallback_family(&mut self, family: &str) {
+        if !self
+            .fallback_families
+            .iter()
+            .any(|f| same_family(f, family))
+        {
+            self.fallback_families.push(family.to_string());
+            self.revision = next_revision();
+        }
+    }
+
+    pub fn fallback_families(&self) -> &[String] {
+        &self.fallback_families
+    }
+
+    /// Changes every time a face, the default family or a fallback family is added.
+    /// Unique across registries, only clones share one until they change
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn faces(&self) -> &[FontFace] {
+        &self.faces
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    pub fn has_family(&self, family: &str) -> bool {
+        self.faces
+            .iter()
+            .any(|face| same_family(&face.family, family))
+    }
+
+    /// Resolve the face for a CSS-like family list ("Inter, Arial, sans-serif").
+    /// Each family is tried in order, then the default family, then any face.
+    /// Within a family the style must match if possible, then the closest weight wins.
+    pub fn resolve(&self, font_family: &str, weight: u32, style: FontStyle) -> Option<&FontFace> {
+        let candidates = font_family
+            .split(',')
+            .map(|f| f.trim().trim_matches(|c| c == '"' || c == '\''))
+            .filter(|f| !f.is_empty());
+
+        for family in candidates {
+            if let Some(face) = self.best_face_in_family(family, weight, style) {
+                return Some(face);
+            }
+        }
+
+        if let Some(default_family) = &self.default_family {
+            if let Some(face) = self.best_face_in_family(default_family, weight, style) {
+                return Some(face);
+            }
+        }
+
+        self.faces.first()
+    }
+
+    /// Resolve the face for the given text options
+    pub fn resolve_for(&self, options: &TextOptions) -> Option<&FontFace> {
+        self.resolve(
+            &options.font_family,
+            options.font_weight,
+            options.font_style,
+        )
+    }
+
+    /// Split a line into runs of graphemes sharing a face. Graphemes the
+    /// primary face can't draw go to the first face of the fallback chain
+    /// that can: the families listed in font_family, the registry fallback
+    /// families, the default family and finally any registered face.
+    pub fn font_runs<'a>(&'a self, text: &str, options: &TextOptions) -> Vec<FontRun<'a>> {
+        let primary = match self.resolve_for(options) {
+            Some(face) => face,
+            None => return Vec::new(),
+        };
+
+        let mut runs: Vec<FontRun<'a>> = Vec::new();
+        for grapheme in graphemes(text) {
+            let face = self.face_for_grapheme(grapheme, primary, options);
+            match runs.last_mut() {
+                Some(run) if std::ptr::eq(run.face, face) => run.text.push_str(grapheme),
+                _ => runs.push(FontRun {
+                    face,
+                    text: grapheme.to_string(),
+                }),
+            }
+        }
+        runs
+    }
+
+    /// Measure a single line of text with the face matching the text options,
+    /// using fallback faces for missing glyphs
+    pub fn measure(&self, text: &str, options: &TextOptions) -> (Float, Float) {
+        let primary = match self.resolve_for(options) {
+            Some(face) => face,
+            None => return (0.0, 0.0),
+        };
+
+        let (_, mut height) = primary.measure("", options.font_size);
+        let mut width = 0.0;
+        for run in self.font_runs(text, options) {
+            let (run_width, run_height) = run.face.measure(&run.text, options.font_size);
+            width += run_width;
+            height = height.max(run_height);
+        }
+        (width, height)
+    }
+
+    fn face_for_grapheme<'a>(
+        &'a self,
+        grapheme: &str,
+        primary: &'a FontFace,
+        options: &TextOptions,
+    ) -> &'a FontFace {
+        let ch = match grapheme.chars().next() {
+            Some(ch) if !ch.is_whitespace() && !ch.is_control() => ch,
+            _ => return primary,
+        };
+        if primary.has_glyph(ch) {
+            return primary;
+        }
+
+        let requested = options
+            .font_family
+            .split(',')
+            .map(|f| f.trim().trim_matches(|c| c == '"' || c == '\''))
+            .filter(|f| !f.is_empty());
+        let chain = requested
+            .chain(self.fallback_families.iter().map(|f| f.as_str()))
+            .chain(self.default_family.as_deref());
+
+        for family in chain {
+            if let Some(face) =
+                self.best_face_in_family(family, options.font_weight, options.font_style)
+            {
+                if face.has_glyph(ch) {
+                    return face;
+                }
+            }
+        }
+
+        self.faces
+            .iter()
+            .find(|face| face.has_glyph(ch))
+            .unwrap_or(primary)
+    }
+
+    fn best_face_in_family(
+        &self,
+        family: &str,
+        weight: u32,
+        style: FontStyle,
+    ) -> Option<&FontFace> {
+        self.faces
+            .iter()
+            .filter(|face| same_family(&face.family, family))
+            .min_by_key(|face| {
+                let style_penalty = if face.style == style { 0 } else { 1 };
+                (style_penalty, face.weight.abs_diff(weight))
+            })
+    }
+}
+
+fn same_family(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b)
+}
+
+/// Measure function backed by the bundled font. Useful as a fallback where
+/// only a plain `fn` pointer can be used (see `DiagramBuilder::set_measure_text_fn`).
+pub fn measure_text_with_default_font(text: &str, options: &TextOptions) -> (Float, Float) {
+    static REGISTRY: OnceLock<FontRegistry> = OnceLock::new();
+    REGISTRY
+        .get_or_init(FontRegistry::with_default_font)
+        .measure(text, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_font_measurement() {
+        let registry = FontRegistry::with_default_font();
+        let options = TextOptions::default();
+
+        let (w_short, h) = registry.measure("Hi", &options);
+        let (w_long, _) = registry.measure("Hello World", &options);
+        assert!(w_short > 0.0 && h > 0.0);
+        assert!(w_long > w_short);
+        assert_eq!(registry.measure("", &options).0, 0.0);
+
+        // bigger font, wider text
+        let mut big = options.clone();
+        big.font_size = options.font_size * 2.0;
+        let (w_big, _) = registry.measure("Hello World", &big);
+        assert!((w_big - w_long * 2.0).abs() < 0.01);
+
+        assert_eq!(FontRegistry::new().measure("Hello", &options), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_resolve_family_weight_and_fallback() {
+        let mut registry = FontRegistry::with_default_font();
+        registry
+            .register_font_bytes("Brand", 400, FontStyle::Normal, DEFAULT_FONT_DATA.to_vec())
+            .unwrap();
+        registry
+            .register_font_bytes("Brand", 700, FontStyle::Normal, DEFAULT_FONT_DATA.to_vec())
+            .unwrap();
+        assert!(registry
+            .register_font_bytes("Broken", 400, FontStyle::Normal, vec![1, 2, 3])
+            .is_err());
+
+        let face = registry.resolve("brand", 600, FontStyle::Normal).unwrap();
+        assert_eq!((face.family.as_str(), face.weight), ("Brand", 700));
+
+        let face = registry
+            .resolve("'Missing', Brand", 300, FontStyle::Italic)
+            .unwrap();
+        assert_eq!((face.family.as_str(), face.weight), ("Brand", 400));
+
+        let face = registry.resolve("Arial", 400, FontStyle::Normal).unwrap();
+        assert_eq!(face.family, DEFAULT_FONT_FAMILY);
+        assert!(face.builtin);
+    }
+
+    #[test]
+    fn test_fallback_chain_for_missing_glyphs() {
+        let mut registry = FontRegistry::with_default_font();
+        let options = TextOptions::default();
+        // U+E0A0 (powerline branch icon) is not in the bundled font
+        let text = "a\u{e0a0}b";
+        assert_eq!(registry.font_runs(text, &options).len(), 1);
+
+        registry
+            .register_font_bytes(
+                "Icons",
+                400,
+                FontStyle::Normal,
+                include_bytes!("../../demo/assets/AnonymiceProNerdFont-Regular.ttf").to_vec(),
+            )
+            .unwrap();
+        registry.add_fallback_family("Icons");
+
+        let runs = registry.font_runs(text, &options);
+        let families: Vec<&str> = runs.iter().map(|run| run.face.family.as_str()).collect();
+        assert_eq!(
+            families,
+            vec![DEFAULT_FONT_FAMILY, "Icons", DEFAULT_FONT_FAMILY]
+        );
+        assert_eq!(runs[1].text, "\u{e0a0}");
+    }
+}
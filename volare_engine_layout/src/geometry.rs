@@ -0,0 +1,1755 @@
+use std::f32::consts::PI;
+
+use crate::components::{
+    ConnectorOptions, ConnectorType, EntityID, Float, LabelAlignment, Marker,
+    OrthogonalRoutingStrategy,
+};
+use crate::layout::BoundingBox;
+
+// Path geometry shared by layout and renderers.
+// Coordinates are absolute within the coordinate space of whoever builds the path.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathCommand {
+    MoveTo((Float, Float)),
+    LineTo((Float, Float)),
+    CubicTo((Float, Float), (Float, Float), (Float, Float)),
+    ArcTo {
+        radius: (Float, Float),
+        x_rotation: Float, // degrees
+        large_arc: bool,
+        sweep: bool,
+        to: (Float, Float),
+    },
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PathData {
+    pub commands: Vec<PathCommand>,
+}
+
+impl PathData {
+    pub fn new() -> PathData {
+        PathData {
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn move_to(mut self, x: Float, y: Float) -> Self {
+        self.commands.push(PathCommand::MoveTo((x, y)));
+        self
+    }
+
+    pub fn line_to(mut self, x: Float, y: Float) -> Self {
+        self.commands.push(PathCommand::LineTo((x, y)));
+        self
+    }
+
+    pub fn cubic_to(mut self, c1: (Float, Float), c2: (Float, Float), to: (Float, Float)) -> Self {
+        self.commands.push(PathCommand::CubicTo(c1, c2, to));
+        self
+    }
+
+    pub fn arc_to(
+        mut self,
+        radius: (Float, Float),
+        large_arc: bool,
+        sweep: bool,
+        to: (Float, Float),
+    ) -> Self {
+        self.commands.push(PathCommand::ArcTo {
+            radius,
+            x_rotation: 0.0,
+            large_arc,
+            sweep,
+            to,
+        });
+        self
+    }
+
+    pub fn close(mut self) -> Self {
+        self.commands.push(PathCommand::Close);
+        self
+    }
+
+    /// Same path moved by an offset
+    pub fn translated(&self, dx: Float, dy: Float) -> PathData {
+        let shift = |(x, y): (Float, Float)| (x + dx, y + dy);
+        let commands = self
+            .commands
+            .iter()
+            .map(|command| match command {
+                PathCommand::MoveTo(p) => PathCommand::MoveTo(shift(*p)),
+                PathCommand::LineTo(p) => PathCommand::LineTo(shift(*p)),
+                PathCommand::CubicTo(c1, c2, to) => {
+                    PathCommand::CubicTo(shift(*c1), shift(*c2), shift(*to))
+                }
+                PathCommand::ArcTo {
+                    radius,
+                    x_rotation,
+                    large_arc,
+                    sweep,
+                    to,
+                } => PathCommand::ArcTo {
+                    radius: *radius,
+                    x_rotation: *x_rotation,
+                    large_arc: *large_arc,
+                    sweep: *sweep,
+                    to: shift(*to),
+                },
+                PathCommand::Close => PathCommand::Close,
+            })
+            .collect();
+        PathData { commands }
+    }
+
+    /// SVG path data ("d" attribute)
+    pub fn to_svg_path(&self) -> String {
+        let parts: Vec<String> = self
+            .commands
+            .iter()
+            .map(|command| match command {
+                PathCommand::MoveTo((x, y)) => format!("M {} {}", x, y),
+                PathCommand::LineTo((x, y)) => format!("L {} {}", x, y),
+                PathCommand::CubicTo(c1, c2, to) => {
+                    format!("C {} {}, {} {}, {} {}", c1.0, c1.1, c2.0, c2.1, to.0, to.1)
+                }
+                PathCommand::ArcTo {
+                    radius,
+                    x_rotation,
+                    large_arc,
+                    sweep,
+                    to,
+                } => format!(
+                    "A {} {} {} {} {} {} {}",
+                    radius.0, radius.1, x_rotation, *large_arc as u8, *sweep as u8, to.0, to.1
+                ),
+                PathCommand::Close => "Z".to_string(),
+            })
+            .collect();
+        parts.join(" ")
+    }
+
+    /// Approximate the path with polylines, one per subpath
+    pub fn flatten(&self) -> Vec<Vec<(Float, Float)>> {
+        let mut subpaths: Vec<Vec<(Float, Float)>> = Vec::new();
+        let mut current: Vec<(Float, Float)> = Vec::new();
+        let mut pen = (0.0, 0.0);
+
+        for command in &self.commands {
+            match command {
+                PathCommand::MoveTo(p) => {
+                    if current.len() > 1 {
+                        subpaths.push(std::mem::take(&mut current));
+                    }
+                    current.clear();
+                    current.push(*p);
+                    pen = *p;
+                }
+                PathCommand::LineTo(p) => {
+                    if current.is_empty() {
+                        current.push(pen);
+                    }
+                    current.push(*p);
+                    pen = *p;
+                }
+                PathCommand::CubicTo(c1, c2, to) => {
+                    if current.is_empty() {
+                        current.push(pen);
+                    }
+                    let steps = CURVE_STEPS;
+                    for i in 1..=steps {
+                        let t = i as Float / steps as Float;
+                        current.push(cubic_point(pen, *c1, *c2, *to, t));
+                    }
+                    pen = *to;
+                }
+                PathCommand::ArcTo {
+                    radius,
+                    x_rotation,
+                    large_arc,
+                    sweep,
+                    to,
+                } => {
+                    if current.is_empty() {
+                        current.push(pen);
+                    }
+                    current.extend(flatten_arc(
+                        pen,
+                        *radius,
+                        *x_rotation,
+                        *large_arc,
+                        *sweep,
+                        *to,
+                    ));
+                    pen = *to;
+                }
+                PathCommand::Close => {
+                    if let Some(first) = current.first().copied() {
+                        current.push(first);
+                        pen = first;
+                    }
+                }
+            }
+        }
+
+        if current.len() > 1 {
+            subpaths.push(current);
+        }
+        subpaths
+    }
+
+    /// Same path walked in the opposite direction. Only the first subpath is kept.
+    pub fn reversed(&self) -> PathData {
+        let mut pen = (0.0, 0.0);
+        // (start point, command that ends at the next point)
+        let mut segments: Vec<((Float, Float), &PathCommand)> = Vec::new();
+        let mut started = false;
+
+        for command in &self.commands {
+            match command {
+                PathCommand::MoveTo(p) => {
+                    if started {
+                        break;
+                    }
+                    started = true;
+                    pen = *p;
+                }
+                PathCommand::Close => break,
+                PathCommand::LineTo(to) | PathCommand::CubicTo(_, _, to) => {
+                    segments.push((pen, command));
+                    pen = *to;
+                }
+                PathCommand::ArcTo { to, .. } => {
+                    segments.push((pen, command));
+                    pen = *to;
+                }
+            }
+        }
+
+        let mut reversed = PathData::new().move_to(pen.0, pen.1);
+        for (start, command) in segments.into_iter().rev() {
+            let reversed_command = match command {
+                PathCommand::LineTo(_) => PathCommand::LineTo(start),
+                PathCommand::CubicTo(c1, c2, _) => PathCommand::CubicTo(*c2, *c1, start),
+                PathCommand::ArcTo {
+                    radius,
+                    x_rotation,
+                    large_arc,
+                    sweep,
+                    ..
+                } => PathCommand::ArcTo {
+                    radius: *radius,
+                    x_rotation: *x_rotation,
+                    large_arc: *large_arc,
+                    sweep: !*sweep,
+                    to: start,
+                },
+                _ => continue,
+            };
+            reversed.commands.push(reversed_command);
+        }
+        reversed
+    }
+
+    /// Reads SVG path data (the `d` attribute): M, L, H, V, C, S, Q, T, A and Z,
+    /// absolute and relative, with repeated parameters continuing the previous
+    /// command. Quadratic segments are kept as the equivalent cubics
+    pub fn parse(data: &str) -> Result<PathData, String> {
+        let mut reader = PathDataReader {
+            data: data.as_bytes(),
+            position: 0,
+        };
+        let mut path = PathData::new();
+        let mut pen = (0.0, 0.0);
+        let mut subpath_start = (0.0, 0.0);
+        let mut command: Option<u8> = None;
+        let mut closed = false;
+        // Control points that a following S or T mirrors
+        let mut last_cubic: Option<(Float, Float)> = None;
+        let mut last_quad: Option<(Float, Float)> = None;
+        let mirror = |control: Option<(Float, Float)>, pen: (Float, Float)| {
+            control.map_or(pen, |(x, y)| (2.0 * pen.0 - x, 2.0 * pen.1 - y))
+        };
+
+        while reader.peek().is_some() {
+            let letter = match reader.command() {
+                Some(letter) => letter,
+                None => {
+                    command.ok_or_else(|| format!("expected a command at {}", reader.position))?
+                }
+            };
+            let is_move = letter.eq_ignore_ascii_case(&b'M');
+            if path.commands.is_empty() && !is_move {
+                return Err("path data must start with a move to".to_string());
+            }
+            // Drawing on after a close starts a new subpath where the last one began
+            if closed && !is_move {
+                path = path.move_to(pen.0, pen.1);
+            }
+            closed = false;
+            command = Some(letter);
+
+            let origin = if letter.is_ascii_lowercase() {
+                pen
+            } else {
+                (0.0, 0.0)
+            };
+            let (mut cubic, mut quad) = (None, None);
+            match letter.to_ascii_uppercase() {
+                b'M' => {
+                    let to = reader.point(origin)?;
+                    path = path.move_to(to.0, to.1);
+                    (pen, subpath_start) = (to, to);
+                    // Further pairs are line segments
+                    command = Some(if letter == b'm' { b'l' } else { b'L' });
+                }
+                b'L' => {
+                    pen = reader.point(origin)?;
+                    path = path.line_to(pen.0, pen.1);
+                }
+                b'H' => {
+                    pen.0 = origin.0 + reader.number()?;
+                    path = path.line_to(pen.0, pen.1);
+                }
+                b'V' => {
+                    pen.1 = origin.1 + reader.number()?;
+                    path = path.line_to(pen.0, pen.1);
+                }
+                b'C' | b'S' => {
+                    let c1 = if letter.eq_ignore_ascii_case(&b'C') {
+                        reader.point(origin)?
+                    } else {
+                        mirror(last_cubic, pen)
+                    };
+                    let c2 = reader.point(origin)?;
+                    let to = reader.point(origin)?;
+                    path = path.cubic_to(c1, c2, to);
+                    (pen, cubic) = (to, Some(c2));
+                }
+                b'Q' | b'T' => {
+                    let control = if letter.eq_ignore_ascii_case(&b'Q') {
+                        reader.point(origin)?
+                    } else {
+                        mirror(last_quad, pen)
+                    };
+                    let to = reader.point(origin)?;
+                    let toward = |from: (Float, Float)| {
+                        (
+                            from.0 + 2.0 / 3.0 * (control.0 - from.0),
+                            from.1 + 2.0 / 3.0 * (control.1 - from.1),
+                        )
+                    };
+                    path = path.cubic_to(toward(pen), toward(to), to);
+                    (pen, quad) = (to, Some(control));
+                }
+                b'A' => {
+                    let radius = (reader.number()?, reader.number()?);
+                    let x_rotation = reader.number()?;
+                    let large_arc = reader.flag()?;
+                    let sweep = reader.flag()?;
+                    let to = reader.point(origin)?;
+                    path.commands.push(PathCommand::ArcTo {
+                        radius,
+                        x_rotation,
+                        large_arc,
+                        sweep,
+                        to,
+                    });
+                    pen = to;
+                }
+                b'Z' => {
+                    path = path.close();
+                    pen = subpath_start;
+                    closed = true;
+                    command = None;
+                }
+                _ => return Err(format!("unknown path command '{}'", letter as char)),
+            }
+            (last_cubic, last_quad) = (cubic, quad);
+        }
+        Ok(path)
+    }
+
+    /// Exact bounds of the path, the extremes of curves and arcs are solved
+    /// for rather than sampled. None for an empty path
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        let mut extremes: Vec<(Float, Float)> = Vec::new();
+        let mut pen = (0.0, 0.0);
+        let mut subpath_start = (0.0, 0.0);
+
+        for command in &self.commands {
+            match command {
+                PathCommand::MoveTo(p) => {
+                    extremes.push(*p);
+                    (pen, subpath_start) = (*p, *p);
+                }
+                PathCommand::LineTo(p) => {
+                    extremes.push(*p);
+                    pen = *p;
+                }
+                PathCommand::CubicTo(c1, c2, to) => {
+                    extremes.push(*to);
+                    let turns = cubic_turns(pen.0, c1.0, c2.0, to.0)
+                        .into_iter()
+                        .chain(cubic_turns(pen.1, c1.1, c2.1, to.1));
+                    extremes.extend(turns.map(|t| cubic_point(pen, *c1, *c2, *to, t)));
+                    pen = *to;
+                }
+                PathCommand::ArcTo {
+                    radius,
+                    x_rotation,
+                    large_arc,
+                    sweep,
+                    to,
+                } => {
+                    extremes.push(*to);
+                    if let Some(arc) =
+                        center_arc(pen, *radius, *x_rotation, *large_arc, *sweep, *to)
+                    {
+                        // Angles where the ellipse runs vertically, then horizontally
+                        let (rx, ry) = arc.radius;
+                        let (sin_phi, cos_phi) = arc.phi.sin_cos();
+                        let along_x = (-ry * sin_phi).atan2(rx * cos_phi);
+                        let along_y = (ry * cos_phi).atan2(rx * sin_phi);
+                        for angle in [along_x, along_x + PI, along_y, along_y + PI] {
+                            if arc.covers(angle) {
+                                extremes.push(arc.point_at(angle));
+                            }
+                        }
+                    }
+                    pen = *to;
+                }
+                PathCommand::Close => pen = subpath_start,
+            }
+        }
+
+        let first = *extremes.first()?;
+        let (min_x, min_y, max_x, max_y) = extremes.iter().fold(
+            (first.0, first.1, first.0, first.1),
+            |(min_x, min_y, max_x, max_y), (x, y)| {
+                (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y))
+            },
+        );
+        Some(BoundingBox {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x,
+            height: max_y - min_y,
+        })
+    }
+}
+
+/// Cursor over SVG path data
+struct PathDataReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl PathDataReader<'_> {
+    /// Next byte after any whitespace and commas
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(c) = self.data.get(self.position) {
+            if !c.is_ascii_whitespace() && *c != b',' {
+                return Some(*c);
+            }
+            self.position += 1;
+        }
+        None
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        let letter = self.peek().filter(u8::is_ascii_alphabetic)?;
+        self.position += 1;
+        Some(letter)
+    }
+
+    fn digits(&mut self) -> bool {
+        let start = self.position;
+        while self.data.get(self.position).is_some_and(u8::is_ascii_digit) {
+            self.position += 1;
+        }
+        self.position > start
+    }
+
+    /// A number as SVG writes them: "-.5e2", or "1.5.5" for 1.5 and 0.5
+    fn number(&mut self) -> Result<Float, String> {
+        self.peek();
+        let start = self.position;
+        if matches!(self.data.get(self.position), Some(b'+' | b'-')) {
+            self.position += 1;
+        }
+        let mut has_digits = self.digits();
+        if self.data.get(self.position) == Some(&b'.') {
+            self.position += 1;
+            has_digits |= self.digits();
+        }
+        if !has_digits {
+            return Err(format!("expected a number at {}", start));
+        }
+        if matches!(self.data.get(self.position), Some(b'e' | b'E')) {
+            let mantissa_end = self.position;
+            self.position += 1;
+            if matches!(self.data.get(self.position), Some(b'+' | b'-')) {
+                self.position += 1;
+            }
+            if !self.digits() {
+                self.position = mantissa_end;
+            }
+        }
+        std::str::from_utf8(&self.data[start..self.position])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(|| format!("invalid number at {}", start))
+    }
+
+    /// Arc flags are a single digit and may be written without separators
+    fn flag(&mut self) -> Result<bool, String> {
+        let flag = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(format!("expected an arc flag at {}", self.position)),
+        };
+        self.position += 1;
+        Ok(flag)
+    }
+
+    /// A coordinate pair, offset by `origin` for relative commands
+    fn point(&mut self, origin: (Float, Float)) -> Result<(Float, Float), String> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok((origin.0 + x, origin.1 + y))
+    }
+}
+
+const CURVE_STEPS: usize = 32;
+const ARC_TOLERANCE: Float = 0.1;
+
+fn cubic_point(
+    p0: (Float, Float),
+    p1: (Float, Float),
+    p2: (Float, Float),
+    p3: (Float, Float),
+    t: Float,
+) -> (Float, Float) {
+    let mt = 1.0 - t;
+    let a = mt * mt * mt;
+    let b = 3.0 * mt * mt * t;
+    let c = 3.0 * mt * t * t;
+    let d = t * t * t;
+    (
+        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+    )
+}
+
+/// Parameters in (0, 1) where one coordinate of a cubic Bézier turns back,
+/// the roots of its derivative
+fn cubic_turns(p0: Float, p1: Float, p2: Float, p3: Float) -> Vec<Float> {
+    let a = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+    let b = 2.0 * (p0 - 2.0 * p1 + p2);
+    let c = p1 - p0;
+    let roots = if a.abs() < 1e-6 {
+        if b.abs() < 1e-6 {
+            vec![]
+        } else {
+            vec![-c / b]
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            vec![]
+        } else {
+            let root = discriminant.sqrt();
+            vec![(-b + root) / (2.0 * a), (-b - root) / (2.0 * a)]
+        }
+    };
+    roots.into_iter().filter(|t| *t > 0.0 && *t < 1.0).collect()
+}
+
+/// Elliptical arc in center parameterization, angles in radians
+struct CenterArc {
+    center: (Float, Float),
+    radius: (Float, Float),
+    phi: Float,
+    start_angle: Float,
+    delta: Float,
+}
+
+impl CenterArc {
+    fn point_at(&self, angle: Float) -> (Float, Float) {
+        let (sin_a, cos_a) = angle.sin_cos();
+        let (sin_phi, cos_phi) = self.phi.sin_cos();
+        let (rx, ry) = self.radius;
+        (
+            self.center.0 + rx * cos_a * cos_phi - ry * sin_a * sin_phi,
+            self.center.1 + rx * cos_a * sin_phi + ry * sin_a * cos_phi,
+        )
+    }
+
+    /// True when the arc passes through `angle`
+    fn covers(&self, angle: Float) -> bool {
+        let past_start = (angle - self.start_angle) * self.delta.signum();
+        past_start.rem_euclid(2.0 * PI) <= self.delta.abs()
+    }
+}
+
+/// Endpoint to center parameterization (SVG spec, appendix B.2.4), None when
+/// the arc is drawn as a straight line
+fn center_arc(
+    from: (Float, Float),
+    radius: (Float, Float),
+    x_rotation: Float,
+    large_arc: bool,
+    sweep: bool,
+    to: (Float, Float),
+) -> Option<CenterArc> {
+    let (mut rx, mut ry) = (radius.0.abs(), radius.1.abs());
+    if rx == 0.0 || ry == 0.0 || from == to {
+        return None;
+    }
+
+    let phi = x_rotation * PI / 180.0;
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let dx2 = (from.0 - to.0) / 2.0;
+    let dy2 = (from.1 - to.1) / 2.0;
+    let x1p = cos_phi * dx2 + sin_phi * dy2;
+    let y1p = -sin_phi * dx2 + cos_phi * dy2;
+
+    // Scale radii up if they can't reach the end point
+    let lambda = (x1p * x1p) / (rx * rx) + (y1p * y1p) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = rx * rx * ry * ry - rx * rx * y1p * y1p - ry * ry * x1p * x1p;
+    let denominator = rx * rx * y1p * y1p + ry * ry * x1p * x1p;
+    let mut coef = (numerator / denominator).max(0.0).sqrt();
+    if large_arc == sweep {
+        coef = -coef;
+    }
+    let cxp = coef * rx * y1p / ry;
+    let cyp = -coef * ry * x1p / rx;
+    let cx = cos_phi * cxp - sin_phi * cyp + (from.0 + to.0) / 2.0;
+    let cy = sin_phi * cxp + cos_phi * cyp + (from.1 + to.1) / 2.0;
+
+    let start_angle = ((y1p - cyp) / ry).atan2((x1p - cxp) / rx);
+    let end_angle = ((-y1p - cyp) / ry).atan2((-x1p - cxp) / rx);
+    let mut delta = end_angle - start_angle;
+    if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    } else if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    }
+
+    Some(CenterArc {
+        center: (cx, cy),
+        radius: (rx, ry),
+        phi,
+        start_angle,
+        delta,
+    })
+}
+
+/// Points of an SVG arc after `from`
+fn flatten_arc(
+    from: (Float, Float),
+    radius: (Float, Float),
+    x_rotation: Float,
+    large_arc: bool,
+    sweep: bool,
+    to: (Float, Float),
+) -> Vec<(Float, Float)> {
+    let Some(arc) = center_arc(from, radius, x_rotation, large_arc, sweep, to) else {
+        return vec![to];
+    };
+    let CenterArc {
+        radius: (rx, ry),
+        start_angle,
+        delta,
+        ..
+    } = arc;
+
+    // Steps of PI / 16, subdivided on large radii so the chords stay within
+    // ARC_TOLERANCE of the arc
+    let max_step = 2.0 * (1.0 - ARC_TOLERANCE / rx.max(ry)).max(0.0).acos();
+    let subdivisions = ((PI / 16.0 / max_step).ceil() as usize).max(1);
+    let steps = ((delta.abs() / (PI / 16.0)).ceil() as usize).max(1) * subdivisions;
+    let mut points = Vec::with_capacity(steps);
+    for i in 1..=steps {
+        points.push(arc.point_at(start_angle + delta * i as Float / steps as Float));
+    }
+    // land exactly on the end point
+    if let Some(last) = points.last_mut() {
+        *last = to;
+    }
+    points
+}
+
+/// Catmull-Rom spline through `points` as cubic segments, appended to `path`
+/// which is expected to be at the first point. The ends of open splines are
+/// treated as repeated, closed ones wrap around and end back at the first point
+fn spline_through(path: PathData, points: &[(Float, Float)], closed: bool) -> PathData {
+    let count = points.len() as isize;
+    let at = |i: isize| {
+        if closed {
+            points[i.rem_euclid(count) as usize]
+        } else {
+            points[i.clamp(0, count - 1) as usize]
+        }
+    };
+    let segments = if closed { count } else { count - 1 };
+    (0..segments).fold(path, |path, i| {
+        let before = at(i - 1);
+        let (from, to) = (at(i), at(i + 1));
+        let after = at(i + 2);
+        path.cubic_to(
+            (
+                from.0 + (to.0 - before.0) / 6.0,
+                from.1 + (to.1 - before.1) / 6.0,
+            ),
+            (
+                to.0 - (after.0 - from.0) / 6.0,
+                to.1 - (after.1 - from.1) / 6.0,
+            ),
+            to,
+        )
+    })
+}
+
+/// Smooth curve through `points`, closed splines join the last point back to the first
+pub fn spline_path(points: &[(Float, Float)], closed: bool) -> PathData {
+    let Some(&(x, y)) = points.first() else {
+        return PathData::new();
+    };
+    let path = spline_through(PathData::new().move_to(x, y), points, closed);
+    if closed {
+        path.close()
+    } else {
+        path
+    }
+}
+
+/// Path drawn for a connector between two points, following its type and routing strategy.
+/// Connectors with `waypoints` pass through them instead, curved ones along a smooth spline
+pub fn connector_path(
+    options: &ConnectorOptions,
+    start: (Float, Float),
+    end: (Float, Float),
+    waypoints: &[(Float, Float)],
+) -> PathData {
+    let (x1, y1) = start;
+    let (x2, y2) = end;
+    let dx = x2 - x1;
+    let dy = y2 - y1;
+    let path = PathData::new().move_to(x1, y1);
+
+    match options.connector_type {
+        ConnectorType::Straight | ConnectorType::Orthogonal if !waypoints.is_empty() => waypoints
+            .iter()
+            .fold(path, |path, (x, y)| path.line_to(*x, *y))
+            .line_to(x2, y2),
+        ConnectorType::Straight => path.line_to(x2, y2),
+        ConnectorType::Curved if !waypoints.is_empty() => {
+            let mut points = vec![start];
+            points.extend_from_slice(waypoints);
+            points.push(end);
+            spline_through(path, &points, false)
+        }
+        ConnectorType::Curved => {
+            let curve_offset = options.curve_offset.unwrap_or(50.0);
+            if dx.abs() > dy.abs() {
+                // Horizontal curve: control points offset vertically
+                path.cubic_to(
+                    (x1 + dx * 0.5, y1 + curve_offset),
+                    (x2 - dx * 0.5, y2 + curve_offset),
+                    (x2, y2),
+                )
+            } else {
+                // Vertical curve: control points offset horizontally
+                path.cubic_to(
+                    (x1 + curve_offset, y1 + dy * 0.5),
+                    (x2 + curve_offset, y2 - dy * 0.5),
+                    (x2, y2),
+                )
+            }
+        }
+        ConnectorType::Orthogonal => {
+            let hvh = |path: PathData| {
+                let mid_x = (x1 + x2) / 2.0;
+                path.line_to(mid_x, y1).line_to(mid_x, y2).line_to(x2, y2)
+            };
+            let vhv = |path: PathData| {
+                let mid_y = (y1 + y2) / 2.0;
+                path.line_to(x1, mid_y).line_to(x2, mid_y).line_to(x2, y2)
+            };
+
+            match options.routing_strategy {
+                OrthogonalRoutingStrategy::HV => path.line_to(x2, y1).line_to(x2, y2),
+                OrthogonalRoutingStrategy::VH => path.line_to(x1, y2).line_to(x2, y2),
+                OrthogonalRoutingStrategy::HVH => hvh(path),
+                OrthogonalRoutingStrategy::VHV => {
+                    if dx.abs() < 5.0 || dy.abs() < 5.0 {
+                        path.line_to(x2, y2)
+                    } else {
+                        vhv(path)
+                    }
+                }
+                OrthogonalRoutingStrategy::Auto | OrthogonalRoutingStrategy::Avoid => {
+                    if dy.abs() > dx.abs() {
+                        if dx.abs() < 5.0 {
+                            path.line_to(x2, y2)
+                        } else {
+                            vhv(path)
+                        }
+                    } else if dy.abs() < 5.0 {
+                        path.line_to(x2, y2)
+                    } else {
+                        hvh(path)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Where an end marker sits: the tip and the direction it points in (radians)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArrowAnchor {
+    pub tip: (Float, Float),
+    pub angle: Float,
+}
+
+impl ArrowAnchor {
+    /// Moves a point from the marker frame (tip at the origin, pointing along
+    /// the positive x axis) to where the marker is drawn
+    pub fn place(&self, point: (Float, Float)) -> (Float, Float) {
+        let (sin, cos) = self.angle.sin_cos();
+        (
+            self.tip.0 + point.0 * cos - point.1 * sin,
+            self.tip.1 + point.0 * sin + point.1 * cos,
+        )
+    }
+}
+
+const ARROW_HALF_ANGLE: Float = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerPaint {
+    Fill,
+    Stroke, // With the line's stroke color and width
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerShape {
+    pub path: PathData,
+    pub paint: MarkerPaint,
+}
+
+/// What an end marker draws, in the marker frame: the tip at the origin,
+/// pointing along the positive x axis
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MarkerGeometry {
+    pub shapes: Vec<MarkerShape>,
+    /// Entity drawn as the marker and its size, with the middle of its right side at the tip
+    pub entity: Option<(EntityID, (Float, Float))>,
+    /// How far back from the tip the line stops, so it doesn't show through the marker
+    pub inset: Float,
+}
+
+impl MarkerGeometry {
+    /// Geometry of a marker `size` long, `entity_size` is only used by entity markers
+    pub fn new(marker: &Marker, size: Float, entity_size: (Float, Float)) -> MarkerGeometry {
+        let s = size;
+        let fill = |path| MarkerShape {
+            path,
+            paint: MarkerPaint::Fill,
+        };
+        let stroke = |path| MarkerShape {
+            path,
+            paint: MarkerPaint::Stroke,
+        };
+        let barb = (
+            -s * ARROW_HALF_ANGLE.cos(),
+            s * ARROW_HALF_ANGLE.sin(),
+        );
+        let triangle = PathData::new()
+            .move_to(0.0, 0.0)
+            .line_to(barb.0, -barb.1)
+            .line_to(barb.0, barb.1)
+            .close();
+        let diamond = PathData::new()
+            .move_to(0.0, 0.0)
+            .line_to(-s, -s / 2.0)
+            .line_to(-2.0 * s, 0.0)
+            .line_to(-s, s / 2.0)
+            .close();
+        let circle = |x: Float| ellipse_path((x, 0.0), (s / 2.0, s / 2.0));
+        let bar = |x: Float| PathData::new().move_to(x, -s / 2.0).line_to(x, s / 2.0);
+        let crow_foot = PathData::new()
+            .move_to(0.0, -s / 2.0)
+            .line_to(-s, 0.0)
+            .line_to(0.0, s / 2.0);
+        // Carries the line on through the circle of the zero cardinalities
+        let stem = PathData::new().move_to(-s, 0.0).line_to(0.0, 0.0);
+
+        let (shapes, inset) = match marker {
+            Marker::None => (vec![], 0.0),
+            Marker::Entity(_) => (vec![], entity_size.0),
+            Marker::Arrow => (vec![fill(triangle)], -barb.0),
+            Marker::HollowArrow => (vec![stroke(triangle)], -barb.0),
+            Marker::OpenArrow => {
+                let path = PathData::new()
+                    .move_to(barb.0, -barb.1)
+                    .line_to(0.0, 0.0)
+                    .line_to(barb.0, barb.1);
+                (vec![stroke(path)], 0.0)
+            }
+            Marker::Diamond => (vec![fill(diamond)], 2.0 * s),
+            Marker::HollowDiamond => (vec![stroke(diamond)], 2.0 * s),
+            Marker::Circle => (vec![fill(circle(-s / 2.0))], s),
+            Marker::HollowCircle => (vec![stroke(circle(-s / 2.0))], s),
+            Marker::Bar => (vec![stroke(bar(-s / 2.0))], 0.0),
+            Marker::CrowFoot => (vec![stroke(crow_foot)], 0.0),
+            Marker::OneOnly => (vec![stroke(bar(-s / 2.0)), stroke(bar(-s))], 0.0),
+            Marker::OneOrMany => (vec![stroke(crow_foot), stroke(bar(-1.5 * s))], 0.0),
+            Marker::ZeroOrOne => {
+                let shapes = vec![stroke(bar(-s / 2.0)), stroke(stem), stroke(circle(-1.5 * s))];
+                (shapes, 2.0 * s)
+            }
+            Marker::ZeroOrMany => {
+                let shapes = vec![stroke(crow_foot), stroke(stem), stroke(circle(-1.5 * s))];
+                (shapes, 2.0 * s)
+            }
+        };
+
+        let entity = match marker {
+            Marker::Entity(id) => Some((id.clone(), entity_size)),
+            _ => None,
+        };
+        MarkerGeometry {
+            shapes,
+            entity,
+            inset,
+        }
+    }
+
+    /// How far back from the tip the marker reaches
+    pub fn length(&self) -> Float {
+        self.shapes
+            .iter()
+            .flat_map(|shape| shape.path.flatten())
+            .flatten()
+            .map(|(x, _)| -x)
+            .fold(self.inset, Float::max)
+    }
+
+    /// Points covering the marker once placed at an anchor
+    pub fn extent(&self, anchor: &ArrowAnchor) -> Vec<(Float, Float)> {
+        let mut points: Vec<(Float, Float)> = self
+            .shapes
+            .iter()
+            .flat_map(|shape| shape.path.flatten())
+            .flatten()
+            .map(|point| anchor.place(point))
+            .collect();
+        if let Some((_, (width, height))) = &self.entity {
+            let corners = [
+                (-width, -height / 2.0),
+                (0.0, -height / 2.0),
+                (0.0, height / 2.0),
+                (-width, height / 2.0),
+            ];
+            points.extend(corners.iter().map(|corner| anchor.place(*corner)));
+        }
+        points
+    }
+}
+
+/// Everything needed to paint a connector, computed once by layout in absolute coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectorGeometry {
+    /// Stops short of the ends by the marker insets
+    pub path: PathData,
+    pub start_arrow: ArrowAnchor, // Points back at the source
+    pub end_arrow: ArrowAnchor,
+    pub start_marker: MarkerGeometry,
+    pub end_marker: MarkerGeometry,
+    /// Point on the path where the label goes, following the label alignment,
+    /// and the direction of the path there
+    pub label_anchor: (Float, Float),
+    pub label_angle: Float,
+    /// Covers the path and the markers
+    pub bounds: BoundingBox,
+}
+
+/// Anchor at the first point, pointing the way the first segment with a length comes from
+fn arrow_anchor<'a>(mut points: impl Iterator<Item = &'a (Float, Float)>) -> ArrowAnchor {
+    let tip = points.next().copied().unwrap_or((0.0, 0.0));
+    let angle = points
+        .find(|p| (p.0 - tip.0).abs() > 1e-3 || (p.1 - tip.1).abs() > 1e-3)
+        .map_or(0.0, |p| (tip.1 - p.1).atan2(tip.0 - p.0));
+    ArrowAnchor { tip, angle }
+}
+
+/// Drops the first `distance` units of a polyline
+fn trim_polyline_start(points: &[(Float, Float)], distance: Float) -> Vec<(Float, Float)> {
+    let mut remaining = distance;
+    for (i, w) in points.windows(2).enumerate() {
+        let length = ((w[1].0 - w[0].0).powi(2) + (w[1].1 - w[0].1).powi(2)).sqrt();
+        if remaining < length {
+            let t = remaining / length;
+            let first = (w[0].0 + (w[1].0 - w[0].0) * t, w[0].1 + (w[1].1 - w[0].1) * t);
+            return std::iter::once(first)
+                .chain(points[i + 1..].iter().copied())
+                .collect();
+        }
+        remaining -= length;
+    }
+    points.last().into_iter().copied().collect()
+}
+
+/// Path, marker anchors, label anchor and bounds of a connector
+pub fn connector_geometry(
+    options: &ConnectorOptions,
+    start: (Float, Float),
+    end: (Float, Float),
+    waypoints: &[(Float, Float)],
+    start_marker: MarkerGeometry,
+    end_marker: MarkerGeometry,
+) -> ConnectorGeometry {
+    let mut path = connector_path(options, start, end, waypoints);
+    let points = path
+        .flatten()
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| vec![start, end]);
+
+    // The line stops where the markers begin, unless they would swallow it
+    let (start_inset, end_inset) = (start_marker.inset, end_marker.inset);
+    let trimmed = if (start_inset > 0.0 || end_inset > 0.0)
+        && polyline_length(&points) > start_inset + end_inset
+    {
+        let mut trimmed = trim_polyline_start(&points, start_inset);
+        trimmed.reverse();
+        let mut trimmed = trim_polyline_start(&trimmed, end_inset);
+        trimmed.reverse();
+        path = trimmed
+            .iter()
+            .skip(1)
+            .fold(PathData::new().move_to(trimmed[0].0, trimmed[0].1), |path, p| {
+                path.line_to(p.0, p.1)
+            });
+        trimmed
+    } else {
+        points.clone()
+    };
+
+    // Markers point from where the drawn line stops to the ends
+    let start_arrow = arrow_anchor(points.iter().take(1).chain(trimmed.iter()));
+    let end_arrow = arrow_anchor(points.iter().rev().take(1).chain(trimmed.iter().rev()));
+
+    let fraction = match options.label_alignment {
+        LabelAlignment::Start => 0.4,
+        LabelAlignment::Center => 0.5,
+        LabelAlignment::End => 0.85,
+    };
+    let (label_anchor, label_angle) =
+        point_along_polyline(&points, polyline_length(&points) * fraction);
+
+    let mut extent: Vec<(Float, Float)> = points.clone();
+    extent.extend(start_marker.extent(&start_arrow));
+    extent.extend(end_marker.extent(&end_arrow));
+    let (min_x, min_y, max_x, max_y) = extent.iter().fold(
+        (Float::MAX, Float::MAX, Float::MIN, Float::MIN),
+        |(min_x, min_y, max_x, max_y), (x, y)| {
+            (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y))
+        },
+    );
+
+    ConnectorGeometry {
+        path,
+        start_arrow,
+        end_arrow,
+        start_marker,
+        end_marker,
+        label_anchor,
+        label_angle,
+        bounds: BoundingBox {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x,
+            height: max_y - min_y,
+        },
+    }
+}
+
+/// Geometry of a straight arrow between two points, painted like a connector
+pub fn arrow_geometry(
+    start: (Float, Float),
+    end: (Float, Float),
+    start_marker: MarkerGeometry,
+    end_marker: MarkerGeometry,
+) -> ConnectorGeometry {
+    let options = ConnectorOptions::default();
+    connector_geometry(&options, start, end, &[], start_marker, end_marker)
+}
+
+/// Circular arc path, angles in degrees clockwise from the positive x axis (y down)
+pub fn arc_path(
+    center: (Float, Float),
+    radius: Float,
+    start_angle: Float,
+    end_angle: Float,
+) -> PathData {
+    let point_at = |degrees: Float| {
+        let rad = degrees * PI / 180.0;
+        (center.0 + radius * rad.cos(), center.1 + radius * rad.sin())
+    };
+
+    let mut sweep = (end_angle - start_angle) % 360.0;
+    if sweep <= 0.0 {
+        sweep += 360.0;
+    }
+
+    let start = point_at(start_angle);
+    let path = PathData::new().move_to(start.0, start.1);
+    if (sweep - 360.0).abs() < 0.1 {
+        // Full circle: two half arcs, a single arc can't start and end at the same point
+        let middle = point_at(start_angle + 180.0);
+        path.arc_to((radius, radius), false, true, middle).arc_to(
+            (radius, radius),
+            false,
+            true,
+            start,
+        )
+    } else {
+        path.arc_to((radius, radius), sweep > 180.0, true, point_at(end_angle))
+    }
+}
+
+/// Rectangle from the origin with corners rounded like SVG `rx`/`ry`, the
+/// radius is clamped to half the shorter side
+pub fn rounded_rect_path(width: Float, height: Float, radius: Float) -> PathData {
+    let r = radius.min(width / 2.0).min(height / 2.0).max(0.0);
+    if r == 0.0 {
+        return PathData::new()
+            .move_to(0.0, 0.0)
+            .line_to(width, 0.0)
+            .line_to(width, height)
+            .line_to(0.0, height)
+            .close();
+    }
+    PathData::new()
+        .move_to(r, 0.0)
+        .line_to(width - r, 0.0)
+        .arc_to((r, r), false, true, (width, r))
+        .line_to(width, height - r)
+        .arc_to((r, r), false, true, (width - r, height))
+        .line_to(r, height)
+        .arc_to((r, r), false, true, (0.0, height - r))
+        .line_to(0.0, r)
+        .arc_to((r, r), false, true, (r, 0.0))
+        .close()
+}
+
+/// Closed ellipse as two half arcs
+pub fn ellipse_path(center: (Float, Float), radius: (Float, Float)) -> PathData {
+    let left = (center.0 - radius.0, center.1);
+    let right = (center.0 + radius.0, center.1);
+    PathData::new()
+        .move_to(left.0, left.1)
+        .arc_to(radius, false, true, right)
+        .arc_to(radius, false, true, left)
+        .close()
+}
+
+/// Shape an entity's boundary ports are clipped to, in its local coordinates
+/// with the origin at the top left corner of its box
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outline {
+    /// Corners rounded like `rounded_rect_path`
+    Rect {
+        width: Float,
+        height: Float,
+        radius: Float,
+    },
+    Ellipse {
+        width: Float,
+        height: Float,
+    },
+    /// Arc of the circle centered in its box, angles in degrees like `ShapeArc`.
+    /// Filled arcs are pie slices
+    Arc {
+        radius: Float,
+        start: Float,
+        sweep: Float,
+        filled: bool,
+    },
+}
+
+impl Outline {
+    fn center(&self) -> (Float, Float) {
+        match self {
+            Outline::Rect { width, height, .. } | Outline::Ellipse { width, height } => {
+                (width / 2.0, height / 2.0)
+            }
+            Outline::Arc { radius, .. } => (*radius, *radius),
+        }
+    }
+
+    /// Where the ray from the center towards `toward` leaves the outline, the
+    /// center itself when `toward` is the center
+    pub fn exit_point(&self, toward: (Float, Float)) -> (Float, Float) {
+        let (cx, cy) = self.center();
+        let (dx, dy) = (toward.0 - cx, toward.1 - cy);
+        if dx == 0.0 && dy == 0.0 {
+            return (cx, cy);
+        }
+
+        match *self {
+            Outline::Rect {
+                width,
+                height,
+                radius,
+            } => {
+                let (half_w, half_h) = (width / 2.0, height / 2.0);
+                let t = (half_w / dx.abs()).min(half_h / dy.abs());
+                let (x, y) = (dx * t, dy * t);
+
+                // Past the straight part of both sides the ray hits a corner arc
+                let r = radius.min(half_w).min(half_h).max(0.0);
+                if r == 0.0 || x.abs() <= half_w - r || y.abs() <= half_h - r {
+                    return (cx + x, cy + y);
+                }
+                let corner = (
+                    (half_w - r) * dx.signum(),
+                    (half_h - r) * dy.signum(),
+                );
+                // |d * t - corner| = r, the far root
+                let a = dx * dx + dy * dy;
+                let b = -2.0 * (dx * corner.0 + dy * corner.1);
+                let c = corner.0 * corner.0 + corner.1 * corner.1 - r * r;
+                let t = (-b + (b * b - 4.0 * a * c).max(0.0).sqrt()) / (2.0 * a);
+                (cx + dx * t, cy + dy * t)
+            }
+            Outline::Ellipse { width, height } => {
+                let (rx, ry) = (width / 2.0, height / 2.0);
+                if rx <= 0.0 || ry <= 0.0 {
+                    return (cx, cy);
+                }
+                let t = 1.0 / ((dx / rx).powi(2) + (dy / ry).powi(2)).sqrt();
+                (cx + dx * t, cy + dy * t)
+            }
+            Outline::Arc {
+                radius,
+                start,
+                sweep,
+                filled,
+            } => {
+                let point_at = |degrees: Float| {
+                    let angle = degrees * PI / 180.0;
+                    (cx + radius * angle.cos(), cy + radius * angle.sin())
+                };
+                let angle = dy.atan2(dx) * 180.0 / PI;
+                let past_start = (angle - start).rem_euclid(360.0);
+                if past_start <= sweep {
+                    point_at(angle)
+                } else if filled {
+                    // Outside the slice the ray leaves through the center
+                    (cx, cy)
+                } else if past_start - sweep < 360.0 - past_start {
+                    point_at(start + sweep)
+                } else {
+                    point_at(start)
+                }
+            }
+        }
+    }
+}
+
+/// Total length of a polyline
+pub fn polyline_length(points: &[(Float, Float)]) -> Float {
+    points
+        .windows(2)
+        .map(|w| ((w[1].0 - w[0].0).powi(2) + (w[1].1 - w[0].1).powi(2)).sqrt())
+        .sum()
+}
+
+/// Point at a distance along a polyline and the direction (radians) of the
+/// segment it falls on. Distances outside the polyline are clamped.
+pub fn point_along_polyline(points: &[(Float, Float)], distance: Float) -> ((Float, Float), Float) {
+    if points.is_empty() {
+        return ((0.0, 0.0), 0.0);
+    }
+
+    let mut remaining = distance.max(0.0);
+    let mut last_angle = 0.0;
+    for w in points.windows(2) {
+        let (dx, dy) = (w[1].0 - w[0].0, w[1].1 - w[0].1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            continue;
+        }
+        last_angle = dy.atan2(dx);
+        if remaining <= length {
+            let t = remaining / length;
+            return ((w[0].0 + dx * t, w[0].1 + dy * t), last_angle);
+        }
+        remaining -= length;
+    }
+
+    (*points.last().unwrap(), last_angle)
+}
+
+/// True if text laid along the polyline would be upside down (path runs right to left)
+pub fn runs_right_to_left(points: &[(Float, Float)]) -> bool {
+    match (points.first(), points.last()) {
+        (Some(first), Some(last)) => last.0 < first.0,
+        _ => false,
+    }
+}
+
+/// Splits a polyline into the drawn pieces of a dash pattern. The pattern runs
+/// on across vertices and odd length patterns are repeated, as with SVG's
+/// `stroke-dasharray`. Zero length dashes come back as two equal points so
+/// round or square caps can still draw them as dots.
+pub fn dash_polyline(
+    points: &[(Float, Float)],
+    dashes: &[Float],
+    offset: Float,
+) -> Vec<Vec<(Float, Float)>> {
+    let pattern: Vec<Float> = if dashes.len() % 2 == 1 {
+        dashes.iter().chain(dashes.iter()).copied().collect()
+    } else {
+        dashes.to_vec()
+    };
+    let total: Float = pattern.iter().sum();
+    if points.len() < 2 || pattern.is_empty() || total <= 0.0 || pattern.iter().any(|d| *d < 0.0) {
+        return vec![points.to_vec()];
+    }
+
+    // Find where in the pattern the path starts
+    let mut index = 0;
+    let mut left = offset.rem_euclid(total);
+    while left > 0.0 && left >= pattern[index] {
+        left -= pattern[index];
+        index = (index + 1) % pattern.len();
+    }
+    let mut left_in_dash = pattern[index] - left;
+
+    let mut pieces = Vec::new();
+    let mut current = if index % 2 == 0 {
+        vec![points[0]]
+    } else {
+        Vec::new()
+    };
+    for w in points.windows(2) {
+        let (dx, dy) = (w[1].0 - w[0].0, w[1].1 - w[0].1);
+        let length = (dx * dx + dy * dy).sqrt();
+        let mut travelled = 0.0;
+        while length - travelled > left_in_dash {
+            travelled += left_in_dash;
+            let t = travelled / length;
+            let point = (w[0].0 + dx * t, w[0].1 + dy * t);
+            if index % 2 == 0 {
+                current.push(point);
+                if current.len() == 1 {
+                    current.push(point);
+                }
+                pieces.push(std::mem::take(&mut current));
+            } else {
+                current.push(point);
+            }
+            index = (index + 1) % pattern.len();
+            left_in_dash = pattern[index];
+        }
+        left_in_dash -= length - travelled;
+        if index % 2 == 0 {
+            current.push(w[1]);
+        }
+    }
+    if index % 2 == 0 && current.len() > 1 {
+        pieces.push(current);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (Float, Float), b: (Float, Float)) -> bool {
+        (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01
+    }
+
+    #[test]
+    fn test_connector_paths() {
+        let mut options = ConnectorOptions::default();
+        let straight = connector_path(&options, (0.0, 0.0), (100.0, 0.0), &[]);
+        assert_eq!(straight.to_svg_path(), "M 0 0 L 100 0");
+
+        options.connector_type = ConnectorType::Orthogonal;
+        options.routing_strategy = OrthogonalRoutingStrategy::HVH;
+        let orthogonal = connector_path(&options, (0.0, 0.0), (100.0, 50.0), &[]);
+        assert_eq!(
+            orthogonal.flatten(),
+            vec![vec![(0.0, 0.0), (50.0, 0.0), (50.0, 50.0), (100.0, 50.0)]]
+        );
+        assert_eq!(polyline_length(&orthogonal.flatten()[0]), 150.0);
+
+        let reversed = orthogonal.reversed().flatten();
+        assert_eq!(
+            reversed,
+            vec![vec![(100.0, 50.0), (50.0, 50.0), (50.0, 0.0), (0.0, 0.0)]]
+        );
+        assert!(runs_right_to_left(&reversed[0]));
+
+        let ((x, y), angle) = point_along_polyline(&orthogonal.flatten()[0], 75.0);
+        assert!(close((x, y), (50.0, 25.0)));
+        assert!((angle - PI / 2.0).abs() < 0.001);
+
+        // Routed connectors pass through their waypoints
+        let routed = connector_path(
+            &options,
+            (0.0, 0.0),
+            (100.0, 50.0),
+            &[(20.0, 0.0), (20.0, 50.0)],
+        );
+        assert_eq!(
+            routed.flatten(),
+            vec![vec![(0.0, 0.0), (20.0, 0.0), (20.0, 50.0), (100.0, 50.0)]]
+        );
+
+        // Straight connectors bend at their waypoints, curved ones pass through them smoothly
+        options.connector_type = ConnectorType::Straight;
+        let forked = connector_path(&options, (0.0, 0.0), (100.0, 50.0), &[(50.0, 0.0)]);
+        assert_eq!(forked.to_svg_path(), "M 0 0 L 50 0 L 100 50");
+        options.connector_type = ConnectorType::Curved;
+        let curved = connector_path(&options, (0.0, 0.0), (120.0, 60.0), &[(60.0, 0.0)]);
+        assert_eq!(
+            curved.to_svg_path(),
+            "M 0 0 C 10 0, 40 -10, 60 0 C 80 10, 110 50, 120 60"
+        );
+    }
+
+    #[test]
+    fn test_outline_exit_points() {
+        let rect = Outline::Rect {
+            width: 100.0,
+            height: 50.0,
+            radius: 0.0,
+        };
+        assert!(close(rect.exit_point((200.0, 25.0)), (100.0, 25.0)));
+        assert!(close(rect.exit_point((100.0, 50.0)), (100.0, 50.0)));
+        assert!(close(rect.exit_point((50.0, 25.0)), (50.0, 25.0)));
+
+        // Towards the corner the ray meets the rounded part instead
+        let rounded = Outline::Rect {
+            width: 100.0,
+            height: 50.0,
+            radius: 10.0,
+        };
+        assert!(close(rounded.exit_point((100.0, 50.0)), (96.0, 48.0)));
+        assert!(close(rounded.exit_point((50.0, -10.0)), (50.0, 0.0)));
+
+        let ellipse = Outline::Ellipse {
+            width: 100.0,
+            height: 50.0,
+        };
+        assert!(close(ellipse.exit_point((50.0, 100.0)), (50.0, 50.0)));
+        assert!(close(ellipse.exit_point((-20.0, 25.0)), (0.0, 25.0)));
+
+        // A quarter from the right to the bottom
+        let arc = |filled| Outline::Arc {
+            radius: 50.0,
+            start: 0.0,
+            sweep: 90.0,
+            filled,
+        };
+        assert!(close(arc(true).exit_point((150.0, 50.0)), (100.0, 50.0)));
+        assert!(close(arc(true).exit_point((0.0, 50.0)), (50.0, 50.0)));
+        assert!(close(arc(false).exit_point((0.0, 50.0)), (50.0, 100.0)));
+    }
+
+    #[test]
+    fn test_connector_geometry() {
+        let options = ConnectorOptions {
+            connector_type: ConnectorType::Orthogonal,
+            marker_end: Marker::Arrow,
+            arrow_size: 10.0,
+            ..Default::default()
+        };
+        let geometry = connector_geometry(
+            &options,
+            (0.0, 0.0),
+            (100.0, 50.0),
+            &[(20.0, 0.0), (20.0, 50.0)],
+            MarkerGeometry::default(),
+            MarkerGeometry::new(&options.marker_end, 10.0, (0.0, 0.0)),
+        );
+
+        // The end arrow follows the last segment, the start arrow points back at the source
+        assert_eq!(geometry.end_arrow.tip, (100.0, 50.0));
+        assert!(geometry.end_arrow.angle.abs() < 0.001);
+        assert!((geometry.start_arrow.angle - PI).abs() < 0.001);
+
+        // Halfway along the 150 units of path
+        assert!(close(geometry.label_anchor, (25.0, 50.0)));
+        assert!(geometry.label_angle.abs() < 0.001);
+
+        // Exact bounds, the arrowhead reaches below the path
+        let bounds = geometry.bounds;
+        assert_eq!((bounds.x, bounds.y, bounds.width), (0.0, 0.0, 100.0));
+        let below = 10.0 * (0.5 as Float).sin();
+        assert!((bounds.height - (50.0 + below)).abs() < 0.01);
+
+        // Curves are bounded by the curve itself, not the control points
+        let curved = ConnectorOptions {
+            connector_type: ConnectorType::Curved,
+            curve_offset: Some(40.0),
+            ..Default::default()
+        };
+        let bounds = connector_geometry(
+            &curved,
+            (0.0, 0.0),
+            (100.0, 0.0),
+            &[],
+            MarkerGeometry::default(),
+            MarkerGeometry::default(),
+        )
+        .bounds;
+        assert!((bounds.height - 30.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_connector_markers() {
+        let options = ConnectorOptions::default();
+        let diamond = MarkerGeometry::new(&Marker::Diamond, 10.0, (0.0, 0.0));
+        let entity = MarkerGeometry::new(&Marker::Entity("tag".to_string()), 10.0, (20.0, 16.0));
+        assert_eq!((diamond.inset, entity.inset), (20.0, 20.0));
+        assert_eq!(MarkerGeometry::new(&Marker::CrowFoot, 10.0, (0.0, 0.0)).inset, 0.0);
+
+        // The line stops where the markers begin, which still point at the ends
+        let geometry = connector_geometry(
+            &options,
+            (0.0, 0.0),
+            (100.0, 0.0),
+            &[],
+            diamond.clone(),
+            entity.clone(),
+        );
+        assert_eq!(geometry.path.to_svg_path(), "M 20 0 L 80 0");
+        assert_eq!(geometry.end_arrow.tip, (100.0, 0.0));
+        assert!(geometry.end_arrow.angle.abs() < 0.001);
+        assert_eq!(geometry.start_arrow.tip, (0.0, 0.0));
+        assert!((geometry.start_arrow.angle - PI).abs() < 0.001);
+
+        // The diamond is 10 high, the entity 16
+        let bounds = geometry.bounds;
+        assert_eq!((bounds.x, bounds.width), (0.0, 100.0));
+        assert!((bounds.y + 8.0).abs() < 0.01 && (bounds.height - 16.0).abs() < 0.01);
+        assert!(close(geometry.start_arrow.place((-20.0, 0.0)), (20.0, 0.0)));
+
+        // Markers longer than the line leave it whole
+        let short = connector_geometry(&options, (0.0, 0.0), (30.0, 0.0), &[], diamond, entity);
+        assert_eq!(short.path.to_svg_path(), "M 0 0 L 30 0");
+    }
+
+    #[test]
+    fn test_arc_path_flattening() {
+        // Top half of a circle, clockwise on screen from the left to the right
+        let arc = arc_path((50.0, 50.0), 50.0, 180.0, 360.0);
+        let points = &arc.flatten()[0];
+        assert!(close(points[0], (0.0, 50.0)));
+        assert!(close(*points.last().unwrap(), (100.0, 50.0)));
+        assert!(points.iter().any(|p| close(*p, (50.0, 0.0))));
+        assert!((polyline_length(points) - PI * 50.0).abs() < 0.5);
+
+        let reversed = arc.reversed().flatten();
+        assert!(close(reversed[0][0], (100.0, 50.0)));
+        assert!(reversed[0].iter().all(|p| p.1 <= 50.01));
+
+        // Full circles are split in two half arcs
+        let circle = arc_path((0.0, 0.0), 10.0, 0.0, 360.0);
+        assert_eq!(circle.commands.len(), 3);
+        assert!((polyline_length(&circle.flatten()[0]) - 2.0 * PI * 10.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_dash_polyline() {
+        let line = [(0.0, 0.0), (10.0, 0.0)];
+        assert_eq!(
+            dash_polyline(&line, &[3.0, 2.0], 0.0),
+            vec![vec![(0.0, 0.0), (3.0, 0.0)], vec![(5.0, 0.0), (8.0, 0.0)],]
+        );
+
+        // Offset shifts the pattern back along the path
+        assert_eq!(
+            dash_polyline(&line, &[3.0, 2.0], 4.0),
+            vec![vec![(1.0, 0.0), (4.0, 0.0)], vec![(6.0, 0.0), (9.0, 0.0)],]
+        );
+
+        // Dashes continue around corners
+        let corner = [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)];
+        let pieces = dash_polyline(&corner, &[6.0, 1.0], 0.0);
+        assert_eq!(pieces[0], vec![(0.0, 0.0), (4.0, 0.0), (4.0, 2.0)]);
+        assert_eq!(pieces[1], vec![(4.0, 3.0), (4.0, 4.0)]);
+
+        // Odd patterns repeat, zero length dashes become dots
+        let dots = dash_polyline(&[(0.0, 0.0), (12.0, 0.0)], &[0.0, 5.0], 0.0);
+        assert_eq!(dots.len(), 3);
+        assert!(dots.iter().all(|dot| dot.len() == 2 && dot[0] == dot[1]));
+        assert_eq!(dash_polyline(&line, &[4.0], 0.0).len(), 2);
+
+        // Empty or invalid patterns are solid
+        assert_eq!(dash_polyline(&line, &[], 0.0), vec![line.to_vec()]);
+        assert_eq!(dash_polyline(&line, &[-1.0, 2.0], 0.0), vec![line.to_vec()]);
+    }
+
+    #[test]
+    fn test_path_data_parsing() {
+        use PathCommand::*;
+
+        // Relative commands, H/V, and a subpath starting after a close
+        let path = PathData::parse("M10 20 l 30 0 h-10 v 5 z m 5,5 L 1e1 .5").unwrap();
+        assert_eq!(
+            path.commands,
+            vec![
+                MoveTo((10.0, 20.0)),
+                LineTo((40.0, 20.0)),
+                LineTo((30.0, 20.0)),
+                LineTo((30.0, 25.0)),
+                Close,
+                MoveTo((15.0, 25.0)),
+                LineTo((10.0, 0.5)),
+            ]
+        );
+
+        // Extra pairs after a move are lines
+        let path = PathData::parse("m 1 1 2 2").unwrap();
+        assert_eq!(path.commands, vec![MoveTo((1.0, 1.0)), LineTo((3.0, 3.0))]);
+
+        // Drawing on after a close starts again from the subpath start
+        let path = PathData::parse("M 0 0 h 10 v 10 z l 5 5").unwrap();
+        assert_eq!(
+            path.commands[3..],
+            [Close, MoveTo((0.0, 0.0)), LineTo((5.0, 5.0))]
+        );
+
+        // Arc flags written without separators
+        let path = PathData::parse("M0 0a10 10 0 0110 10").unwrap();
+        assert_eq!(
+            path.commands[1],
+            ArcTo {
+                radius: (10.0, 10.0),
+                x_rotation: 0.0,
+                large_arc: false,
+                sweep: true,
+                to: (10.0, 10.0),
+            }
+        );
+
+        // S and T mirror the previous control point, quadratics become cubics
+        let path = PathData::parse("M0 0 C 0 10 20 10 20 0 S 40 -10 40 0").unwrap();
+        assert_eq!(
+            path.commands[2],
+            CubicTo((20.0, -10.0), (40.0, -10.0), (40.0, 0.0))
+        );
+        let path = PathData::parse("M0 0 Q 30 30 60 0 T 120 0").unwrap();
+        let cubics: Vec<_> = path.commands[1..]
+            .iter()
+            .map(|command| match command {
+                CubicTo(c1, c2, to) => [*c1, *c2, *to],
+                other => panic!("expected a cubic, got {:?}", other),
+            })
+            .collect();
+        let expected = [
+            [(20.0, 20.0), (40.0, 20.0), (60.0, 0.0)],
+            [(80.0, -20.0), (100.0, -20.0), (120.0, 0.0)],
+        ];
+        for (cubic, expected) in cubics.iter().zip(expected) {
+            assert!(cubic.iter().zip(expected).all(|(a, b)| close(*a, b)));
+        }
+
+        assert!(PathData::parse("L 10 10").is_err());
+        assert!(PathData::parse("M 0 0 X 1").is_err());
+        assert!(PathData::parse("M 0").is_err());
+        assert!(PathData::parse("M 0 0 A 1 1 0 2 0 3 3").is_err());
+        assert!(PathData::parse("M 0 0 z 5").is_err());
+    }
+
+    #[test]
+    fn test_path_bounds() {
+        let bounds = |data: &str| PathData::parse(data).unwrap().bounds().unwrap();
+        let sampled = |data: &str| {
+            let points = PathData::parse(data).unwrap().flatten().concat();
+            let (min, max) = points.iter().fold(
+                ((Float::MAX, Float::MAX), (Float::MIN, Float::MIN)),
+                |(min, max), p| {
+                    (
+                        (min.0.min(p.0), min.1.min(p.1)),
+                        (max.0.max(p.0), max.1.max(p.1)),
+                    )
+                },
+            );
+            (min, max)
+        };
+
+        // Curves bulge past their end points but not to their control points
+        let curve = bounds("M 0 0 C 0 -40 100 -40 100 0");
+        assert_eq!((curve.x, curve.width, curve.width), (0.0, 100.0, 100.0));
+        assert!((curve.y + 30.0).abs() < 0.01 && (curve.height - 30.0).abs() < 0.01);
+
+        // Clockwise half circle over the chord
+        let arc = bounds("M 0 0 A 50 50 0 0 1 100 0");
+        assert!(close((arc.x, arc.y), (0.0, -50.0)));
+        assert!(close((arc.width, arc.height), (100.0, 50.0)));
+
+        // Rotated elliptical arcs agree with the flattened outline
+        for data in [
+            "M 0 0 A 40 20 30 1 1 10 30",
+            "M 0 0 a 40 20 -60 0 0 50 10 z",
+        ] {
+            let exact = bounds(data);
+            let (min, max) = sampled(data);
+            assert!((exact.x - min.0).abs() < 0.1 && (exact.y - min.1).abs() < 0.1);
+            assert!((exact.x + exact.width - max.0).abs() < 0.1);
+            assert!((exact.y + exact.height - max.1).abs() < 0.1);
+        }
+
+        assert_eq!(PathData::new().bounds(), None);
+    }
+
+    #[test]
+    fn test_spline_path() {
+        let points = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)];
+        let open = spline_path(&points, false);
+        assert_eq!(open.commands.len(), 3);
+        match open.commands[2] {
+            PathCommand::CubicTo(c1, c2, to) => {
+                // Tangent at the middle point runs from the first to the last
+                assert!(close(c1, (10.0 + 10.0 / 6.0, 10.0 / 6.0)));
+                assert!(close(c2, (10.0, 10.0 - 10.0 / 6.0)));
+                assert_eq!(to, (10.0, 10.0));
+            }
+            ref other => panic!("expected a cubic, got {:?}", other),
+        }
+
+        // Closed splines come back to the first point smoothly
+        let closed = spline_path(&points, true);
+        assert_eq!(closed.commands.len(), 5);
+        assert_eq!(closed.commands[4], PathCommand::Close);
+        match closed.commands[3] {
+            PathCommand::CubicTo(c1, c2, to) => {
+                assert_eq!(to, (0.0, 0.0));
+                assert!(close(c1, (10.0 - 10.0 / 6.0, 10.0)));
+                assert!(close(c2, (0.0, 10.0 / 6.0)));
+            }
+            ref other => panic!("expected a cubic, got {:?}", other),
+        }
+        assert!(spline_path(&[], false).commands.is_empty());
+    }
+}
@@ -10,11 +10,14 @@ use crate::{
     ShapeLine, ShapeText, Table, VerticalStack,
 };
 use crate::{
-    ConnectorType, ConstraintLayoutContainer, ConstraintSystem, HorizontalAlignment,
-    LabelAlignment, LinePointReference, Point, ShapeArc, ShapeConnector, ShapeRect, ShapeSpacer,
-    SizeBehavior, SpacerDirection, TextLine, VerticalAlignment,
+    connector_geometry, point_along_polyline, polyline_length, route_orthogonal,
+    ConnectorGeometry, ConnectorType, ConstraintLayoutContainer, ConstraintSystem,
+    HorizontalAlignment, LabelRole, Layer, LayerRoot, LinePointReference, MarkerGeometry,
+    OrthogonalRoutingStrategy, Point, Port, PortSide, ShapeArc, ShapeConnector, ShapeRect,
+    ShapePath, ShapeSpacer, SizeBehavior, SpacerDirection, TextLine, VerticalAlignment,
 };
 
+use crate::text_shaping::{text_columns, wrap_text};
 use crate::transform::Transform;
 
 /* The box layout includes the padding and the dimensions
@@ -129,7 +132,7 @@ pub fn calculate_optimal_line_width(
 ) -> usize {
     // Binary search for optimal line_width
     let mut min_width = 10;
-    let mut max_width = text.len();
+    let mut max_width = text_columns(text);
     let mut best_width = min_width;
 
     while min_width <= max_width {
@@ -139,7 +142,7 @@ pub fn calculate_optimal_line_width(
         let mut test_options = text_options.clone();
         test_options.line_width = mid_width;
 
-        let wrapped_lines = textwrap::wrap(text, mid_width);
+        let wrapped_lines = wrap_text(text, mid_width);
         if wrapped_lines.is_empty() {
             break;
         }
@@ -147,7 +150,7 @@ pub fn calculate_optimal_line_width(
         // Measure the widest line
         let max_line_width = wrapped_lines
             .iter()
-            .map(|line| session.measure_text.unwrap()(line, &test_options).0)
+            .map(|line| session.measure(line, &test_options).0)
             .fold(0.0f32, |a, b| a.max(b));
 
         if max_line_width <= available_width {
@@ -183,7 +186,7 @@ fn auto_wrap_text_in_box(
         new_text_options.line_width = new_line_width;
 
         // Re-create text lines with new wrapping
-        let text_lines = textwrap::wrap(&text_shape.text, new_line_width);
+        let text_lines = wrap_text(&text_shape.text, new_line_width);
         let mut new_lines = Vec::new();
 
         // Update existing lines or create new ones
@@ -298,7 +301,7 @@ pub fn layout_text(session: &mut DiagramBuilder, shape_text: &ShapeText) {
 
     for (i, line) in shape_text.lines.iter().enumerate() {
         let textLine = session.get_text_line(line.clone());
-        let line_size = session.measure_text.unwrap()(&textLine.text, &shape_text.text_options);
+        let line_size = session.measure(&textLine.text, &shape_text.text_options);
 
         if line_size.0 > max_line_width {
             max_line_width = line_size.0;
@@ -332,52 +335,28 @@ pub fn layout_spacer(session: &mut DiagramBuilder, spacer: &ShapeSpacer) {
 /**
  * Updates the size of the line entity based on the start and end points
  * Points can be set directly or through other Point entitys
+ * Ends on entity ports are sized later, by layout_bound_shapes
  */
 pub fn layout_line(session: &mut DiagramBuilder, shape_line: &ShapeLine) {
-    let start = shape_line.start.clone();
-    let end = shape_line.end.clone();
-
-    let mut start_point = Point::new(0.0, 0.0);
-    let mut end_point = Point::new(0.0, 0.0);
-
-    match start {
-        LinePointReference::Value(x, y) => {
-            start_point.x = x;
-            start_point.y = y;
-        }
-        // Lines can refer a point (technically it can be any other entity with a position)
-        LinePointReference::PointID(id) => {
-            let pos = session.get_local_position(id);
-            start_point.x = pos.0;
-            start_point.y = pos.1;
-        }
-    }
-
-    match end {
-        LinePointReference::Value(x, y) => {
-            end_point.x = x;
-            end_point.y = y;
-        }
-        // Lines can refer a point (technically it can be any other entity with a position)
-        LinePointReference::PointID(id) => {
-            let pos = session.get_local_position(id);
-            end_point.x = pos.0;
-            end_point.y = pos.1;
-        }
+    let ends = [&shape_line.start, &shape_line.end];
+    if ends
+        .iter()
+        .any(|end| matches!(end, LinePointReference::EntityPort { .. }))
+    {
+        return;
     }
+    let start_point = session.resolve_line_point(&shape_line.entity, &shape_line.start);
+    let end_point = session.resolve_line_point(&shape_line.entity, &shape_line.end);
 
     session.set_size(
         shape_line.entity.clone(),
-        (end_point.x - start_point.x).abs(),
-        (end_point.y - start_point.y).abs(),
+        (end_point.0 - start_point.0).abs(),
+        (end_point.1 - start_point.1).abs(),
     );
 }
 pub fn layout_connector(session: &mut DiagramBuilder, connector: &ShapeConnector) {
     // Get port positions
-    let (start_x, start_y) =
-        session.get_port_position(&connector.source_id, &connector.options.source_port);
-    let (end_x, end_y) =
-        session.get_port_position(&connector.target_id, &connector.options.target_port);
+    let ((start_x, start_y), (end_x, end_y)) = session.get_connector_ends(connector);
 
     println!(
         "🔗 Connector {} connecting ({:.1}, {:.1}) to ({:.1}, {:.1})",
@@ -386,144 +365,91 @@ pub fn layout_connector(session: &mut DiagramBuilder, connector: &ShapeConnector
 
     session.set_position(connector.start_point_id.clone(), start_x, start_y);
     session.set_position(connector.end_point_id.clone(), end_x, end_y);
-
-    // Calculate bounding box based on connector type
-    let (min_x, max_x, min_y, max_y) = match connector.options.connector_type {
-        ConnectorType::Curved => {
-            // For curved connectors, we need to account for the curve's bounding box
-            // The curve extends beyond the straight line between points
-            let curve_offset = connector.options.curve_offset.unwrap_or(50.0);
-
-            // Determine curve direction based on relative positions
-            let dx = end_x - start_x;
-            let dy = end_y - start_y;
-
-            // For horizontal curves (primary direction is horizontal)
-            let extends_horizontally = dx.abs() > dy.abs();
-
-            let (min_x, max_x) = if extends_horizontally {
-                (start_x.min(end_x), start_x.max(end_x))
-            } else {
-                // Vertical curve - control points extend horizontally
-                let curve_extent = curve_offset.abs();
-                (
-                    start_x.min(end_x) - curve_extent,
-                    start_x.max(end_x) + curve_extent,
-                )
-            };
-
-            let (min_y, max_y) = if extends_horizontally {
-                // Horizontal curve - control points extend vertically
-                let curve_extent = curve_offset.abs();
-                (
-                    start_y.min(end_y) - curve_extent,
-                    start_y.max(end_y) + curve_extent,
-                )
-            } else {
-                (start_y.min(end_y), start_y.max(end_y))
-            };
-
-            (min_x, max_x, min_y, max_y)
-        }
-        ConnectorType::Orthogonal => {
-            // For orthogonal connectors, add some buffer for the segments
-            let buffer = 20.0; // Buffer for orthogonal segments
-            (
-                start_x.min(end_x) - buffer,
-                start_x.max(end_x) + buffer,
-                start_y.min(end_y) - buffer,
-                start_y.max(end_y) + buffer,
-            )
-        }
-        ConnectorType::Straight => {
-            // Straight line - just use the endpoints
-            (
-                start_x.min(end_x),
-                start_x.max(end_x),
-                start_y.min(end_y),
-                start_y.max(end_y),
-            )
-        }
-    };
-
-    let width = max_x - min_x;
-    let height = max_y - min_y;
+    // The absolute positions pass ran before the connectors were laid out,
+    // connector coordinates are absolute already
+    session
+        .absolute_positions
+        .insert(connector.start_point_id.clone(), (start_x, start_y));
+    session
+        .absolute_positions
+        .insert(connector.end_point_id.clone(), (end_x, end_y));
+
+    // The path, markers and label anchor renderers paint as is
+    let options = &connector.options;
+    let geometry = connector_geometry(
+        options,
+        (start_x, start_y),
+        (end_x, end_y),
+        &connector.waypoints,
+        session.get_marker_geometry(&options.marker_start, options.arrow_size),
+        session.get_marker_geometry(&options.marker_end, options.arrow_size),
+    );
+    let bounds = geometry.bounds;
 
     println!(
         "   Connector bounds: pos=({:.1}, {:.1}), size=({:.1}, {:.1})",
-        min_x, min_y, width, height
+        bounds.x, bounds.y, bounds.width, bounds.height
     );
 
-    session.set_position(connector.entity.clone(), min_x, min_y);
-    session.set_size(connector.entity.clone(), width, height);
-
-    // Position label intelligently based on connector orientation
-    if let Some(label_text) = connector.label.clone() {
-        if !label_text.is_empty() {
-            // Get label dimensions
-            let label_size =
-                session.measure_text.unwrap()(label_text.as_str(), &TextOptions::default());
-
-            // Calculate connector vector and orientation
-            let dx = end_x - start_x;
-            let dy = end_y - start_y;
-            let length = (dx * dx + dy * dy).sqrt();
-
-            // Determine label offset based on connector orientation
-            let label_offset = 0.0; // Distance from connector line
-
-            // Get label alignment setting
-            let alignment = &connector.options.label_alignment;
-
-            let (label_x, label_y) = if length > 0.0 {
-                // Determine if connector is more horizontal or vertical
-                let is_horizontal = dx.abs() > dy.abs();
-
-                // Calculate position along the connector based on alignment
-                let (position_x, position_y) = match alignment {
-                    LabelAlignment::Start => {
-                        // Position near start (10% along the line)
-                        (start_x + dx * 0.4, start_y + dy * 0.4)
-       
-                    }
-                    LabelAlignment::Center => {
-                        // Position at midpoint (current behavior)
-                        let mid_x = (start_x + end_x) / 2.0;
-                        let mid_y = (start_y + end_y) / 2.0;
-                        (mid_x, mid_y)
-                    }
-                    LabelAlignment::End => {
-                        // Position near end (85% along the line)
-                        // (start_x + dx * 0.85, start_y + dy * 0.85)
-                        (start_x + dx * 0.85, start_y + dy * 0.85)
-                    }
-                };
+    session.set_position(connector.entity.clone(), bounds.x, bounds.y);
+    session.set_size(connector.entity.clone(), bounds.width, bounds.height);
+    session
+        .absolute_positions
+        .insert(connector.entity.clone(), (bounds.x, bounds.y));
 
-                if is_horizontal {
-                    // For horizontal connectors: place label above (negative y direction)
-                    let lx = position_x - label_size.0 / 2.0;
-                    let ly = position_y - label_offset - label_size.1;
-                    (lx, ly)
-                } else {
-                    // For vertical connectors: place label to the right (positive x direction)
-                    let lx = position_x + label_offset;
-                    let ly = position_y - label_size.1 / 2.0;
-                    (lx, ly)
-                }
-            } else {
-                // Fallback for zero-length connectors (shouldn't happen)
-                let mid_x = (start_x + end_x) / 2.0;
-                let mid_y = (start_y + end_y) / 2.0;
-                (
-                    mid_x - label_size.0 / 2.0,
-                    mid_y - label_offset - label_size.1,
-                )
-            };
+    // Place the label boxes along the path by their role
+    let points = geometry.path.flatten().into_iter().next().unwrap_or_default();
+    for (index, label) in connector.labels.iter().enumerate() {
+        if label.text.is_empty() {
+            continue;
+        }
+        let label_id = connector.label_entity(index);
+        let size = session.get_size(label_id.clone());
+        let (label_x, label_y) = label_position(label.role, &geometry, &points, size);
+        session.set_position(label_id, label_x, label_y);
+    }
 
-            session.set_position(format!("{}_label_elem", connector.entity), label_x, label_y);
+    session.set_connector_geometry(connector.entity.clone(), geometry);
+}
+/// Gap between a label box and the path it labels
+const LABEL_GAP: Float = 2.0;
+
+/// Top left corner of a label box of `size`. Middle labels sit at the label
+/// anchor, end labels just far enough along `points` (the drawn path) to clear
+/// their end and its marker. Boxes go above horizontal stretches of the path
+/// and to the right of vertical ones
+fn label_position(
+    role: LabelRole,
+    geometry: &ConnectorGeometry,
+    points: &[(Float, Float)],
+    size: (Float, Float),
+) -> (Float, Float) {
+    let length = polyline_length(points);
+    let end_distance = |angle: Float, marker: &MarkerGeometry| {
+        let extent = (size.0 * angle.cos()).abs() + (size.1 * angle.sin()).abs();
+        // The path already stops at the marker's inset
+        let marker = (marker.length() - marker.inset).max(0.0);
+        (marker + LABEL_GAP + extent / 2.0).min(length / 2.0)
+    };
+    let ((x, y), angle) = match role {
+        LabelRole::Middle => (geometry.label_anchor, geometry.label_angle),
+        LabelRole::Source => {
+            let (_, angle) = point_along_polyline(points, 0.0);
+            point_along_polyline(points, end_distance(angle, &geometry.start_marker))
+        }
+        LabelRole::Target => {
+            let (_, angle) = point_along_polyline(points, length);
+            point_along_polyline(points, length - end_distance(angle, &geometry.end_marker))
         }
+    };
+
+    if angle.cos().abs() >= angle.sin().abs() {
+        (x - size.0 / 2.0, y - size.1 - LABEL_GAP)
+    } else {
+        (x + LABEL_GAP, y - size.1 / 2.0)
     }
 }
+
 /**
  * Updates the size of the arrow entity based on the start and end points
  */
@@ -826,13 +752,28 @@ pub fn layout_polyline(session: &mut DiagramBuilder, polyline: &PolyLine) {
         return;
     }
 
+    // Points on entity ports keep their last position until layout_bound_shapes
+    let points: Vec<(Float, Float)> = polyline
+        .references
+        .iter()
+        .zip(&polyline.points)
+        .map(|(reference, point)| match reference {
+            LinePointReference::EntityPort { .. } => *point,
+            reference => session.resolve_line_point(&polyline.entity, reference),
+        })
+        .collect();
+    set_polyline_points(session, &polyline.entity, points);
+}
+
+/// Stores the resolved points of a polyline and sizes it to their bounds
+fn set_polyline_points(session: &mut DiagramBuilder, id: &EntityID, points: Vec<(Float, Float)>) {
     // Find the actual bounding box of all points
     let mut min_x = Float::INFINITY;
     let mut min_y = Float::INFINITY;
     let mut max_x = Float::NEG_INFINITY;
     let mut max_y = Float::NEG_INFINITY;
 
-    for point in polyline.points.iter() {
+    for point in points.iter() {
         min_x = min_x.min(point.0);
         min_y = min_y.min(point.1);
         max_x = max_x.max(point.0);
@@ -843,7 +784,10 @@ pub fn layout_polyline(session: &mut DiagramBuilder, polyline: &PolyLine) {
     let height = max_y - min_y;
 
     // Set the polyline size to its actual bounding box
-    session.set_size(polyline.entity.clone(), width, height);
+    session.set_size(id.clone(), width, height);
+    if let Some(polyline) = session.get_polyline_mut(id.clone()) {
+        polyline.points = points;
+    }
 }
 
 /**
@@ -882,6 +826,19 @@ pub fn layout_arc(session: &mut DiagramBuilder, shape_arc: &ShapeArc) {
     session.set_size(shape_arc.entity.clone(), diameter, diameter);
 }
 
+/// Sizes a path shape to the exact bounds of its curves and moves the path so
+/// they start at its origin
+pub fn layout_path(session: &mut DiagramBuilder, shape_path: &ShapePath) {
+    let Some(bounds) = shape_path.path.bounds() else {
+        session.set_size(shape_path.entity.clone(), 0.0, 0.0);
+        return;
+    };
+    if let Some(shape) = session.get_path_mut(shape_path.entity.clone()) {
+        shape.path = shape_path.path.translated(-bounds.x, -bounds.y);
+    }
+    session.set_size(shape_path.entity.clone(), bounds.width, bounds.height);
+}
+
 /// Determina si el size de un shape puede ser cambiado por un constraint
 /// Solo rectangulos y boxes pueden alterar su tamaño, el resto se considera fijo
 /// y el solver cassowary debe respetar esto
@@ -989,19 +946,23 @@ pub fn layout_constraint_container(
     // NOW layout lines that use PointID references
     // At this point, all points have their final positions from the constraint solver
     for child_id in &container.children {
-        let entity_type = builder.entityTypes.get(child_id);
-
-        if let Some(EntityType::LineShape) = entity_type {
-            let line = builder.get_line(child_id.clone()).clone();
-            if matches!(line.start, LinePointReference::PointID(_))
-                || matches!(line.end, LinePointReference::PointID(_))
-            {
-                println!(
-                    "✅ Now laying out line {} with final point positions",
-                    child_id
-                );
+        if !has_point_references(builder, child_id) {
+            continue;
+        }
+        println!(
+            "✅ Now laying out line {} with final point positions",
+            child_id
+        );
+        match builder.entityTypes.get(child_id) {
+            Some(EntityType::LineShape) => {
+                let line = builder.get_line(child_id.clone()).clone();
                 layout_line(builder, &line);
             }
+            Some(EntityType::PolyLine) => {
+                let polyline = builder.get_polyline(child_id.clone()).clone();
+                layout_polyline(builder, &polyline);
+            }
+            _ => {}
         }
     }
 
@@ -1022,6 +983,22 @@ pub fn layout_constraint_container(
     Ok(())
 }
 
+/// Lines and polylines with points on other entities
+fn has_point_references(session: &DiagramBuilder, id: &EntityID) -> bool {
+    match session.entityTypes.get(id) {
+        Some(EntityType::LineShape) => {
+            let line = session.get_line(id.clone());
+            line.start.is_bound() || line.end.is_bound()
+        }
+        Some(EntityType::PolyLine) => session
+            .get_polyline(id.clone())
+            .references
+            .iter()
+            .any(LinePointReference::is_bound),
+        _ => false,
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct BoundingBox {
     pub x: Float,
@@ -1030,6 +1007,16 @@ pub struct BoundingBox {
     pub height: Float,
 }
 
+impl BoundingBox {
+    /// True when the boxes share some area, touching edges don't count
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
 //Calculate the layout for a tree of elements
 pub fn layout_tree_node(session: &mut DiagramBuilder, root: &DiagramTreeNode) -> BoundingBox {
     //start with the bottom elements
@@ -1043,19 +1030,14 @@ pub fn layout_tree_node(session: &mut DiagramBuilder, root: &DiagramTreeNode) ->
 
         // Skip lines that use PointID if we're inside a ConstraintLayoutContainer
         // They will be laid out AFTER constraints are solved
-        if root.entity_type == EntityType::ConstraintLayoutContainer {
-            if child.entity_type == EntityType::LineShape {
-                let line = session.get_line(child.entity_id.clone());
-                if matches!(line.start, LinePointReference::PointID(_))
-                    || matches!(line.end, LinePointReference::PointID(_))
-                {
-                    println!(
-                        "⏭️  Skipping line {} during initial layout (uses PointID)",
-                        child.entity_id
-                    );
-                    continue; // Skip this line, it will be laid out after constraints
-                }
-            }
+        if root.entity_type == EntityType::ConstraintLayoutContainer
+            && has_point_references(session, &child.entity_id)
+        {
+            println!(
+                "⏭️  Skipping line {} during initial layout (uses PointID)",
+                child.entity_id
+            );
+            continue; // Skip this line, it will be laid out after constraints
         }
 
         layout_tree_node(session, child);
@@ -1163,6 +1145,11 @@ pub fn layout_tree_node(session: &mut DiagramBuilder, root: &DiagramTreeNode) ->
             layout_arc(session, &arc);
         }
 
+        EntityType::PathShape => {
+            let shape_path = session.get_path(root.entity_id.clone()).clone();
+            layout_path(session, &shape_path);
+        }
+
         EntityType::PointShape => {
             // Ignore points
         }
@@ -1171,6 +1158,15 @@ pub fn layout_tree_node(session: &mut DiagramBuilder, root: &DiagramTreeNode) ->
         _ => panic!("Unknown entity type: {:?}", root.entity_type),
     }
 
+    // Clip and mask shapes sit at their x/y inside the entity
+    if let Some(clip) = session.get_clip(root.entity_id.clone()).cloned() {
+        for shape in clip.shapes() {
+            layout_tree_node(session, shape);
+            let position = session.get_container_relative_position(&shape.entity_id);
+            session.set_position(shape.entity_id.clone(), position.x, position.y);
+        }
+    }
+
     session.get_effective_bounds(root.entity_id.clone())
 }
 
@@ -1195,6 +1191,9 @@ fn calculate_absolute_positions(
     session
         .absolute_positions
         .insert(node.entity_id.clone(), absolute_pos);
+    session
+        .absolute_transforms
+        .insert(node.entity_id.clone(), absolute_transform.clone());
 
     println!(
         "📍 Absolute position for {}: ({:.1}, {:.1})",
@@ -1205,6 +1204,12 @@ fn calculate_absolute_positions(
     for child in &node.children {
         calculate_absolute_positions(session, child, absolute_transform.clone());
     }
+
+    if let Some(clip) = session.get_clip(node.entity_id.clone()).cloned() {
+        for shape in clip.shapes() {
+            calculate_absolute_positions(session, shape, absolute_transform.clone());
+        }
+    }
 }
 /// Complete diagram layout in three passes:
 /// 1. Calculate sizes and local positions (layout_tree_node) - skips connectors
@@ -1217,37 +1222,814 @@ pub fn layout_diagram(session: &mut DiagramBuilder, root: &DiagramTreeNode) -> B
     // Connectors are skipped in this pass
     let bbox = layout_tree_node(session, root);
 
+    // Pattern tiles are laid out on their own, at the origin
+    let tiles: Vec<DiagramTreeNode> = session.pattern_tiles.values().cloned().collect();
+    for tile in &tiles {
+        layout_tree_node(session, tile);
+        session.set_position(tile.entity_id.clone(), 0.0, 0.0);
+    }
+
     println!("✅ Layout complete, now calculating absolute positions...");
 
     // Pass 2: Calculate and cache absolute positions
     session.absolute_positions.clear();
+    session.absolute_transforms.clear();
     calculate_absolute_positions(session, root, Transform::identity());
+    for tile in &tiles {
+        calculate_absolute_positions(session, tile, Transform::identity());
+    }
 
     println!(
         "✅ Absolute positions calculated for {} elements",
         session.absolute_positions.len()
     );
+    layout_bound_shapes(session, root);
 
     // Pass 3: Layout all connectors now that absolute positions are available
     println!("🔗 Laying out connectors...");
-    layout_connectors_recursive(session, root);
+    arrange_connectors(session, root);
+    let mut ancestors = HashMap::new();
+    collect_ancestors(root, &mut Vec::new(), &mut ancestors);
+    layout_connectors_recursive(session, root, root, &ancestors);
+    separate_labels(session, root);
     println!("✅ Connectors laid out");
 
+    // Pass 4: Find the entities painted in a layer pass
+    session.layer_roots.clear();
+    assign_layers(session, root, Layer::Content, &mut Vec::new(), &mut 0);
+
     bbox
 }
 
+/// Resolves the points of lines, polylines and arcs that sit on entity ports,
+/// now that every entity has its absolute position. Bound arcs move to fit
+/// their ends. Connectors aren't placed yet, they can end on these shapes
+fn layout_bound_shapes(session: &mut DiagramBuilder, node: &DiagramTreeNode) {
+    let on_port =
+        |reference: &LinePointReference| matches!(reference, LinePointReference::EntityPort { .. });
+    match node.entity_type {
+        EntityType::LineShape => {
+            let line = session.get_line(node.entity_id.clone()).clone();
+            if on_port(&line.start) || on_port(&line.end) {
+                let start = session.resolve_line_point(&line.entity, &line.start);
+                let end = session.resolve_line_point(&line.entity, &line.end);
+                let size = ((end.0 - start.0).abs(), (end.1 - start.1).abs());
+                session.set_size(line.entity.clone(), size.0, size.1);
+            }
+        }
+        EntityType::PolyLine => {
+            let polyline = session.get_polyline(node.entity_id.clone()).clone();
+            if polyline.references.iter().any(on_port) {
+                let points = polyline
+                    .references
+                    .iter()
+                    .map(|reference| session.resolve_line_point(&polyline.entity, reference))
+                    .collect();
+                set_polyline_points(session, &polyline.entity, points);
+            }
+        }
+        EntityType::ArcShape => {
+            let arc = session.get_arc(node.entity_id.clone()).clone();
+            place_bound_arc(session, &arc);
+        }
+        _ => {}
+    }
+    for child in &node.children {
+        layout_bound_shapes(session, child);
+    }
+}
+
+/// Moves an arc with endpoints so it runs clockwise from the first to the second
+fn place_bound_arc(session: &mut DiagramBuilder, arc: &ShapeArc) {
+    let Some((start, end)) = &arc.endpoints else {
+        return;
+    };
+    let from = session.resolve_line_point(&arc.entity, start);
+    let to = session.resolve_line_point(&arc.entity, end);
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let chord = (dx * dx + dy * dy).sqrt();
+    if chord < 0.01 {
+        println!("⚠️  The ends of arc {} are the same point", arc.entity);
+        return;
+    }
+
+    // Clockwise on screen puts the center on the right of the chord
+    let radius = arc.radius.max(chord / 2.0);
+    let rise = (radius * radius - chord * chord / 4.0).max(0.0).sqrt();
+    let center = (
+        (from.0 + to.0) / 2.0 - dy / chord * rise,
+        (from.1 + to.1) / 2.0 + dx / chord * rise,
+    );
+    let angle = |point: (Float, Float)| (point.1 - center.1).atan2(point.0 - center.0).to_degrees();
+
+    // Arcs are drawn centered in their box, the absolute transform moves with it
+    let shift = Transform::translation(center.0 - radius, center.1 - radius);
+    let local = session.get_transform(arc.entity.clone()).combine(&shift);
+    let absolute = session.get_absolute_transform(&arc.entity).combine(&shift);
+    session.set_transform(arc.entity.clone(), local);
+    session
+        .absolute_positions
+        .insert(arc.entity.clone(), (absolute.matrix[4], absolute.matrix[5]));
+    session
+        .absolute_transforms
+        .insert(arc.entity.clone(), absolute);
+    session.set_size(arc.entity.clone(), radius * 2.0, radius * 2.0);
+
+    if let Some(shape) = session.get_arc_mut(arc.entity.clone()) {
+        shape.radius = radius;
+        shape.start_angle = angle(from);
+        shape.end_angle = angle(to);
+    }
+}
+
+/// Records the nodes whose layer differs from their parent's, the root's
+/// parent being the content layer
+fn assign_layers(
+    session: &mut DiagramBuilder,
+    node: &DiagramTreeNode,
+    parent_layer: Layer,
+    ancestors: &mut Vec<EntityID>,
+    order: &mut usize,
+) {
+    let layer = session.node_layer(node).unwrap_or(parent_layer);
+    if layer != parent_layer {
+        let root = LayerRoot {
+            node: node.clone(),
+            layer,
+            z_index: session.node_layer_options(node).z_index,
+            order: *order,
+            ancestors: if node.connector().is_some() {
+                Vec::new()
+            } else {
+                ancestors.clone()
+            },
+        };
+        session.layer_roots.insert(node.entity_id.clone(), root);
+    }
+    *order += 1;
+
+    ancestors.push(node.entity_id.clone());
+    for child in &node.children {
+        assign_layers(session, child, layer, ancestors, order);
+    }
+    ancestors.pop();
+}
+
 /// Recursively finds and layouts all connectors in the tree
-fn layout_connectors_recursive(session: &mut DiagramBuilder, node: &DiagramTreeNode) {
+fn layout_connectors_recursive(
+    session: &mut DiagramBuilder,
+    root: &DiagramTreeNode,
+    node: &DiagramTreeNode,
+    ancestors: &HashMap<EntityID, Vec<EntityID>>,
+) {
     // If this node is a connector, layout it
     if node.entity_type == EntityType::ConnectorShape {
         println!("  📌 Laying out connector: {}", node.entity_id);
         let connector = session.get_connector(node.entity_id.clone()).clone();
+        route_connector(session, root, ancestors, &connector);
+        let connector = session.get_connector(node.entity_id.clone()).clone();
         layout_connector(session, &connector);
     }
 
     // Recursively process all children
     for child in &node.children {
-        layout_connectors_recursive(session, child);
+        layout_connectors_recursive(session, root, child, ancestors);
+    }
+
+    // Labels were placed with their connector, after the absolute positions pass
+    if node.entity_type != EntityType::ConnectorShape && node.connector().is_some() {
+        let Some(&(x, y)) = session.absolute_positions.get(&node.entity_id) else {
+            return;
+        };
+        for child in &node.children {
+            if child.entity_type != EntityType::ConnectorShape {
+                calculate_absolute_positions(session, child, Transform::translation(x, y));
+            }
+        }
+    }
+}
+
+/// Steps a label is moved by when it overlaps something, and how many are tried each way
+const LABEL_NUDGE: Float = 4.0;
+const LABEL_NUDGE_STEPS: usize = 8;
+
+/// Moves connector labels off the labels placed before them and off other
+/// entities, trying the nearest spots first, up and down before sideways.
+/// Labels with no free spot nearby stay where they are
+fn separate_labels(session: &mut DiagramBuilder, root: &DiagramTreeNode) {
+    let mut obstacles = Vec::new();
+    collect_label_obstacles(session, root, Transform::identity(), &mut obstacles);
+    let mut wrappers = Vec::new();
+    collect_label_wrappers(root, &mut wrappers);
+
+    let offsets: Vec<(Float, Float)> = std::iter::once((0.0, 0.0))
+        .chain((1..=LABEL_NUDGE_STEPS).flat_map(|step| {
+            let d = step as Float * LABEL_NUDGE;
+            [(0.0, -d), (0.0, d), (-d, 0.0), (d, 0.0)]
+        }))
+        .collect();
+
+    let mut placed: Vec<BoundingBox> = Vec::new();
+    for wrapper in wrappers {
+        let Some(&(x, y)) = session.absolute_positions.get(&wrapper.entity_id) else {
+            continue;
+        };
+        for label in &wrapper.children {
+            let (width, height) = session.get_size(label.entity_id.clone());
+            if label.entity_type == EntityType::ConnectorShape || width <= 0.0 || height <= 0.0 {
+                continue;
+            }
+            let Some(&(label_x, label_y)) = session.absolute_positions.get(&label.entity_id) else {
+                continue;
+            };
+            let at = |(dx, dy): (Float, Float)| BoundingBox {
+                x: label_x + dx,
+                y: label_y + dy,
+                width,
+                height,
+            };
+            let offset = offsets
+                .iter()
+                .copied()
+                .find(|offset| {
+                    let bounds = at(*offset);
+                    !placed.iter().chain(&obstacles).any(|other| other.intersects(&bounds))
+                })
+                .unwrap_or((0.0, 0.0));
+
+            if offset != (0.0, 0.0) {
+                let (local_x, local_y) = session.get_local_position(label.entity_id.clone());
+                session.set_position(
+                    label.entity_id.clone(),
+                    local_x + offset.0,
+                    local_y + offset.1,
+                );
+                calculate_absolute_positions(session, label, Transform::translation(x, y));
+            }
+            placed.push(at(offset));
+        }
+    }
+}
+
+/// Groups wrapping a connector and its labels, once each
+fn collect_label_wrappers(node: &DiagramTreeNode, wrappers: &mut Vec<DiagramTreeNode>) {
+    if node.entity_type != EntityType::ConnectorShape && node.connector().is_some() {
+        if !wrappers.iter().any(|w| w.entity_id == node.entity_id) {
+            wrappers.push(node.clone());
+        }
+        return;
+    }
+    for child in &node.children {
+        collect_label_wrappers(child, wrappers);
+    }
+}
+
+/// Absolute bounds of the entities labels keep off: the ones without children,
+/// connectors and their labels aside
+fn collect_label_obstacles(
+    session: &DiagramBuilder,
+    node: &DiagramTreeNode,
+    parent_transform: Transform,
+    obstacles: &mut Vec<BoundingBox>,
+) {
+    if node.connector().is_some() {
+        return;
+    }
+    let transform = parent_transform.combine(&session.get_transform(node.entity_id.clone()));
+    if node.children.is_empty() {
+        let (width, height) = session.get_size(node.entity_id.clone());
+        if width > 0.0 && height > 0.0 {
+            obstacles.push(transform.transform_rect(0.0, 0.0, width, height));
+        }
+    }
+    for child in &node.children {
+        collect_label_obstacles(session, child, transform.clone(), obstacles);
+    }
+}
+
+/// Moves the connector ends off their ports before routing:
+/// - ends sharing a side port spread along that side when their connector distributes ports
+/// - connectors joining the same two points are set `parallel_spacing` apart
+/// - connectors of a bundle sharing an end leave it through a common trunk
+fn arrange_connectors(session: &mut DiagramBuilder, root: &DiagramTreeNode) {
+    let mut ids = Vec::new();
+    collect_connectors(root, &mut ids);
+    for id in &ids {
+        if let Some(connector) = session.get_connector_mut(id.clone()) {
+            connector.start_offset = (0.0, 0.0);
+            connector.end_offset = (0.0, 0.0);
+            connector.waypoints.clear();
+        }
+    }
+
+    distribute_ports(session, &ids);
+    separate_parallel_connectors(session, &ids);
+    bundle_connectors(session, &ids);
+    place_waypoints(session, &ids);
+}
+
+/// Connectors pass through their waypoints, in place of a bundle trunk.
+/// Self-loops without waypoints run straight out of their loop side and back
+fn place_waypoints(session: &mut DiagramBuilder, ids: &[EntityID]) {
+    for id in ids {
+        let connector = session.get_connector(id.clone());
+        let waypoints: Vec<(Float, Float)> = if !connector.options.waypoints.is_empty() {
+            connector
+                .options
+                .waypoints
+                .iter()
+                .map(|waypoint| session.get_waypoint_position(waypoint))
+                .collect()
+        } else if connector.is_self_loop() {
+            let (start, end) = session.get_connector_ends(connector);
+            let (start_out, end_out) = session.get_loop_references(connector);
+            let (nx, ny) = connector.options.loop_side.direction();
+            let out = |point: (Float, Float), reference: (Float, Float)| {
+                let distance = (reference.0 - point.0) * nx + (reference.1 - point.1) * ny;
+                (point.0 + nx * distance, point.1 + ny * distance)
+            };
+            vec![out(start, start_out), out(end, end_out)]
+        } else {
+            continue;
+        };
+        session.set_connector_waypoints(id.clone(), waypoints);
+    }
+}
+
+/// Connector ids in tree order, once each even when the tree holds a connector twice
+fn collect_connectors(node: &DiagramTreeNode, ids: &mut Vec<EntityID>) {
+    if node.entity_type == EntityType::ConnectorShape && !ids.contains(&node.entity_id) {
+        ids.push(node.entity_id.clone());
+    }
+    for child in &node.children {
+        collect_connectors(child, ids);
+    }
+}
+
+/// The corners a side port sits between
+fn side_corners(port: &Port) -> Option<(Port, Port)> {
+    match port {
+        Port::Top => Some((Port::TopLeft, Port::TopRight)),
+        Port::Bottom => Some((Port::BottomLeft, Port::BottomRight)),
+        Port::Left => Some((Port::TopLeft, Port::BottomLeft)),
+        Port::Right => Some((Port::TopRight, Port::BottomRight)),
+        _ => None,
+    }
+}
+
+/// Direction a side port faces, ignoring rotation
+fn port_direction(port: &Port) -> Option<(Float, Float)> {
+    port.side().map(|side| side.direction())
+}
+
+/// One end of a connector and the port it faces, named and auto ports resolved
+struct ConnectorEnd {
+    connector: EntityID,
+    entity: EntityID,
+    port: Port,
+    is_start: bool,
+    other: EntityID,
+    other_port: Port,
+}
+
+fn connector_ends(session: &DiagramBuilder, connector: &ShapeConnector) -> [ConnectorEnd; 2] {
+    let (source, target) = (&connector.source_id, &connector.target_id);
+    let (source_port, target_port) = session.get_connector_ports(connector);
+    [
+        ConnectorEnd {
+            connector: connector.entity.clone(),
+            entity: source.clone(),
+            port: source_port.clone(),
+            is_start: true,
+            other: target.clone(),
+            other_port: target_port.clone(),
+        },
+        ConnectorEnd {
+            connector: connector.entity.clone(),
+            entity: target.clone(),
+            port: target_port,
+            is_start: false,
+            other: source.clone(),
+            other_port: source_port,
+        },
+    ]
+}
+
+fn set_end_offset(
+    session: &mut DiagramBuilder,
+    id: &EntityID,
+    is_start: bool,
+    offset: (Float, Float),
+) {
+    if let Some(connector) = session.get_connector_mut(id.clone()) {
+        if is_start {
+            connector.start_offset = offset;
+        } else {
+            connector.end_offset = offset;
+        }
+    }
+}
+
+/// Spreads the ends attached to the same side port evenly along the side,
+/// ordered by where the other end lies so the connectors don't cross
+fn distribute_ports(session: &mut DiagramBuilder, ids: &[EntityID]) {
+    let mut groups: Vec<Vec<ConnectorEnd>> = Vec::new();
+    for id in ids {
+        let connector = session.get_connector(id.clone());
+        if !connector.options.distribute_ports {
+            continue;
+        }
+        for end in connector_ends(session, connector) {
+            if side_corners(&end.port).is_none() {
+                continue;
+            }
+            let same_port = |ends: &&mut Vec<ConnectorEnd>| {
+                ends[0].entity == end.entity && ends[0].port == end.port
+            };
+            match groups.iter_mut().find(same_port) {
+                Some(ends) => ends.push(end),
+                None => groups.push(vec![end]),
+            }
+        }
+    }
+
+    for ends in groups {
+        if ends.len() < 2 {
+            continue;
+        }
+        let (entity, port) = (ends[0].entity.clone(), ends[0].port.clone());
+        let Some((first_corner, second_corner)) = side_corners(&port) else {
+            continue;
+        };
+        let first = session.get_port_position(&entity, &first_corner);
+        let second = session.get_port_position(&entity, &second_corner);
+        let middle = session.get_port_position(&entity, &port);
+        let side = (second.0 - first.0, second.1 - first.1);
+
+        let along = |end: ConnectorEnd| {
+            let p = session.get_port_position(&end.other, &end.other_port);
+            let along = (p.0 - first.0) * side.0 + (p.1 - first.1) * side.1;
+            (end.connector, end.is_start, along)
+        };
+        let mut ends: Vec<_> = ends.into_iter().map(along).collect();
+        ends.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+        let count = ends.len() as Float;
+        for (i, (id, is_start, _)) in ends.iter().enumerate() {
+            let t = (i as Float + 1.0) / (count + 1.0);
+            let offset = (
+                first.0 + side.0 * t - middle.0,
+                first.1 + side.1 * t - middle.1,
+            );
+            set_end_offset(session, id, *is_start, offset);
+        }
+    }
+}
+
+/// Moves connectors joining the same two points apart, perpendicular to the
+/// line between them, keeping the group centered on it
+fn separate_parallel_connectors(session: &mut DiagramBuilder, ids: &[EntityID]) {
+    const SAME_POINT: Float = 0.5;
+    let close = |a: (Float, Float), b: (Float, Float)| {
+        (a.0 - b.0).abs() < SAME_POINT && (a.1 - b.1).abs() < SAME_POINT
+    };
+
+    // Grouped with the ends of their first connector
+    let ends = |id: &EntityID| session.get_connector_ends(session.get_connector(id.clone()));
+    let mut groups: Vec<Vec<EntityID>> = Vec::new();
+    for id in ids {
+        if session.get_connector(id.clone()).options.parallel_spacing <= 0.0 {
+            continue;
+        }
+        let (start, end) = ends(id);
+        match groups.iter_mut().find(|group| {
+            let (s, e) = ends(&group[0]);
+            (close(s, start) && close(e, end)) || (close(s, end) && close(e, start))
+        }) {
+            Some(group) => group.push(id.clone()),
+            None => groups.push(vec![id.clone()]),
+        }
+    }
+
+    for group in groups {
+        let (start, end) = session.get_connector_ends(session.get_connector(group[0].clone()));
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if group.len() < 2 || length < SAME_POINT {
+            continue;
+        }
+        let normal = (-dy / length, dx / length);
+        let spacing = session.get_connector(group[0].clone()).options.parallel_spacing;
+        let center = (group.len() - 1) as Float / 2.0;
+        for (i, id) in group.iter().enumerate() {
+            let distance = (i as Float - center) * spacing;
+            if let Some(connector) = session.get_connector_mut(id.clone()) {
+                connector.start_offset.0 += normal.0 * distance;
+                connector.start_offset.1 += normal.1 * distance;
+                connector.end_offset.0 += normal.0 * distance;
+                connector.end_offset.1 += normal.1 * distance;
+            }
+        }
+    }
+}
+
+/// Joins the connectors of a bundle that share an entity port: they leave it
+/// together up to a fork point halfway to the nearest other end, then split.
+/// A connector is part of one trunk at most
+fn bundle_connectors(session: &mut DiagramBuilder, ids: &[EntityID]) {
+    const DEFAULT_TRUNK: Float = 20.0;
+
+    let mut groups: Vec<(String, Vec<ConnectorEnd>)> = Vec::new();
+    for id in ids {
+        let connector = session.get_connector(id.clone());
+        let Some(bundle) = connector.options.bundle.clone() else {
+            continue;
+        };
+        for end in connector_ends(session, connector) {
+            let same_port = |(name, ends): &&mut (String, Vec<ConnectorEnd>)| {
+                *name == bundle && ends[0].entity == end.entity && ends[0].port == end.port
+            };
+            match groups.iter_mut().find(same_port) {
+                Some((_, ends)) => ends.push(end),
+                None => groups.push((bundle.clone(), vec![end])),
+            }
+        }
+    }
+
+    let mut bundled: Vec<EntityID> = Vec::new();
+    for (_, ends) in groups {
+        let (entity, port) = (ends[0].entity.clone(), ends[0].port.clone());
+        let members: Vec<(EntityID, bool)> = ends
+            .into_iter()
+            .filter(|end| !bundled.contains(&end.connector))
+            .map(|end| (end.connector, end.is_start))
+            .collect();
+        if members.len() < 2 {
+            continue;
+        }
+        let others: Vec<(Float, Float)> = members
+            .iter()
+            .map(|(id, is_start)| {
+                let (start, end) = session.get_connector_ends(session.get_connector(id.clone()));
+                if *is_start {
+                    end
+                } else {
+                    start
+                }
+            })
+            .collect();
+
+        let count = others.len() as Float;
+        let (sum_x, sum_y) = others
+            .iter()
+            .fold((0.0, 0.0), |(x, y), other| (x + other.0, y + other.1));
+        let centroid = (sum_x / count, sum_y / count);
+        let shared = session.get_port_position_toward(&entity, &port, centroid);
+
+        // Out of the side the port is on, or towards the other ends
+        let direction = port_direction(&port).or_else(|| {
+            let (dx, dy) = (centroid.0 - shared.0, centroid.1 - shared.1);
+            let length = (dx * dx + dy * dy).sqrt();
+            (length > 0.0).then(|| (dx / length, dy / length))
+        });
+        let Some(direction) = direction else {
+            continue;
+        };
+
+        let nearest = others
+            .iter()
+            .map(|other| {
+                (other.0 - shared.0) * direction.0 + (other.1 - shared.1) * direction.1
+            })
+            .fold(Float::INFINITY, Float::min);
+        let trunk = if nearest > 0.0 {
+            nearest / 2.0
+        } else {
+            DEFAULT_TRUNK
+        };
+        let fork = (
+            shared.0 + direction.0 * trunk,
+            shared.1 + direction.1 * trunk,
+        );
+        let horizontal = direction.0.abs() >= direction.1.abs();
+
+        for ((id, is_start), other) in members.iter().zip(others) {
+            // Boundary ends differ per connector, all of them start the trunk at `shared`
+            set_end_offset(session, id, *is_start, (0.0, 0.0));
+            let (start, end) = session.get_connector_ends(session.get_connector(id.clone()));
+            let own = if *is_start { start } else { end };
+            let offset = (shared.0 - own.0, shared.1 - own.1);
+            set_end_offset(session, id, *is_start, offset);
+
+            let Some(connector) = session.get_connector_mut(id.clone()) else {
+                continue;
+            };
+            let mut waypoints = match connector.options.connector_type {
+                ConnectorType::Orthogonal if horizontal => {
+                    vec![(fork.0, shared.1), (fork.0, other.1)]
+                }
+                ConnectorType::Orthogonal => vec![(shared.0, fork.1), (other.0, fork.1)],
+                _ => vec![fork],
+            };
+            if !is_start {
+                waypoints.reverse();
+            }
+            connector.waypoints = waypoints;
+            bundled.push(id.clone());
+        }
+    }
+}
+
+/// Finds the waypoints of orthogonal connectors that avoid the other entities.
+/// The entities containing either end are not obstacles, the ends themselves are.
+/// Bundled connectors keep their trunk, connectors with waypoints are routed
+/// through them, see `route_through_waypoints`
+fn route_connector(
+    session: &mut DiagramBuilder,
+    root: &DiagramTreeNode,
+    ancestors: &HashMap<EntityID, Vec<EntityID>>,
+    connector: &ShapeConnector,
+) {
+    if !matches!(connector.options.connector_type, ConnectorType::Orthogonal) {
+        return;
+    }
+    let avoid = connector.options.routing_strategy == OrthogonalRoutingStrategy::Avoid;
+    if !connector.options.waypoints.is_empty() || connector.is_self_loop() {
+        let obstacles = (avoid && !connector.is_self_loop())
+            .then(|| connector_obstacles(session, root, ancestors, connector));
+        route_through_waypoints(session, connector, obstacles.as_deref());
+        return;
+    }
+    if !avoid || !connector.waypoints.is_empty() {
+        return;
+    }
+
+    let (start, end) = session.get_connector_ends(connector);
+    let (start_port, end_port) = session.get_connector_ports(connector);
+    let obstacles = connector_obstacles(session, root, ancestors, connector);
+
+    let waypoints = route_orthogonal(
+        start,
+        &start_port,
+        end,
+        &end_port,
+        &obstacles,
+        connector.options.clearance,
+    )
+    .unwrap_or_else(|| {
+        println!(
+            "⚠️  No route around the obstacles for connector {}",
+            connector.entity
+        );
+        Vec::new()
+    });
+    session.set_connector_waypoints(connector.entity.clone(), waypoints);
+}
+
+/// Joins the ends and waypoints of an orthogonal connector with horizontal and
+/// vertical segments. Each leg is routed around `obstacles` when there are some,
+/// otherwise it bends once: out of the side the start port is on, into the side
+/// of the end port, and between waypoints carrying on in the direction it came
+fn route_through_waypoints(
+    session: &mut DiagramBuilder,
+    connector: &ShapeConnector,
+    obstacles: Option<&[BoundingBox]>,
+) {
+    let (start, end) = session.get_connector_ends(connector);
+    let (start_port, end_port) = session.get_connector_ports(connector);
+    let vertical_side = |port: &Port| {
+        port.side()
+            .map(|side| matches!(side, PortSide::Top | PortSide::Bottom))
+    };
+
+    let mut points = vec![start];
+    points.extend(connector.waypoints.iter().copied());
+    points.push(end);
+    let legs = points.len() - 1;
+
+    let mut waypoints = Vec::new();
+    let mut horizontal = !vertical_side(&start_port).unwrap_or(false);
+    for (i, leg) in points.windows(2).enumerate() {
+        let (from, to) = (leg[0], leg[1]);
+        let from_port = if i == 0 {
+            start_port.clone()
+        } else {
+            Port::Center
+        };
+        let to_port = if i == legs - 1 {
+            end_port.clone()
+        } else {
+            Port::Center
+        };
+
+        let routed = obstacles.and_then(|obstacles| {
+            route_orthogonal(
+                from,
+                &from_port,
+                to,
+                &to_port,
+                obstacles,
+                connector.options.clearance,
+            )
+        });
+        let bends = routed.unwrap_or_else(|| {
+            let horizontal_first = match vertical_side(&to_port) {
+                Some(vertical) if i == legs - 1 && i > 0 => vertical,
+                _ => horizontal,
+            };
+            if (from.0 - to.0).abs() < 0.01 || (from.1 - to.1).abs() < 0.01 {
+                Vec::new()
+            } else if horizontal_first {
+                vec![(to.0, from.1)]
+            } else {
+                vec![(from.0, to.1)]
+            }
+        });
+
+        let last = bends.last().copied().unwrap_or(from);
+        if (last.1 - to.1).abs() < 0.01 && (last.0 - to.0).abs() >= 0.01 {
+            horizontal = true;
+        } else if (last.0 - to.0).abs() < 0.01 && (last.1 - to.1).abs() >= 0.01 {
+            horizontal = false;
+        }
+        waypoints.extend(bends);
+        if i < legs - 1 {
+            waypoints.push(to);
+        }
+    }
+    session.set_connector_waypoints(connector.entity.clone(), waypoints);
+}
+
+/// Bounds of the entities a routed connector goes around
+fn connector_obstacles(
+    session: &DiagramBuilder,
+    root: &DiagramTreeNode,
+    ancestors: &HashMap<EntityID, Vec<EntityID>>,
+    connector: &ShapeConnector,
+) -> Vec<BoundingBox> {
+    let containers: Vec<EntityID> = [&connector.source_id, &connector.target_id]
+        .into_iter()
+        .filter_map(|end_id| ancestors.get(end_id))
+        .flatten()
+        .cloned()
+        .collect();
+
+    let mut obstacles = Vec::new();
+    collect_obstacles(
+        session,
+        root,
+        Transform::identity(),
+        &containers,
+        &mut obstacles,
+    );
+    obstacles
+}
+
+/// Maps every node to the ids of the nodes above it, outermost first
+fn collect_ancestors(
+    node: &DiagramTreeNode,
+    path: &mut Vec<EntityID>,
+    ancestors: &mut HashMap<EntityID, Vec<EntityID>>,
+) {
+    ancestors
+        .entry(node.entity_id.clone())
+        .or_insert_with(|| path.clone());
+    path.push(node.entity_id.clone());
+    for child in &node.children {
+        collect_ancestors(child, path, ancestors);
+    }
+    path.pop();
+}
+
+/// Absolute bounds of the outermost entities that aren't one of the `containers`.
+/// Connectors and their labels are skipped
+fn collect_obstacles(
+    session: &DiagramBuilder,
+    node: &DiagramTreeNode,
+    parent_transform: Transform,
+    containers: &[EntityID],
+    obstacles: &mut Vec<BoundingBox>,
+) {
+    // Connector wrappers hold nothing but the connector and its labels
+    if node.entity_type == EntityType::ConnectorShape || node.connector().is_some() {
+        return;
+    }
+    if containers.contains(&node.entity_id) {
+        let transform = parent_transform.combine(&session.get_transform(node.entity_id.clone()));
+        for child in &node.children {
+            collect_obstacles(session, child, transform.clone(), containers, obstacles);
+        }
+        return;
+    }
+
+    let bounds = session.get_effective_bounds(node.entity_id.clone());
+    if bounds.width > 0.0 && bounds.height > 0.0 {
+        obstacles.push(parent_transform.transform_rect(
+            bounds.x,
+            bounds.y,
+            bounds.width,
+            bounds.height,
+        ));
     }
 }
 
@@ -6,6 +6,12 @@ pub use crate::layout::*;
 pub use crate::utils::*;
 pub use crate::renderer_base::*;
 pub use crate::theme::*;
+pub use crate::fonts::*;
+pub use crate::text_shaping::*;
+pub use crate::geometry::*;
+pub use crate::measure_cache::*;
+pub use crate::color::*;
+pub use crate::routing::*;
 
 pub mod diagram_builder;
 pub mod utils;
@@ -16,6 +22,12 @@ pub mod renderer_base;
 pub mod parser;
 pub mod transform;
 pub mod theme;
+pub mod fonts;
+pub mod text_shaping;
+pub mod geometry;
+pub mod measure_cache;
+pub mod color;
+pub mod routing;
 
 /// Generates a complete LLM prompt for converting natural language to JSONL operations
 /// 
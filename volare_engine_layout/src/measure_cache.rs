@@ -0,0 +1,127 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use crate::components::{Float, TextOptions};
+use crate::fonts::FontStyle;
+
+// Cache of text measurements shared by every layout pass of a DiagramBuilder.
+//
+// Layout measures the same strings many times: once per line, again for each
+// candidate width tried by `calculate_optimal_line_width`, and again whenever
+// the editor rebuilds the diagram. Measurements only depend on the text and
+// the font, so they are kept across `clear_cache` and dropped when the font
+// registry or the measure function changes.
+
+const MAX_ENTRIES: usize = 50_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MeasureKey {
+    pub text: String,
+    pub font_family: String,
+    // f32 bits, font sizes compare exactly
+    pub font_size: u32,
+    pub font_weight: u32,
+    pub font_style: FontStyle,
+}
+
+impl MeasureKey {
+    /// `text` is the display text (after case transforms)
+    pub fn new(text: &str, text_options: &TextOptions) -> MeasureKey {
+        MeasureKey {
+            text: text.to_string(),
+            font_family: text_options.font_family.clone(),
+            font_size: text_options.font_size.to_bits(),
+            font_weight: text_options.font_weight,
+            font_style: text_options.font_style,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MeasureCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+impl MeasureCacheStats {
+    /// Fraction of lookups answered from the cache
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// Interior mutable so measuring keeps working through `&DiagramBuilder`
+#[derive(Debug, Default)]
+pub struct TextMeasureCache {
+    entries: RefCell<HashMap<MeasureKey, (Float, Float)>>,
+    // Font registry revision the entries were measured with
+    font_revision: Cell<u64>,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+}
+
+impl TextMeasureCache {
+    pub fn new() -> TextMeasureCache {
+        TextMeasureCache {
+            entries: RefCell::new(HashMap::new()),
+            font_revision: Cell::new(0),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+        }
+    }
+
+    /// Cached size for `key`, calling `measure` on a miss.
+    /// Entries from an older font revision are discarded first.
+    pub fn get_or_measure<F>(
+        &self,
+        key: MeasureKey,
+        font_revision: u64,
+        measure: F,
+    ) -> (Float, Float)
+    where
+        F: FnOnce() -> (Float, Float),
+    {
+        if self.font_revision.get() != font_revision {
+            self.entries.borrow_mut().clear();
+            self.font_revision.set(font_revision);
+        }
+
+        if let Some(size) = self.entries.borrow().get(&key) {
+            self.hits.set(self.hits.get() + 1);
+            return *size;
+        }
+
+        self.misses.set(self.misses.get() + 1);
+        let size = measure();
+        let mut entries = self.entries.borrow_mut();
+        if entries.len() >= MAX_ENTRIES {
+            entries.clear();
+        }
+        entries.insert(key, size);
+        size
+    }
+
+    /// Drop every entry, statistics are kept
+    pub fn invalidate(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    pub fn stats(&self) -> MeasureCacheStats {
+        MeasureCacheStats {
+            hits: self.hits.get(),
+            misses: self.misses.get(),
+            entries: self.entries.borrow().len(),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        self.hits.set(0);
+        self.misses.set(0);
+    }
+}
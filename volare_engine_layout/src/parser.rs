@@ -1,11 +1,16 @@
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
 
+use crate::theme::Theme;
 use crate::transform::Transform;
-use crate::{components::*, diagram_builder::*, DiagramBuilder, SimpleConstraint};
+use crate::{
+    color::Color, components::*, diagram_builder::*, fonts::FontStyle, geometry::PathData,
+    DiagramBuilder, SimpleConstraint,
+};
 use anyhow::{bail, Context, Error, Result};
 use thiserror::Error;
 
@@ -282,6 +287,440 @@ pub fn get_bool_attr(attrs: &Map<String, Value>, keys: &[&str], default: bool) -
     default
 }
 
+/// Color string checked with `Color::parse`. The original spelling is kept,
+/// invalid colors are reported and replaced by `default`.
+pub fn get_color_attr(attrs: &Map<String, Value>, keys: &[&str], default: &str) -> String {
+    let warnings = RefCell::new(Vec::new());
+    let color = get_color_attr_with_warnings(
+        attrs,
+        keys,
+        default,
+        &EntityWarnings::new("", None, &warnings),
+    );
+    warnings
+        .into_inner()
+        .iter()
+        .for_each(|w| println!("⚠️ Invalid '{}': {}", w.attribute, w.message));
+    color
+}
+
+/// `get_color_attr` adding invalid colors to `warnings`
+pub fn get_color_attr_with_warnings(
+    attrs: &Map<String, Value>,
+    keys: &[&str],
+    default: &str,
+    warnings: &EntityWarnings,
+) -> String {
+    let value = get_string_attr(attrs, keys, default);
+    match Color::parse(&value) {
+        Ok(_) => value,
+        Err(e) => {
+            let key = keys.iter().find(|key| attrs.contains_key(**key)).unwrap_or(&keys[0]);
+            warnings.push(key, format!("{}, using {}", e, default));
+            default.to_string()
+        }
+    }
+}
+
+/// Font weight from a number (700) or a CSS name ("bold", "semibold", "light"...)
+pub fn get_font_weight_attr(attrs: &Map<String, Value>, keys: &[&str], default: u32) -> u32 {
+    for key in keys {
+        if let Some(value) = attrs.get(*key) {
+            if let Some(n) = value.as_u64() {
+                return n as u32;
+            }
+            if let Some(s) = value.as_str() {
+                return parse_font_weight(s).unwrap_or(default);
+            }
+        }
+    }
+    default
+}
+
+pub fn parse_font_weight(value: &str) -> Option<u32> {
+    let value = value.trim().to_lowercase();
+    if let Ok(n) = value.parse::<u32>() {
+        return Some(n);
+    }
+    match value.as_str() {
+        "thin" | "hairline" => Some(100),
+        "extralight" | "extra-light" | "ultralight" => Some(200),
+        "light" => Some(300),
+        "normal" | "regular" => Some(400),
+        "medium" => Some(500),
+        "semibold" | "semi-bold" | "demibold" => Some(600),
+        "bold" => Some(700),
+        "extrabold" | "extra-bold" | "ultrabold" => Some(800),
+        "black" | "heavy" => Some(900),
+        _ => None,
+    }
+}
+
+pub fn parse_font_style(value: &str) -> FontStyle {
+    match value.trim().to_lowercase().as_str() {
+        "italic" | "oblique" => FontStyle::Italic,
+        _ => FontStyle::Normal,
+    }
+}
+
+/// Accepts space separated values, e.g "underline line-through"
+pub fn parse_text_decoration(value: &str) -> TextDecoration {
+    let mut decoration = TextDecoration::default();
+    for part in value.split_whitespace() {
+        match part.to_lowercase().as_str() {
+            "underline" => decoration.underline = true,
+            "line-through" | "line_through" | "strikethrough" => decoration.line_through = true,
+            _ => {}
+        }
+    }
+    decoration
+}
+
+pub fn parse_text_transform(value: &str) -> TextTransform {
+    match value.trim().to_lowercase().as_str() {
+        "uppercase" => TextTransform::Uppercase,
+        "lowercase" => TextTransform::Lowercase,
+        _ => TextTransform::None,
+    }
+}
+
+/// Fill from a color string, a CSS gradient string or a gradient object:
+/// `"linear-gradient(90deg, red, blue 80%)"`, `"radial-gradient(circle at 30% 30%, white, navy)"`,
+/// `{"type":"linear","angle":90,"stops":[{"offset":0,"color":"red"},{"offset":1,"color":"blue"}]}`
+/// or a pattern object, `{"type":"pattern","pattern":"diagonal","spacing":6,"color":"red"}`.
+/// Invalid gradients, patterns and colors are reported and fall back to `default`.
+pub fn get_fill_attr(attrs: &Map<String, Value>, keys: &[&str], default: &str) -> Fill {
+    let warnings = RefCell::new(Vec::new());
+    let fill = get_fill_attr_with_warnings(
+        attrs,
+        keys,
+        default,
+        &EntityWarnings::new("", None, &warnings),
+    );
+    warnings
+        .into_inner()
+        .iter()
+        .for_each(|w| println!("⚠️ Invalid '{}': {}", w.attribute, w.message));
+    fill
+}
+
+/// `get_fill_attr` adding invalid fills to `warnings`
+pub fn get_fill_attr_with_warnings(
+    attrs: &Map<String, Value>,
+    keys: &[&str],
+    default: &str,
+    warnings: &EntityWarnings,
+) -> Fill {
+    for key in keys {
+        let fill = match attrs.get(*key) {
+            Some(Value::String(value)) if value.contains("gradient(") => parse_css_gradient(value),
+            Some(Value::String(_)) => {
+                return Fill::Color(get_color_attr_with_warnings(
+                    attrs,
+                    &[*key],
+                    default,
+                    warnings,
+                ));
+            }
+            Some(Value::Object(object)) if is_pattern_object(object) => {
+                parse_pattern_object(object)
+            }
+            Some(Value::Object(object)) => parse_gradient_object(object),
+            _ => continue,
+        };
+        return fill.unwrap_or_else(|| {
+            warnings.push(key, format!("invalid fill, using {}", default));
+            Fill::Color(default.to_string())
+        });
+    }
+    Fill::Color(default.to_string())
+}
+
+/// Parses `linear-gradient(...)` and `radial-gradient(...)`
+pub fn parse_css_gradient(value: &str) -> Option<Fill> {
+    let value = value.trim();
+    let open = value.find('(')?;
+    let kind = value[..open].trim().to_lowercase();
+    let body = value[open + 1..].strip_suffix(')')?;
+    let mut args = split_top_level(body, ',');
+    if args.is_empty() {
+        return None;
+    }
+
+    match kind.as_str() {
+        "linear-gradient" => {
+            let angle = parse_gradient_direction(&args[0]);
+            if angle.is_some() {
+                args.remove(0);
+            }
+            let stops = parse_css_color_stops(&args)?;
+            Some(Fill::LinearGradient(LinearGradient::from_angle(
+                angle.unwrap_or(180.0),
+                stops,
+            )))
+        }
+        "radial-gradient" => {
+            // Shape / position prelude, e.g "circle at 30% 40%"
+            let first = args[0].to_lowercase();
+            let mut center = (0.5, 0.5);
+            if first.starts_with("circle")
+                || first.starts_with("ellipse")
+                || first.starts_with("at ")
+            {
+                if let Some(position) = first.split(" at ").nth(1).or(first.strip_prefix("at ")) {
+                    let mut parts = position.split_whitespace().map(parse_fraction);
+                    center.0 = parts.next().flatten().unwrap_or(0.5);
+                    center.1 = parts.next().flatten().unwrap_or(center.0);
+                }
+                args.remove(0);
+            }
+            let stops = parse_css_color_stops(&args)?;
+            Some(Fill::RadialGradient(RadialGradient::new(
+                center.0, center.1, 0.5, stops,
+            )))
+        }
+        _ => None,
+    }
+}
+
+fn parse_gradient_object(object: &Map<String, Value>) -> Option<Fill> {
+    let stops = match object.get("stops")? {
+        Value::Array(values) => {
+            let mut stops = Vec::new();
+            let mut css_stops = Vec::new();
+            for value in values {
+                match value {
+                    Value::String(stop) => css_stops.push(stop.clone()),
+                    Value::Object(stop) => {
+                        let offset = match stop.get("offset") {
+                            Some(Value::String(offset)) => parse_fraction(offset)?,
+                            _ => get_float_attr(stop, &["offset"], 0.0),
+                        };
+                        if stop.contains_key("color") {
+                            let color = get_string_attr(stop, &["color"], "black");
+                            Color::parse(&color).ok()?;
+                            stops.push(GradientStop::ColorStop { offset, color });
+                            // A stop can carry both, e.g {"offset":1,"color":"red","opacity":0}
+                            if stop.contains_key("opacity") {
+                                let opacity = get_float_attr(stop, &["opacity"], 1.0);
+                                stops.push(GradientStop::OpacityStop { offset, opacity });
+                            }
+                        } else {
+                            let opacity = get_float_attr(stop, &["opacity"], 1.0);
+                            stops.push(GradientStop::OpacityStop { offset, opacity });
+                        }
+                    }
+                    _ => return None,
+                }
+            }
+            if !css_stops.is_empty() {
+                stops.extend(parse_css_color_stops(&css_stops)?);
+            }
+            stops
+        }
+        _ => return None,
+    };
+    if stops.is_empty() {
+        return None;
+    }
+
+    match get_string_attr(object, &["type"], "linear").as_str() {
+        "radial" => Some(Fill::RadialGradient(RadialGradient::new(
+            get_float_attr(object, &["cx"], 0.5),
+            get_float_attr(object, &["cy"], 0.5),
+            get_float_attr(object, &["r"], 0.5),
+            stops,
+        ))),
+        "linear" => {
+            if object.contains_key("x1") || object.contains_key("x2") {
+                Some(Fill::LinearGradient(LinearGradient::new(
+                    get_float_attr(object, &["x1"], 0.0),
+                    get_float_attr(object, &["y1"], 0.0),
+                    get_float_attr(object, &["x2"], 1.0),
+                    get_float_attr(object, &["y2"], 0.0),
+                    stops,
+                )))
+            } else {
+                let angle = get_float_attr(object, &["angle"], 180.0);
+                Some(Fill::LinearGradient(LinearGradient::from_angle(
+                    angle, stops,
+                )))
+            }
+        }
+        _ => None,
+    }
+}
+
+fn is_pattern_object(object: &Map<String, Value>) -> bool {
+    object.get("type").and_then(|v| v.as_str()) == Some("pattern")
+}
+
+/// `{"type":"pattern","pattern":"cross"}` for a built-in hatch or
+/// `{"type":"pattern","entity":"tile"}` to repeat another entity
+fn parse_pattern_object(object: &Map<String, Value>) -> Option<Fill> {
+    let pattern = match object.get("entity").and_then(|v| v.as_str()) {
+        Some(entity) => Pattern::Entity(entity.to_string()),
+        None => match get_string_attr(object, &["pattern", "hatch"], "diagonal").as_str() {
+            "diagonal" => Pattern::Diagonal,
+            "cross" => Pattern::Cross,
+            "dots" => Pattern::Dots,
+            "grid" => Pattern::Grid,
+            _ => return None,
+        },
+    };
+
+    let defaults = PatternFill::new(pattern);
+    let color = get_string_attr(object, &["color"], &defaults.color);
+    Color::parse(&color).ok()?;
+    let background = match object.get("background").and_then(|v| v.as_str()) {
+        Some(background) => {
+            Color::parse(background).ok()?;
+            Some(background.to_string())
+        }
+        None => None,
+    };
+    let spacing = get_float_attr(object, &["spacing"], defaults.spacing as f64);
+    let line_width = get_float_attr(object, &["line_width", "width"], defaults.line_width as f64);
+    if spacing < 0.0 || line_width < 0.0 {
+        return None;
+    }
+    if spacing == 0.0 && !matches!(defaults.pattern, Pattern::Entity(_)) {
+        return None;
+    }
+
+    Some(Fill::Pattern(PatternFill {
+        spacing,
+        angle: get_float_attr(object, &["angle"], defaults.angle as f64),
+        color,
+        line_width,
+        background,
+        ..defaults
+    }))
+}
+
+/// CSS gradient direction in degrees ("90deg", "0.25turn", "to top right")
+fn parse_gradient_direction(arg: &str) -> Option<Float> {
+    let arg = arg.trim().to_lowercase();
+    if let Some(side) = arg.strip_prefix("to ") {
+        let mut words: Vec<&str> = side.split_whitespace().collect();
+        words.sort();
+        return match words.as_slice() {
+            ["top"] => Some(0.0),
+            ["right", "top"] => Some(45.0),
+            ["right"] => Some(90.0),
+            ["bottom", "right"] => Some(135.0),
+            ["bottom"] => Some(180.0),
+            ["bottom", "left"] => Some(225.0),
+            ["left"] => Some(270.0),
+            ["left", "top"] => Some(315.0),
+            _ => None,
+        };
+    }
+    if let Some(value) = arg.strip_suffix("deg") {
+        return value.trim().parse().ok();
+    }
+    if let Some(value) = arg.strip_suffix("turn") {
+        return value
+            .trim()
+            .parse::<Float>()
+            .ok()
+            .map(|turns| turns * 360.0);
+    }
+    if let Some(value) = arg.strip_suffix("rad") {
+        return value
+            .trim()
+            .parse::<Float>()
+            .ok()
+            .map(|rad| rad.to_degrees());
+    }
+    None
+}
+
+/// "50%" or "0.5"
+fn parse_fraction(value: &str) -> Option<Float> {
+    let value = value.trim();
+    match value.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<Float>().ok().map(|p| p / 100.0),
+        None => value.parse().ok(),
+    }
+}
+
+/// "red", "blue 40%", "rgb(0, 0, 0) 100%". Stops without a position are
+/// spread evenly between their neighbours, as in CSS.
+fn parse_css_color_stops(args: &[String]) -> Option<Vec<GradientStop>> {
+    let mut parsed: Vec<(String, Option<Float>)> = Vec::new();
+    for arg in args {
+        let parts = split_top_level(arg, ' ');
+        let (color, offset) = match parts.as_slice() {
+            [color] => (color.clone(), None),
+            [color, offset] => (color.clone(), Some(parse_fraction(offset)?)),
+            _ => return None,
+        };
+        Color::parse(&color).ok()?;
+        parsed.push((color, offset));
+    }
+    if parsed.is_empty() {
+        return None;
+    }
+
+    let last = parsed.len() - 1;
+    if parsed[0].1.is_none() {
+        parsed[0].1 = Some(0.0);
+    }
+    if parsed[last].1.is_none() {
+        parsed[last].1 = Some(1.0);
+    }
+    let mut i = 0;
+    while i < last {
+        let start = i;
+        let mut end = i + 1;
+        while parsed[end].1.is_none() {
+            end += 1;
+        }
+        let (from, to) = (parsed[start].1.unwrap(), parsed[end].1.unwrap());
+        for (step, stop) in parsed.iter_mut().enumerate().take(end).skip(start + 1) {
+            stop.1 = Some(from + (to - from) * (step - start) as Float / (end - start) as Float);
+        }
+        i = end;
+    }
+
+    Some(
+        parsed
+            .into_iter()
+            .map(|(color, offset)| GradientStop::ColorStop {
+                offset: offset.unwrap_or(0.0),
+                color,
+            })
+            .collect(),
+    )
+}
+
+/// Split on `separator` outside of parentheses, e.g "rgb(1, 2, 3), red" on ','
+fn split_top_level(value: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        if c == separator && depth == 0 {
+            if !current.trim().is_empty() {
+                parts.push(current.trim().to_string());
+            }
+            current.clear();
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+    parts
+}
+
 pub fn get_array_attr(attrs: &Map<String, Value>, key: &str) -> Option<Vec<String>> {
     attrs.get(key).and_then(|v| {
         v.as_array().map(|arr| {
@@ -353,6 +792,230 @@ pub fn parse_unified_dimension(attrs: &Map<String, Value>, keys: &[&str]) -> Siz
     }
     SizeBehavior::Content // Default
 }
+/// `opacity`, `fill_opacity` and `stroke_opacity`, as 0..1 or a percentage
+fn parse_opacity_attributes(
+    obj: &Map<String, Value>,
+    session: &mut DiagramBuilder,
+    entity_id: EntityID,
+) {
+    let opacity = OpacityOptions {
+        opacity: get_opacity_attr(obj, &["opacity"]),
+        fill_opacity: get_opacity_attr(obj, &["fill_opacity", "fill-opacity"]),
+        stroke_opacity: get_opacity_attr(obj, &["stroke_opacity", "stroke-opacity"]),
+    };
+    if !opacity.is_opaque() {
+        session.set_opacity(entity_id, opacity);
+    }
+}
+
+/// `z_index`, `layer` and `mix_blend_mode`
+fn parse_layer_attributes(
+    obj: &Map<String, Value>,
+    session: &mut DiagramBuilder,
+    entity_id: EntityID,
+) {
+    let mut options = LayerOptions::default();
+    if let Some(z_index) = ["z_index", "z-index"]
+        .iter()
+        .find_map(|key| obj.get(*key))
+        .and_then(|v| v.as_f64())
+    {
+        options.z_index = z_index.round() as i32;
+    }
+
+    let layer = get_string_attr(obj, &["layer"], "");
+    if !layer.is_empty() {
+        options.layer = Layer::parse(&layer);
+        if options.layer.is_none() {
+            println!(
+                "⚠️ Unknown layer '{}' on '{}', keeping its parent's",
+                layer, entity_id
+            );
+        }
+    }
+
+    let blend_mode = get_string_attr(obj, &["mix_blend_mode", "mix-blend-mode", "blend_mode"], "");
+    if !blend_mode.is_empty() {
+        options.blend_mode = BlendMode::parse(&blend_mode).unwrap_or_else(|| {
+            println!("⚠️ Unknown blend mode '{}' on '{}'", blend_mode, entity_id);
+            BlendMode::Normal
+        });
+    }
+
+    if options != LayerOptions::default() {
+        session.set_layer_options(entity_id, options);
+    }
+}
+
+/// `shadow` as `true` for the default shadow, a CSS `box-shadow` style string
+/// (`"0 4 8 2 rgba(0,0,0,0.3)"`: offsets, then optional blur and spread, and a
+/// color anywhere) or an object with `offset_x`, `offset_y`, `blur`, `spread`
+/// and `color`
+fn parse_shadow_attribute(
+    obj: &Map<String, Value>,
+    session: &mut DiagramBuilder,
+    entity_id: EntityID,
+    warnings: &EntityWarnings,
+) {
+    let shadow = match obj.get("shadow") {
+        None | Some(Value::Bool(false)) | Some(Value::Null) => return,
+        Some(Value::Bool(true)) => Some(ShadowOptions::default()),
+        Some(Value::String(s)) if s.trim() == "none" => return,
+        Some(Value::String(s)) => parse_css_shadow(s),
+        Some(Value::Object(shadow)) => {
+            let defaults = ShadowOptions::default();
+            Some(ShadowOptions {
+                offset_x: get_float_attr(shadow, &["offset_x", "x"], defaults.offset_x as f64),
+                offset_y: get_float_attr(shadow, &["offset_y", "y"], defaults.offset_y as f64),
+                blur: get_float_attr(shadow, &["blur"], defaults.blur as f64).max(0.0),
+                spread: get_float_attr(shadow, &["spread"], defaults.spread as f64),
+                color: get_color_attr_with_warnings(shadow, &["color"], &defaults.color, warnings),
+            })
+        }
+        Some(_) => None,
+    };
+
+    match shadow {
+        Some(shadow) => session.set_shadow(entity_id, shadow),
+        None => println!("⚠️ Invalid shadow on '{}', ignoring it", entity_id),
+    }
+}
+
+fn parse_css_shadow(value: &str) -> Option<ShadowOptions> {
+    let mut lengths = Vec::new();
+    let mut color = None;
+    for part in split_top_level(value, ' ') {
+        match part.trim_end_matches("px").parse::<Float>() {
+            Ok(length) => lengths.push(length),
+            Err(_) if color.is_none() && Color::parse(&part).is_ok() => color = Some(part),
+            Err(_) => return None,
+        }
+    }
+    if !(2..=4).contains(&lengths.len()) {
+        return None;
+    }
+
+    let defaults = ShadowOptions::default();
+    Some(ShadowOptions {
+        offset_x: lengths[0],
+        offset_y: lengths[1],
+        blur: lengths.get(2).copied().unwrap_or(0.0).max(0.0),
+        spread: lengths.get(3).copied().unwrap_or(0.0),
+        color: color.unwrap_or(defaults.color),
+    })
+}
+
+fn get_opacity_attr(attrs: &Map<String, Value>, keys: &[&str]) -> Float {
+    for key in keys {
+        let opacity = match attrs.get(*key) {
+            Some(Value::Number(n)) => n.as_f64().map(|n| n as Float),
+            Some(Value::String(s)) => parse_fraction(s),
+            _ => continue,
+        };
+        return match opacity {
+            Some(opacity) => opacity.clamp(0.0, 1.0),
+            None => {
+                println!("⚠️ Invalid opacity in '{}', using 1", key);
+                1.0
+            }
+        };
+    }
+    1.0
+}
+
+/// Reads `stroke_dasharray`, `stroke_dashoffset`, `stroke_linecap` and
+/// `stroke_linejoin`. The dash array takes numbers as an array or an SVG
+/// style list, or the keywords `dashed` and `dotted` which scale with the
+/// stroke width.
+fn parse_stroke_style(attrs: &Map<String, Value>, stroke_width: Float) -> StrokeStyle {
+    let mut style = StrokeStyle {
+        dash_offset: get_float_attr(attrs, &["stroke_dashoffset", "stroke-dashoffset"], 0.0),
+        ..StrokeStyle::default()
+    };
+
+    let dash_value = ["stroke_dasharray", "stroke-dasharray"]
+        .iter()
+        .find_map(|key| attrs.get(*key).map(|value| (*key, value)));
+    if let Some((key, value)) = dash_value {
+        let dashes = match value {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| item.as_f64().map(|n| n as Float))
+                .collect::<Option<Vec<_>>>(),
+            Value::Number(n) => n.as_f64().map(|n| vec![n as Float]),
+            Value::String(s) => match s.trim() {
+                "" | "none" | "solid" => Some(Vec::new()),
+                "dashed" => Some(vec![4.0 * stroke_width, 2.0 * stroke_width]),
+                "dotted" => {
+                    style.line_cap = LineCap::Round;
+                    Some(vec![0.0, 2.0 * stroke_width])
+                }
+                list => list
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|part| !part.is_empty())
+                    .map(|part| part.parse::<Float>().ok())
+                    .collect(),
+            },
+            _ => None,
+        };
+        match dashes {
+            Some(dashes) if dashes.iter().all(|d| *d >= 0.0) => style.dash_array = dashes,
+            _ => println!("⚠️ Invalid dash array in '{}', using a solid stroke", key),
+        }
+    }
+
+    match get_string_attr(attrs, &["stroke_linecap", "stroke-linecap"], "").as_str() {
+        "" => {}
+        "butt" => style.line_cap = LineCap::Butt,
+        "round" => style.line_cap = LineCap::Round,
+        "square" => style.line_cap = LineCap::Square,
+        other => println!("⚠️ Unknown stroke_linecap '{}', using butt", other),
+    }
+    match get_string_attr(attrs, &["stroke_linejoin", "stroke-linejoin"], "").as_str() {
+        "" => {}
+        "miter" => style.line_join = LineJoin::Miter,
+        "round" => style.line_join = LineJoin::Round,
+        "bevel" => style.line_join = LineJoin::Bevel,
+        other => println!("⚠️ Unknown stroke_linejoin '{}', using miter", other),
+    }
+
+    style
+}
+
+/// Fill and stroke of path and spline shapes, `fill_rule` is `nonzero` or `evenodd`
+fn parse_path_options(
+    attrs: &Map<String, Value>,
+    theme: &Theme,
+    warnings: &EntityWarnings,
+) -> PathOptions {
+    let stroke_width = get_float_attr(attrs, &["stroke_width"], theme.stroke_width as f64);
+    let fill_rule = match get_string_attr(attrs, &["fill_rule", "fill-rule"], "").as_str() {
+        "" | "nonzero" => FillRule::NonZero,
+        "evenodd" => FillRule::EvenOdd,
+        other => {
+            let key = if attrs.contains_key("fill_rule") {
+                "fill_rule"
+            } else {
+                "fill-rule"
+            };
+            warnings.push(key, format!("unknown fill rule '{}', using nonzero", other));
+            FillRule::NonZero
+        }
+    };
+    PathOptions {
+        fill_color: get_color_attr_with_warnings(attrs, &["fill", "fill_color"], "none", warnings),
+        stroke_color: get_color_attr_with_warnings(
+            attrs,
+            &["stroke", "stroke_color"],
+            &theme.line_color,
+            warnings,
+        ),
+        stroke_width,
+        stroke_style: parse_stroke_style(attrs, stroke_width),
+        fill_rule,
+    }
+}
+
 fn parse_transform_attributes(
     obj: &Map<String, Value>,
     session: &mut DiagramBuilder,
@@ -430,16 +1093,161 @@ fn parse_css_transform(transform_str: &str) -> Result<Transform, String> {
     Ok(result)
 }
 
+// Names the parser treats as the same attribute. When a class or the entity
+// sets one of them, the others inherited from earlier classes are dropped so
+// the alias lookup order can't bring them back.
+const ATTRIBUTE_ALIASES: &[&[&str]] = &[
+    &["fill", "fill_color", "background", "background_color"],
+    &["stroke_color", "border_color", "stroke"],
+    &["stroke_width", "border_width"],
+    &["color", "text_color"],
+    &["content", "text"],
+    &["h_align", "horizontal_alignment"],
+    &["v_align", "vertical_alignment"],
+    &["stroke_dasharray", "stroke-dasharray"],
+    &["stroke_dashoffset", "stroke-dashoffset"],
+    &["stroke_linecap", "stroke-linecap"],
+    &["stroke_linejoin", "stroke-linejoin"],
+];
+
+/// Class names from a `class` or `extends` attribute, either a space
+/// separated string or an array
+fn get_class_list(attrs: &Map<String, Value>, key: &str) -> Vec<String> {
+    match attrs.get(key) {
+        Some(Value::String(names)) => names.split_whitespace().map(String::from).collect(),
+        Some(Value::Array(names)) => names
+            .iter()
+            .filter_map(|name| name.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn merge_style_attributes(attributes: &mut Map<String, Value>, overrides: Map<String, Value>) {
+    for (key, value) in overrides {
+        if let Some(aliases) = ATTRIBUTE_ALIASES
+            .iter()
+            .find(|group| group.contains(&key.as_str()))
+        {
+            for alias in aliases.iter() {
+                attributes.remove(*alias);
+            }
+        }
+        attributes.insert(key, value);
+    }
+}
+
+/// Replaces `$name` strings with the theme palette color, also inside
+/// objects such as shadows and gradients
+fn resolve_theme_colors(value: &mut Value, theme: &Theme, entity_id: &str) {
+    match value {
+        Value::String(text) if text.starts_with('$') => match theme.resolve_color(text) {
+            Some(color) => *text = color.to_string(),
+            None => println!("⚠️ Unknown theme color '{}' on '{}'", text, entity_id),
+        },
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| resolve_theme_colors(item, theme, entity_id)),
+        Value::Object(fields) => fields
+            .values_mut()
+            .for_each(|field| resolve_theme_colors(field, theme, entity_id)),
+        _ => {}
+    }
+}
+
+/// Attribute value that couldn't be used while building, the parser kept a default instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseWarning {
+    pub entity_id: String,
+    /// JSONL line the entity was read from, when it came from one
+    pub line: Option<usize>,
+    pub attribute: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid '{}' on '{}'", self.attribute, self.entity_id)?;
+        if let Some(line) = self.line {
+            write!(f, " (line {})", line)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Warnings found while reading the attributes of one entity
+pub struct EntityWarnings<'a> {
+    entity_id: &'a str,
+    line: Option<usize>,
+    warnings: &'a RefCell<Vec<ParseWarning>>,
+}
+
+impl<'a> EntityWarnings<'a> {
+    pub fn new(
+        entity_id: &'a str,
+        line: Option<usize>,
+        warnings: &'a RefCell<Vec<ParseWarning>>,
+    ) -> Self {
+        Self {
+            entity_id,
+            line,
+            warnings,
+        }
+    }
+
+    /// Entities built more than once, like connectors, report each warning once
+    pub fn push(&self, attribute: &str, message: String) {
+        let warning = ParseWarning {
+            entity_id: self.entity_id.to_string(),
+            line: self.line,
+            attribute: attribute.to_string(),
+            message,
+        };
+        let mut warnings = self.warnings.borrow_mut();
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+}
+
 /// Parser for JSON Lines diagram format
 pub struct JsonLinesParser {
     pub entities: HashMap<String, JsonEntity>,
+    // Style classes declared with {"type":"style"}, referenced through `class`
+    pub styles: HashMap<String, Map<String, Value>>,
+    // Collected by `build`, which only has shared access to the parser
+    warnings: RefCell<Vec<ParseWarning>>,
+    // JSONL line each entity was read from, for the warnings
+    lines: HashMap<String, usize>,
 }
 
 impl JsonLinesParser {
     pub fn new() -> Self {
         Self {
             entities: HashMap::new(),
+            styles: HashMap::new(),
+            warnings: RefCell::new(Vec::new()),
+            lines: HashMap::new(),
+        }
+    }
+
+    /// Invalid attribute values found by `build` so far. Custom components
+    /// build their children through the same parser, so they are included.
+    pub fn warnings(&self) -> Vec<ParseWarning> {
+        self.warnings.borrow().clone()
+    }
+
+    /// Stores a parsed line, style declarations never become the root
+    fn add_entity(&mut self, entity: JsonEntity, line: usize, root_id: &mut Option<String>) {
+        if entity.entity_type == "style" {
+            self.styles.insert(entity.id, entity.attributes);
+            return;
         }
+        if root_id.is_none() {
+            *root_id = Some(entity.id.clone());
+        }
+        self.lines.insert(entity.id.clone(), line);
+        self.entities.insert(entity.id.clone(), entity);
     }
 
     /// Parse from a string containing JSON Lines
@@ -453,12 +1261,7 @@ impl JsonLinesParser {
             }
 
             match serde_json::from_str::<JsonEntity>(line) {
-                Ok(entity) => {
-                    if root_id.is_none() {
-                        root_id = Some(entity.id.clone());
-                    }
-                    self.entities.insert(entity.id.clone(), entity);
-                }
+                Ok(entity) => self.add_entity(entity, line_num + 1, &mut root_id),
                 Err(e) => {
                     return Err(JsonLinesError::ParseError {
                         line: line_num + 1,
@@ -486,12 +1289,7 @@ impl JsonLinesParser {
             }
 
             match serde_json::from_str::<JsonEntity>(&line) {
-                Ok(entity) => {
-                    if root_id.is_none() {
-                        root_id = Some(entity.id.clone());
-                    }
-                    self.entities.insert(entity.id.clone(), entity);
-                }
+                Ok(entity) => self.add_entity(entity, line_num + 1, &mut root_id),
                 Err(e) => {
                     return Err(JsonLinesError::ParseError {
                         line: line_num + 1,
@@ -518,12 +1316,7 @@ impl JsonLinesParser {
             }
 
             match serde_json::from_str::<JsonEntity>(line) {
-                Ok(entity) => {
-                    if root_id.is_none() {
-                        root_id = Some(entity.id.clone());
-                    }
-                    self.entities.insert(entity.id.clone(), entity);
-                }
+                Ok(entity) => self.add_entity(entity, line_num + 1, &mut root_id),
                 Err(e) => {
                     return Err(JsonLinesError::ParseError {
                         line: line_num + 1,
@@ -607,11 +1400,37 @@ impl JsonLinesParser {
             .get(entity_id)
             .ok_or_else(|| JsonLinesError::EntityNotFound(entity_id.to_string()))?;
 
+        let warnings = EntityWarnings::new(
+            &entity.id,
+            self.lines.get(entity_id).copied(),
+            &self.warnings,
+        );
+
+        // Everything below reads the attributes with classes and theme colors applied
+        let theme = builder.theme.clone();
+        let entity = &JsonEntity {
+            id: entity.id.clone(),
+            entity_type: entity.entity_type.clone(),
+            attributes: self.resolve_attributes(entity, &theme),
+        };
+
         // Clone the entity type to avoid borrow conflicts
         let component_type = entity.entity_type.clone();
         let attributes = entity.attributes.clone();
         println!("Attributes length {}", attributes.len());
 
+        parse_opacity_attributes(&attributes, builder, entity_id.to_string());
+        parse_layer_attributes(&attributes, builder, entity_id.to_string());
+        parse_named_ports(&attributes, builder, entity_id.to_string(), &warnings);
+        if matches!(
+            component_type.as_str(),
+            "box" | "rect" | "ellipse" | "image"
+        ) {
+            parse_shadow_attribute(&attributes, builder, entity_id.to_string(), &warnings);
+        }
+        self.parse_clip_attributes(&attributes, &component_type, builder, entity_id, &warnings);
+        self.build_pattern_tiles(&attributes, builder, entity_id, &warnings);
+
         // Check for custom components FIRST - they get the raw attributes map
         if builder.has_custom_component(&component_type) {
             return builder.create_custom_component(
@@ -651,27 +1470,50 @@ impl JsonLinesParser {
                     bail!("Missing attribute content or text");
                 }
 
-                // Only support "bold", otherwise return the value or "400" by default
-                let str_font_weight = &get_string_attr(&entity.attributes, &["font_weight"], "400");
-                let f_weight: u32 = if let Ok(val) = str::parse::<u32>(&str_font_weight) {
-                    val
-                } else if str_font_weight == "bold " {
-                    900
-                } else {
-                    400
-                };
-
                 let options = TextOptions {
-                    font_size: get_float_attr(&entity.attributes, &["font_size"], 12.0),
-                    text_color: get_string_attr(
+                    font_size: get_float_attr(
+                        &entity.attributes,
+                        &["font_size"],
+                        theme.font_size as f64,
+                    ),
+                    text_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["color", "text_color"],
-                        "black",
+                        &theme.text_color,
+                        &warnings,
+                    ),
+                    font_weight: get_font_weight_attr(
+                        &entity.attributes,
+                        &["font_weight"],
+                        theme.font_weight,
+                    ),
+                    font_family: get_string_attr(
+                        &entity.attributes,
+                        &["font_family"],
+                        &theme.font_family,
                     ),
-                    font_weight: f_weight,
-                    font_family: get_string_attr(&entity.attributes, &["font_family"], "Arial"),
-                    line_width: get_int_attr(&entity.attributes, &["line_width"], 200) as usize,
+                    line_width: get_int_attr(
+                        &entity.attributes,
+                        &["line_width"],
+                        theme.line_width as i64,
+                    ) as usize,
                     line_spacing: get_float_attr(&entity.attributes, &["line_spacing"], 0.0),
+                    font_style: parse_font_style(&get_string_attr(
+                        &entity.attributes,
+                        &["font_style"],
+                        "normal",
+                    )),
+                    text_decoration: parse_text_decoration(&get_string_attr(
+                        &entity.attributes,
+                        &["text_decoration"],
+                        "none",
+                    )),
+                    letter_spacing: get_float_attr(&entity.attributes, &["letter_spacing"], 0.0),
+                    text_transform: parse_text_transform(&get_string_attr(
+                        &entity.attributes,
+                        &["text_transform"],
+                        "none",
+                    )),
                 };
 
                 // Parse and apply transforms
@@ -692,28 +1534,29 @@ impl JsonLinesParser {
 
                 let width_behavior = parse_unified_dimension(&entity.attributes, &["width"]);
                 let height_behavior = parse_unified_dimension(&entity.attributes, &["height"]);
+                let stroke_width = get_float_attr(
+                    &entity.attributes,
+                    &["border_width", "stroke_width"],
+                    theme.stroke_width as f64,
+                );
 
                 let options = BoxOptions {
-                    padding: get_float_attr(&entity.attributes, &["padding"], 0.0),
-                    fill_color: {
-                        let color = get_string_attr(
-                            &entity.attributes,
-                            &["background", "background_color", "fill"],
-                            "white",
-                        );
-                        Fill::Color(color)
-                    },
-                    stroke_color: get_string_attr(
+                    padding: get_float_attr(&entity.attributes, &["padding"], theme.padding as f64),
+                    fill_color: get_fill_attr_with_warnings(
                         &entity.attributes,
-                        &["border_color", "stroke_color"],
-                        "black",
+                        &["background", "background_color", "fill"],
+                        &theme.fill_color,
+                        &warnings,
                     ),
-                    stroke_width: get_float_attr(
+                    stroke_color: get_color_attr_with_warnings(
                         &entity.attributes,
-                        &["border_width", "stroke_width"],
-                        1.0,
+                        &["border_color", "stroke_color"],
+                        &theme.stroke_color,
+                        &warnings,
                     ),
+                    stroke_width,
                     border_radius: get_float_attr(&entity.attributes, &["border_radius"], 0.0),
+                    stroke_style: parse_stroke_style(&entity.attributes, stroke_width),
                     width_behavior,
                     height_behavior,
                     // TODO: leer de atributo
@@ -824,29 +1667,30 @@ impl JsonLinesParser {
             "rect" => {
                 let width_behavior = parse_unified_dimension(&entity.attributes, &["width"]);
                 let height_behavior = parse_unified_dimension(&entity.attributes, &["height"]);
+                let stroke_width = get_float_attr(
+                    &entity.attributes,
+                    &["border_width", "stroke_width"],
+                    theme.stroke_width as f64,
+                );
 
                 let options = RectOptions {
                     width_behavior,
                     height_behavior,
-                    fill_color: {
-                        let color = get_string_attr(
-                            &entity.attributes,
-                            &["background", "background_color", "fill"],
-                            "white",
-                        );
-                        Fill::Color(color)
-                    },
-                    stroke_color: get_string_attr(
+                    fill_color: get_fill_attr_with_warnings(
                         &entity.attributes,
-                        &["border_color", "stroke_color"],
-                        "black",
+                        &["background", "background_color", "fill"],
+                        &theme.fill_color,
+                        &warnings,
                     ),
-                    stroke_width: get_float_attr(
+                    stroke_color: get_color_attr_with_warnings(
                         &entity.attributes,
-                        &["border_width", "stroke_width"],
-                        1.0,
+                        &["border_color", "stroke_color"],
+                        &theme.stroke_color,
+                        &warnings,
                     ),
+                    stroke_width,
                     border_radius: get_float_attr(&entity.attributes, &["border_radius"], 0.0),
+                    stroke_style: parse_stroke_style(&entity.attributes, stroke_width),
                 };
                 // Parse and apply transforms
                 parse_transform_attributes(&entity.attributes, builder, entity_id.to_string());
@@ -868,22 +1712,37 @@ impl JsonLinesParser {
                     (0.0, 0.0),
                 );
 
+                let stroke_width = get_float_attr(
+                    &entity.attributes,
+                    &["stroke_width"],
+                    theme.stroke_width as f64,
+                );
                 let options = LineOptions {
-                    stroke_color: get_string_attr(
+                    stroke_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["stroke_color", "color"],
-                        "black",
+                        &theme.line_color,
+                        &warnings,
                     ),
-                    stroke_width: get_float_attr(&entity.attributes, &["stroke_width"], 1.0),
+                    stroke_width,
+                    stroke_style: parse_stroke_style(&entity.attributes, stroke_width),
                 };
 
                 // Parse and apply transforms
                 parse_transform_attributes(&entity.attributes, builder, entity_id.to_string());
 
+                // `start_point` and `end_point` can follow other entities
+                let reference = |key: &str, (x, y): (Float, Float)| {
+                    entity
+                        .attributes
+                        .get(key)
+                        .and_then(|value| self.parse_point_reference(value, key, &warnings))
+                        .unwrap_or(LinePointReference::Value(x, y))
+                };
                 Ok(builder.new_line(
                     entity_id.to_string(),
-                    LinePointReference::Value(start_point.0, start_point.1),
-                    LinePointReference::Value(end_point.0, end_point.1),
+                    reference("start_point", start_point),
+                    reference("end_point", end_point),
                     options,
                 ))
             }
@@ -903,13 +1762,18 @@ impl JsonLinesParser {
                 }
 
                 // Optional styling
-                let stroke_color = get_string_attr(
+                let stroke_color = get_color_attr_with_warnings(
                     &entity.attributes,
                     &["stroke_color", "color", "stroke"],
-                    "black",
+                    &theme.line_color,
+                    &warnings,
                 );
 
-                let stroke_width = get_float_attr(&entity.attributes, &["stroke_width"], 1.0);
+                let stroke_width = get_float_attr(
+                    &entity.attributes,
+                    &["stroke_width"],
+                    theme.stroke_width as f64,
+                );
 
                 // Connector type
                 let connector_type_str =
@@ -935,13 +1799,27 @@ impl JsonLinesParser {
                 };
 
                 // Parse ports
-                let source_port = parse_port(&entity.attributes, "source_port");
-                let target_port = parse_port(&entity.attributes, "target_port");
+                let source_port = parse_port(&entity.attributes, "source_port", &warnings);
+                let target_port = parse_port(&entity.attributes, "target_port", &warnings);
+                let loop_side = get_string_attr(&entity.attributes, &["loop_side"], "top");
+                let loop_side = parse_port_side(&loop_side).unwrap_or_else(|| {
+                    warnings.push(
+                        "loop_side",
+                        format!("unknown side '{}', using top", loop_side),
+                    );
+                    PortSide::Top
+                });
 
-                let arrow_start =
-                    get_bool_attr(&entity.attributes, &["arrow_start", "arrow_begin"], false);
-                let arrow_end = get_bool_attr(&entity.attributes, &["arrow_end"], false);
+                let marker_start = self.parse_marker(
+                    &entity.attributes,
+                    "marker_start",
+                    &["arrow_start", "arrow_begin"],
+                    &warnings,
+                );
+                let marker_end =
+                    self.parse_marker(&entity.attributes, "marker_end", &["arrow_end"], &warnings);
                 let arrow_size = get_float_attr(&entity.attributes, &["arrow_size"], 8.0);
+                let (distribute_ports, bundle) = self.connector_layout_options(entity);
 
                 let options = ConnectorOptions {
                     connector_type,
@@ -950,16 +1828,46 @@ impl JsonLinesParser {
                     curve_offset,
                     source_port,
                     target_port,
-                    arrow_start,
-                    arrow_end,
+                    marker_start,
+                    marker_end,
                     arrow_size,
-                    // TODO: read from params
-                    routing_strategy: OrthogonalRoutingStrategy::Auto,
-                    label_alignment: LabelAlignment::Center,
-                };
+                    routing_strategy: parse_routing_strategy(&entity.attributes),
+                    label_alignment: parse_label_alignment(&entity.attributes),
+                    label_path: if get_bool_attr(&entity.attributes, &["label_on_path"], false) {
+                        Some(parse_text_path_options(&entity.attributes, &warnings))
+                    } else {
+                        None
+                    },
+                    stroke_style: parse_stroke_style(&entity.attributes, stroke_width),
+                    clearance: get_float_attr(&entity.attributes, &["clearance"], 10.0),
+                    parallel_spacing: get_float_attr(
+                        &entity.attributes,
+                        &["parallel_spacing"],
+                        10.0,
+                    ),
+                    distribute_ports,
+                    bundle,
+                    waypoints: self.parse_waypoints(&entity.attributes, &warnings),
+                    loop_side,
+                    loop_size: get_float_attr(&entity.attributes, &["loop_size"], 30.0),
+                };
+
+                let labels = parse_connector_labels(
+                    &entity.attributes,
+                    options.label_path.is_some(),
+                    &warnings,
+                );
+                let label = Some(label).filter(|label| !label.is_empty());
 
                 // Create connector
-                Ok(builder.new_connector_with_label(entity_id.to_string(), source_id, target_id,label,options))
+                Ok(builder.new_connector_with_labels(
+                    entity_id.to_string(),
+                    source_id,
+                    target_id,
+                    label,
+                    labels,
+                    options,
+                ))
             }
 
             "ellipse" => {
@@ -971,20 +1879,22 @@ impl JsonLinesParser {
                 );
 
                 let options = EllipseOptions {
-                    fill_color: get_string_attr(
+                    fill_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["fill", "fill_color", "background"],
-                        "white",
+                        &theme.fill_color,
+                        &warnings,
                     ),
-                    stroke_color: get_string_attr(
+                    stroke_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["stroke", "stroke_color", "border_color"],
-                        "black",
+                        &theme.stroke_color,
+                        &warnings,
                     ),
                     stroke_width: get_float_attr(
                         &entity.attributes,
                         &["stroke_width", "border_width"],
-                        1.0,
+                        theme.stroke_width as f64,
                     ),
                 };
 
@@ -1000,31 +1910,50 @@ impl JsonLinesParser {
                     get_float_attr(&entity.attributes, &["start_angle", "start"], 0.0);
                 let end_angle = get_float_attr(&entity.attributes, &["end_angle", "end"], 90.0);
 
+                let stroke_width = get_float_attr(
+                    &entity.attributes,
+                    &["stroke_width"],
+                    theme.stroke_width as f64,
+                );
                 let options = ArcOptions {
-                    fill_color: get_string_attr(
+                    fill_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["fill", "fill_color"],
                         "none",
+                        &warnings,
                     ),
-                    stroke_color: get_string_attr(
+                    stroke_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["stroke", "stroke_color"],
-                        "black",
+                        &theme.line_color,
+                        &warnings,
                     ),
-                    stroke_width: get_float_attr(&entity.attributes, &["stroke_width"], 1.0),
+                    stroke_width,
                     filled: get_bool_attr(&entity.attributes, &["filled"], false),
+                    label: Some(get_string_attr(&entity.attributes, &["label"], ""))
+                        .filter(|label| !label.is_empty()),
+                    label_path: parse_text_path_options(&entity.attributes, &warnings),
+                    stroke_style: parse_stroke_style(&entity.attributes, stroke_width),
                 };
 
                 // Parse and apply transforms
                 parse_transform_attributes(&entity.attributes, builder, entity_id.to_string());
 
-                Ok(builder.new_arc(
+                let node = builder.new_arc(
                     entity_id.to_string(),
                     radius,
                     start_angle,
                     end_angle,
                     options,
-                ))
+                );
+                let endpoint = |key: &str| {
+                    let value = entity.attributes.get(key)?;
+                    self.parse_point_reference(value, key, &warnings)
+                };
+                if let (Some(start), Some(end)) = (endpoint("start_point"), endpoint("end_point")) {
+                    builder.set_arc_endpoints(entity_id.to_string(), start, end);
+                }
+                Ok(node)
             }
 
             // Complete fixed semicircle section for parser.rs
@@ -1038,19 +1967,30 @@ impl JsonLinesParser {
                     (0.0, 180.0) // FIXED: Bottom semicircle should be 0° to 180°
                 };
 
+                let stroke_width = get_float_attr(
+                    &entity.attributes,
+                    &["stroke_width"],
+                    theme.stroke_width as f64,
+                );
                 let options = ArcOptions {
-                    fill_color: get_string_attr(
+                    fill_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["fill", "fill_color"],
                         "none",
+                        &warnings,
                     ),
-                    stroke_color: get_string_attr(
+                    stroke_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["stroke", "stroke_color"],
-                        "black",
+                        &theme.line_color,
+                        &warnings,
                     ),
-                    stroke_width: get_float_attr(&entity.attributes, &["stroke_width"], 1.0),
+                    stroke_width,
                     filled: get_bool_attr(&entity.attributes, &["filled"], false),
+                    label: Some(get_string_attr(&entity.attributes, &["label"], ""))
+                        .filter(|label| !label.is_empty()),
+                    label_path: parse_text_path_options(&entity.attributes, &warnings),
+                    stroke_style: parse_stroke_style(&entity.attributes, stroke_width),
                 };
 
                 // Parse and apply transforms
@@ -1063,19 +2003,30 @@ impl JsonLinesParser {
                 let radius = get_float_attr(&entity.attributes, &["radius", "r"], 50.0);
                 let quadrant = get_int_attr(&entity.attributes, &["quadrant"], 1) as u8;
 
+                let stroke_width = get_float_attr(
+                    &entity.attributes,
+                    &["stroke_width"],
+                    theme.stroke_width as f64,
+                );
                 let options = ArcOptions {
-                    fill_color: get_string_attr(
+                    fill_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["fill", "fill_color"],
                         "none",
+                        &warnings,
                     ),
-                    stroke_color: get_string_attr(
+                    stroke_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["stroke", "stroke_color"],
-                        "black",
+                        &theme.line_color,
+                        &warnings,
                     ),
-                    stroke_width: get_float_attr(&entity.attributes, &["stroke_width"], 1.0),
+                    stroke_width,
                     filled: get_bool_attr(&entity.attributes, &["filled"], false),
+                    label: Some(get_string_attr(&entity.attributes, &["label"], ""))
+                        .filter(|label| !label.is_empty()),
+                    label_path: parse_text_path_options(&entity.attributes, &warnings),
+                    stroke_style: parse_stroke_style(&entity.attributes, stroke_width),
                 };
 
                 // Parse and apply transforms
@@ -1122,20 +2073,30 @@ impl JsonLinesParser {
                     .collect();
 
                 let options = TableOptions {
-                    header_fill_color: get_string_attr(
+                    header_fill_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["header_fill_color", "header_background"],
-                        "lightgray",
+                        &theme.table_header_fill,
+                        &warnings,
                     ),
-                    fill_color: get_string_attr(
+                    fill_color: get_color_attr_with_warnings(
                         &entity.attributes,
                         &["fill_color", "background"],
-                        "white",
+                        &theme.fill_color,
+                        &warnings,
+                    ),
+                    border_color: get_color_attr_with_warnings(
+                        &entity.attributes,
+                        &["border_color"],
+                        &theme.stroke_color,
+                        &warnings,
                     ),
-                    border_color: get_string_attr(&entity.attributes, &["border_color"], "black"),
                     border_width: get_int_attr(&entity.attributes, &["border_width"], 1) as usize,
-                    cell_padding: get_int_attr(&entity.attributes, &["cell_padding", "padding"], 20)
-                        as Float,
+                    cell_padding: get_int_attr(
+                        &entity.attributes,
+                        &["cell_padding", "padding"],
+                        theme.cell_padding as i64,
+                    ) as Float,
                     with_header: true,
                 };
 
@@ -1145,20 +2106,87 @@ impl JsonLinesParser {
             }
 
             "polyline" => {
-                let points = get_points_attr(&entity.attributes, "points")
+                let points = entity
+                    .attributes
+                    .get("points")
+                    .and_then(|value| value.as_array())
                     .ok_or_else(|| JsonLinesError::MissingAttribute("points".to_string()))?;
+                let references: Vec<LinePointReference> = points
+                    .iter()
+                    .filter_map(|point| self.parse_point_reference(point, "points", &warnings))
+                    .collect();
 
+                let stroke_width = get_float_attr(
+                    &entity.attributes,
+                    &["stroke_width"],
+                    theme.stroke_width as f64,
+                );
                 let options = LineOptions {
                     stroke_color: get_string_attr(
                         &entity.attributes,
                         &["stroke_color", "color"],
-                        "black",
+                        &theme.line_color,
                     ),
-                    stroke_width: get_float_attr(&entity.attributes, &["stroke_width"], 1.0),
+                    stroke_width,
+                    stroke_style: parse_stroke_style(&entity.attributes, stroke_width),
                 };
                 parse_transform_attributes(&entity.attributes, builder, entity_id.to_string());
 
-                Ok(builder.new_polyline(entity_id.to_string(), points, options))
+                let node = builder.new_polyline_with_references(
+                    entity_id.to_string(),
+                    references,
+                    options,
+                );
+                Ok(node)
+            }
+
+            "path" => {
+                let data = entity
+                    .attributes
+                    .get("d")
+                    .and_then(|value| value.as_str())
+                    .ok_or_else(|| JsonLinesError::MissingAttribute("d".to_string()))?;
+                let path = PathData::parse(data).map_err(|error| {
+                    JsonLinesError::InvalidStructure(format!(
+                        "Invalid path data in {}: {}",
+                        entity_id, error
+                    ))
+                })?;
+                let options = parse_path_options(&entity.attributes, &theme, &warnings);
+                parse_transform_attributes(&entity.attributes, builder, entity_id.to_string());
+
+                Ok(builder.new_path(entity_id.to_string(), path, options))
+            }
+
+            "spline" => {
+                let points = entity
+                    .attributes
+                    .get("points")
+                    .and_then(|value| value.as_array())
+                    .ok_or_else(|| JsonLinesError::MissingAttribute("points".to_string()))?;
+                let points: Vec<(Float, Float)> = points
+                    .iter()
+                    .filter_map(|point| {
+                        match self.parse_point_reference(point, "points", &warnings)? {
+                            LinePointReference::Value(x, y) => Some((x, y)),
+                            _ => {
+                                warnings.push(
+                                    "points",
+                                    format!(
+                                        "spline points must be coordinates, skipping {}",
+                                        point
+                                    ),
+                                );
+                                None
+                            }
+                        }
+                    })
+                    .collect();
+                let closed = get_bool_attr(&entity.attributes, &["closed"], false);
+                let options = parse_path_options(&entity.attributes, &theme, &warnings);
+                parse_transform_attributes(&entity.attributes, builder, entity_id.to_string());
+
+                Ok(builder.new_spline(entity_id.to_string(), points, closed, options))
             }
 
             "free_container" => {
@@ -1241,6 +2269,332 @@ impl JsonLinesParser {
         }
     }
 
+    /// `clip`/`overflow` on containers, plus `clip_path` and `mask`, which name
+    /// another entity that is built on its own to be used as the shape
+    fn parse_clip_attributes(
+        &self,
+        attrs: &Map<String, Value>,
+        entity_type: &str,
+        builder: &mut DiagramBuilder,
+        entity_id: &str,
+        warnings: &EntityWarnings,
+    ) {
+        let is_container = matches!(
+            entity_type,
+            "box" | "vstack" | "hstack" | "group" | "free_container" | "constraint_container"
+        );
+        let clip = is_container
+            && (get_bool_attr(attrs, &["clip"], false)
+                || get_string_attr(attrs, &["overflow"], "visible") == "hidden");
+
+        let options = ClipOptions {
+            clip,
+            clip_path: self.build_clip_shape(
+                attrs,
+                &["clip_path", "clip-path"],
+                builder,
+                entity_id,
+                warnings,
+            ),
+            mask: self.build_clip_shape(attrs, &["mask"], builder, entity_id, warnings),
+        };
+        if options.clip || options.clip_path.is_some() || options.mask.is_some() {
+            builder.set_clip(entity_id.to_string(), options);
+        }
+    }
+
+    /// Builds the entities repeated by `{"type":"pattern","entity":...}`
+    /// fills and the ones drawn as connector end markers, once per entity
+    fn build_pattern_tiles(
+        &self,
+        attrs: &Map<String, Value>,
+        builder: &mut DiagramBuilder,
+        entity_id: &str,
+        warnings: &EntityWarnings,
+    ) {
+        let pattern_ids = attrs.iter().filter_map(|(key, value)| {
+            let object = value
+                .as_object()
+                .filter(|object| is_pattern_object(object))?;
+            Some((key.as_str(), object.get("entity")?.as_str()?))
+        });
+        let marker_ids = ["marker_start", "marker_end"]
+            .iter()
+            .filter_map(|key| Some((*key, attrs.get(*key)?.as_str()?)))
+            .filter(|(_, name)| parse_marker_name(name).is_none())
+            .filter(|(_, name)| self.entities.contains_key(*name));
+        for (attribute, tile_id) in pattern_ids.chain(marker_ids) {
+            if tile_id == entity_id {
+                warnings.push(
+                    attribute,
+                    "an entity can't use itself as its tile".to_string(),
+                );
+                continue;
+            }
+            if builder.get_pattern_tile(tile_id.to_string()).is_some() {
+                continue;
+            }
+            match self.build_entity(tile_id, builder) {
+                Ok(tile) => builder.set_pattern_tile(tile_id.to_string(), tile),
+                Err(e) => warnings.push(attribute, format!("'{}': {}", tile_id, e)),
+            }
+        }
+    }
+
+    fn build_clip_shape(
+        &self,
+        attrs: &Map<String, Value>,
+        keys: &[&str],
+        builder: &mut DiagramBuilder,
+        entity_id: &str,
+        warnings: &EntityWarnings,
+    ) -> Option<DiagramTreeNode> {
+        let shape_id = get_string_attr(attrs, keys, "");
+        if shape_id.is_empty() {
+            return None;
+        }
+        if shape_id == entity_id {
+            warnings.push(
+                keys[0],
+                "an entity can't use itself as its shape".to_string(),
+            );
+            return None;
+        }
+        match self.build_entity(&shape_id, builder) {
+            Ok(shape) => Some(shape),
+            Err(e) => {
+                warnings.push(keys[0], format!("'{}': {}", shape_id, e));
+                None
+            }
+        }
+    }
+
+    /// Attributes of an entity with its style classes and theme colors applied.
+    /// Classes apply in the order they are listed and the entity's own
+    /// attributes win over all of them.
+    pub fn resolve_attributes(&self, entity: &JsonEntity, theme: &Theme) -> Map<String, Value> {
+        let mut attributes = Map::new();
+        for class in get_class_list(&entity.attributes, "class") {
+            let mut visiting = Vec::new();
+            let style = self.style_attributes(&class, &entity.id, &mut visiting);
+            merge_style_attributes(&mut attributes, style);
+        }
+
+        let mut own = entity.attributes.clone();
+        own.remove("class");
+        merge_style_attributes(&mut attributes, own);
+
+        for value in attributes.values_mut() {
+            resolve_theme_colors(value, theme, &entity.id);
+        }
+        attributes
+    }
+
+    /// Attributes of a style class, including the classes it `extends`
+    fn style_attributes(
+        &self,
+        name: &str,
+        entity_id: &str,
+        visiting: &mut Vec<String>,
+    ) -> Map<String, Value> {
+        let Some(style) = self.styles.get(name) else {
+            println!("⚠️ Unknown style class '{}' on '{}'", name, entity_id);
+            return Map::new();
+        };
+        if visiting.iter().any(|visited| visited == name) {
+            println!(
+                "⚠️ Style class '{}' extends itself, ignoring the cycle",
+                name
+            );
+            return Map::new();
+        }
+
+        visiting.push(name.to_string());
+        let mut attributes = Map::new();
+        for parent in get_class_list(style, "extends") {
+            let inherited = self.style_attributes(&parent, entity_id, visiting);
+            merge_style_attributes(&mut attributes, inherited);
+        }
+        visiting.pop();
+
+        let mut own = style.clone();
+        own.remove("extends");
+        merge_style_attributes(&mut attributes, own);
+        attributes
+    }
+
+    /// An attribute set on the nearest container listing the entity in its
+    /// children, along with that container's id
+    fn container_attr(&self, entity_id: &str, key: &str) -> Option<(&str, &Value)> {
+        let mut id = entity_id;
+        // Bounded by the entity count so a children cycle can't loop forever
+        for _ in 0..self.entities.len() {
+            let parent = self.entities.values().find(|entity| {
+                get_array_attr(&entity.attributes, "children")
+                    .is_some_and(|children| children.iter().any(|child| child == id))
+            })?;
+            if let Some(value) = parent.attributes.get(key) {
+                return Some((&parent.id, value));
+            }
+            id = &parent.id;
+        }
+        None
+    }
+
+    /// End marker named by `key`, an entity id draws that entity. Without one
+    /// the boolean `arrow_keys` ask for the plain arrow
+    fn parse_marker(
+        &self,
+        attributes: &Map<String, Value>,
+        key: &str,
+        arrow_keys: &[&str],
+        warnings: &EntityWarnings,
+    ) -> Marker {
+        let Some(name) = attributes.get(key).and_then(|v| v.as_str()) else {
+            return if get_bool_attr(attributes, arrow_keys, false) {
+                Marker::Arrow
+            } else {
+                Marker::None
+            };
+        };
+        if let Some(marker) = parse_marker_name(name) {
+            return marker;
+        }
+        if self.entities.contains_key(name) {
+            return Marker::Entity(name.to_string());
+        }
+        warnings.push(key, format!("unknown marker '{}'", name));
+        Marker::None
+    }
+
+    /// Points a connector passes through: `[x, y]` or `{"x":..,"y":..}` in absolute
+    /// coordinates, or `{"entity":"id","port":"top","dx":..,"dy":..}` relative to a
+    /// port of an entity, its center when no port is given
+    fn parse_waypoints(
+        &self,
+        attributes: &Map<String, Value>,
+        warnings: &EntityWarnings,
+    ) -> Vec<Waypoint> {
+        let Some(Value::Array(values)) = attributes.get("waypoints") else {
+            return Vec::new();
+        };
+        values
+            .iter()
+            .filter_map(|value| match value {
+                Value::Array(point) if point.len() >= 2 => Some(Waypoint::Absolute(
+                    point[0].as_f64().unwrap_or(0.0) as Float,
+                    point[1].as_f64().unwrap_or(0.0) as Float,
+                )),
+                Value::Object(point) if point.contains_key("entity") => {
+                    let (entity, port, offset) =
+                        self.parse_entity_point(point, "waypoints", warnings)?;
+                    Some(Waypoint::Relative {
+                        entity,
+                        port,
+                        offset,
+                    })
+                }
+                Value::Object(point) if point.contains_key("x") && point.contains_key("y") => {
+                    let (x, y) = get_point_attr(point, &["x"], &["y"], (0.0, 0.0));
+                    Some(Waypoint::Absolute(x, y))
+                }
+                _ => {
+                    warnings.push(
+                        "waypoints",
+                        format!("{} is not a point, skipping it", value),
+                    );
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// `{"entity":"id","port":"top","dx":..,"dy":..}`, the entity's center when no
+    /// port is given. `None` for unknown entities
+    fn parse_entity_point(
+        &self,
+        point: &Map<String, Value>,
+        key: &str,
+        warnings: &EntityWarnings,
+    ) -> Option<(EntityID, Port, (Float, Float))> {
+        let entity = get_string_attr(point, &["entity"], "");
+        if !self.entities.contains_key(&entity) {
+            warnings.push(
+                key,
+                format!("unknown entity '{}', skipping the point", entity),
+            );
+            return None;
+        }
+        let port = point
+            .get("port")
+            .and_then(parse_port_value)
+            .unwrap_or(Port::Center);
+        let offset = get_point_attr(point, &["dx"], &["dy"], (0.0, 0.0));
+        Some((entity, port, offset))
+    }
+
+    /// A line, polyline or arc point: `[x, y]` or `{"x":..,"y":..}` in the shape's own
+    /// coordinates, the id of an entity in the same container to use its position,
+    /// or a port of any entity like connector waypoints
+    fn parse_point_reference(
+        &self,
+        value: &Value,
+        key: &str,
+        warnings: &EntityWarnings,
+    ) -> Option<LinePointReference> {
+        match value {
+            Value::Array(point) if point.len() >= 2 => Some(LinePointReference::Value(
+                point[0].as_f64().unwrap_or(0.0) as Float,
+                point[1].as_f64().unwrap_or(0.0) as Float,
+            )),
+            Value::String(id) if self.entities.contains_key(id) => {
+                Some(LinePointReference::PointID(id.clone()))
+            }
+            Value::Object(point) if point.contains_key("entity") => {
+                let (entity, port, offset) = self.parse_entity_point(point, key, warnings)?;
+                Some(LinePointReference::EntityPort {
+                    entity,
+                    port,
+                    offset,
+                })
+            }
+            Value::Object(point) if point.contains_key("x") && point.contains_key("y") => {
+                let (x, y) = get_point_attr(point, &["x"], &["y"], (0.0, 0.0));
+                Some(LinePointReference::Value(x, y))
+            }
+            _ => {
+                warnings.push(key, format!("{} is not a point, skipping it", value));
+                None
+            }
+        }
+    }
+
+    /// Connector options that containers can set for the connectors they hold:
+    /// `distribute_ports`, and `bundle_connectors` which is either a bundle
+    /// name or `true` to bundle under the container id.
+    /// A connector's own `bundle: true` joins the default bundle
+    fn connector_layout_options(&self, entity: &JsonEntity) -> (bool, Option<String>) {
+        let inherited_distribute = self
+            .container_attr(&entity.id, "distribute_ports")
+            .and_then(|(_, value)| value.as_bool())
+            .unwrap_or(false);
+        let distribute_ports =
+            get_bool_attr(&entity.attributes, &["distribute_ports"], inherited_distribute);
+
+        let bundle_name = |container: &str, value: &Value| match value {
+            Value::Bool(true) => Some(container.to_string()),
+            Value::String(name) if !name.is_empty() => Some(name.clone()),
+            _ => None,
+        };
+        let bundle = match entity.attributes.get("bundle") {
+            Some(value) => bundle_name("default", value),
+            None => self
+                .container_attr(&entity.id, "bundle_connectors")
+                .and_then(|(container, value)| bundle_name(container, value)),
+        };
+        (distribute_ports, bundle)
+    }
+
     /// Validate that all child references exist
     pub fn validate(&self) -> Result<(), JsonLinesError> {
         for (id, entity) in &self.entities {
@@ -1514,6 +2868,1036 @@ mod tests {
         assert!(diagram.is_ok());
     }
 
+    #[test]
+    fn test_text_style_attributes() {
+        let input = r#"
+{"id":"root","type":"vstack","children":["t1","t2","t3"]}
+{"id":"t1","type":"text","content":"Title","font_weight":"bold","font_style":"italic","text_decoration":"underline line-through","letter_spacing":2,"text_transform":"uppercase"}
+{"id":"t2","type":"text","content":"Light","font_weight":"semibold"}
+{"id":"t3","type":"text","content":"Numeric","font_weight":300}
+"#;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        builder.set_measure_text_fn(|text, _| (text.len() as Float * 8.0, 16.0));
+        parser.build(&root_id, &mut builder).unwrap();
+
+        let t1 = builder.get_text("t1".to_string()).text_options.clone();
+        assert_eq!(t1.font_weight, 700);
+        assert_eq!(t1.font_style, FontStyle::Italic);
+        assert!(t1.text_decoration.underline && t1.text_decoration.line_through);
+        assert_eq!(t1.text_transform, TextTransform::Uppercase);
+        assert_eq!(t1.display_text("Title"), "TITLE");
+        // 5 chars * 8 + 5 * 2 letter spacing
+        assert_eq!(builder.measure("Title", &t1).0, 50.0);
+
+        assert_eq!(builder.get_text("t2".to_string()).text_options.font_weight, 600);
+        assert_eq!(builder.get_text("t3".to_string()).text_options.font_weight, 300);
+        assert_eq!(parse_font_weight("bold "), Some(700));
+        assert_eq!(parse_font_weight("light"), Some(300));
+    }
+
+    #[test]
+    fn test_labels_on_path() {
+        let input = r#"
+{"id":"root","type":"free_container","children":["a","b","c1","arc1"]}
+{"id":"a","type":"rect","width":20,"height":20}
+{"id":"b","type":"rect","width":20,"height":20}
+{"id":"c1","type":"connector","source":"a","target":"b","label":"calls","label_on_path":true,"label_alignment":"end","label_offset":6,"label_font_size":10}
+{"id":"arc1","type":"arc","radius":40,"start_angle":180,"end_angle":360,"label":"Top"}
+"#;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        builder.set_measure_text_fn(|text, _| (text.len() as Float * 8.0, 16.0));
+        let root = parser.build(&root_id, &mut builder).unwrap();
+
+        let connector = builder.get_connector("c1".to_string());
+        let label_path = connector.options.label_path.as_ref().unwrap();
+        assert_eq!(label_path.alignment, LabelAlignment::End);
+        assert_eq!(label_path.offset, 6.0);
+        assert_eq!(label_path.text_options.font_size, 10.0);
+        assert_eq!(connector.options.label_alignment, LabelAlignment::End);
+
+        // The label is painted with the connector, no separate text entity in the tree
+        fn has_entity(node: &DiagramTreeNode, id: &str) -> bool {
+            node.entity_id == id || node.children.iter().any(|c| has_entity(c, id))
+        }
+        assert!(!has_entity(&root, "c1_label_0"));
+        assert!(builder.get_connector("c1".to_string()).labels.is_empty());
+
+        let arc = builder.get_arc("arc1".to_string());
+        assert_eq!(arc.arc_options.label.as_deref(), Some("Top"));
+        assert_eq!(arc.arc_options.label_path.alignment, LabelAlignment::Center);
+    }
+
+    #[test]
+    fn test_gradient_fills() {
+        let input = r##"
+{"id":"root","type":"vstack","children":["r1","r2","r3"]}
+{"id":"r1","type":"rect","width":50,"height":20,"fill":"linear-gradient(to right, red, rgb(0, 128, 0), #0000ff 80%)"}
+{"id":"r2","type":"rect","width":50,"height":20,"fill":"radial-gradient(circle at 30% 40%, white, navy)"}
+{"id":"r3","type":"rect","width":50,"height":20,"fill":{"type":"linear","angle":180,"stops":[{"offset":0,"color":"black"},{"offset":"100%","opacity":0.2}]}}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        parser.build(&root_id, &mut builder).unwrap();
+
+        match &builder.get_rectangle("r1".to_string()).rect_options.fill_color {
+            Fill::LinearGradient(gradient) => {
+                assert_eq!(
+                    (gradient.x1, gradient.y1, gradient.x2, gradient.y2),
+                    (0.0, 0.5, 1.0, 0.5)
+                );
+                let offsets: Vec<Float> = gradient.stops.iter().map(|s| s.offset()).collect();
+                assert_eq!(offsets, vec![0.0, 0.4, 0.8]);
+                assert_eq!(
+                    gradient.stops[1],
+                    GradientStop::ColorStop {
+                        offset: 0.4,
+                        color: "rgb(0, 128, 0)".to_string()
+                    }
+                );
+            }
+            other => panic!("expected linear gradient, got {:?}", other),
+        }
+
+        match &builder.get_rectangle("r2".to_string()).rect_options.fill_color {
+            Fill::RadialGradient(gradient) => {
+                assert_eq!((gradient.cx, gradient.cy, gradient.r), (0.3, 0.4, 0.5));
+                assert_eq!(gradient.stops.len(), 2);
+            }
+            other => panic!("expected radial gradient, got {:?}", other),
+        }
+
+        match &builder.get_rectangle("r3".to_string()).rect_options.fill_color {
+            Fill::LinearGradient(gradient) => {
+                // Opacity stops reuse the previous color
+                let stops = resolve_gradient_stops(&gradient.stops);
+                assert_eq!(stops[1].color, "black");
+                assert_eq!((stops[1].offset, stops[1].opacity), (1.0, 0.2));
+            }
+            other => panic!("expected linear gradient, got {:?}", other),
+        }
+
+        assert_eq!(parse_css_gradient("linear-gradient(to nowhere, red)"), None);
+        assert_eq!(parse_css_gradient("linear-gradient(90deg)"), None);
+        assert_eq!(parse_css_gradient("linear-gradient(red, bleu)"), None);
+    }
+
+    #[test]
+    fn test_color_validation() {
+        let input = r##"
+{"id":"root","type":"vstack","children":["t1","r1","r2","r3"]}
+{"id":"t1","type":"text","content":"Hi","color":"hsl(120 100% 25% / 0.5)"}
+{"id":"r1","type":"rect","width":50,"height":20,"fill":"#abc","stroke_color":"rgb(1, 2)"}
+{"id":"r2","type":"rect","width":50,"height":20,"fill":"blurple","stroke_color":"teal"}
+{"id":"r3","type":"rect","width":50,"height":20,"fill":"light gray"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        parser.build(&root_id, &mut builder).unwrap();
+
+        // Valid colors keep their original spelling
+        let text = builder.get_text("t1".to_string());
+        assert_eq!(text.text_options.text_color, "hsl(120 100% 25% / 0.5)");
+
+        let r1 = builder.get_rectangle("r1".to_string());
+        assert_eq!(r1.rect_options.fill_color, Fill::Color("#abc".to_string()));
+        assert_eq!(r1.rect_options.stroke_color, "black");
+
+        let r2 = builder.get_rectangle("r2".to_string());
+        assert_eq!(r2.rect_options.fill_color, Fill::Color("white".to_string()));
+        assert_eq!(r2.rect_options.stroke_color, "teal");
+
+        // Spellings accepted before CSS parsing still work
+        let r3 = builder.get_rectangle("r3".to_string());
+        assert_eq!(
+            r3.rect_options.fill_color,
+            Fill::Color("light gray".to_string())
+        );
+
+        // Each fallback is reported with the entity, line and attribute it came from
+        let warnings = parser.warnings();
+        assert_eq!(warnings.len(), 2);
+        let stroke = warnings
+            .iter()
+            .find(|w| w.attribute == "stroke_color")
+            .unwrap();
+        assert_eq!((stroke.entity_id.as_str(), stroke.line), ("r1", Some(4)));
+        let fill = warnings.iter().find(|w| w.attribute == "fill").unwrap();
+        assert_eq!((fill.entity_id.as_str(), fill.line), ("r2", Some(5)));
+        assert!(fill
+            .to_string()
+            .starts_with("Invalid 'fill' on 'r2' (line 5): "));
+        assert!(fill.message.contains("blurple"));
+    }
+
+    #[test]
+    fn test_opacity_attributes() {
+        let input = r##"
+{"id":"root","type":"vstack","opacity":0.5,"children":["r1","r2","e1"]}
+{"id":"r1","type":"rect","width":50,"height":20,"fill_opacity":"25%","stroke_opacity":2}
+{"id":"r2","type":"rect","width":50,"height":20}
+{"id":"e1","type":"ellipse","rx":10,"ry":10,"opacity":"half"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        parser.build(&root_id, &mut builder).unwrap();
+
+        assert_eq!(builder.get_opacity("root".to_string()).opacity, 0.5);
+
+        let r1 = builder.get_opacity("r1".to_string());
+        assert_eq!(
+            (r1.opacity, r1.fill_opacity, r1.stroke_opacity),
+            (1.0, 0.25, 1.0)
+        );
+
+        // Opaque entities don't get an entry, invalid values fall back to 1
+        assert!(!builder.opacities.contains_key("r2"));
+        assert!(!builder.opacities.contains_key("e1"));
+        assert_eq!(
+            builder.get_opacity("r2".to_string()),
+            OpacityOptions::default()
+        );
+    }
+
+    #[test]
+    fn test_stroke_style_attributes() {
+        let input = r##"
+{"id":"root","type":"vstack","children":["l1","l2","p1","r1","a1"]}
+{"id":"l1","type":"line","x2":50,"stroke_dasharray":"6, 3","stroke_dashoffset":2,"stroke_linecap":"square"}
+{"id":"l2","type":"line","x2":50,"stroke_width":2,"stroke_dasharray":"dotted"}
+{"id":"p1","type":"polyline","points":[[0,0],[10,10]],"stroke_dasharray":[4,2,1],"stroke_linejoin":"round"}
+{"id":"r1","type":"rect","width":50,"height":20,"border_width":3,"stroke_dasharray":"dashed"}
+{"id":"a1","type":"arc","radius":10,"stroke_dasharray":"4 x","stroke_linecap":"pointy"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        parser.build(&root_id, &mut builder).unwrap();
+
+        let l1 = &builder.get_line("l1".to_string()).line_options.stroke_style;
+        assert_eq!(l1.dash_array, vec![6.0, 3.0]);
+        assert_eq!(l1.dash_offset, 2.0);
+        assert_eq!(l1.line_cap, LineCap::Square);
+        assert_eq!(l1.line_join, LineJoin::Miter);
+
+        // Keywords scale with the stroke width, dots need round caps
+        let l2 = &builder.get_line("l2".to_string()).line_options.stroke_style;
+        assert_eq!(l2.dash_array, vec![0.0, 4.0]);
+        assert_eq!(l2.line_cap, LineCap::Round);
+        let r1 = &builder
+            .get_rectangle("r1".to_string())
+            .rect_options
+            .stroke_style;
+        assert_eq!(r1.dash_array, vec![12.0, 6.0]);
+
+        let p1 = &builder
+            .get_polyline("p1".to_string())
+            .line_options
+            .stroke_style;
+        assert_eq!(p1.dash_array, vec![4.0, 2.0, 1.0]);
+        assert_eq!(p1.line_join, LineJoin::Round);
+
+        // Invalid values fall back to a plain solid stroke
+        let a1 = &builder.get_arc("a1".to_string()).arc_options.stroke_style;
+        assert_eq!(*a1, StrokeStyle::default());
+        assert!(!a1.is_dashed());
+    }
+
+    #[test]
+    fn test_shadow_attribute() {
+        let input = r##"
+{"id":"root","type":"vstack","children":["b1","r1","e1","r2","l1"]}
+{"id":"b1","type":"box","shadow":true,"children":["t1"]}
+{"id":"t1","type":"text","content":"card"}
+{"id":"r1","type":"rect","width":50,"height":20,"shadow":"2px 4px 8px -1px rgba(0,0,0,0.3)"}
+{"id":"e1","type":"ellipse","rx":10,"ry":10,"shadow":{"x":1,"blur":3,"color":"navy"}}
+{"id":"r2","type":"rect","width":50,"height":20,"shadow":"2 4 8 1 2"}
+{"id":"l1","type":"line","x2":50,"shadow":true}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        parser.build(&root_id, &mut builder).unwrap();
+
+        assert_eq!(
+            builder.get_shadow("b1".to_string()),
+            Some(&ShadowOptions::default())
+        );
+
+        let r1 = builder.get_shadow("r1".to_string()).unwrap();
+        assert_eq!(
+            (r1.offset_x, r1.offset_y, r1.blur, r1.spread),
+            (2.0, 4.0, 8.0, -1.0)
+        );
+        assert_eq!(r1.color, "rgba(0,0,0,0.3)");
+        assert_eq!(r1.std_deviation(), 4.0);
+
+        let e1 = builder.get_shadow("e1".to_string()).unwrap();
+        assert_eq!((e1.offset_x, e1.offset_y, e1.blur), (1.0, 2.0, 3.0));
+        assert_eq!(e1.color, "navy");
+
+        // Too many lengths, and shapes without shadow support, get none
+        assert!(builder.get_shadow("r2".to_string()).is_none());
+        assert!(builder.get_shadow("l1".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_style_classes() {
+        let input = r##"
+{"id":"root","type":"vstack","children":["card","plain","loop"]}
+{"type":"style","id":"base","stroke_color":"gray","border_radius":4,"padding":6}
+{"type":"style","id":"card","extends":"base","background":"ivory","padding":12}
+{"type":"style","id":"alert","fill":"mistyrose","border_color":"red"}
+{"type":"style","id":"a","extends":"b","stroke_width":3}
+{"type":"style","id":"b","extends":"a","border_radius":9}
+{"id":"card","type":"box","class":"card alert","fill":"white","children":["t1"]}
+{"id":"t1","type":"text","content":"Hi"}
+{"id":"plain","type":"rect","class":["base","missing"],"width":10,"height":10}
+{"id":"loop","type":"rect","class":"a","width":10,"height":10}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        assert_eq!(root_id, "root");
+        assert_eq!(parser.styles.len(), 5);
+        assert!(!parser.entities.contains_key("base"));
+
+        let mut builder = DiagramBuilder::new();
+        builder.set_measure_text_fn(|text, _| (text.len() as Float * 8.0, 16.0));
+        parser.build(&root_id, &mut builder).unwrap();
+
+        // Later classes win over earlier ones and inline attributes win over
+        // classes, even when they use a different alias
+        let card = &builder.get_box("card".to_string()).box_options;
+        assert_eq!(card.fill_color, Fill::Color("white".to_string()));
+        assert_eq!(card.stroke_color, "red");
+        assert_eq!(card.padding, 12.0);
+        assert_eq!(card.border_radius, 4.0);
+
+        // Unknown classes are skipped
+        let plain = &builder.get_rectangle("plain".to_string()).rect_options;
+        assert_eq!(plain.stroke_color, "gray");
+
+        // Cycles stop at the first repeated class
+        let looped = &builder.get_rectangle("loop".to_string()).rect_options;
+        assert_eq!(looped.stroke_width, 3.0);
+        assert_eq!(looped.border_radius, 9.0);
+    }
+
+    #[test]
+    fn test_theme_defaults() {
+        let input = r##"
+{"id":"root","type":"vstack","children":["t1","r1","r2"]}
+{"id":"t1","type":"text","content":"Hi"}
+{"id":"r1","type":"rect","width":10,"height":10,"fill":"$accent","shadow":{"color":"$muted"}}
+{"id":"r2","type":"rect","width":10,"height":10,"fill":"$unknown","stroke_color":"teal"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        builder.set_theme(Theme::dark());
+        parser.build(&root_id, &mut builder).unwrap();
+
+        let dark = Theme::dark();
+        let text = &builder.get_text("t1".to_string()).text_options;
+        assert_eq!(text.text_color, dark.text_color);
+        assert_eq!(text.font_family, dark.font_family);
+
+        let r1 = &builder.get_rectangle("r1".to_string()).rect_options;
+        assert_eq!(r1.fill_color, Fill::Color(dark.colors["accent"].clone()));
+        assert_eq!(r1.stroke_color, dark.stroke_color);
+        let shadow = builder.get_shadow("r1".to_string()).unwrap();
+        assert_eq!(shadow.color, dark.colors["muted"]);
+
+        // Unknown palette names fall back like any invalid color
+        let r2 = &builder.get_rectangle("r2".to_string()).rect_options;
+        assert_eq!(r2.fill_color, Fill::Color(dark.fill_color.clone()));
+        assert_eq!(r2.stroke_color, "teal");
+
+        // Swapping the theme rebuilds the same document with the new defaults
+        builder.clear_cache();
+        builder.set_theme(Theme::light());
+        parser.build(&root_id, &mut builder).unwrap();
+        let text = &builder.get_text("t1".to_string()).text_options;
+        assert_eq!(text.text_color, "black");
+    }
+
+    #[test]
+    fn test_pattern_fills() {
+        let input = r##"
+{"id":"root","type":"vstack","children":["r1","r2","b1","r3"]}
+{"id":"r1","type":"rect","width":50,"height":20,"fill":{"type":"pattern","pattern":"cross","spacing":6,"color":"red","background":"white"}}
+{"id":"r2","type":"rect","width":50,"height":20,"fill":{"type":"pattern","entity":"tile","spacing":2}}
+{"id":"tile","type":"ellipse","rx":4,"ry":4}
+{"id":"b1","type":"box","fill":{"type":"pattern","pattern":"dots","angle":30},"children":["t1"]}
+{"id":"t1","type":"text","content":"hatched"}
+{"id":"r3","type":"rect","width":50,"height":20,"fill":{"type":"pattern","pattern":"waves"}}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        let r1 = &builder
+            .get_rectangle("r1".to_string())
+            .rect_options
+            .fill_color;
+        assert_eq!(
+            *r1,
+            Fill::Pattern(PatternFill {
+                spacing: 6.0,
+                color: "red".to_string(),
+                background: Some("white".to_string()),
+                ..PatternFill::new(Pattern::Cross)
+            })
+        );
+
+        // Entity tiles are built and laid out at the origin
+        match &builder
+            .get_rectangle("r2".to_string())
+            .rect_options
+            .fill_color
+        {
+            Fill::Pattern(pattern) => {
+                assert_eq!(pattern.pattern, Pattern::Entity("tile".to_string()));
+                let size = builder.get_size("tile".to_string());
+                assert_eq!(pattern.tile_size(size), (10.0, 10.0));
+            }
+            other => panic!("expected pattern, got {:?}", other),
+        }
+        assert!(builder.get_pattern_tile("tile".to_string()).is_some());
+        assert_eq!(builder.get_local_position("tile".to_string()), (0.0, 0.0));
+
+        match &builder.get_box("b1".to_string()).box_options.fill_color {
+            Fill::Pattern(pattern) => {
+                assert_eq!(pattern.pattern, Pattern::Dots);
+                assert_eq!((pattern.angle, pattern.spacing), (30.0, 8.0));
+            }
+            other => panic!("expected pattern, got {:?}", other),
+        }
+
+        // Unknown hatches fall back to the default fill
+        let r3 = &builder
+            .get_rectangle("r3".to_string())
+            .rect_options
+            .fill_color;
+        assert!(!r3.is_pattern());
+    }
+
+    #[test]
+    fn test_layer_attributes() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["a","b","c","panel","conn1"]}
+{"id":"a","type":"rect","x":0,"y":0,"width":40,"height":40,"z_index":2}
+{"id":"b","type":"rect","x":10,"y":10,"width":40,"height":40,"z-index":-1,"mix_blend_mode":"multiply"}
+{"id":"c","type":"rect","x":20,"y":20,"width":40,"height":40,"layer":"annotations","z_index":5}
+{"id":"panel","type":"free_container","x":100,"y":0,"children":["bg","note"]}
+{"id":"bg","type":"rect","x":0,"y":0,"width":80,"height":80,"layer":"background","mix-blend-mode":"glow"}
+{"id":"note","type":"rect","x":10,"y":10,"width":20,"height":20,"layer":"annotations"}
+{"id":"conn1","type":"connector","source":"a","target":"bg","layer":"stickers"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        let b = builder.get_layer_options("b".to_string());
+        assert_eq!((b.z_index, b.blend_mode), (-1, BlendMode::Multiply));
+        // Unknown blend modes and layers fall back to the defaults
+        let bg = builder.get_layer_options("bg".to_string());
+        assert_eq!(
+            (bg.layer, bg.blend_mode),
+            (Some(Layer::Background), BlendMode::Normal)
+        );
+        assert_eq!(builder.get_layer_options("conn1".to_string()).layer, None);
+
+        // Siblings are painted by z_index, moved entities are left out
+        let order: Vec<&str> = builder
+            .paint_order(&root)
+            .iter()
+            .map(|child| child.entity_id.as_str())
+            .collect();
+        assert_eq!(order, vec!["b", "panel", "a"]);
+
+        let ids = |layer| -> Vec<String> {
+            builder
+                .layer_entries(layer)
+                .iter()
+                .map(|entry| entry.node.entity_id.clone())
+                .collect()
+        };
+        assert_eq!(ids(Layer::Background), vec!["bg"]);
+        assert_eq!(ids(Layer::Annotations), vec!["note", "c"]);
+        // Connectors are painted with the group wrapping them and their label
+        assert_eq!(ids(Layer::Connectors), vec!["conn1_wrapper"]);
+
+        // Moved entities keep the transforms of the containers they're in,
+        // connectors are already in absolute coordinates
+        let note = &builder.layer_roots["note"];
+        assert_eq!(
+            note.ancestors,
+            vec!["root".to_string(), "panel".to_string()]
+        );
+        assert!(builder.layer_roots["conn1_wrapper"].ancestors.is_empty());
+    }
+
+    #[test]
+    fn test_connector_routing() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["a","b","wall","routed","fixed"]}
+{"id":"a","type":"rect","x":0,"y":40,"width":40,"height":40}
+{"id":"b","type":"rect","x":200,"y":40,"width":40,"height":40}
+{"id":"wall","type":"rect","x":100,"y":0,"width":20,"height":120}
+{"id":"routed","type":"connector","source":"a","source_port":"right","target":"b","target_port":"left","connector_type":"orthogonal","routing":"avoid","clearance":5}
+{"id":"fixed","type":"connector","source":"a","target":"b","connector_type":"orthogonal","routing":"hvh"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        // Around the wall, the detour keeps 5 units away from it
+        let routed = builder.get_connector("routed".to_string());
+        assert_eq!(routed.options.routing_strategy, OrthogonalRoutingStrategy::Avoid);
+        let waypoints = &routed.waypoints;
+        assert_eq!(waypoints.len(), 4);
+        assert_eq!((waypoints[0].0, waypoints[3].0), (95.0, 125.0));
+        assert!(waypoints[1].1 == -5.0 || waypoints[1].1 == 125.0);
+
+        // The connector bounds cover the detour
+        let (_, y) = builder.get_local_position("routed".to_string());
+        let (_, height) = builder.get_size("routed".to_string());
+        assert!(y <= waypoints[1].1 && y + height >= waypoints[1].1);
+
+        let fixed = builder.get_connector("fixed".to_string());
+        assert_eq!(fixed.options.routing_strategy, OrthogonalRoutingStrategy::HVH);
+        assert!(fixed.waypoints.is_empty());
+    }
+
+    #[test]
+    fn test_connector_routing_default() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["a","b","wall","plain","unknown"]}
+{"id":"a","type":"rect","x":0,"y":40,"width":40,"height":40}
+{"id":"b","type":"rect","x":200,"y":80,"width":40,"height":40}
+{"id":"wall","type":"rect","x":100,"y":0,"width":20,"height":160}
+{"id":"plain","type":"connector","source":"a","source_port":"right","target":"b","target_port":"left","connector_type":"orthogonal"}
+{"id":"unknown","type":"connector","source":"a","target":"b","connector_type":"orthogonal","routing":"around"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        // Not routed around the wall, the path keeps its one step halfway
+        let plain = builder.get_connector("plain".to_string());
+        assert_eq!(
+            plain.options.routing_strategy,
+            OrthogonalRoutingStrategy::Auto
+        );
+        assert!(plain.waypoints.is_empty());
+        let (start, end) = builder.get_connector_ends(plain);
+        let path = crate::geometry::connector_path(&plain.options, start, end, &plain.waypoints);
+        assert_eq!(path.to_svg_path(), "M 40 60 L 120 60 L 120 100 L 200 100");
+
+        let unknown = builder.get_connector("unknown".to_string());
+        assert_eq!(
+            unknown.options.routing_strategy,
+            OrthogonalRoutingStrategy::Auto
+        );
+        assert!(unknown.waypoints.is_empty());
+    }
+
+    #[test]
+    fn test_connector_arrangement() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["a","b","c","d1","d2","fan","e","f1","f2","p1","p2","b1","b2"]}
+{"id":"a","type":"rect","x":0,"y":0,"width":40,"height":40}
+{"id":"b","type":"rect","x":200,"y":0,"width":40,"height":40}
+{"id":"p1","type":"connector","source":"a","target":"b"}
+{"id":"p2","type":"connector","source":"b","target":"a"}
+{"id":"c","type":"rect","x":0,"y":100,"width":90,"height":40}
+{"id":"d1","type":"rect","x":200,"y":200,"width":40,"height":40}
+{"id":"d2","type":"rect","x":0,"y":200,"width":40,"height":40}
+{"id":"fan","type":"free_container","distribute_ports":true,"children":["c1","c2","c3"]}
+{"id":"c1","type":"connector","source":"c","source_port":"bottom","target":"d1","target_port":"top"}
+{"id":"c2","type":"connector","source":"c","source_port":"bottom","target":"d2","target_port":"top"}
+{"id":"c3","type":"connector","source":"c","source_port":"bottom","target":"d2","target_port":"top","distribute_ports":false}
+{"id":"e","type":"rect","x":0,"y":300,"width":40,"height":40}
+{"id":"f1","type":"rect","x":200,"y":260,"width":40,"height":40}
+{"id":"f2","type":"rect","x":200,"y":340,"width":40,"height":40}
+{"id":"b1","type":"connector","source":"e","source_port":"right","target":"f1","target_port":"left","connector_type":"orthogonal","bundle":"out"}
+{"id":"b2","type":"connector","source":"f2","source_port":"left","target":"e","target_port":"right","connector_type":"orthogonal","bundle":"out"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        let ends = |id: &str| builder.get_connector_ends(builder.get_connector(id.to_string()));
+
+        // Same two points in either direction, moved apart around the center line
+        assert_eq!(ends("p1"), ((40.0, 15.0), (200.0, 15.0)));
+        assert_eq!(ends("p2"), ((200.0, 25.0), (40.0, 25.0)));
+
+        // Spread along the bottom side, the end heading right last
+        let c1 = builder.get_connector("c1".to_string());
+        assert!(c1.options.distribute_ports && c1.options.bundle.is_none());
+        assert_eq!(ends("c2").0, (30.0, 140.0));
+        assert_eq!(ends("c1").0, (60.0, 140.0));
+        assert_eq!(ends("c3").0, (45.0, 140.0));
+
+        // Both leave e through the trunk up to halfway, then split
+        let b1 = builder.get_connector("b1".to_string());
+        assert_eq!(b1.options.bundle.as_deref(), Some("out"));
+        assert_eq!(b1.waypoints, vec![(120.0, 320.0), (120.0, 280.0)]);
+        let b2 = builder.get_connector("b2".to_string());
+        assert_eq!(b2.waypoints, vec![(120.0, 360.0), (120.0, 320.0)]);
+    }
+
+    #[test]
+    fn test_connector_ports() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["a","b","c","named","outline","facing","missing"]}
+{"id":"a","type":"rect","x":0,"y":0,"width":40,"height":40,"ports":{"out":{"side":"right","offset":0.25},"in":"bottom"}}
+{"id":"b","type":"ellipse","x":200,"y":0,"rx":20,"ry":20}
+{"id":"c","type":"rect","x":0,"y":200,"width":40,"height":40,"ports":{"bad":{"side":"middle"},"alias":"out"}}
+{"id":"named","type":"connector","source":"a","source_port":"out","target":"c","target_port":{"side":"top","offset":0.75}}
+{"id":"outline","type":"connector","source":"b","target":"a","target_port":"in"}
+{"id":"facing","type":"connector","source":"a","source_port":"auto","target":"c","target_port":"auto"}
+{"id":"missing","type":"connector","source":"a","source_port":"nowhere","target":"b","target_port":"center","loop_side":"inside"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        let connector = |id: &str| builder.get_connector(id.to_string());
+        let ends = |id: &str| builder.get_connector_ends(connector(id));
+        let close = |a: (Float, Float), b: (Float, Float)| {
+            (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01
+        };
+
+        // Declared on the entity, and a quarter of the way along a side
+        assert_eq!(connector("named").options.source_port, Port::Named("out".to_string()));
+        assert_eq!(ends("named"), ((40.0, 10.0), (30.0, 200.0)));
+
+        // Without a port the end sits on the circle, facing the other end
+        assert_eq!(connector("outline").options.source_port, Port::Boundary);
+        let (start, end) = ends("outline");
+        assert_eq!(end, (20.0, 40.0));
+        let (dx, dy): (Float, Float) = (20.0 - 220.0, 40.0 - 20.0);
+        let length = (dx * dx + dy * dy).sqrt();
+        assert!(close(start, (220.0 + dx / length * 20.0, 20.0 + dy / length * 20.0)));
+
+        // The sides facing each other
+        let ports = builder.get_connector_ports(connector("facing"));
+        assert_eq!(ports, (Port::Bottom, Port::Top));
+        assert_eq!(ends("facing"), ((20.0, 40.0), (20.0, 200.0)));
+
+        // Unknown names fall back to the boundary
+        assert_eq!(ends("missing"), ((40.0, 20.0), (220.0, 20.0)));
+
+        // Invalid port declarations are reported and skipped
+        assert_eq!(builder.get_named_port(&"c".to_string(), "bad"), None);
+        assert_eq!(builder.get_named_port(&"c".to_string(), "alias"), None);
+        let warnings = parser.warnings();
+        let reported = |id: &str, attribute: &str| {
+            warnings
+                .iter()
+                .filter(|w| w.entity_id == id && w.attribute == attribute)
+                .count()
+        };
+        assert_eq!(reported("c", "ports"), 2);
+        assert_eq!(reported("missing", "loop_side"), 1);
+        assert_eq!(warnings.len(), 3);
+    }
+
+    #[test]
+    fn test_connector_ports_in_transformed_containers() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["a","outer","scaled","turned","grown"]}
+{"id":"a","type":"rect","x":0,"y":0,"width":20,"height":20}
+{"id":"outer","type":"free_container","x":200,"y":100,"rotation":90,"children":["inner"]}
+{"id":"inner","type":"free_container","x":0,"y":0,"rotation":90,"children":["r1"]}
+{"id":"r1","type":"rect","x":10,"y":0,"width":20,"height":10}
+{"id":"scaled","type":"free_container","x":300,"y":0,"scale":2,"children":["r2"]}
+{"id":"r2","type":"rect","x":10,"y":10,"width":20,"height":20}
+{"id":"turned","type":"connector","source":"a","source_port":"right","target":"r1","target_port":"top"}
+{"id":"grown","type":"connector","source":"a","source_port":"right","target":"r2","target_port":{"side":"left","offset":0.25}}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        let ends = |id: &str| builder.get_connector_ends(builder.get_connector(id.to_string()));
+        let world = |id: &str, point: (Float, Float)| {
+            builder.get_absolute_transform(&id.to_string()).transform_point(point.0, point.1)
+        };
+        let close = |a: (Float, Float), b: (Float, Float)| {
+            (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01
+        };
+
+        // Two quarter turns put the top of r1 upside down, left of the outer origin
+        let (start, end) = ends("turned");
+        assert_eq!(start, (20.0, 10.0));
+        assert!(close(end, world("r1", (10.0, 0.0))));
+        assert!(close(end, (180.0, 100.0)));
+        assert!(close(builder.get_absolute_center(&"r1".to_string()), (180.0, 95.0)));
+
+        // The container's scale applies to the child's offset and to its port
+        let (_, end) = ends("grown");
+        assert!(close(end, world("r2", (0.0, 5.0))));
+        assert!(close(end, (320.0, 30.0)));
+    }
+
+    #[test]
+    fn test_connector_loops_and_waypoints() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["a","b","c","d","up","side","through","bent"]}
+{"id":"a","type":"rect","x":0,"y":0,"width":40,"height":40}
+{"id":"b","type":"rect","x":200,"y":0,"width":40,"height":40}
+{"id":"c","type":"rect","x":0,"y":200,"width":40,"height":40}
+{"id":"d","type":"rect","x":200,"y":200,"width":40,"height":40}
+{"id":"up","type":"connector","source":"a","target":"a"}
+{"id":"side","type":"connector","source":"b","target":"b","source_port":"auto","target_port":"auto","loop_side":"right","connector_type":"orthogonal"}
+{"id":"through","type":"connector","source":"c","target":"d","waypoints":[[100,300],{"entity":"d","port":"bottom","dy":20},{"nowhere":1}]}
+{"id":"bent","type":"connector","source":"a","source_port":"right","target":"c","target_port":"right","connector_type":"orthogonal","routing":"hvh","waypoints":[{"x":80,"y":100}]}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        let connector = |id: &str| builder.get_connector(id.to_string());
+        let ends = |id: &str| builder.get_connector_ends(connector(id));
+
+        // Loops leave and return a quarter of the way in from the ends of the side
+        assert_eq!(ends("up"), ((10.0, 0.0), (30.0, 0.0)));
+        assert_eq!(
+            connector("up").waypoints,
+            vec![(10.0, -30.0), (30.0, -30.0)]
+        );
+        assert_eq!(ends("side"), ((240.0, 10.0), (240.0, 30.0)));
+        assert_eq!(
+            connector("side").waypoints,
+            vec![(270.0, 10.0), (270.0, 30.0)]
+        );
+
+        // Waypoints can follow a port, the ends face the nearest one
+        let through = connector("through");
+        assert_eq!(
+            through.options.waypoints,
+            vec![
+                Waypoint::Absolute(100.0, 300.0),
+                Waypoint::Relative {
+                    entity: "d".to_string(),
+                    port: Port::Bottom,
+                    offset: (0.0, 20.0),
+                },
+            ]
+        );
+        assert_eq!(through.waypoints, vec![(100.0, 300.0), (220.0, 260.0)]);
+        let warnings = parser.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].entity_id, "through");
+        assert_eq!(warnings[0].attribute, "waypoints");
+        let (start, _) = ends("through");
+        assert!((start.0 - 40.0).abs() < 0.01 && (start.1 - 240.0).abs() < 0.01);
+
+        // Orthogonal legs bend out of the start side and into the end side
+        assert_eq!(
+            connector("bent").waypoints,
+            vec![(80.0, 20.0), (80.0, 100.0), (80.0, 220.0)]
+        );
+    }
+
+    #[test]
+    fn test_bound_shape_points() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["stack","note","call","bridge","tie"]}
+{"id":"stack","type":"vstack","x":0,"y":0,"children":["s1","s2"]}
+{"id":"s1","type":"rect","width":40,"height":20}
+{"id":"s2","type":"rect","width":40,"height":30}
+{"id":"note","type":"rect","x":200,"y":0,"width":40,"height":40}
+{"id":"call","type":"polyline","x":10,"y":10,"points":[{"entity":"note","port":"left"},[100,0],{"entity":"s2","port":"right","dx":5},"missing",{"entity":"ghost"}]}
+{"id":"bridge","type":"arc","radius":10,"start_point":{"entity":"note","port":"top_left"},"end_point":{"entity":"note","port":"top_right"}}
+{"id":"tie","type":"line","x":0,"y":100,"start_point":{"entity":"s1","port":"right"},"end_point":[0,0]}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        // Literal points stay in the polyline's coordinates, the others follow
+        // their entity wherever the stack put it
+        let call = builder.get_polyline("call".to_string());
+        assert_eq!(call.references.len(), 3);
+        assert_eq!(call.points, vec![(190.0, 10.0), (100.0, 0.0), (35.0, 25.0)]);
+        assert_eq!(builder.get_size("call".to_string()), (155.0, 25.0));
+        // Points that can't be used are reported and skipped
+        let warnings = parser.warnings();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings
+            .iter()
+            .all(|w| w.entity_id == "call" && w.attribute == "points"));
+
+        // Too far apart for the radius, the arc grows into a half circle over the note
+        let bridge = builder.get_arc("bridge".to_string());
+        assert_eq!(bridge.radius, 20.0);
+        assert_eq!(builder.absolute_positions["bridge"], (200.0, -20.0));
+        let (start, end) = bridge.normalize_angles();
+        assert!((start - 180.0).abs() < 0.01 && end.abs() < 0.01);
+
+        let tie = builder.get_line("tie".to_string());
+        assert_eq!(
+            builder.resolve_line_point(&tie.entity, &tie.start),
+            (40.0, -90.0)
+        );
+        assert_eq!(builder.get_size("tie".to_string()), (40.0, 90.0));
+    }
+
+    #[test]
+    fn test_path_and_spline_shapes() {
+        use crate::geometry::PathCommand;
+
+        let input = r##"
+{"id":"root","type":"free_container","children":["wave","frame","blob"]}
+{"id":"wave","type":"path","x":10,"y":10,"d":"M 10 10 q 40 -40 80 0 z","fill":"gold","fill_rule":"winding"}
+{"id":"frame","type":"path","x":100,"y":0,"d":"M0 0h40v40h-40z M10 10v20h20v-20z","fill":"gray","fill_rule":"evenodd"}
+{"id":"blob","type":"spline","x":0,"y":60,"points":[[0,0],[40,0],[40,40],{"entity":"wave"}],"closed":true,"stroke":"red"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        // Sized to the top of the curve, not its control point, and moved so
+        // the bounds start at the shape's position
+        let wave = builder.get_path("wave".to_string());
+        assert_eq!(builder.get_size("wave".to_string()), (80.0, 20.0));
+        assert_eq!(wave.path.commands[0], PathCommand::MoveTo((0.0, 20.0)));
+        assert_eq!(builder.absolute_positions["wave"], (10.0, 10.0));
+        assert_eq!(wave.path_options.fill_rule, FillRule::NonZero);
+
+        let frame = builder.get_path("frame".to_string());
+        assert_eq!(frame.path_options.fill_rule, FillRule::EvenOdd);
+        assert_eq!(builder.get_size("frame".to_string()), (40.0, 40.0));
+
+        // The closed spline bulges past the triangle through its points
+        let blob = builder.get_path("blob".to_string());
+        assert_eq!(blob.path.commands.last(), Some(&PathCommand::Close));
+        assert_eq!(blob.path_options.fill_color, "none");
+        let size = builder.get_size("blob".to_string());
+        assert!(size.0 > 40.0 && size.1 > 40.0);
+
+        // Unknown fill rules and entity points on splines are reported
+        let warnings = parser.warnings();
+        let attributes: Vec<_> = warnings
+            .iter()
+            .map(|w| (w.entity_id.as_str(), w.attribute.as_str()))
+            .collect();
+        assert_eq!(attributes, [("wave", "fill_rule"), ("blob", "points")]);
+
+        for input in [
+            r#"{"id":"bad","type":"path","d":"M 0 0 X 10"}"#,
+            r#"{"id":"bad","type":"path"}"#,
+        ] {
+            let mut parser = JsonLinesParser::new();
+            let root_id = parser.parse_string(input).unwrap();
+            assert!(parser.build(&root_id, &mut DiagramBuilder::new()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_connector_labels() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["a","b","block","c1"]}
+{"id":"a","type":"rect","x":0,"y":0,"width":40,"height":40}
+{"id":"b","type":"rect","x":200,"y":0,"width":40,"height":40}
+{"id":"block","type":"rect","x":100,"y":4,"width":40,"height":12}
+{"id":"c1","type":"connector","source":"a","target":"b","label":"owns","label_font_size":10,"source_label":"1","target_label":"0..*","labels":["extra",{"text":"note","role":"middle","font_size":14,"background":"white"}]}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        // The main label first, label objects override the connector's label attributes
+        let connector = builder.get_connector("c1".to_string()).clone();
+        let labels: Vec<(&str, LabelRole, Float, bool)> = connector
+            .labels
+            .iter()
+            .map(|l| (l.text.as_str(), l.role, l.text_options.font_size, l.background.is_some()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("owns", LabelRole::Middle, 10.0, false),
+                ("1", LabelRole::Source, 10.0, false),
+                ("0..*", LabelRole::Target, 10.0, false),
+                ("extra", LabelRole::Middle, 10.0, false),
+                ("note", LabelRole::Middle, 14.0, true),
+            ]
+        );
+
+        let bounds = |index: usize| {
+            let id = connector.label_entity(index);
+            let (x, y) = builder.absolute_positions[&id];
+            let (width, height) = builder.get_size(id);
+            crate::BoundingBox { x, y, width, height }
+        };
+
+        // End labels sit above the path from (40, 20) to (200, 20), next to their ends
+        let (source, target) = (bounds(1), bounds(2));
+        assert!((source.x - 42.0).abs() < 0.01 && (source.y + source.height - 18.0).abs() < 0.01);
+        assert!((target.x + target.width - 198.0).abs() < 0.01);
+
+        // Middle labels keep off each other and the rect above the middle of the path
+        let block = crate::BoundingBox { x: 100.0, y: 4.0, width: 40.0, height: 12.0 };
+        let middle = [bounds(0), bounds(3), bounds(4)];
+        for (i, label) in middle.iter().enumerate() {
+            assert!(!label.intersects(&block));
+            for other in &middle[i + 1..] {
+                assert!(!label.intersects(other));
+            }
+        }
+        // The box holds the styled text
+        assert!(builder.absolute_positions.contains_key("c1_label_4_text"));
+    }
+
+    #[test]
+    fn test_connector_markers() {
+        let input = r##"
+{"id":"root","type":"free_container","children":["a","b","c","uml","er","legacy","custom","unknown"]}
+{"id":"a","type":"rect","x":0,"y":0,"width":40,"height":40}
+{"id":"b","type":"rect","x":200,"y":0,"width":40,"height":40}
+{"id":"c","type":"rect","x":400,"y":0,"width":40,"height":40}
+{"id":"tag","type":"rect","width":12,"height":8}
+{"id":"uml","type":"connector","source":"a","target":"b","marker_start":"hollow_diamond","marker_end":"open_arrow"}
+{"id":"er","type":"connector","source":"a","target":"b","marker_start":"one_only","marker_end":"zero_or_many","arrow_end":true}
+{"id":"legacy","type":"connector","source":"a","target":"b","arrow_start":true}
+{"id":"custom","type":"connector","source":"b","target":"c","marker_end":"tag","arrow_size":6}
+{"id":"unknown","type":"connector","source":"a","target":"b","marker_end":"sprocket"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        let markers = |id: &str| {
+            let options = &builder.get_connector(id.to_string()).options;
+            (options.marker_start.clone(), options.marker_end.clone())
+        };
+        assert_eq!(markers("uml"), (Marker::HollowDiamond, Marker::OpenArrow));
+        // The marker name wins over the arrow flag
+        assert_eq!(markers("er"), (Marker::OneOnly, Marker::ZeroOrMany));
+        assert_eq!(markers("legacy"), (Marker::Arrow, Marker::None));
+        assert_eq!(markers("unknown"), (Marker::None, Marker::None));
+        let warnings = parser.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            (
+                warnings[0].entity_id.as_str(),
+                warnings[0].attribute.as_str()
+            ),
+            ("unknown", "marker_end")
+        );
+
+        // Entity markers are built on their own and sized by layout
+        assert_eq!(markers("custom").1, Marker::Entity("tag".to_string()));
+        assert!(builder.get_pattern_tile("tag".to_string()).is_some());
+        let geometry = builder.get_connector_geometry("custom".to_string()).unwrap();
+        assert_eq!(geometry.end_marker.entity, Some(("tag".to_string(), (12.0, 8.0))));
+        assert_eq!(geometry.end_arrow.tip, (400.0, 20.0));
+        assert_eq!(geometry.path.to_svg_path(), "M 240 20 L 388 20");
+    }
+
+    #[test]
+    fn test_clip_attributes() {
+        let input = r##"
+{"id":"root","type":"hstack","children":["b1","b2","r1","r2","t1"]}
+{"id":"b1","type":"box","clip":true,"border_radius":6,"children":["inner"]}
+{"id":"inner","type":"rect","width":80,"height":80}
+{"id":"b2","type":"box","overflow":"hidden","children":["t2"]}
+{"id":"t2","type":"text","content":"clipped"}
+{"id":"r1","type":"rect","width":50,"height":50,"clip_path":"circle","mask":"fade"}
+{"id":"circle","type":"ellipse","rx":20,"ry":20,"x":5,"y":5}
+{"id":"fade","type":"rect","width":50,"height":25,"opacity":0.5}
+{"id":"r2","type":"rect","width":50,"height":50,"clip_path":"r2","mask":"missing"}
+{"id":"t1","type":"text","content":"no overflow","overflow":"hidden"}
+"##;
+
+        let mut parser = JsonLinesParser::new();
+        let root_id = parser.parse_string(input).unwrap();
+        let mut builder = DiagramBuilder::new();
+        let root = parser.build(&root_id, &mut builder).unwrap();
+        crate::layout::layout_diagram(&mut builder, &root);
+
+        assert!(builder.get_clip("b1".to_string()).unwrap().clip);
+        assert!(builder.get_clip("b2".to_string()).unwrap().clip);
+        assert_eq!(
+            builder.get_outline("b1".to_string()).to_svg_path(),
+            crate::geometry::rounded_rect_path(80.0, 80.0, 6.0).to_svg_path()
+        );
+
+        let r1 = builder.get_clip("r1".to_string()).unwrap();
+        assert!(!r1.clip);
+        assert_eq!(r1.clip_path.as_ref().unwrap().entity_id, "circle");
+        assert_eq!(r1.mask.as_ref().unwrap().entity_id, "fade");
+        // Clip shapes are laid out at their x/y inside the entity
+        assert_eq!(builder.get_local_position("circle".to_string()), (5.0, 5.0));
+
+        // Self references, missing shapes and overflow on leaves are ignored
+        assert!(builder.get_clip("r2".to_string()).is_none());
+        assert!(builder.get_clip("t1".to_string()).is_none());
+        let warnings = parser.warnings();
+        let attributes: Vec<_> = warnings
+            .iter()
+            .map(|w| (w.entity_id.as_str(), w.attribute.as_str()))
+            .collect();
+        assert_eq!(attributes, [("r2", "clip_path"), ("r2", "mask")]);
+    }
+
     #[test]
     fn test_custom_component_access() {
         // Test that custom components get all attributes
@@ -1753,17 +4137,232 @@ impl JsonLinesParser {
     }
 }
 
-fn parse_port(attributes: &Map<String, Value>, key: &str) -> Port {
-    let port_str = get_string_attr(attributes, &[key], "center");
-    match port_str.as_str() {
-        "top" => Port::Top,
-        "bottom" => Port::Bottom,
-        "left" => Port::Left,
-        "right" => Port::Right,
-        "top_left" | "topleft" => Port::TopLeft,
-        "top_right" | "topright" => Port::TopRight,
-        "bottom_left" | "bottomleft" => Port::BottomLeft,
-        "bottom_right" | "bottomright" => Port::BottomRight,
-        _ => Port::Center,
+fn parse_label_alignment(attributes: &Map<String, Value>) -> LabelAlignment {
+    match get_string_attr(attributes, &["label_alignment"], "center").as_str() {
+        "start" => LabelAlignment::Start,
+        "end" => LabelAlignment::End,
+        _ => LabelAlignment::Center,
+    }
+}
+
+/// Options for labels drawn along a connector or arc, text attributes use a `label_` prefix
+fn parse_text_path_options(
+    attributes: &Map<String, Value>,
+    warnings: &EntityWarnings,
+) -> TextPathOptions {
+    TextPathOptions {
+        alignment: parse_label_alignment(attributes),
+        offset: get_float_attr(attributes, &["label_offset"], 4.0),
+        text_options: TextOptions {
+            font_size: get_float_attr(attributes, &["label_font_size"], 12.0),
+            text_color: get_color_attr_with_warnings(
+                attributes,
+                &["label_color"],
+                "black",
+                warnings,
+            ),
+            font_weight: get_font_weight_attr(attributes, &["label_font_weight"], 400),
+            font_family: get_string_attr(attributes, &["label_font_family"], "Arial"),
+            letter_spacing: get_float_attr(attributes, &["label_letter_spacing"], 0.0),
+            ..Default::default()
+        },
+    }
+}
+
+/// Text boxes placed along a connector: the main `label` unless it follows the
+/// path, `source_label`, `target_label`, and `labels`, a list of texts or of
+/// objects like `{"text":"0..*","role":"target","background":"white"}`
+fn parse_connector_labels(
+    attributes: &Map<String, Value>,
+    on_path: bool,
+    warnings: &EntityWarnings,
+) -> Vec<ConnectorLabel> {
+    let mut labels = Vec::new();
+    let main = get_string_attr(attributes, &["label"], "");
+    if !main.is_empty() && !on_path {
+        labels.push(parse_connector_label(
+            attributes,
+            &main,
+            LabelRole::Middle,
+            warnings,
+        ));
+    }
+    for (key, role) in [("source_label", LabelRole::Source), ("target_label", LabelRole::Target)] {
+        let text = get_string_attr(attributes, &[key], "");
+        if !text.is_empty() {
+            labels.push(parse_connector_label(attributes, &text, role, warnings));
+        }
+    }
+
+    let Some(Value::Array(items)) = attributes.get("labels") else {
+        return labels;
+    };
+    for item in items {
+        match item {
+            Value::String(text) => labels.push(parse_connector_label(
+                attributes,
+                text,
+                LabelRole::Middle,
+                warnings,
+            )),
+            Value::Object(object) => {
+                let text = get_string_attr(object, &["text", "label"], "");
+                if text.is_empty() {
+                    continue;
+                }
+                let role = match get_string_attr(object, &["role", "position"], "middle").as_str() {
+                    "source" | "start" => LabelRole::Source,
+                    "target" | "end" => LabelRole::Target,
+                    _ => LabelRole::Middle,
+                };
+                // Its own attributes win over the connector's `label_` ones
+                let mut merged = attributes.clone();
+                for (key, value) in object {
+                    merged.insert(format!("label_{}", key), value.clone());
+                }
+                labels.push(parse_connector_label(&merged, &text, role, warnings));
+            }
+            _ => println!("⚠️ Invalid connector label {}", item),
+        }
+    }
+    labels
+}
+
+/// Label styled by the `label_` text attributes, with a box behind it when
+/// `label_background` is set
+fn parse_connector_label(
+    attributes: &Map<String, Value>,
+    text: &str,
+    role: LabelRole,
+    warnings: &EntityWarnings,
+) -> ConnectorLabel {
+    let background = get_string_attr(attributes, &["label_background"], "");
+    let background = (!background.is_empty()).then(|| BoxOptions {
+        fill_color: Fill::Color(background.clone()),
+        stroke_color: get_color_attr_with_warnings(
+            attributes,
+            &["label_border_color"],
+            &background,
+            warnings,
+        ),
+        stroke_width: get_float_attr(attributes, &["label_border_width"], 0.0),
+        padding: get_float_attr(attributes, &["label_padding"], 2.0),
+        border_radius: get_float_attr(attributes, &["label_border_radius"], 2.0),
+        ..BoxOptions::new()
+    });
+    ConnectorLabel {
+        text: text.to_string(),
+        role,
+        text_options: parse_text_path_options(attributes, warnings).text_options,
+        background,
+    }
+}
+
+/// Orthogonal connectors pick their own shape, they route around other
+/// entities only when asked to with `"routing":"avoid"`
+fn parse_routing_strategy(attributes: &Map<String, Value>) -> OrthogonalRoutingStrategy {
+    match get_string_attr(attributes, &["routing", "routing_strategy"], "auto").as_str() {
+        "hv" => OrthogonalRoutingStrategy::HV,
+        "vh" => OrthogonalRoutingStrategy::VH,
+        "hvh" => OrthogonalRoutingStrategy::HVH,
+        "vhv" => OrthogonalRoutingStrategy::VHV,
+        "avoid" => OrthogonalRoutingStrategy::Avoid,
+        _ => OrthogonalRoutingStrategy::Auto,
+    }
+}
+
+/// Built-in end marker names
+fn parse_marker_name(name: &str) -> Option<Marker> {
+    Some(match name {
+        "none" | "" => Marker::None,
+        "arrow" => Marker::Arrow,
+        "open_arrow" => Marker::OpenArrow,
+        "hollow_arrow" | "triangle" => Marker::HollowArrow,
+        "diamond" => Marker::Diamond,
+        "hollow_diamond" => Marker::HollowDiamond,
+        "circle" => Marker::Circle,
+        "hollow_circle" => Marker::HollowCircle,
+        "bar" | "one" => Marker::Bar,
+        "crow_foot" | "many" => Marker::CrowFoot,
+        "one_only" => Marker::OneOnly,
+        "zero_or_one" => Marker::ZeroOrOne,
+        "one_or_many" => Marker::OneOrMany,
+        "zero_or_many" => Marker::ZeroOrMany,
+        _ => return None,
+    })
+}
+
+/// Connector end port, the entity boundary when not set or invalid
+fn parse_port(attributes: &Map<String, Value>, key: &str, warnings: &EntityWarnings) -> Port {
+    let Some(value) = attributes.get(key) else {
+        return Port::default();
+    };
+    parse_port_value(value).unwrap_or_else(|| {
+        warnings.push(key, format!("{} is not a port, using the boundary", value));
+        Port::default()
+    })
+}
+
+/// A port name, or `{"side":"top","offset":0.25}` for a point along a side.
+/// Names that aren't built in refer to ports declared on the entity
+fn parse_port_value(value: &Value) -> Option<Port> {
+    match value {
+        Value::String(name) => Some(match name.as_str() {
+            "" => return None,
+            "center" => Port::Center,
+            "boundary" | "outline" => Port::Boundary,
+            "auto" => Port::Auto,
+            "top" => Port::Top,
+            "bottom" => Port::Bottom,
+            "left" => Port::Left,
+            "right" => Port::Right,
+            "top_left" | "topleft" => Port::TopLeft,
+            "top_right" | "topright" => Port::TopRight,
+            "bottom_left" | "bottomleft" => Port::BottomLeft,
+            "bottom_right" | "bottomright" => Port::BottomRight,
+            _ => Port::Named(name.clone()),
+        }),
+        Value::Object(attributes) => {
+            let side = parse_port_side(&get_string_attr(attributes, &["side"], ""))?;
+            let offset = get_float_attr(attributes, &["offset"], 0.5).clamp(0.0, 1.0);
+            Some(Port::Side { side, offset })
+        }
+        _ => None,
+    }
+}
+
+fn parse_port_side(name: &str) -> Option<PortSide> {
+    match name {
+        "top" => Some(PortSide::Top),
+        "bottom" => Some(PortSide::Bottom),
+        "left" => Some(PortSide::Left),
+        "right" => Some(PortSide::Right),
+        _ => None,
+    }
+}
+
+/// `ports` declares named ports connectors can use as their source or target
+/// port, e.g. `"ports":{"in":"left","out":{"side":"right","offset":0.25}}`
+fn parse_named_ports(
+    attributes: &Map<String, Value>,
+    builder: &mut DiagramBuilder,
+    entity_id: String,
+    warnings: &EntityWarnings,
+) {
+    let Some(Value::Object(ports)) = attributes.get("ports") else {
+        return;
+    };
+    for (name, value) in ports {
+        match parse_port_value(value) {
+            Some(Port::Named(other)) => warnings.push(
+                "ports",
+                format!(
+                    "port '{}' can't refer to another named port '{}'",
+                    name, other
+                ),
+            ),
+            Some(port) => builder.set_named_port(entity_id.clone(), name.clone(), port),
+            None => warnings.push("ports", format!("{} is not a port for '{}'", value, name)),
+        }
     }
 }
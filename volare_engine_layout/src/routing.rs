@@ -0,0 +1,424 @@
+/* Orthogonal connector routing around obstacles */
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::components::{Float, Port, PortSide};
+use crate::layout::BoundingBox;
+
+const EPSILON: Float = 0.01;
+
+// Right, down, left, up. The opposite of a direction is two steps away
+const STEPS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const NO_DIRECTION: usize = 4;
+
+/// Direction a route leaves a port in, `None` for ports off the sides
+fn port_direction(port: &Port) -> Option<usize> {
+    match port.side()? {
+        PortSide::Right => Some(0),
+        PortSide::Bottom => Some(1),
+        PortSide::Left => Some(2),
+        PortSide::Top => Some(3),
+    }
+}
+
+/// Point `length` away from `point` in a direction, the point itself without one
+fn step_out(point: (Float, Float), direction: Option<usize>, length: Float) -> (Float, Float) {
+    match direction {
+        Some(d) => (
+            point.0 + STEPS[d].0 as Float * length,
+            point.1 + STEPS[d].1 as Float * length,
+        ),
+        None => point,
+    }
+}
+
+struct Rect {
+    min_x: Float,
+    min_y: Float,
+    max_x: Float,
+    max_y: Float,
+}
+
+impl Rect {
+    fn inflated(bounds: &BoundingBox, margin: Float) -> Rect {
+        Rect {
+            min_x: bounds.x - margin,
+            min_y: bounds.y - margin,
+            max_x: bounds.x + bounds.width + margin,
+            max_y: bounds.y + bounds.height + margin,
+        }
+    }
+
+    /// Strictly inside, points on the border are free
+    fn contains(&self, (x, y): (Float, Float)) -> bool {
+        x > self.min_x + EPSILON
+            && x < self.max_x - EPSILON
+            && y > self.min_y + EPSILON
+            && y < self.max_y - EPSILON
+    }
+}
+
+/// Sorted, deduplicated coordinates with an extra line halfway between each pair,
+/// so routes can run through the middle of the gaps between obstacles
+fn grid_lines(mut coords: Vec<Float>) -> Vec<Float> {
+    coords.sort_by(|a, b| a.total_cmp(b));
+    coords.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+
+    let mut lines = Vec::with_capacity(coords.len() * 2);
+    for (i, coord) in coords.iter().enumerate() {
+        if i > 0 {
+            lines.push((coords[i - 1] + coord) / 2.0);
+        }
+        lines.push(*coord);
+    }
+    lines
+}
+
+fn line_index(lines: &[Float], value: Float) -> usize {
+    lines.partition_point(|line| *line < value - EPSILON)
+}
+
+/// Bends first, then length (in hundredths, so equal routes compare equal),
+/// then how far the route strays from the line between its ends
+#[derive(Clone, Copy, PartialEq)]
+struct Cost {
+    bends: u32,
+    length: i64,
+    deviation: Float,
+}
+
+impl Eq for Cost {}
+
+impl Ord for Cost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bends
+            .cmp(&other.bends)
+            .then(self.length.cmp(&other.length))
+            .then(self.deviation.total_cmp(&other.deviation))
+    }
+}
+
+impl PartialOrd for Cost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(PartialEq, Eq)]
+struct Candidate {
+    cost: Cost,
+    state: usize,
+}
+
+// Reversed so the binary heap pops the cheapest candidate first
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .cmp(&self.cost)
+            .then(other.state.cmp(&self.state))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Drops repeated points and the middle point of straight runs
+fn simplify(points: Vec<(Float, Float)>) -> Vec<(Float, Float)> {
+    let mut result: Vec<(Float, Float)> = Vec::with_capacity(points.len());
+    for point in points {
+        if let Some(last) = result.last() {
+            if (last.0 - point.0).abs() < EPSILON && (last.1 - point.1).abs() < EPSILON {
+                continue;
+            }
+        }
+        if result.len() >= 2 {
+            let a = result[result.len() - 2];
+            let b = result[result.len() - 1];
+            let vertical = (a.0 - b.0).abs() < EPSILON && (b.0 - point.0).abs() < EPSILON;
+            let horizontal = (a.1 - b.1).abs() < EPSILON && (b.1 - point.1).abs() < EPSILON;
+            if vertical || horizontal {
+                result.pop();
+            }
+        }
+        result.push(point);
+    }
+    result
+}
+
+/// Orthogonal route from `start` to `end` that keeps `clearance` away from the obstacles.
+///
+/// Side ports leave and enter their entity perpendicular to the side, the center and
+/// corner ports in any direction. The search runs over a grid made of the obstacle
+/// edges and the gaps between them and picks the route with the fewest bends, then the
+/// shortest one. Obstacles the route has to start or end in are ignored.
+///
+/// Returns the bend points between `start` and `end`, `None` when every route is blocked.
+pub fn route_orthogonal(
+    start: (Float, Float),
+    start_port: &Port,
+    end: (Float, Float),
+    end_port: &Port,
+    obstacles: &[BoundingBox],
+    clearance: Float,
+) -> Option<Vec<(Float, Float)>> {
+    let start_direction = port_direction(start_port);
+    let end_direction = port_direction(end_port);
+    let from = step_out(start, start_direction, clearance);
+    let to = step_out(end, end_direction, clearance);
+
+    let rects: Vec<Rect> = obstacles
+        .iter()
+        .map(|bounds| Rect::inflated(bounds, clearance))
+        .filter(|rect| !rect.contains(from) && !rect.contains(to))
+        .collect();
+
+    let xs = grid_lines(
+        rects
+            .iter()
+            .flat_map(|rect| [rect.min_x, rect.max_x])
+            .chain([from.0, to.0])
+            .collect(),
+    );
+    let ys = grid_lines(
+        rects
+            .iter()
+            .flat_map(|rect| [rect.min_y, rect.max_y])
+            .chain([from.1, to.1])
+            .collect(),
+    );
+    let columns = xs.len();
+    let nodes = columns * ys.len();
+
+    // Nodes inside an obstacle, and edges to the right and down that run through one
+    let mut blocked = vec![false; nodes];
+    let mut right_blocked = vec![false; nodes];
+    let mut down_blocked = vec![false; nodes];
+    for rect in &rects {
+        let (left, right) = (line_index(&xs, rect.min_x), line_index(&xs, rect.max_x));
+        let (top, bottom) = (line_index(&ys, rect.min_y), line_index(&ys, rect.max_y));
+        for row in top..=bottom {
+            for column in left..=right {
+                let node = row * columns + column;
+                let inner_column = column > left && column < right;
+                let inner_row = row > top && row < bottom;
+                blocked[node] |= inner_column && inner_row;
+                right_blocked[node] |= inner_row && column < right;
+                down_blocked[node] |= inner_column && row < bottom;
+            }
+        }
+    }
+
+    let node_at =
+        |point: (Float, Float)| line_index(&ys, point.1) * columns + line_index(&xs, point.0);
+    let position = |node: usize| (xs[node % columns], ys[node / columns]);
+    let center = ((from.0 + to.0) / 2.0, (from.1 + to.1) / 2.0);
+    let start_node = node_at(from);
+    let end_node = node_at(to);
+
+    // States are a node and the direction the route arrived in, plus the goal
+    let goal = nodes * 5;
+    let mut best: Vec<Option<Cost>> = vec![None; goal + 1];
+    let mut previous = vec![usize::MAX; goal + 1];
+    let mut heap = BinaryHeap::new();
+
+    let start_state = start_node * 5 + start_direction.unwrap_or(NO_DIRECTION);
+    let zero = Cost {
+        bends: 0,
+        length: 0,
+        deviation: 0.0,
+    };
+    best[start_state] = Some(zero);
+    heap.push(Candidate {
+        cost: zero,
+        state: start_state,
+    });
+
+    let mut relax = |heap: &mut BinaryHeap<Candidate>,
+                     best: &mut Vec<Option<Cost>>,
+                     state: usize,
+                     from: usize,
+                     cost: Cost| {
+        if best[state].is_none_or(|known| cost < known) {
+            best[state] = Some(cost);
+            previous[state] = from;
+            heap.push(Candidate { cost, state });
+        }
+    };
+
+    let mut reached = false;
+    while let Some(Candidate { cost, state }) = heap.pop() {
+        if state == goal {
+            reached = true;
+            break;
+        }
+        if best[state].is_some_and(|known| cost > known) {
+            continue;
+        }
+        let (node, direction) = (state / 5, state % 5);
+
+        if node == end_node {
+            // The last segment runs into the target side
+            let turns = match end_direction {
+                Some(out) if direction != NO_DIRECTION && direction != (out + 2) % 4 => 1,
+                _ => 0,
+            };
+            let done = Cost {
+                bends: cost.bends + turns,
+                ..cost
+            };
+            relax(&mut heap, &mut best, goal, state, done);
+        }
+
+        let (column, row) = ((node % columns) as isize, (node / columns) as isize);
+        for (step, (dx, dy)) in STEPS.iter().enumerate() {
+            if direction != NO_DIRECTION && step == (direction + 2) % 4 {
+                continue;
+            }
+            let (next_column, next_row) = (column + dx, row + dy);
+            if next_column < 0
+                || next_row < 0
+                || next_column >= columns as isize
+                || next_row >= ys.len() as isize
+            {
+                continue;
+            }
+            let next = next_row as usize * columns + next_column as usize;
+            let edge_blocked = match step {
+                0 => right_blocked[node],
+                1 => down_blocked[node],
+                2 => right_blocked[next],
+                _ => down_blocked[next],
+            };
+            if blocked[next] || edge_blocked {
+                continue;
+            }
+
+            let (a, b) = (position(node), position(next));
+            let length = (b.0 - a.0).abs() + (b.1 - a.1).abs();
+            let offset = if *dy == 0 {
+                a.1 - center.1
+            } else {
+                a.0 - center.0
+            };
+            let next_cost = Cost {
+                bends: cost.bends + u32::from(direction != NO_DIRECTION && step != direction),
+                length: cost.length + (length * 100.0).round() as i64,
+                deviation: cost.deviation + offset.abs() * length,
+            };
+            relax(&mut heap, &mut best, next * 5 + step, state, next_cost);
+        }
+    }
+
+    if !reached {
+        return None;
+    }
+
+    let mut nodes_on_route = Vec::new();
+    let mut state = previous[goal];
+    while state != usize::MAX {
+        nodes_on_route.push(position(state / 5));
+        state = previous[state];
+    }
+    nodes_on_route.reverse();
+
+    let mut points = vec![start];
+    points.extend(nodes_on_route);
+    points.push(end);
+    let points = simplify(points);
+    Some(points[1..points.len() - 1].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(x: Float, y: Float, width: Float, height: Float) -> BoundingBox {
+        BoundingBox {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_route_around_obstacle() {
+        // Two boxes side by side with a third one in between
+        let source = bounds(0.0, 0.0, 40.0, 40.0);
+        let blocker = bounds(100.0, -20.0, 40.0, 80.0);
+        let target = bounds(200.0, 0.0, 40.0, 40.0);
+        let obstacles = [source, blocker, target];
+
+        let waypoints = route_orthogonal(
+            (40.0, 20.0),
+            &Port::Right,
+            (200.0, 20.0),
+            &Port::Left,
+            &obstacles,
+            10.0,
+        )
+        .unwrap();
+
+        // Out of the source, around the blocker at the clearance and back into the target
+        assert_eq!(waypoints.len(), 4);
+        let detour_y = waypoints[1].1;
+        assert!(detour_y == -30.0 || detour_y == 70.0);
+        assert_eq!(waypoints[0], (90.0, 20.0));
+        assert_eq!(waypoints[1], (90.0, detour_y));
+        assert_eq!(waypoints[2], (150.0, detour_y));
+        assert_eq!(waypoints[3], (150.0, 20.0));
+
+        // Without the blocker the route is a straight line
+        let straight = route_orthogonal(
+            (40.0, 20.0),
+            &Port::Right,
+            (200.0, 20.0),
+            &Port::Left,
+            &[source, target],
+            10.0,
+        )
+        .unwrap();
+        assert!(straight.is_empty());
+    }
+
+    #[test]
+    fn test_route_bends_in_the_gap() {
+        let source = bounds(0.0, 0.0, 40.0, 40.0);
+        let target = bounds(200.0, 100.0, 40.0, 40.0);
+
+        let waypoints = route_orthogonal(
+            (40.0, 20.0),
+            &Port::Right,
+            (200.0, 120.0),
+            &Port::Left,
+            &[source, target],
+            10.0,
+        )
+        .unwrap();
+        assert_eq!(waypoints, vec![(120.0, 20.0), (120.0, 120.0)]);
+
+        // A target walled in on all sides can't be reached
+        let walls = [
+            bounds(150.0, 50.0, 140.0, 10.0),
+            bounds(150.0, 180.0, 140.0, 10.0),
+            bounds(150.0, 50.0, 10.0, 140.0),
+            bounds(280.0, 50.0, 10.0, 140.0),
+        ];
+        let mut obstacles = walls.to_vec();
+        obstacles.push(source);
+        assert!(route_orthogonal(
+            (40.0, 20.0),
+            &Port::Right,
+            (200.0, 120.0),
+            &Port::Left,
+            &obstacles,
+            10.0,
+        )
+        .is_none());
+    }
+}
@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod simple_rotation_test {
     use crate::transform::Transform;
-    use crate::{Fill, RectOptions, SizeBehavior};
+    use crate::{Fill, RectOptions, SizeBehavior, StrokeStyle};
     use crate::{DiagramBuilder, layout::layout_tree_node};
 
     #[test]
@@ -19,6 +19,7 @@ mod simple_rotation_test {
             stroke_color: "darkblue".to_string(),
             stroke_width: 1.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         };
         
         let rect = builder.new_rectangle("test_rect".to_string(), rect_opts);
@@ -51,7 +52,9 @@ mod simple_rotation_test {
 mod debug_rotation_positioning {
     use super::*;
     use crate::transform::Transform;
-    use crate::{Fill, HorizontalAlignment, RectOptions, SizeBehavior, VerticalAlignment};
+    use crate::{
+        Fill, HorizontalAlignment, RectOptions, SizeBehavior, StrokeStyle, VerticalAlignment,
+    };
     use crate::{DiagramBuilder, layout::layout_tree_node};
 
     #[test]
@@ -69,6 +72,7 @@ mod debug_rotation_positioning {
             stroke_color: "darkred".to_string(),
             stroke_width: 2.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         });
         
         let rotated_rect = builder.new_rectangle("rotated_rect".to_string(), RectOptions {
@@ -78,6 +82,7 @@ mod debug_rotation_positioning {
             stroke_color: "darkblue".to_string(),
             stroke_width: 2.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         });
         
         let normal_rect2 = builder.new_rectangle("normal_rect2".to_string(), RectOptions {
@@ -87,6 +92,7 @@ mod debug_rotation_positioning {
             stroke_color: "darkgreen".to_string(),
             stroke_width: 2.0,
             border_radius: 0.0,
+            stroke_style: StrokeStyle::default(),
         });
         
         // Apply rotation to middle rectangle
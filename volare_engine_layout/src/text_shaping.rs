@@ -0,0 +1,89 @@
+use textwrap::{core::display_width, Options, WordSeparator};
+use unicode_bidi::BidiInfo;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Unicode helpers used when breaking, measuring and drawing text.
+//
+// - Line breaking follows UAX #14, so CJK text gets break opportunities
+//   between ideographs and URLs/hyphenated words break where expected.
+// - Counts are done in grapheme clusters, never in bytes.
+// - Lines are stored in logical order. Painters that place glyphs one by
+//   one (PNG) use `visual_order` to reorder right-to-left runs. SVG viewers
+//   apply the bidi algorithm themselves.
+
+/// Wrap text to `line_width` columns. East asian wide characters count as two columns.
+pub fn wrap_text(text: &str, line_width: usize) -> Vec<String> {
+    let options = Options::new(line_width.max(1))
+        .word_separator(WordSeparator::UnicodeBreakProperties)
+        .break_words(true);
+    textwrap::wrap(text, options)
+        .into_iter()
+        .map(|line| line.into_owned())
+        .collect()
+}
+
+/// Width of the text in columns, the unit used by `TextOptions::line_width`
+pub fn text_columns(text: &str) -> usize {
+    display_width(text)
+}
+
+/// Number of user-perceived characters
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+pub fn graphemes(text: &str) -> impl Iterator<Item = &str> {
+    text.graphemes(true)
+}
+
+/// True if the text contains right-to-left characters (Arabic, Hebrew...)
+pub fn has_rtl(text: &str) -> bool {
+    BidiInfo::new(text, None).has_rtl()
+}
+
+/// Reorder a single line from logical to visual (left to right) order.
+/// RTL runs are reversed and mirrored characters such as brackets are kept
+/// in the order the bidi algorithm produces.
+pub fn visual_order(line: &str) -> String {
+    let bidi_info = BidiInfo::new(line, None);
+    if !bidi_info.has_rtl() {
+        return line.to_string();
+    }
+
+    let mut visual = String::with_capacity(line.len());
+    for paragraph in &bidi_info.paragraphs {
+        let reordered = bidi_info.reorder_line(paragraph, paragraph.range.clone());
+        visual.push_str(&reordered);
+    }
+    visual
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cjk_and_latin_line_breaking() {
+        // Ideographs are two columns wide and can break anywhere
+        let lines = wrap_text("日本語のテキストです", 6);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| text_columns(line) <= 6));
+        assert_eq!(lines.concat(), "日本語のテキストです");
+
+        assert_eq!(wrap_text("hello world", 5), vec!["hello", "world"]);
+    }
+
+    #[test]
+    fn test_graphemes_and_bidi() {
+        // e + combining acute, flag, family emoji with ZWJ
+        assert_eq!(grapheme_count("e\u{301}"), 1);
+        assert_eq!(grapheme_count("🇨🇴"), 1);
+        assert_eq!(grapheme_count("👨‍👩‍👧"), 1);
+        assert_eq!(grapheme_count("añb"), 3);
+
+        assert_eq!(visual_order("abc"), "abc");
+        assert!(has_rtl("שלום"));
+        assert_eq!(visual_order("שלום"), "םולש");
+        assert_eq!(visual_order("id: שלום!"), "id: םולש!");
+    }
+}
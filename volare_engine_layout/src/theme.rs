@@ -1,3 +1,154 @@
+use std::collections::HashMap;
+
+use crate::components::Float;
+
+/// Defaults for attributes a document leaves out.
+///
+/// The parser falls back to the theme for fonts, colors and spacing, so the
+/// same document can be rendered light or dark by swapping the theme on the
+/// `DiagramBuilder`. Attribute values written as `$name` are looked up in
+/// `colors`, which lets documents use the palette without hardcoding it.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Theme {
-    
-}
\ No newline at end of file
+    pub name: String,
+    /// Canvas color, `None` keeps the SVG background transparent
+    pub background: Option<String>,
+
+    // Typography
+    pub font_family: String,
+    pub font_size: Float,
+    pub font_weight: u32,
+    pub text_color: String,
+    pub line_width: usize,
+
+    // Shapes
+    pub fill_color: String,
+    pub stroke_color: String,
+    pub stroke_width: Float,
+    // Lines, polylines, connectors and arcs
+    pub line_color: String,
+
+    // Spacing
+    pub padding: Float,
+    pub cell_padding: Float,
+
+    // Tables
+    pub table_header_fill: String,
+
+    /// Named colors referenced as `$name` from attribute values
+    pub colors: HashMap<String, String>,
+}
+
+impl Theme {
+    /// The built-in look, matches the parser defaults from before themes
+    pub fn light() -> Theme {
+        Theme {
+            name: "light".to_string(),
+            background: None,
+            font_family: "Arial".to_string(),
+            font_size: 12.0,
+            font_weight: 400,
+            text_color: "black".to_string(),
+            line_width: 200,
+            fill_color: "white".to_string(),
+            stroke_color: "black".to_string(),
+            stroke_width: 1.0,
+            line_color: "black".to_string(),
+            padding: 0.0,
+            cell_padding: 20.0,
+            table_header_fill: "lightgray".to_string(),
+            colors: palette(&[
+                ("text", "#212529"),
+                ("muted", "#6c757d"),
+                ("surface", "#ffffff"),
+                ("surface_alt", "#f8f9fa"),
+                ("border", "#dee2e6"),
+                ("accent", "#0d6efd"),
+                ("success", "#198754"),
+                ("warning", "#ffc107"),
+                ("danger", "#dc3545"),
+            ]),
+        }
+    }
+
+    pub fn dark() -> Theme {
+        Theme {
+            name: "dark".to_string(),
+            background: Some("#121212".to_string()),
+            text_color: "#e9ecef".to_string(),
+            fill_color: "#1e1e1e".to_string(),
+            stroke_color: "#adb5bd".to_string(),
+            line_color: "#ced4da".to_string(),
+            table_header_fill: "#343a40".to_string(),
+            colors: palette(&[
+                ("text", "#e9ecef"),
+                ("muted", "#adb5bd"),
+                ("surface", "#1e1e1e"),
+                ("surface_alt", "#2b2b2b"),
+                ("border", "#495057"),
+                ("accent", "#6ea8fe"),
+                ("success", "#75b798"),
+                ("warning", "#ffda6a"),
+                ("danger", "#ea868f"),
+            ]),
+            ..Theme::light()
+        }
+    }
+
+    /// Black canvas, white text and strokes, saturated palette
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "high_contrast".to_string(),
+            background: Some("#000000".to_string()),
+            text_color: "#ffffff".to_string(),
+            fill_color: "#000000".to_string(),
+            stroke_color: "#ffffff".to_string(),
+            stroke_width: 2.0,
+            line_color: "#ffffff".to_string(),
+            table_header_fill: "#000080".to_string(),
+            colors: palette(&[
+                ("text", "#ffffff"),
+                ("muted", "#ffff00"),
+                ("surface", "#000000"),
+                ("surface_alt", "#000000"),
+                ("border", "#ffffff"),
+                ("accent", "#00ffff"),
+                ("success", "#00ff00"),
+                ("warning", "#ffff00"),
+                ("danger", "#ff4040"),
+            ]),
+            ..Theme::light()
+        }
+    }
+
+    /// Built-in theme by name, `None` for unknown names
+    pub fn by_name(name: &str) -> Option<Theme> {
+        match name {
+            "light" => Some(Theme::light()),
+            "dark" => Some(Theme::dark()),
+            "high_contrast" | "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// Resolves a `$name` palette reference, other values are returned as is
+    pub fn resolve_color<'a>(&'a self, value: &'a str) -> Option<&'a str> {
+        match value.strip_prefix('$') {
+            Some(name) => self.colors.get(name).map(|color| color.as_str()),
+            None => Some(value),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::light()
+    }
+}
+
+fn palette(colors: &[(&str, &str)]) -> HashMap<String, String> {
+    colors
+        .iter()
+        .map(|(name, color)| (name.to_string(), color.to_string()))
+        .collect()
+}
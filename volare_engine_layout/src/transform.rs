@@ -57,6 +57,25 @@ impl Transform {
         }
     }
     
+    /// Undoes this transform, None when it collapses the plane to a line or a point
+    pub fn inverse(&self) -> Option<Transform> {
+        let [a, b, c, d, e, f] = self.matrix;
+        let det = a * d - b * c;
+        if det.abs() < Float::EPSILON {
+            return None;
+        }
+        Some(Transform {
+            matrix: [
+                d / det,
+                -b / det,
+                -c / det,
+                a / det,
+                (c * f - d * e) / det,
+                (b * e - a * f) / det,
+            ],
+        })
+    }
+
     pub fn transform_point(&self, x: Float, y: Float) -> (Float, Float) {
         let [a, b, c, d, e, f] = self.matrix;
         (
@@ -4,14 +4,6 @@ use volare_engine_layout::*;
 use svg_renderer::SVGRenderer;
 use custom_components::*;
 
-// Simple text measurement function
-fn simple_measure_text(text: &str, options: &TextOptions) -> (Float, Float) {
-    let char_width = options.font_size * 0.6;
-    let width = text.len() as Float * char_width;
-    let height = options.font_size;
-    (width, height)
-}
-
 // JavaScript callback for text measurement
 #[wasm_bindgen]
 extern "C" {
@@ -38,12 +30,12 @@ fn measure_text_wrapper(text: &str, options: &TextOptions) -> (Float, Float) {
             if result.len() >= 2 {
                 (result[0], result[1])
             } else {
-                // Fallback to simple measurement
-                simple_measure_text(text, options)
+                // Fallback to the bundled font
+                measure_text_with_default_font(text, options)
             }
         } else {
-            // Fallback to simple measurement
-            simple_measure_text(text, options)
+            // Fallback to the bundled font
+            measure_text_with_default_font(text, options)
         }
     }
 }
@@ -59,8 +51,8 @@ pub struct VolareEngine {
 impl VolareEngine {
     #[wasm_bindgen(constructor)]
     pub fn new() -> VolareEngine {
+        // Text is measured with the builder's font registry until a JS callback is set
         let mut builder = DiagramBuilder::new();
-        builder.set_measure_text_fn(measure_text_wrapper);
         custom_components::register_all_components(&mut builder);
         println!("Volare Engine initialized with custom components!");
         VolareEngine { builder }
@@ -74,6 +66,46 @@ impl VolareEngine {
         unsafe {
             MEASURE_TEXT_CALLBACK = Some(callback);
         }
+        self.builder.set_measure_text_fn(measure_text_wrapper);
+    }
+
+    /// Register a TTF/OTF font used for measurement and embedded in the SVG output
+    #[wasm_bindgen(js_name = "registerFont")]
+    pub fn register_font(
+        &mut self,
+        family: &str,
+        weight: u32,
+        italic: bool,
+        bytes: Vec<u8>,
+    ) -> Result<(), JsValue> {
+        let style = if italic { FontStyle::Italic } else { FontStyle::Normal };
+        self.builder
+            .fonts
+            .register_font_bytes(family, weight, style, bytes)
+            .map_err(|e| JsValue::from_str(&format!("Font error: {}", e)))
+    }
+
+    /// Select the built-in theme (light, dark or high_contrast) used for defaults
+    /// and document components. A document's own `theme` attribute still wins.
+    #[wasm_bindgen(js_name = "setTheme")]
+    pub fn set_theme(&mut self, name: &str) -> Result<(), JsValue> {
+        let theme = Theme::by_name(name)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown theme: {}", name)))?;
+        self.builder.set_theme(theme);
+        Ok(())
+    }
+
+    /// Drop cached text measurements, e.g after web fonts used by the JS callback finish loading
+    #[wasm_bindgen(js_name = "invalidateMeasureCache")]
+    pub fn invalidate_measure_cache(&self) {
+        self.builder.invalidate_measure_cache();
+    }
+
+    /// Text measurement cache statistics as [hits, misses, entries]
+    #[wasm_bindgen(js_name = "measureCacheStats")]
+    pub fn measure_cache_stats(&self) -> Vec<f64> {
+        let stats = self.builder.measure_cache_stats();
+        vec![stats.hits as f64, stats.misses as f64, stats.entries as f64]
     }
 
     /// Create SVG from JSON Lines string
@@ -86,6 +118,9 @@ impl VolareEngine {
 
         let diagram = parser.build(&root_id, &mut self.builder)
             .map_err(|e| JsValue::from_str(&format!("Build error: {}", e)))?;
+        for warning in parser.warnings() {
+            web_sys::console::warn_1(&warning.to_string().into());
+        }
 
         layout::layout_diagram(&mut self.builder, &diagram);
 